@@ -1,279 +1,292 @@
-use core_foundation::{
-    base::{CFIndexConvertible, OSStatus},
-    boolean::CFBoolean,
-    dictionary::{
-        kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionaryCreate,
-    },
-    string::CFStringRef,
-};
-use std::{convert::TryInto, os::raw::c_void};
-use video_toolbox_sys::{
-    kCMVideoCodecType_HEVC, kVTVideoEncoderSpecification_RequireHardwareAcceleratedVideoEncoder,
-    CMBlockBufferCopyDataBytes, CMFormatDescriptionRef, CMSampleBufferGetDataBuffer,
-    CMSampleBufferGetFormatDescription, CMSampleBufferGetTotalSampleSize, CMSampleBufferIsValid,
-    CMSampleBufferRef, CMTime, CMVideoFormatDescriptionGetHEVCParameterSetAtIndex,
-    CVPixelBufferCreateWithBytes, CVPixelBufferRef, VTCompressionSessionCompleteFrames,
-    VTCompressionSessionCreate, VTCompressionSessionEncodeFrame, VTCompressionSessionRef,
-    VTEncodeInfoFlags,
-};
-
-extern "C" fn encode_callback(
-    _output_callback_ref_con: *mut std::os::raw::c_void,
-    source_frame_ref_con: *mut std::os::raw::c_void,
-    status: OSStatus,
-    _info_flags: VTEncodeInfoFlags,
-    sample_buffer: CMSampleBufferRef,
-) {
-    println!("encode_callback");
-
-    println!("Status: {}", status);
-
-    println!("Valid buffer: {}", unsafe { CMSampleBufferIsValid(sample_buffer) });
-    // Returns the total size in bytes of sample data in a CMSampleBuffer.
-    let data_length = unsafe { CMSampleBufferGetTotalSampleSize(sample_buffer) };
-    println!("Total sample size: {}", data_length);
-
-    let data_buffer = unsafe { CMSampleBufferGetDataBuffer(sample_buffer) };
-    println!("Data buffer: {:?}", data_buffer);
-
-    let format = unsafe { CMSampleBufferGetFormatDescription(sample_buffer) };
-
-    let vps = get_hevc_param(format, HevcParam::Vps).unwrap();
-    let sps = get_hevc_param(format, HevcParam::Sps).unwrap();
-    let pps = get_hevc_param(format, HevcParam::Pps).unwrap();
-
-    let mut hevc_data = vec![0u8; data_length];
-
-    let offset = 0;
-    let _ = unsafe {
-        CMBlockBufferCopyDataBytes(
-            data_buffer,
-            offset,
-            data_length,
-            hevc_data.as_mut_ptr() as *mut _,
-        )
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod example {
+    use core_foundation::{
+        base::{CFIndexConvertible, OSStatus},
+        boolean::CFBoolean,
+        dictionary::{
+            kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionaryCreate,
+        },
+        string::CFStringRef,
+    };
+    use std::{convert::TryInto, os::raw::c_void};
+    use video_toolbox_sys::{
+        kCMVideoCodecType_HEVC,
+        kVTVideoEncoderSpecification_RequireHardwareAcceleratedVideoEncoder,
+        CMBlockBufferCopyDataBytes, CMFormatDescriptionRef, CMSampleBufferGetDataBuffer,
+        CMSampleBufferGetFormatDescription, CMSampleBufferGetTotalSampleSize,
+        CMSampleBufferIsValid, CMSampleBufferRef, CMTime,
+        CMVideoFormatDescriptionGetHEVCParameterSetAtIndex, CVPixelBufferCreateWithBytes,
+        CVPixelBufferRef, VTCompressionSessionCompleteFrames, VTCompressionSessionCreate,
+        VTCompressionSessionEncodeFrame, VTCompressionSessionRef, VTEncodeInfoFlags,
     };
 
-    const HEADER: &[u8; 4] = &[0, 0, 0, 1];
+    extern "C" fn encode_callback(
+        _output_callback_ref_con: *mut std::os::raw::c_void,
+        source_frame_ref_con: *mut std::os::raw::c_void,
+        status: OSStatus,
+        _info_flags: VTEncodeInfoFlags,
+        sample_buffer: CMSampleBufferRef,
+    ) {
+        println!("encode_callback");
 
-    let mut output = vec![];
-    output.extend_from_slice(HEADER);
-    output.extend_from_slice(&vps);
+        println!("Status: {}", status);
 
-    output.extend_from_slice(HEADER);
-    output.extend_from_slice(&sps);
+        println!("Valid buffer: {}", unsafe { CMSampleBufferIsValid(sample_buffer) });
+        // Returns the total size in bytes of sample data in a CMSampleBuffer.
+        let data_length = unsafe { CMSampleBufferGetTotalSampleSize(sample_buffer) };
+        println!("Total sample size: {}", data_length);
 
-    output.extend_from_slice(HEADER);
-    output.extend_from_slice(&pps);
+        let data_buffer = unsafe { CMSampleBufferGetDataBuffer(sample_buffer) };
+        println!("Data buffer: {:?}", data_buffer);
 
-    let mut buffer_offset = 0;
+        let format = unsafe { CMSampleBufferGetFormatDescription(sample_buffer) };
 
-    while buffer_offset < (hevc_data.len() - HEADER.len()) {
-        let mut nal_len = u32::from_ne_bytes([
-            hevc_data[buffer_offset],
-            hevc_data[(buffer_offset + 1)],
-            hevc_data[(buffer_offset + 2)],
-            hevc_data[(buffer_offset + 3)],
-        ]);
-        nal_len = u32::from_be(nal_len);
-        dbg!(nal_len);
+        let vps = get_hevc_param(format, HevcParam::Vps).unwrap();
+        let sps = get_hevc_param(format, HevcParam::Sps).unwrap();
+        let pps = get_hevc_param(format, HevcParam::Pps).unwrap();
 
-        output.extend_from_slice(HEADER);
-        let hevc_offset = buffer_offset + HEADER.len();
-        output.extend_from_slice(&hevc_data[hevc_offset..(hevc_offset + nal_len as usize)]);
+        let mut hevc_data = vec![0u8; data_length];
 
-        buffer_offset += HEADER.len();
-        buffer_offset += nal_len as usize;
-    }
+        let offset = 0;
+        let _ = unsafe {
+            CMBlockBufferCopyDataBytes(
+                data_buffer,
+                offset,
+                data_length,
+                hevc_data.as_mut_ptr() as *mut _,
+            )
+        };
 
-    std::mem::forget(vps);
-    std::mem::forget(sps);
-    std::mem::forget(pps);
+        const HEADER: &[u8; 4] = &[0, 0, 0, 1];
+
+        let mut output = vec![];
+        output.extend_from_slice(HEADER);
+        output.extend_from_slice(&vps);
 
-    std::fs::write("out.hevc", &output).unwrap();
+        output.extend_from_slice(HEADER);
+        output.extend_from_slice(&sps);
 
-    unsafe {
-        if let Some(custom_val) = (source_frame_ref_con as *mut u32).as_mut() {
-            *custom_val = 37;
+        output.extend_from_slice(HEADER);
+        output.extend_from_slice(&pps);
+
+        let mut buffer_offset = 0;
+
+        while buffer_offset < (hevc_data.len() - HEADER.len()) {
+            let mut nal_len = u32::from_ne_bytes([
+                hevc_data[buffer_offset],
+                hevc_data[(buffer_offset + 1)],
+                hevc_data[(buffer_offset + 2)],
+                hevc_data[(buffer_offset + 3)],
+            ]);
+            nal_len = u32::from_be(nal_len);
+            dbg!(nal_len);
+
+            output.extend_from_slice(HEADER);
+            let hevc_offset = buffer_offset + HEADER.len();
+            output.extend_from_slice(&hevc_data[hevc_offset..(hevc_offset + nal_len as usize)]);
+
+            buffer_offset += HEADER.len();
+            buffer_offset += nal_len as usize;
         }
-    }
 
-    dbg!(hevc_data.len());
-}
+        std::mem::forget(vps);
+        std::mem::forget(sps);
+        std::mem::forget(pps);
 
-#[derive(Debug)]
-enum HevcParam {
-    Vps,
-    Sps,
-    Pps,
-}
+        std::fs::write("out.hevc", &output).unwrap();
 
-impl HevcParam {
-    fn index(&self) -> usize {
-        match self {
-            HevcParam::Vps => 0,
-            HevcParam::Sps => 1,
-            HevcParam::Pps => 2,
+        unsafe {
+            if let Some(custom_val) = (source_frame_ref_con as *mut u32).as_mut() {
+                *custom_val = 37;
+            }
         }
-    }
-}
 
-fn get_hevc_param(format: CMFormatDescriptionRef, param: HevcParam) -> Option<Vec<u8>> {
-    let mut param_set_ptr: *const u8 = std::ptr::null_mut();
-    let mut param_set_size: usize = 0;
-    let mut param_set_count: usize = 0;
-    let mut nal_unit_header_length: std::os::raw::c_int = 0;
-
-    let status = unsafe {
-        CMVideoFormatDescriptionGetHEVCParameterSetAtIndex(
-            format,
-            param.index(),
-            &mut param_set_ptr,
-            &mut param_set_size,
-            &mut param_set_count,
-            &mut nal_unit_header_length,
-        )
-    };
+        dbg!(hevc_data.len());
+    }
 
-    println!(
-        "{:?} - size: {}, count: {}, NAL header len: {:?}",
-        param, param_set_size, param_set_count, nal_unit_header_length
-    );
+    #[derive(Debug)]
+    enum HevcParam {
+        Vps,
+        Sps,
+        Pps,
+    }
 
-    if status == 0 {
-        unsafe {
-            let vec = Vec::from_raw_parts(param_set_ptr as *mut _, param_set_size, param_set_size);
-            println!("{:?}", vec);
-            Some(vec)
+    impl HevcParam {
+        fn index(&self) -> usize {
+            match self {
+                HevcParam::Vps => 0,
+                HevcParam::Sps => 1,
+                HevcParam::Pps => 2,
+            }
         }
-    } else {
-        None
     }
-}
 
-fn main() {
-    let frame_width = 1280usize;
-    let frame_height = 720usize;
-
-    let mut compression_ref = std::mem::MaybeUninit::<VTCompressionSessionRef>::uninit();
-
-    let keys: Vec<CFStringRef> =
-        unsafe { vec![kVTVideoEncoderSpecification_RequireHardwareAcceleratedVideoEncoder] };
-    let values: Vec<CFBoolean> = vec![CFBoolean::true_value()];
-
-    let encoder_specification = unsafe {
-        CFDictionaryCreate(
-            std::ptr::null(),
-            std::mem::transmute(keys.as_ptr()),
-            std::mem::transmute(values.as_ptr()),
-            keys.len().to_CFIndex().try_into().unwrap(),
-            &kCFTypeDictionaryKeyCallBacks,
-            &kCFTypeDictionaryValueCallBacks,
-        )
-    };
-
-    // Create the encoder
-    let create_status = unsafe {
-        VTCompressionSessionCreate(
-            std::ptr::null(),       // Allocator
-            frame_width as i32,     // Width
-            frame_height as i32,    // Height
-            kCMVideoCodecType_HEVC, // Codec type
-            encoder_specification,  // Encoder specification,
-            std::ptr::null(),       // Src pixel buffer attributes
-            std::ptr::null(),       // Compressed data allocator
-            Some(encode_callback), // Output callback, pass NULL if you're using VTCompressionSessionEncodeFrameWithOutputHandler
-            std::ptr::null_mut(),  // Client-defined reference value for the output callback
-            compression_ref.as_mut_ptr() as VTCompressionSessionRef,
-        )
-    };
+    fn get_hevc_param(format: CMFormatDescriptionRef, param: HevcParam) -> Option<Vec<u8>> {
+        let mut param_set_ptr: *const u8 = std::ptr::null_mut();
+        let mut param_set_size: usize = 0;
+        let mut param_set_count: usize = 0;
+        let mut nal_unit_header_length: std::os::raw::c_int = 0;
+
+        let status = unsafe {
+            CMVideoFormatDescriptionGetHEVCParameterSetAtIndex(
+                format,
+                param.index(),
+                &mut param_set_ptr,
+                &mut param_set_size,
+                &mut param_set_count,
+                &mut nal_unit_header_length,
+            )
+        };
+
+        println!(
+            "{:?} - size: {}, count: {}, NAL header len: {:?}",
+            param, param_set_size, param_set_count, nal_unit_header_length
+        );
 
-    if create_status != 0 {
-        println!("Failed to create VT Compression Session: {}", create_status);
-        return;
+        if status == 0 {
+            unsafe {
+                let vec =
+                    Vec::from_raw_parts(param_set_ptr as *mut _, param_set_size, param_set_size);
+                println!("{:?}", vec);
+                Some(vec)
+            }
+        } else {
+            None
+        }
     }
 
-    let compression_session = unsafe { compression_ref.assume_init() };
-
-    // Create the frame to encode
-    // let mut frame_data = vec![0u8; (frame_width * frame_height * 4) as usize];
-    let frame_data = make_image_frame(frame_width, frame_height);
-
-    println!("Uncompressed size: {}", frame_data.len());
-
-    let mut pixel_buffer_ref = std::mem::MaybeUninit::<CVPixelBufferRef>::uninit();
-    let k_cvpixel_format_type_32_argb = 0x00000020; // TODO(bschwind) - get this from CoreVideo
-    let pixel_buffer_create_status = unsafe {
-        CVPixelBufferCreateWithBytes(
-            std::ptr::null(),
-            frame_width as usize,
-            frame_height as usize,
-            k_cvpixel_format_type_32_argb,
-            frame_data.as_ptr() as *mut c_void,
-            (4 * frame_width) as usize, // bytes per row
-            None,
-            std::ptr::null_mut(),
-            std::ptr::null(),
-            pixel_buffer_ref.as_mut_ptr() as *mut CVPixelBufferRef,
-        )
-    };
+    pub fn main() {
+        let frame_width = 1280usize;
+        let frame_height = 720usize;
+
+        let mut compression_ref = std::mem::MaybeUninit::<VTCompressionSessionRef>::uninit();
+
+        let keys: Vec<CFStringRef> =
+            unsafe { vec![kVTVideoEncoderSpecification_RequireHardwareAcceleratedVideoEncoder] };
+        let values: Vec<CFBoolean> = vec![CFBoolean::true_value()];
+
+        let encoder_specification = unsafe {
+            CFDictionaryCreate(
+                std::ptr::null(),
+                std::mem::transmute(keys.as_ptr()),
+                std::mem::transmute(values.as_ptr()),
+                keys.len().to_CFIndex().try_into().unwrap(),
+                &kCFTypeDictionaryKeyCallBacks,
+                &kCFTypeDictionaryValueCallBacks,
+            )
+        };
+
+        // Create the encoder
+        let create_status = unsafe {
+            VTCompressionSessionCreate(
+                std::ptr::null(),       // Allocator
+                frame_width as i32,     // Width
+                frame_height as i32,    // Height
+                kCMVideoCodecType_HEVC, // Codec type
+                encoder_specification,  // Encoder specification,
+                std::ptr::null(),       // Src pixel buffer attributes
+                std::ptr::null(),       // Compressed data allocator
+                Some(encode_callback), // Output callback, pass NULL if you're using VTCompressionSessionEncodeFrameWithOutputHandler
+                std::ptr::null_mut(),  // Client-defined reference value for the output callback
+                compression_ref.as_mut_ptr() as VTCompressionSessionRef,
+            )
+        };
+
+        if create_status != 0 {
+            println!("Failed to create VT Compression Session: {}", create_status);
+            return;
+        }
 
-    if pixel_buffer_create_status != 0 {
-        println!("Failed to create Pixel Buffer: {}", pixel_buffer_create_status);
-        return;
-    }
+        let compression_session = unsafe { compression_ref.assume_init() };
+
+        // Create the frame to encode
+        // let mut frame_data = vec![0u8; (frame_width * frame_height * 4) as usize];
+        let frame_data = make_image_frame(frame_width, frame_height);
+
+        println!("Uncompressed size: {}", frame_data.len());
+
+        let mut pixel_buffer_ref = std::mem::MaybeUninit::<CVPixelBufferRef>::uninit();
+        let k_cvpixel_format_type_32_argb = 0x00000020; // TODO(bschwind) - get this from CoreVideo
+        let pixel_buffer_create_status = unsafe {
+            CVPixelBufferCreateWithBytes(
+                std::ptr::null(),
+                frame_width as usize,
+                frame_height as usize,
+                k_cvpixel_format_type_32_argb,
+                frame_data.as_ptr() as *mut c_void,
+                (4 * frame_width) as usize, // bytes per row
+                None,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                pixel_buffer_ref.as_mut_ptr() as *mut CVPixelBufferRef,
+            )
+        };
+
+        if pixel_buffer_create_status != 0 {
+            println!("Failed to create Pixel Buffer: {}", pixel_buffer_create_status);
+            return;
+        }
 
-    let pixel_buffer = unsafe { pixel_buffer_ref.assume_init() };
+        let pixel_buffer = unsafe { pixel_buffer_ref.assume_init() };
 
-    println!("Got a pixel buffer, good to go!");
+        println!("Got a pixel buffer, good to go!");
 
-    let frame_time = CMTime { value: 0i64, timescale: 1i32, flags: 0u32, epoch: 0i64 };
+        let frame_time = CMTime { value: 0i64, timescale: 1i32, flags: 0u32, epoch: 0i64 };
 
-    let invalid_duration = CMTime { value: 0i64, timescale: 0i32, flags: 0u32, epoch: 0i64 };
+        let invalid_duration = CMTime { value: 0i64, timescale: 0i32, flags: 0u32, epoch: 0i64 };
 
-    let mut custom_val = 0u32;
+        let mut custom_val = 0u32;
 
-    let encode_start = std::time::Instant::now();
-    // Encode the frame
-    let encode_status = unsafe {
-        VTCompressionSessionEncodeFrame(
-            compression_session,
-            pixel_buffer,
-            frame_time,                                 // Presentation timestamp
-            invalid_duration,                           // Frame duration
-            std::ptr::null(),                           // Frame Properties
-            &mut custom_val as *mut u32 as *mut c_void, // Source frame ref con
-            std::ptr::null_mut(),                       // Info flags out
-        );
-    };
+        let encode_start = std::time::Instant::now();
+        // Encode the frame
+        let encode_status = unsafe {
+            VTCompressionSessionEncodeFrame(
+                compression_session,
+                pixel_buffer,
+                frame_time,                                 // Presentation timestamp
+                invalid_duration,                           // Frame duration
+                std::ptr::null(),                           // Frame Properties
+                &mut custom_val as *mut u32 as *mut c_void, // Source frame ref con
+                std::ptr::null_mut(),                       // Info flags out
+            );
+        };
 
-    println!("Encode status: {:?}", encode_status);
+        println!("Encode status: {:?}", encode_status);
 
-    // Wait for the encode to finish.
-    let _ = unsafe {
-        VTCompressionSessionCompleteFrames(compression_session, invalid_duration);
-    };
+        // Wait for the encode to finish.
+        let _ = unsafe {
+            VTCompressionSessionCompleteFrames(compression_session, invalid_duration);
+        };
 
-    println!("Took: {:?}", encode_start.elapsed());
-    println!("Our custom value is {}", custom_val);
-}
+        println!("Took: {:?}", encode_start.elapsed());
+        println!("Our custom value is {}", custom_val);
+    }
 
-fn make_image_frame(width: usize, height: usize) -> Vec<u8> {
-    let mut frame = vec![0u8; width * height * 4];
+    fn make_image_frame(width: usize, height: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; width * height * 4];
 
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_offset = (y * width * 4) + (x * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel_offset = (y * width * 4) + (x * 4);
 
-            let width_factor = x as f32 / width as f32;
-            let height_factor = y as f32 / height as f32;
+                let width_factor = x as f32 / width as f32;
+                let height_factor = y as f32 / height as f32;
 
-            frame[pixel_offset] = 255; // Alpha
-            frame[pixel_offset + 1] = (width_factor * 255.0) as u8; // Red
-            frame[pixel_offset + 2] = 255; // Green
-            frame[pixel_offset + 3] = (height_factor * 255.0) as u8; // Blue
+                frame[pixel_offset] = 255; // Alpha
+                frame[pixel_offset + 1] = (width_factor * 255.0) as u8; // Red
+                frame[pixel_offset + 2] = 255; // Green
+                frame[pixel_offset + 3] = (height_factor * 255.0) as u8; // Blue
+            }
         }
+
+        frame
     }
+}
+
+fn main() {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    example::main();
 
-    frame
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    eprintln!("This example requires macOS or iOS");
 }
@@ -5,7 +5,7 @@
 
 use core_foundation::{
     array::CFArrayRef,
-    base::{Boolean, CFAllocatorRef, CFIndex, OSStatus},
+    base::{Boolean, CFAllocatorRef, CFIndex, CFTypeRef, OSStatus},
     dictionary::CFDictionaryRef,
     string::CFStringRef,
 };
@@ -27,6 +27,8 @@ pub type VTEncodeInfoFlags = u32;
 pub type VTDecodeInfoFlags = u32;
 pub type VTDecodeFrameFlags = u32;
 
+pub const kVTVideoEncoderMalfunctionErr: OSStatus = -12911;
+
 // CoreMedia Types
 pub type FourCharCode = u32;
 pub type OSType = FourCharCode;
@@ -70,6 +72,7 @@ const fn fourcc(data: &[u8; 4]) -> u32 {
 }
 
 pub const kCMVideoCodecType_HEVC: CMVideoCodecType = fourcc(b"hvc1");
+pub const kCMVideoCodecType_HEVCWithAlpha: CMVideoCodecType = fourcc(b"muxa");
 // TODO - Define all others listed here:
 // https://developer.apple.com/documentation/coremedia/cmvideocodectype?language=objc
 
@@ -78,7 +81,29 @@ pub type CVReturn = i32;
 pub type CVOptionFlags = u64;
 
 pub const kCVPixelBufferLock_ReadOnly: CVOptionFlags = 0x00000001;
-pub const kCVPixelFormatType_32BGRA: u32 = fourcc(b"BGRA");
+pub const kCVPixelFormatType_32ARGB: OSType = 0x00000020;
+pub const kCVPixelFormatType_32BGRA: OSType = fourcc(b"BGRA");
+pub const kCVPixelFormatType_32RGBA: OSType = fourcc(b"RGBA");
+pub const kCVPixelFormatType_ARGB2101010LEPacked: OSType = fourcc(b"l10r");
+pub const kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange: OSType = fourcc(b"420v");
+pub const kCVPixelFormatType_420YpCbCr8BiPlanarFullRange: OSType = fourcc(b"420f");
+pub const kCVPixelFormatType_420YpCbCr8VideoRange_8A_TriPlanar: OSType = fourcc(b"v0a8");
+pub const kCVPixelFormatType_420YpCbCr8Planar: OSType = fourcc(b"y420");
+pub const kCVPixelFormatType_420YpCbCr8PlanarFullRange: OSType = fourcc(b"f420");
+pub const kCVPixelFormatType_444YpCbCr8BiPlanarVideoRange: OSType = fourcc(b"444v");
+pub const kCVPixelFormatType_444YpCbCr8BiPlanarFullRange: OSType = fourcc(b"444f");
+pub const kCVPixelFormatType_420YpCbCr10BiPlanarVideoRange: OSType = fourcc(b"x420");
+pub const kCVPixelFormatType_420YpCbCr10BiPlanarFullRange: OSType = fourcc(b"xf20");
+pub const kCVPixelFormatType_422YpCbCr10BiPlanarVideoRange: OSType = fourcc(b"x422");
+pub const kCVPixelFormatType_422YpCbCr10BiPlanarFullRange: OSType = fourcc(b"xf22");
+pub const kCVPixelFormatType_444YpCbCr10BiPlanarVideoRange: OSType = fourcc(b"x444");
+pub const kCVPixelFormatType_444YpCbCr10BiPlanarFullRange: OSType = fourcc(b"xf44");
+pub const kCVPixelFormatType_422YpCbCr10: OSType = fourcc(b"v210");
+pub const kCVPixelFormatType_422YpCbCr8_yuvs: OSType = fourcc(b"yuvs");
+pub const kCVPixelFormatType_422YpCbCr8: OSType = fourcc(b"2vuy");
+pub const kCVPixelFormatType_OneComponent8: OSType = fourcc(b"L008");
+pub const kCVPixelFormatType_OneComponent16: OSType = fourcc(b"L016");
+pub const kCVPixelFormatType_64RGBALE: OSType = fourcc(b"l64r");
 
 #[repr(C)]
 pub struct CVBuffer {
@@ -92,6 +117,14 @@ pub type CVPixelBufferRef = CVImageBufferRef;
 pub type CVPixelBufferReleaseBytesCallback =
     extern "C" fn(release_ref_con: *mut c_void, base_address: *const c_void);
 
+pub type CVPixelBufferReleasePlanarBytesCallback = extern "C" fn(
+    release_ref_con: *mut c_void,
+    data_ptr: *const c_void,
+    data_size: usize,
+    number_of_planes: usize,
+    plane_addresses: *const *const c_void,
+);
+
 // Callback Types
 pub type VTCompressionOutputCallback = extern "C" fn(
     output_callback_ref_con: *mut c_void,
@@ -127,8 +160,8 @@ pub type CMSampleBufferMakeDataReadyCallback = extern "C" fn(
 #[repr(C)]
 #[derive(Debug)]
 pub struct CGSize {
-    width: f64,
-    height: f64,
+    pub width: f64,
+    pub height: f64,
 }
 
 // Encoding
@@ -165,6 +198,40 @@ extern "C" {
         complete_until_presentation_timestamp: CMTime,
     ) -> OSStatus;
 
+    // Session properties
+    pub static kVTCompressionPropertyKey_ColorPrimaries: CFStringRef;
+    pub static kVTCompressionPropertyKey_TransferFunction: CFStringRef;
+    pub static kVTCompressionPropertyKey_YCbCrMatrix: CFStringRef;
+    pub static kVTCompressionPropertyKey_MasteringDisplayColorVolume: CFStringRef;
+    pub static kVTCompressionPropertyKey_ContentLightLevelInfo: CFStringRef;
+    pub static kVTCompressionPropertyKey_TargetQualityForAlpha: CFStringRef;
+    pub static kVTCompressionPropertyKey_ProfileLevel: CFStringRef;
+    pub static kVTCompressionPropertyKey_AverageBitRate: CFStringRef;
+    pub static kVTCompressionPropertyKey_DataRateLimits: CFStringRef;
+    pub static kVTCompressionPropertyKey_Quality: CFStringRef;
+    pub static kVTCompressionPropertyKey_MaxKeyFrameInterval: CFStringRef;
+    pub static kVTCompressionPropertyKey_MaxKeyFrameIntervalDuration: CFStringRef;
+    pub static kVTCompressionPropertyKey_RealTime: CFStringRef;
+    pub static kVTCompressionPropertyKey_AllowFrameReordering: CFStringRef;
+    pub static kVTCompressionPropertyKey_ExpectedFrameRate: CFStringRef;
+    pub static kVTCompressionPropertyKey_MaxFrameDelayCount: CFStringRef;
+
+    // Profile levels
+    pub static kVTProfileLevel_HEVC_Main_AutoLevel: CFStringRef;
+    pub static kVTProfileLevel_HEVC_Main10_AutoLevel: CFStringRef;
+    pub static kVTProfileLevel_HEVC_Main42210_AutoLevel: CFStringRef;
+
+    pub fn VTSessionSetProperty(
+        session: CFTypeRef,
+        property_key: CFStringRef,
+        property_value: CFTypeRef,
+    ) -> OSStatus;
+
+    pub fn VTSessionSetProperties(
+        session: CFTypeRef,
+        property_dictionary: CFDictionaryRef,
+    ) -> OSStatus;
+
     // Decoding
     pub static kVTVideoDecoderSpecification_RequireHardwareAcceleratedVideoDecoder: CFStringRef;
 
@@ -202,6 +269,9 @@ extern "C" {
     pub fn CMSampleBufferGetFormatDescription(
         sample_buffer: CMSampleBufferRef,
     ) -> CMFormatDescriptionRef;
+    pub fn CMSampleBufferGetPresentationTimeStamp(sample_buffer: CMSampleBufferRef) -> CMTime;
+    pub fn CMSampleBufferGetDecodeTimeStamp(sample_buffer: CMSampleBufferRef) -> CMTime;
+    pub fn CMSampleBufferGetDuration(sample_buffer: CMSampleBufferRef) -> CMTime;
     pub fn CMSampleBufferCreate(
         allocator: CFAllocatorRef,
         data: CMBlockBufferRef,
@@ -238,6 +308,14 @@ extern "C" {
         extensions: CFDictionaryRef,
         format_description_out: CMVideoFormatDescriptionRef,
     ) -> OSStatus;
+    pub fn CMVideoFormatDescriptionCreateFromH264ParameterSets(
+        allocator: CFAllocatorRef,
+        parameter_set_count: usize,
+        parameter_set_pointers: *const *const u8,
+        parameter_set_sizes: *const usize,
+        nal_unit_header_length: i32,
+        format_description_out: CMVideoFormatDescriptionRef,
+    ) -> OSStatus;
     pub fn CMVideoFormatDescriptionCreateFromHEVCParameterSets(
         allocator: CFAllocatorRef,
         parameter_set_count: usize,
@@ -270,6 +348,26 @@ extern "C" {
     pub static kCVPixelBufferPixelFormatTypeKey: CFStringRef;
     pub static kCVPixelBufferIOSurfacePropertiesKey: CFStringRef;
 
+    // Colour attachment values
+    pub static kCVImageBufferColorPrimaries_ITU_R_709_2: CFStringRef;
+    pub static kCVImageBufferColorPrimaries_SMPTE_C: CFStringRef;
+    pub static kCVImageBufferColorPrimaries_EBU_3213: CFStringRef;
+    pub static kCVImageBufferColorPrimaries_ITU_R_2020: CFStringRef;
+    pub static kCVImageBufferColorPrimaries_DCI_P3: CFStringRef;
+    pub static kCVImageBufferColorPrimaries_P3_D65: CFStringRef;
+
+    pub static kCVImageBufferTransferFunction_ITU_R_709_2: CFStringRef;
+    pub static kCVImageBufferTransferFunction_SMPTE_240M_1995: CFStringRef;
+    pub static kCVImageBufferTransferFunction_Linear: CFStringRef;
+    pub static kCVImageBufferTransferFunction_ITU_R_2020: CFStringRef;
+    pub static kCVImageBufferTransferFunction_SMPTE_ST_2084_PQ: CFStringRef;
+    pub static kCVImageBufferTransferFunction_ITU_R_2100_HLG: CFStringRef;
+
+    pub static kCVImageBufferYCbCrMatrix_ITU_R_709_2: CFStringRef;
+    pub static kCVImageBufferYCbCrMatrix_ITU_R_601_4: CFStringRef;
+    pub static kCVImageBufferYCbCrMatrix_SMPTE_240M_1995: CFStringRef;
+    pub static kCVImageBufferYCbCrMatrix_ITU_R_2020: CFStringRef;
+
     pub fn CVPixelBufferCreateWithBytes(
         allocator: CFAllocatorRef,
         width: usize,
@@ -282,6 +380,23 @@ extern "C" {
         pixel_buffer_attributes: CFDictionaryRef,
         pixel_buffer_out: *mut CVPixelBufferRef,
     ) -> CVReturn;
+    pub fn CVPixelBufferCreateWithPlanarBytes(
+        allocator: CFAllocatorRef,
+        width: usize,
+        height: usize,
+        pixel_format_type: OSType,
+        data_ptr: *mut c_void,
+        data_size: usize,
+        number_of_planes: usize,
+        plane_base_address: *mut *mut c_void,
+        plane_width: *mut usize,
+        plane_height: *mut usize,
+        plane_bytes_per_row: *mut usize,
+        release_callback: Option<CVPixelBufferReleasePlanarBytesCallback>,
+        release_ref_con: *mut c_void,
+        pixel_buffer_attributes: CFDictionaryRef,
+        pixel_buffer_out: *mut CVPixelBufferRef,
+    ) -> CVReturn;
     pub fn CVImageBufferGetEncodedSize(buffer: CVImageBufferRef) -> CGSize;
     pub fn CVImageBufferGetDisplaySize(buffer: CVImageBufferRef) -> CGSize;
     pub fn CVPixelBufferGetDataSize(buffer: CVImageBufferRef) -> usize;
@@ -295,6 +410,7 @@ extern "C" {
         flags: CVOptionFlags,
     ) -> CVReturn;
     pub fn CVPixelBufferGetBaseAddress(buffer: CVImageBufferRef) -> *const c_void;
+    pub fn CVPixelBufferGetBytesPerRow(buffer: CVImageBufferRef) -> usize;
 
     // Planar Functions
     pub fn CVPixelBufferIsPlanar(buffer: CVImageBufferRef) -> bool;
@@ -0,0 +1,107 @@
+//! HEVC with alpha, as VideoToolbox's `muxa` codec produces it: the colour
+//! picture in the base layer and an auxiliary alpha picture in a second layer,
+//! told apart by the NAL header's `nuh_layer_id`.
+
+use crate::{bitstream::BitReader, sei::read_sei_nal, NalIterator, VideoCodec};
+
+const ALPHA_CHANNEL_INFO_PAYLOAD: u32 = 165;
+
+/// The `nuh_layer_id` of an HEVC NAL unit, 0 for the base layer.
+pub fn nal_layer_id(nal: &[u8]) -> Option<u8> {
+    match nal {
+        [first, second, ..] => Some((first & 1) << 5 | second >> 3),
+        _ => None,
+    }
+}
+
+/// Whether an Annex B access unit has NAL units outside the base layer.
+pub fn has_alpha_layer(access_unit: &[u8]) -> bool {
+    NalIterator::new(access_unit).any(|nal| nal_layer_id(nal.data).is_some_and(|id| id > 0))
+}
+
+/// An Annex B access unit split by layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayeredAccessUnit {
+    /// NAL units with `nuh_layer_id` 0, which a decoder without alpha
+    /// support can decode alone.
+    pub base: Vec<u8>,
+    /// NAL units of every other layer.
+    pub alpha: Vec<u8>,
+}
+
+/// Splits an Annex B access unit into its base and alpha layers, keeping
+/// start codes and order within each.
+pub fn split_layers(access_unit: &[u8]) -> LayeredAccessUnit {
+    let mut layers = LayeredAccessUnit::default();
+
+    for nal in NalIterator::new(access_unit) {
+        let layer = match nal_layer_id(nal.data) {
+            Some(0) | None => &mut layers.base,
+            Some(_) => &mut layers.alpha,
+        };
+
+        layer.extend_from_slice(&[0, 0, 0, 1]);
+        layer.extend_from_slice(nal.data);
+    }
+
+    layers
+}
+
+/// How the alpha samples apply to the primary picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaUse {
+    /// `alpha_channel_use_idc` 1: the colour samples are premultiplied by
+    /// alpha.
+    Premultiplied,
+    /// `alpha_channel_use_idc` 0: the colour samples are straight, to be
+    /// multiplied when compositing.
+    Straight,
+    Unspecified,
+}
+
+/// The alpha channel information SEI (HEVC section F.14.2.8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlphaChannelInfo {
+    pub alpha_use: AlphaUse,
+    pub bit_depth: u8,
+    /// The sample value meaning fully transparent.
+    pub transparent_value: u32,
+    /// The sample value meaning fully opaque.
+    pub opaque_value: u32,
+}
+
+impl AlphaChannelInfo {
+    /// Reads the alpha channel information from an SEI NAL unit. Returns
+    /// `None` if there is none, or it cancels an earlier one.
+    pub fn from_sei_nal(nal: &[u8]) -> Option<Self> {
+        let messages = read_sei_nal(VideoCodec::Hevc, nal)?;
+        let (_, payload) = messages
+            .iter()
+            .find(|(payload_type, _)| *payload_type == ALPHA_CHANNEL_INFO_PAYLOAD)?;
+        let mut reader = BitReader::new(payload);
+
+        // alpha_channel_cancel_flag
+        if reader.read_bit()? {
+            return None;
+        }
+
+        let alpha_use = match reader.read_bits(3)? {
+            0 => AlphaUse::Straight,
+            1 => AlphaUse::Premultiplied,
+            _ => AlphaUse::Unspecified,
+        };
+        let bit_depth = reader.read_bits(3)? + 8;
+
+        Some(Self {
+            alpha_use,
+            bit_depth: bit_depth as u8,
+            transparent_value: reader.read_bits(bit_depth + 1)?,
+            opaque_value: reader.read_bits(bit_depth + 1)?,
+        })
+    }
+
+    /// The first alpha channel information in an Annex B access unit.
+    pub fn from_access_unit(access_unit: &[u8]) -> Option<Self> {
+        NalIterator::new(access_unit).find_map(|nal| Self::from_sei_nal(nal.data))
+    }
+}
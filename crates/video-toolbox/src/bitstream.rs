@@ -0,0 +1,105 @@
+//! Bit level reading of RBSP data (H.264 / HEVC section 7.2).
+
+/// Strips emulation prevention bytes (`00 00 03` -> `00 00`) from a NAL unit.
+pub(crate) fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+
+    for &byte in data {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+
+    rbsp
+}
+
+/// Inserts emulation prevention bytes so `rbsp` contains no start code.
+pub(crate) fn add_emulation_prevention(rbsp: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(rbsp.len() + rbsp.len() / 64);
+    let mut zeros = 0;
+
+    for &byte in rbsp {
+        if zeros >= 2 && byte <= 3 {
+            data.push(3);
+            zeros = 0;
+        }
+
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        data.push(byte);
+    }
+
+    data
+}
+
+/// MSB-first reader over an RBSP. Reads past the end return `None`.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.data.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+
+        Some(bit == 1)
+    }
+
+    /// Reads up to 32 bits as an unsigned integer, `u(n)` in the specs.
+    pub(crate) fn read_bits(&mut self, count: u32) -> Option<u32> {
+        debug_assert!(count <= 32);
+        let mut value = 0u64;
+
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+
+        Some(value as u32)
+    }
+
+    pub(crate) fn skip_bits(&mut self, count: usize) -> Option<()> {
+        if self.position + count > self.data.len() * 8 {
+            return None;
+        }
+
+        self.position += count;
+        Some(())
+    }
+
+    /// Reads an Exp-Golomb coded unsigned integer, `ue(v)` in the specs.
+    pub(crate) fn read_ue(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+
+        while !self.read_bit()? {
+            leading_zeros += 1;
+
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+
+        let suffix = self.read_bits(leading_zeros)? as u64;
+        Some(((1u64 << leading_zeros) - 1 + suffix) as u32)
+    }
+
+    /// Reads an Exp-Golomb coded signed integer, `se(v)` in the specs.
+    pub(crate) fn read_se(&mut self) -> Option<i32> {
+        let code = self.read_ue()? as i64;
+
+        if code % 2 == 1 {
+            Some(((code + 1) / 2) as i32)
+        } else {
+            Some((-code / 2) as i32)
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Static HDR metadata: SMPTE ST 2086 mastering display colour volume and
+//! CTA-861.3 content light level. Both serialise identically as SEI payloads
+//! (H.264 / HEVC annex D), MP4 `mdcv` / `clli` boxes and VideoToolbox
+//! properties.
+
+use super::TransferCharacteristics;
+use crate::{
+    sei::{read_sei_nal, write_sei_nal},
+    NalIterator, VideoCodec,
+};
+
+const MASTERING_DISPLAY_PAYLOAD: u32 = 137;
+const CONTENT_LIGHT_LEVEL_PAYLOAD: u32 = 144;
+const ALTERNATIVE_TRANSFER_PAYLOAD: u32 = 147;
+
+/// The colour volume of the display the content was graded on, in SEI units:
+/// chromaticities in steps of 0.00002 and luminance in 0.0001 cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MasteringDisplay {
+    /// `(x, y)` of the green, blue and red primaries, in that order.
+    pub primaries: [(u16, u16); 3],
+    pub white_point: (u16, u16),
+    pub max_luminance: u32,
+    pub min_luminance: u32,
+}
+
+impl MasteringDisplay {
+    /// A P3 D65 display from 0.0001 to 1000 cd/m², as used for most HDR10
+    /// masters.
+    pub const P3_D65_1000_NITS: MasteringDisplay = MasteringDisplay {
+        primaries: [(13250, 34500), (7500, 3000), (34000, 16000)],
+        white_point: (15635, 16450),
+        max_luminance: 10_000_000,
+        min_luminance: 1,
+    };
+
+    pub fn to_bytes(&self) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        let points = self.primaries.iter().chain([&self.white_point]);
+
+        for (chunk, &(x, y)) in bytes.chunks_exact_mut(4).zip(points) {
+            chunk[..2].copy_from_slice(&x.to_be_bytes());
+            chunk[2..].copy_from_slice(&y.to_be_bytes());
+        }
+
+        bytes[16..20].copy_from_slice(&self.max_luminance.to_be_bytes());
+        bytes[20..].copy_from_slice(&self.min_luminance.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; 24] = bytes.get(..24)?.try_into().ok()?;
+        let u16_at = |index: usize| u16::from_be_bytes([bytes[index], bytes[index + 1]]);
+        let u32_at = |index: usize| u32::from_be_bytes(bytes[index..index + 4].try_into().unwrap());
+        let point = |index: usize| (u16_at(index), u16_at(index + 2));
+
+        Some(Self {
+            primaries: [point(0), point(4), point(8)],
+            white_point: point(12),
+            max_luminance: u32_at(16),
+            min_luminance: u32_at(20),
+        })
+    }
+}
+
+/// Content light levels in cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ContentLightLevel {
+    /// MaxCLL, the brightest pixel in the stream.
+    pub max_content_light_level: u16,
+    /// MaxFALL, the brightest frame average.
+    pub max_frame_average_light_level: u16,
+}
+
+impl ContentLightLevel {
+    pub fn new(max_content_light_level: u16, max_frame_average_light_level: u16) -> Self {
+        Self { max_content_light_level, max_frame_average_light_level }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let mut bytes = [0; 4];
+        bytes[..2].copy_from_slice(&self.max_content_light_level.to_be_bytes());
+        bytes[2..].copy_from_slice(&self.max_frame_average_light_level.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..4)?;
+
+        Some(Self {
+            max_content_light_level: u16::from_be_bytes([bytes[0], bytes[1]]),
+            max_frame_average_light_level: u16::from_be_bytes([bytes[2], bytes[3]]),
+        })
+    }
+}
+
+/// Static HDR metadata for a stream. HDR10 carries the mastering display and
+/// light levels; HLG streams usually carry only light levels, optionally with
+/// an alternative transfer so HLG-aware players can override a BT.2020 SDR
+/// transfer in the VUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HdrMetadata {
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light_level: Option<ContentLightLevel>,
+    /// `preferred_transfer_characteristics` from the alternative transfer
+    /// characteristics SEI.
+    pub alternative_transfer: Option<TransferCharacteristics>,
+}
+
+impl HdrMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Takes every field `other` has, keeping ours where it has none.
+    pub fn update(&mut self, other: HdrMetadata) {
+        self.mastering_display = other.mastering_display.or(self.mastering_display);
+        self.content_light_level = other.content_light_level.or(self.content_light_level);
+        self.alternative_transfer = other.alternative_transfer.or(self.alternative_transfer);
+    }
+
+    /// A prefix SEI NAL unit, header included, carrying every present field.
+    /// Returns `None` if there is nothing to carry.
+    pub fn to_sei_nal(&self, codec: VideoCodec) -> Option<Vec<u8>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut messages = vec![];
+
+        if let Some(mastering_display) = self.mastering_display {
+            messages.push((MASTERING_DISPLAY_PAYLOAD, mastering_display.to_bytes().to_vec()));
+        }
+
+        if let Some(content_light_level) = self.content_light_level {
+            messages.push((CONTENT_LIGHT_LEVEL_PAYLOAD, content_light_level.to_bytes().to_vec()));
+        }
+
+        if let Some(transfer) = self.alternative_transfer {
+            messages.push((ALTERNATIVE_TRANSFER_PAYLOAD, vec![transfer.into()]));
+        }
+
+        Some(write_sei_nal(codec, &messages))
+    }
+
+    /// Reads the HDR messages of a prefix SEI NAL unit, header included.
+    /// Returns `None` if `nal` is not an SEI or is malformed.
+    pub fn from_sei_nal(codec: VideoCodec, nal: &[u8]) -> Option<Self> {
+        let mut metadata = Self::default();
+
+        for (payload_type, payload) in read_sei_nal(codec, nal)? {
+            match payload_type {
+                MASTERING_DISPLAY_PAYLOAD => {
+                    metadata.mastering_display = Some(MasteringDisplay::from_bytes(&payload)?);
+                },
+                CONTENT_LIGHT_LEVEL_PAYLOAD => {
+                    metadata.content_light_level = Some(ContentLightLevel::from_bytes(&payload)?);
+                },
+                ALTERNATIVE_TRANSFER_PAYLOAD => {
+                    metadata.alternative_transfer = Some((*payload.first()?).into());
+                },
+                _ => {},
+            }
+        }
+
+        Some(metadata)
+    }
+
+    /// Collects the HDR messages of every SEI in an Annex B access unit.
+    pub fn from_access_unit(codec: VideoCodec, access_unit: &[u8]) -> Self {
+        let mut metadata = Self::default();
+
+        for nal in NalIterator::new(access_unit) {
+            if let Some(sei) = Self::from_sei_nal(codec, nal.data) {
+                metadata.update(sei);
+            }
+        }
+
+        metadata
+    }
+}
@@ -0,0 +1,309 @@
+//! Colour metadata as ITU-T H.273 code points, shared by the SPS VUI, MP4
+//! `colr` boxes and VideoToolbox.
+
+use super::{ChromaSiting, Matrix};
+
+/// Colour primaries (H.273 table 2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorPrimaries {
+    Bt709,
+    #[default]
+    Unspecified,
+    Bt470M,
+    Bt470Bg,
+    Smpte170M,
+    Smpte240M,
+    Film,
+    Bt2020,
+    /// SMPTE ST 428-1 (CIE 1931 XYZ).
+    Smpte428,
+    /// SMPTE RP 431-2 (DCI-P3).
+    DciP3,
+    /// SMPTE EG 432-1 (Display P3).
+    DisplayP3,
+    Ebu3213,
+    Other(u8),
+}
+
+impl From<u8> for ColorPrimaries {
+    fn from(code: u8) -> Self {
+        match code {
+            1 => ColorPrimaries::Bt709,
+            2 => ColorPrimaries::Unspecified,
+            4 => ColorPrimaries::Bt470M,
+            5 => ColorPrimaries::Bt470Bg,
+            6 => ColorPrimaries::Smpte170M,
+            7 => ColorPrimaries::Smpte240M,
+            8 => ColorPrimaries::Film,
+            9 => ColorPrimaries::Bt2020,
+            10 => ColorPrimaries::Smpte428,
+            11 => ColorPrimaries::DciP3,
+            12 => ColorPrimaries::DisplayP3,
+            22 => ColorPrimaries::Ebu3213,
+            code => ColorPrimaries::Other(code),
+        }
+    }
+}
+
+impl From<ColorPrimaries> for u8 {
+    fn from(primaries: ColorPrimaries) -> Self {
+        match primaries {
+            ColorPrimaries::Bt709 => 1,
+            ColorPrimaries::Unspecified => 2,
+            ColorPrimaries::Bt470M => 4,
+            ColorPrimaries::Bt470Bg => 5,
+            ColorPrimaries::Smpte170M => 6,
+            ColorPrimaries::Smpte240M => 7,
+            ColorPrimaries::Film => 8,
+            ColorPrimaries::Bt2020 => 9,
+            ColorPrimaries::Smpte428 => 10,
+            ColorPrimaries::DciP3 => 11,
+            ColorPrimaries::DisplayP3 => 12,
+            ColorPrimaries::Ebu3213 => 22,
+            ColorPrimaries::Other(code) => code,
+        }
+    }
+}
+
+/// Transfer characteristics (H.273 table 3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TransferCharacteristics {
+    Bt709,
+    #[default]
+    Unspecified,
+    Gamma22,
+    Gamma28,
+    Smpte170M,
+    Smpte240M,
+    Linear,
+    Log100,
+    Log316,
+    Iec61966_2_4,
+    Bt1361,
+    /// IEC 61966-2-1 (sRGB).
+    Srgb,
+    Bt2020_10,
+    Bt2020_12,
+    /// SMPTE ST 2084 perceptual quantizer, used by HDR10.
+    Pq,
+    Smpte428,
+    /// ARIB STD-B67 hybrid log-gamma.
+    Hlg,
+    Other(u8),
+}
+
+impl From<u8> for TransferCharacteristics {
+    fn from(code: u8) -> Self {
+        match code {
+            1 => TransferCharacteristics::Bt709,
+            2 => TransferCharacteristics::Unspecified,
+            4 => TransferCharacteristics::Gamma22,
+            5 => TransferCharacteristics::Gamma28,
+            6 => TransferCharacteristics::Smpte170M,
+            7 => TransferCharacteristics::Smpte240M,
+            8 => TransferCharacteristics::Linear,
+            9 => TransferCharacteristics::Log100,
+            10 => TransferCharacteristics::Log316,
+            11 => TransferCharacteristics::Iec61966_2_4,
+            12 => TransferCharacteristics::Bt1361,
+            13 => TransferCharacteristics::Srgb,
+            14 => TransferCharacteristics::Bt2020_10,
+            15 => TransferCharacteristics::Bt2020_12,
+            16 => TransferCharacteristics::Pq,
+            17 => TransferCharacteristics::Smpte428,
+            18 => TransferCharacteristics::Hlg,
+            code => TransferCharacteristics::Other(code),
+        }
+    }
+}
+
+impl From<TransferCharacteristics> for u8 {
+    fn from(transfer: TransferCharacteristics) -> Self {
+        match transfer {
+            TransferCharacteristics::Bt709 => 1,
+            TransferCharacteristics::Unspecified => 2,
+            TransferCharacteristics::Gamma22 => 4,
+            TransferCharacteristics::Gamma28 => 5,
+            TransferCharacteristics::Smpte170M => 6,
+            TransferCharacteristics::Smpte240M => 7,
+            TransferCharacteristics::Linear => 8,
+            TransferCharacteristics::Log100 => 9,
+            TransferCharacteristics::Log316 => 10,
+            TransferCharacteristics::Iec61966_2_4 => 11,
+            TransferCharacteristics::Bt1361 => 12,
+            TransferCharacteristics::Srgb => 13,
+            TransferCharacteristics::Bt2020_10 => 14,
+            TransferCharacteristics::Bt2020_12 => 15,
+            TransferCharacteristics::Pq => 16,
+            TransferCharacteristics::Smpte428 => 17,
+            TransferCharacteristics::Hlg => 18,
+            TransferCharacteristics::Other(code) => code,
+        }
+    }
+}
+
+/// Matrix coefficients (H.273 table 4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MatrixCoefficients {
+    /// RGB, or YZX for SMPTE ST 428-1.
+    Identity,
+    Bt709,
+    #[default]
+    Unspecified,
+    Fcc,
+    Bt470Bg,
+    Smpte170M,
+    Smpte240M,
+    YCgCo,
+    Bt2020Ncl,
+    Bt2020Cl,
+    Smpte2085,
+    ChromaDerivedNcl,
+    ChromaDerivedCl,
+    ICtCp,
+    Other(u8),
+}
+
+impl From<u8> for MatrixCoefficients {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => MatrixCoefficients::Identity,
+            1 => MatrixCoefficients::Bt709,
+            2 => MatrixCoefficients::Unspecified,
+            4 => MatrixCoefficients::Fcc,
+            5 => MatrixCoefficients::Bt470Bg,
+            6 => MatrixCoefficients::Smpte170M,
+            7 => MatrixCoefficients::Smpte240M,
+            8 => MatrixCoefficients::YCgCo,
+            9 => MatrixCoefficients::Bt2020Ncl,
+            10 => MatrixCoefficients::Bt2020Cl,
+            11 => MatrixCoefficients::Smpte2085,
+            12 => MatrixCoefficients::ChromaDerivedNcl,
+            13 => MatrixCoefficients::ChromaDerivedCl,
+            14 => MatrixCoefficients::ICtCp,
+            code => MatrixCoefficients::Other(code),
+        }
+    }
+}
+
+impl From<MatrixCoefficients> for u8 {
+    fn from(matrix: MatrixCoefficients) -> Self {
+        match matrix {
+            MatrixCoefficients::Identity => 0,
+            MatrixCoefficients::Bt709 => 1,
+            MatrixCoefficients::Unspecified => 2,
+            MatrixCoefficients::Fcc => 4,
+            MatrixCoefficients::Bt470Bg => 5,
+            MatrixCoefficients::Smpte170M => 6,
+            MatrixCoefficients::Smpte240M => 7,
+            MatrixCoefficients::YCgCo => 8,
+            MatrixCoefficients::Bt2020Ncl => 9,
+            MatrixCoefficients::Bt2020Cl => 10,
+            MatrixCoefficients::Smpte2085 => 11,
+            MatrixCoefficients::ChromaDerivedNcl => 12,
+            MatrixCoefficients::ChromaDerivedCl => 13,
+            MatrixCoefficients::ICtCp => 14,
+            MatrixCoefficients::Other(code) => code,
+        }
+    }
+}
+
+impl MatrixCoefficients {
+    /// The matrix [`super::ColorConversion`] uses for these coefficients, if it
+    /// supports them.
+    pub fn conversion_matrix(self) -> Option<Matrix> {
+        match self {
+            MatrixCoefficients::Bt709 => Some(Matrix::Bt709),
+            MatrixCoefficients::Bt470Bg | MatrixCoefficients::Smpte170M => Some(Matrix::Bt601),
+            MatrixCoefficients::Bt2020Ncl => Some(Matrix::Bt2020),
+            _ => None,
+        }
+    }
+}
+
+impl From<Matrix> for MatrixCoefficients {
+    fn from(matrix: Matrix) -> Self {
+        match matrix {
+            Matrix::Bt601 => MatrixCoefficients::Smpte170M,
+            Matrix::Bt709 => MatrixCoefficients::Bt709,
+            Matrix::Bt2020 => MatrixCoefficients::Bt2020Ncl,
+        }
+    }
+}
+
+/// How a stream's samples map to colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ColorInfo {
+    pub primaries: ColorPrimaries,
+    pub transfer: TransferCharacteristics,
+    pub matrix: MatrixCoefficients,
+    /// Whether samples use the full range, rather than 16-235 for 8-bit luma.
+    pub full_range: bool,
+    /// Chroma siting for 4:2:0, from `chroma_sample_loc_type_top_field`.
+    pub chroma_location: ChromaSiting,
+}
+
+impl ColorInfo {
+    /// Nothing known, as when a stream has no VUI video signal info.
+    pub const UNSPECIFIED: ColorInfo = ColorInfo {
+        primaries: ColorPrimaries::Unspecified,
+        transfer: TransferCharacteristics::Unspecified,
+        matrix: MatrixCoefficients::Unspecified,
+        full_range: false,
+        chroma_location: ChromaSiting::Left,
+    };
+
+    pub const BT709: ColorInfo = ColorInfo {
+        primaries: ColorPrimaries::Bt709,
+        transfer: TransferCharacteristics::Bt709,
+        matrix: MatrixCoefficients::Bt709,
+        full_range: false,
+        chroma_location: ChromaSiting::Left,
+    };
+
+    /// Whether any of the primaries, transfer or matrix are known.
+    pub fn is_specified(&self) -> bool {
+        self.primaries != ColorPrimaries::Unspecified
+            || self.transfer != TransferCharacteristics::Unspecified
+            || self.matrix != MatrixCoefficients::Unspecified
+    }
+
+    /// Whether the transfer is PQ or HLG.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.transfer, TransferCharacteristics::Pq | TransferCharacteristics::Hlg)
+    }
+
+    /// The conversion for these coefficients and siting, defaulting to BT.709
+    /// when the matrix is unspecified or unsupported.
+    pub fn color_conversion(&self) -> super::ColorConversion {
+        let matrix = self.matrix.conversion_matrix().unwrap_or_default();
+        super::ColorConversion::new(matrix, self.chroma_location)
+    }
+
+    /// The payload of an `nclx` MP4 `colr` box.
+    pub fn to_nclx(&self) -> [u8; 11] {
+        let mut nclx = [0; 11];
+        nclx[..4].copy_from_slice(b"nclx");
+        nclx[4..6].copy_from_slice(&u16::from(u8::from(self.primaries)).to_be_bytes());
+        nclx[6..8].copy_from_slice(&u16::from(u8::from(self.transfer)).to_be_bytes());
+        nclx[8..10].copy_from_slice(&u16::from(u8::from(self.matrix)).to_be_bytes());
+        nclx[10] = (self.full_range as u8) << 7;
+        nclx
+    }
+
+    /// Parses the payload of a `colr` box, if it is `nclx`.
+    pub fn from_nclx(data: &[u8]) -> Option<Self> {
+        let nclx: &[u8; 11] = data.get(..11)?.try_into().ok()?;
+        (&nclx[..4] == b"nclx").then_some(())?;
+
+        let code = |index: usize| u8::try_from(u16::from_be_bytes([nclx[index], nclx[index + 1]]));
+
+        Some(Self {
+            primaries: code(4).ok()?.into(),
+            transfer: code(6).ok()?.into(),
+            matrix: code(8).ok()?.into(),
+            full_range: nclx[10] & 0x80 != 0,
+            chroma_location: ChromaSiting::default(),
+        })
+    }
+}
@@ -0,0 +1,435 @@
+//! Colour conversion between packed RGB and 4:2:0 YUV on the CPU.
+//!
+//! Frames are unpacked to normalized `f32` planes, converted with the
+//! selected matrix and packed again. Chroma is only resampled when converting
+//! between RGB and YUV, so YUV to YUV conversions (e.g. NV12 to I420, or 8 to
+//! 10 bits) keep the original chroma samples.
+
+mod hdr;
+mod info;
+mod rows;
+
+pub use hdr::*;
+pub use info::*;
+
+use crate::{FrameBuf, FrameError, PixelFormat, VideoFrame};
+use rows::SampleLayout;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConvertError {
+    #[error("Colour conversion does not support {0:?}")]
+    UnsupportedFormat(PixelFormat),
+
+    #[error("Invalid frame: {0}")]
+    Frame(#[from] FrameError),
+}
+
+/// YCbCr matrix coefficients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Matrix {
+    Bt601,
+    #[default]
+    Bt709,
+    /// BT.2020 non-constant luminance.
+    Bt2020,
+}
+
+impl Matrix {
+    /// The luma weights of red and blue, `(Kr, Kb)`.
+    pub fn coefficients(self) -> (f32, f32) {
+        match self {
+            Matrix::Bt601 => (0.299, 0.114),
+            Matrix::Bt709 => (0.2126, 0.0722),
+            Matrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// Where 4:2:0 chroma samples sit relative to luma, in the order of the
+/// H.273 `chroma_sample_loc_type` code points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ChromaSiting {
+    /// Co-sited with the left luma sample, halfway between rows. The
+    /// H.264/HEVC default.
+    #[default]
+    Left,
+    /// Centered between four luma samples, as in JPEG.
+    Center,
+    /// Co-sited with the top left luma sample.
+    TopLeft,
+    Top,
+    BottomLeft,
+    Bottom,
+}
+
+impl ChromaSiting {
+    /// Whether chroma is co-sited with luma horizontally and vertically.
+    /// Bottom sitings are converted as if they were top sited.
+    fn cosited(self) -> (bool, bool) {
+        match self {
+            ChromaSiting::Left => (true, false),
+            ChromaSiting::Center => (false, false),
+            ChromaSiting::TopLeft | ChromaSiting::BottomLeft => (true, true),
+            ChromaSiting::Top | ChromaSiting::Bottom => (false, true),
+        }
+    }
+
+    /// The siting for a `chroma_sample_loc_type`, if it is in range.
+    pub fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            0 => ChromaSiting::Left,
+            1 => ChromaSiting::Center,
+            2 => ChromaSiting::TopLeft,
+            3 => ChromaSiting::Top,
+            4 => ChromaSiting::BottomLeft,
+            5 => ChromaSiting::Bottom,
+            _ => return None,
+        })
+    }
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Converts frames between the RGB and YUV formats that VideoToolbox
+/// encodes from and decodes to: [`PixelFormat::Argb32`], `Bgra32`, `Rgba32`,
+/// `Nv12`, `I420` and `P010`, in video or full range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ColorConversion {
+    pub matrix: Matrix,
+    pub siting: ChromaSiting,
+}
+
+impl ColorConversion {
+    pub fn new(matrix: Matrix, siting: ChromaSiting) -> Self {
+        Self { matrix, siting }
+    }
+
+    /// Converts `src` into a new, tightly packed frame of `format`.
+    pub fn convert(&self, src: &VideoFrame, format: PixelFormat) -> Result<FrameBuf, ConvertError> {
+        let mut dst = FrameBuf::new(format, src.width(), src.height())?;
+        self.convert_into(src, &mut dst)?;
+        Ok(dst)
+    }
+
+    /// Converts `src` into `dst`'s format. Both must be the same size. The
+    /// crop rectangle and pts are copied.
+    pub fn convert_into(&self, src: &VideoFrame, dst: &mut FrameBuf) -> Result<(), ConvertError> {
+        if (src.width(), src.height()) != (dst.width(), dst.height()) {
+            return Err(FrameError::DimensionsMismatch {
+                expected: (dst.width(), dst.height()),
+                actual: (src.width(), src.height()),
+            }
+            .into());
+        }
+
+        let src_layout = Layout::of(src.format())?;
+        let dst_layout = Layout::of(dst.format())?;
+        let size = Size::new(src.width(), src.height());
+
+        let image = src_layout.unpack(src, size);
+        let image = match (image, &dst_layout) {
+            (Image::Rgb(rgb), Layout::Yuv { .. }) => Image::Yuv(self.rgb_to_yuv(&rgb, size)),
+            (Image::Yuv(yuv), Layout::Rgb { .. }) => Image::Rgb(self.yuv_to_rgb(&yuv, size)),
+            (image, _) => image,
+        };
+
+        dst_layout.pack(&image, size, dst);
+        dst.set_crop(src.crop())?;
+        dst.set_pts(src.pts());
+        dst.set_duration(src.duration());
+
+        // Tag the output with the matrix and range it was converted to.
+        let mut color = src.color();
+        color.full_range = dst.format().is_full_range();
+        color.matrix = match dst_layout {
+            Layout::Rgb { .. } => MatrixCoefficients::Identity,
+            Layout::Yuv { .. } => match src_layout {
+                Layout::Rgb { .. } => self.matrix.into(),
+                Layout::Yuv { .. } => color.matrix,
+            },
+        };
+
+        if let (Layout::Rgb { .. }, Layout::Yuv { .. }) = (&src_layout, &dst_layout) {
+            color.chroma_location = self.siting;
+        }
+
+        dst.set_color(color);
+        dst.set_hdr_metadata(src.hdr_metadata());
+
+        Ok(())
+    }
+
+    fn rgb_to_yuv(&self, rgb: &Rgb, size: Size) -> Yuv {
+        let pixels = size.width * size.height;
+        let (mut y, mut cb, mut cr) = (vec![0.0; pixels], vec![0.0; pixels], vec![0.0; pixels]);
+        rows::rgb_to_yuv(
+            self.matrix.coefficients(),
+            [&rgb.r, &rgb.g, &rgb.b],
+            [&mut y, &mut cb, &mut cr],
+        );
+
+        let cb = downsample_plane(&cb, size, self.siting);
+        let cr = downsample_plane(&cr, size, self.siting);
+        Yuv { y, cb, cr }
+    }
+
+    fn yuv_to_rgb(&self, yuv: &Yuv, size: Size) -> Rgb {
+        let pixels = size.width * size.height;
+        let cb = upsample_plane(&yuv.cb, size, self.siting);
+        let cr = upsample_plane(&yuv.cr, size, self.siting);
+
+        let (mut r, mut g, mut b) = (vec![0.0; pixels], vec![0.0; pixels], vec![0.0; pixels]);
+        rows::yuv_to_rgb(self.matrix.coefficients(), [&yuv.y, &cb, &cr], [&mut r, &mut g, &mut b]);
+
+        Rgb { r, g, b, a: vec![1.0; pixels] }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Size {
+    width: usize,
+    height: usize,
+    chroma_width: usize,
+    chroma_height: usize,
+}
+
+impl Size {
+    fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self { width, height, chroma_width: width.div_ceil(2), chroma_height: height.div_ceil(2) }
+    }
+}
+
+/// Full resolution, normalized to `0.0..=1.0`.
+struct Rgb {
+    r: Vec<f32>,
+    g: Vec<f32>,
+    b: Vec<f32>,
+    a: Vec<f32>,
+}
+
+/// Luma normalized to `0.0..=1.0` and chroma to `-0.5..=0.5`, at 4:2:0.
+struct Yuv {
+    y: Vec<f32>,
+    cb: Vec<f32>,
+    cr: Vec<f32>,
+}
+
+enum Image {
+    Rgb(Rgb),
+    Yuv(Yuv),
+}
+
+enum Layout {
+    /// Packed 8-bit RGB with the byte offset of each component.
+    Rgb { offsets: [usize; 4] },
+    /// 4:2:0 YUV with separate or interleaved chroma planes.
+    Yuv { bit_depth: u8, full_range: bool, interleaved: bool },
+}
+
+impl Layout {
+    fn of(format: PixelFormat) -> Result<Self, ConvertError> {
+        let yuv = |interleaved| Layout::Yuv {
+            bit_depth: format.bit_depth(),
+            full_range: format.is_full_range(),
+            interleaved,
+        };
+
+        Ok(match format {
+            PixelFormat::Argb32 => Layout::Rgb { offsets: [1, 2, 3, 0] },
+            PixelFormat::Bgra32 => Layout::Rgb { offsets: [2, 1, 0, 3] },
+            PixelFormat::Rgba32 => Layout::Rgb { offsets: [0, 1, 2, 3] },
+            PixelFormat::Nv12
+            | PixelFormat::Nv12FullRange
+            | PixelFormat::P010
+            | PixelFormat::P010FullRange => yuv(true),
+            PixelFormat::I420 | PixelFormat::I420FullRange => yuv(false),
+            format => return Err(ConvertError::UnsupportedFormat(format)),
+        })
+    }
+
+    /// Layouts of the luma, Cb and Cr samples within their rows, and the plane
+    /// index of each.
+    fn yuv_samples(
+        bit_depth: u8,
+        full_range: bool,
+        interleaved: bool,
+    ) -> [(usize, SampleLayout); 3] {
+        let scale = (1u32 << (bit_depth - 8)) as f32;
+        let max = ((1u32 << bit_depth) - 1) as f32;
+        let (luma_bias, luma_range, chroma_range) = match full_range {
+            true => (0.0, max, max),
+            false => (16.0 * scale, 219.0 * scale, 224.0 * scale),
+        };
+
+        let (bytes, shift) = match bit_depth {
+            8 => (1, 0),
+            _ => (2, 16 - bit_depth as u32),
+        };
+
+        let luma =
+            SampleLayout { bytes, step: 1, offset: 0, shift, bias: luma_bias, range: luma_range };
+        let chroma = SampleLayout { bias: 128.0 * scale, range: chroma_range, ..luma };
+
+        match interleaved {
+            true => [
+                (0, luma),
+                (1, SampleLayout { step: 2, ..chroma }),
+                (1, SampleLayout { step: 2, offset: 1, ..chroma }),
+            ],
+            false => [(0, luma), (1, chroma), (2, chroma)],
+        }
+    }
+
+    fn unpack(&self, frame: &VideoFrame, size: Size) -> Image {
+        match *self {
+            Layout::Rgb { offsets } => {
+                let pixels = size.width * size.height;
+                let mut components: [Vec<f32>; 4] = std::array::from_fn(|_| vec![0.0; pixels]);
+
+                for (component, offset) in components.iter_mut().zip(offsets) {
+                    let layout = rgb_sample(offset);
+
+                    for (y, out) in component.chunks_exact_mut(size.width).enumerate() {
+                        rows::unpack_samples(frame.planes()[0].row(y), layout, out);
+                    }
+                }
+
+                let [r, g, b, a] = components;
+                Image::Rgb(Rgb { r, g, b, a })
+            },
+            Layout::Yuv { bit_depth, full_range, interleaved } => {
+                let samples = Self::yuv_samples(bit_depth, full_range, interleaved);
+                let [y, cb, cr] = std::array::from_fn(|component| {
+                    let (plane, layout) = samples[component];
+                    let (width, height) = plane_size(component, size);
+                    let mut values = vec![0.0; width * height];
+
+                    for (y, out) in values.chunks_exact_mut(width).enumerate() {
+                        rows::unpack_samples(frame.planes()[plane].row(y), layout, out);
+                    }
+
+                    values
+                });
+
+                Image::Yuv(Yuv { y, cb, cr })
+            },
+        }
+    }
+
+    /// Packs `image`, which is already in this layout's colour model.
+    fn pack(&self, image: &Image, size: Size, dst: &mut FrameBuf) {
+        match (self, image) {
+            (Layout::Rgb { offsets }, Image::Rgb(rgb)) => {
+                let plane = &mut dst.planes_mut()[0];
+                let stride = plane.stride();
+
+                for (component, offset) in [&rgb.r, &rgb.g, &rgb.b, &rgb.a].into_iter().zip(offsets)
+                {
+                    let layout = rgb_sample(*offset);
+
+                    for (y, values) in component.chunks_exact(size.width).enumerate() {
+                        let row = &mut plane.data_mut()[y * stride..];
+                        rows::pack_samples(values, layout, 255.0, row);
+                    }
+                }
+            },
+            (&Layout::Yuv { bit_depth, full_range, interleaved }, Image::Yuv(yuv)) => {
+                let samples = Self::yuv_samples(bit_depth, full_range, interleaved);
+                let max = ((1u32 << bit_depth) - 1) as f32;
+
+                for (component, values) in [&yuv.y, &yuv.cb, &yuv.cr].into_iter().enumerate() {
+                    let (plane, layout) = samples[component];
+                    let (width, _) = plane_size(component, size);
+                    let plane = &mut dst.planes_mut()[plane];
+                    let stride = plane.stride();
+
+                    for (y, values) in values.chunks_exact(width).enumerate() {
+                        let row = &mut plane.data_mut()[y * stride..];
+                        rows::pack_samples(values, layout, max, row);
+                    }
+                }
+            },
+            _ => unreachable!("image converted to the destination colour model"),
+        }
+    }
+}
+
+fn rgb_sample(offset: usize) -> SampleLayout {
+    SampleLayout { bytes: 1, step: 4, offset, shift: 0, bias: 0.0, range: 255.0 }
+}
+
+/// The size in samples of component 0 (luma) or 1 and 2 (chroma).
+fn plane_size(component: usize, size: Size) -> (usize, usize) {
+    match component {
+        0 => (size.width, size.height),
+        _ => (size.chroma_width, size.chroma_height),
+    }
+}
+
+fn downsample_plane(plane: &[f32], size: Size, siting: ChromaSiting) -> Vec<f32> {
+    let (cosited_x, cosited_y) = siting.cosited();
+    let mut columns = vec![0.0; size.chroma_width * size.height];
+
+    for (src, out) in
+        plane.chunks_exact(size.width).zip(columns.chunks_exact_mut(size.chroma_width))
+    {
+        rows::downsample(src, out, cosited_x);
+    }
+
+    let row = |y: usize| {
+        let y = y.min(size.height - 1);
+        &columns[y * size.chroma_width..(y + 1) * size.chroma_width]
+    };
+
+    let mut out = vec![0.0; size.chroma_width * size.chroma_height];
+
+    for (i, out) in out.chunks_exact_mut(size.chroma_width).enumerate() {
+        let center = 2 * i;
+
+        match cosited_y {
+            true => rows::weighted_sum(
+                [0.25, 0.5, 0.25],
+                [row(center.saturating_sub(1)), row(center), row(center + 1)],
+                out,
+            ),
+            false => rows::weighted_sum([0.5, 0.5], [row(center), row(center + 1)], out),
+        }
+    }
+
+    out
+}
+
+fn upsample_plane(plane: &[f32], size: Size, siting: ChromaSiting) -> Vec<f32> {
+    let (cosited_x, cosited_y) = siting.cosited();
+    let mut columns = vec![0.0; size.width * size.chroma_height];
+
+    for (src, out) in
+        plane.chunks_exact(size.chroma_width).zip(columns.chunks_exact_mut(size.width))
+    {
+        rows::upsample(src, out, cosited_x);
+    }
+
+    let row = |i: usize| {
+        let i = i.min(size.chroma_height - 1);
+        &columns[i * size.width..(i + 1) * size.width]
+    };
+
+    let mut out = vec![0.0; size.width * size.height];
+
+    for (y, out) in out.chunks_exact_mut(size.width).enumerate() {
+        let i = y / 2;
+
+        match (cosited_y, y % 2) {
+            (true, 0) => out.copy_from_slice(row(i)),
+            (true, _) => rows::weighted_sum([0.5, 0.5], [row(i), row(i + 1)], out),
+            (false, 0) => rows::weighted_sum([0.75, 0.25], [row(i), row(i.saturating_sub(1))], out),
+            (false, _) => rows::weighted_sum([0.75, 0.25], [row(i), row(i + 1)], out),
+        }
+    }
+
+    out
+}
@@ -0,0 +1,177 @@
+//! Row loops for colour conversion. Each works on whole rows of `f32`
+//! samples. Sample sizes and filter taps are picked outside the loops, which
+//! walk rows through iterators and fixed-size chunks, so the compiler can drop
+//! bounds checks and vectorize them.
+
+/// Reads every `step`th sample starting at `offset` from a row of 8-bit or
+/// little-endian 16-bit samples, normalized as `(value >> shift - bias) / range`.
+pub(super) fn unpack_samples(row: &[u8], layout: SampleLayout, out: &mut [f32]) {
+    let SampleLayout { bytes, step, offset, shift, bias, range } = layout;
+    let scale = 1.0 / range;
+
+    match bytes {
+        1 => {
+            for (out, &value) in out.iter_mut().zip(row[offset..].iter().step_by(step)) {
+                *out = (value as f32 - bias) * scale;
+            }
+        },
+        _ => {
+            let (samples, _) = row.as_chunks::<2>();
+
+            for (out, &bytes) in out.iter_mut().zip(samples[offset..].iter().step_by(step)) {
+                let value = u16::from_le_bytes(bytes) >> shift;
+                *out = (value as f32 - bias) * scale;
+            }
+        },
+    }
+}
+
+/// The inverse of [`unpack_samples`], rounding and clamping to `max`.
+pub(super) fn pack_samples(values: &[f32], layout: SampleLayout, max: f32, row: &mut [u8]) {
+    let SampleLayout { bytes, step, offset, shift, bias, range } = layout;
+
+    match bytes {
+        1 => {
+            for (sample, value) in row[offset..].iter_mut().step_by(step).zip(values) {
+                *sample = (value * range + bias).round().clamp(0.0, max) as u8;
+            }
+        },
+        _ => {
+            let (samples, _) = row.as_chunks_mut::<2>();
+
+            for (sample, value) in samples[offset..].iter_mut().step_by(step).zip(values) {
+                let value = (value * range + bias).round().clamp(0.0, max) as u16;
+                *sample = (value << shift).to_le_bytes();
+            }
+        },
+    }
+}
+
+/// Where samples of one component sit in a row and how they are quantized.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SampleLayout {
+    /// Bytes per sample, 1 or 2.
+    pub(super) bytes: usize,
+    /// Samples from one to the next of this component.
+    pub(super) step: usize,
+    pub(super) offset: usize,
+    /// Padding bits below the value in 16-bit samples.
+    pub(super) shift: u32,
+    pub(super) bias: f32,
+    pub(super) range: f32,
+}
+
+pub(super) fn rgb_to_yuv(
+    (kr, kb): (f32, f32),
+    [r, g, b]: [&[f32]; 3],
+    [y, cb, cr]: [&mut [f32]; 3],
+) {
+    let kg = 1.0 - kr - kb;
+    let (cb_scale, cr_scale) = (0.5 / (1.0 - kb), 0.5 / (1.0 - kr));
+
+    // Equal lengths let the compiler check the bounds once, before the loop.
+    let len = y.len();
+    let (r, g, b, cb, cr) = (&r[..len], &g[..len], &b[..len], &mut cb[..len], &mut cr[..len]);
+
+    for i in 0..len {
+        let luma = kr * r[i] + kg * g[i] + kb * b[i];
+        y[i] = luma;
+        cb[i] = (b[i] - luma) * cb_scale;
+        cr[i] = (r[i] - luma) * cr_scale;
+    }
+}
+
+pub(super) fn yuv_to_rgb(
+    (kr, kb): (f32, f32),
+    [y, cb, cr]: [&[f32]; 3],
+    [r, g, b]: [&mut [f32]; 3],
+) {
+    let kg = 1.0 - kr - kb;
+    let (cr_to_r, cb_to_b) = (2.0 * (1.0 - kr), 2.0 * (1.0 - kb));
+    let (cb_to_g, cr_to_g) = (kb * cb_to_b / kg, kr * cr_to_r / kg);
+
+    let len = y.len();
+    let (cb, cr, r, g, b) = (&cb[..len], &cr[..len], &mut r[..len], &mut g[..len], &mut b[..len]);
+
+    for i in 0..len {
+        r[i] = y[i] + cr_to_r * cr[i];
+        g[i] = y[i] - cb_to_g * cb[i] - cr_to_g * cr[i];
+        b[i] = y[i] + cb_to_b * cb[i];
+    }
+}
+
+/// Halves a row. Co-sited output samples sit on the even input samples and
+/// use a `[1, 2, 1] / 4` filter, others sit between pairs and average them.
+/// An odd last input sample is repeated as its own neighbour.
+pub(super) fn downsample(src: &[f32], out: &mut [f32], cosited: bool) {
+    let (pairs, odd_tail) = src.as_chunks::<2>();
+
+    match cosited {
+        true => {
+            // The odd sample before each pair, starting from the edge.
+            let previous = src.first().into_iter().chain(pairs.iter().map(|[_, odd]| odd));
+
+            for ((out, [even, odd]), previous) in out.iter_mut().zip(pairs).zip(previous) {
+                *out = 0.25 * previous + 0.5 * even + 0.25 * odd;
+            }
+        },
+        false => {
+            for (out, [even, odd]) in out.iter_mut().zip(pairs) {
+                *out = 0.5 * (even + odd);
+            }
+        },
+    }
+
+    if let ([last], Some(out)) = (odd_tail, out.get_mut(pairs.len())) {
+        let previous = pairs.last().map_or(*last, |[_, odd]| *odd);
+
+        *out = match cosited {
+            true => 0.25 * previous + 0.75 * last,
+            false => *last,
+        };
+    }
+}
+
+/// Doubles a row, the inverse of [`downsample`] with linear interpolation.
+pub(super) fn upsample(src: &[f32], out: &mut [f32], cosited: bool) {
+    let (pairs, odd_tail) = out.as_chunks_mut::<2>();
+    // Each sample's neighbours, repeating the edge samples.
+    let previous = src.first().into_iter().chain(src);
+    let next = src.iter().skip(1).chain(src.last());
+
+    match cosited {
+        true => {
+            for ((pair, center), next) in pairs.iter_mut().zip(src).zip(next) {
+                *pair = [*center, 0.5 * (center + next)];
+            }
+        },
+        false => {
+            for (((pair, center), previous), next) in
+                pairs.iter_mut().zip(src).zip(previous).zip(next)
+            {
+                *pair = [0.75 * center + 0.25 * previous, 0.75 * center + 0.25 * next];
+            }
+        },
+    }
+
+    // An odd width ends on an even output sample over the last input sample.
+    if let ([out], Some(&last)) = (odd_tail, src.get(pairs.len())) {
+        let previous = src[..pairs.len()].last().copied().unwrap_or(last);
+
+        *out = match cosited {
+            true => last,
+            false => 0.75 * last + 0.25 * previous,
+        };
+    }
+}
+
+/// `out = weights[0] * rows[0] + weights[1] * rows[1] + ...`
+pub(super) fn weighted_sum<const N: usize>(weights: [f32; N], rows: [&[f32]; N], out: &mut [f32]) {
+    out.fill(0.0);
+
+    for (weight, row) in weights.into_iter().zip(rows) {
+        for (out, value) in out.iter_mut().zip(row) {
+            *out += weight * value;
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! MPEG-DASH (ISO/IEC 23009-1) manifests for fragmented MP4 output.
+//!
+//! Segments are addressed by `$Time$`, which is the `base_media_decode_time`
+//! passed to `mp4::fragment`, and described with a `SegmentTimeline`.
+
+use crate::{
+    date_time::{format_date_time, format_duration, parse_date_time, parse_duration},
+    ParameterSets,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use xml::{escape, XmlElement};
+
+mod xml;
+
+const MPD_NAMESPACE: &str = "urn:mpeg:dash:schema:mpd:2011";
+const LIVE_PROFILE: &str = "urn:mpeg:dash:profile:isoff-live:2011";
+
+#[derive(Debug, Error)]
+pub enum DashError {
+    #[error("Malformed XML")]
+    MalformedXml,
+
+    #[error("Missing element or attribute: {0}")]
+    Missing(&'static str),
+
+    #[error("Invalid value for {0}")]
+    InvalidValue(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpdType {
+    /// On-demand content whose segments all exist up front.
+    Static,
+    /// Live content that clients re-fetch as segments are added.
+    Dynamic {
+        /// Wall clock time corresponding to the start of the first period.
+        /// Segment availability is computed from it.
+        availability_start_time: SystemTime,
+    },
+}
+
+/// A media presentation description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mpd {
+    pub mpd_type: MpdType,
+    pub min_buffer_time: Duration,
+    /// Total duration, for static presentations.
+    pub media_presentation_duration: Option<Duration>,
+    pub publish_time: Option<SystemTime>,
+    pub minimum_update_period: Option<Duration>,
+    pub time_shift_buffer_depth: Option<Duration>,
+    pub periods: Vec<Period>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Period {
+    pub id: String,
+    pub start: Duration,
+    pub adaptation_sets: Vec<AdaptationSet>,
+}
+
+/// A set of interchangeable representations, e.g. a bitrate ladder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptationSet {
+    pub id: u32,
+    pub mime_type: String,
+    pub segment_template: SegmentTemplate,
+    pub representations: Vec<Representation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Representation {
+    pub id: String,
+    /// Peak bitrate in bits per second.
+    pub bandwidth: u64,
+    pub codecs: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Segment URL templates shared by all representations of an adaptation set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentTemplate {
+    pub timescale: u32,
+    pub initialization: String,
+    pub media: String,
+    pub timeline: Vec<TimelineEntry>,
+}
+
+/// An `S` element: `repeat + 1` consecutive segments of equal duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineEntry {
+    pub start: u64,
+    pub duration: u64,
+    /// `None` for `r="-1"` on the last entry: segments repeat until the end
+    /// of the period, or of the live window in a dynamic presentation.
+    pub repeat: Option<u32>,
+}
+
+impl Mpd {
+    /// A presentation with a single period holding `adaptation_set`.
+    pub fn new(mpd_type: MpdType, adaptation_set: AdaptationSet) -> Self {
+        Self {
+            mpd_type,
+            min_buffer_time: Duration::from_secs(2),
+            media_presentation_duration: None,
+            publish_time: None,
+            minimum_update_period: None,
+            time_shift_buffer_depth: None,
+            periods: vec![Period {
+                id: "0".to_string(),
+                start: Duration::ZERO,
+                adaptation_sets: vec![adaptation_set],
+            }],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, DashError> {
+        let root = xml::parse(text).ok_or(DashError::MalformedXml)?;
+
+        if root.name != "MPD" {
+            return Err(DashError::Missing("MPD"));
+        }
+
+        let mpd_type = match root.attribute("type").unwrap_or("static") {
+            "static" => MpdType::Static,
+            "dynamic" => MpdType::Dynamic {
+                availability_start_time: date_time_attribute(&root, "availabilityStartTime")?
+                    .ok_or(DashError::Missing("availabilityStartTime"))?,
+            },
+            _ => return Err(DashError::InvalidValue("type")),
+        };
+
+        let periods = root.children("Period").map(parse_period).collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            mpd_type,
+            min_buffer_time: duration_attribute(&root, "minBufferTime")?
+                .ok_or(DashError::Missing("minBufferTime"))?,
+            media_presentation_duration: duration_attribute(&root, "mediaPresentationDuration")?,
+            publish_time: date_time_attribute(&root, "publishTime")?,
+            minimum_update_period: duration_attribute(&root, "minimumUpdatePeriod")?,
+            time_shift_buffer_depth: duration_attribute(&root, "timeShiftBufferDepth")?,
+            periods,
+        })
+    }
+}
+
+impl AdaptationSet {
+    pub fn new(id: u32, segment_template: SegmentTemplate) -> Self {
+        Self { id, mime_type: "video/mp4".to_string(), segment_template, representations: vec![] }
+    }
+}
+
+impl Representation {
+    /// Describes a stream encoded with `parameter_sets`, which supply the
+    /// codecs string and dimensions.
+    pub fn new(id: &str, bandwidth: u64, parameter_sets: &ParameterSets) -> Option<Self> {
+        let sps_info = parameter_sets.sps_info()?;
+
+        Some(Self {
+            id: id.to_string(),
+            bandwidth,
+            codecs: parameter_sets.codec_string()?,
+            width: sps_info.width,
+            height: sps_info.height,
+        })
+    }
+}
+
+impl SegmentTemplate {
+    /// # Panics
+    /// If `timescale` is zero.
+    pub fn new(timescale: u32) -> Self {
+        assert!(timescale > 0, "segment timescale must be positive");
+
+        Self {
+            timescale,
+            initialization: "init-$RepresentationID$.mp4".to_string(),
+            media: "segment-$RepresentationID$-$Time$.m4s".to_string(),
+            timeline: vec![],
+        }
+    }
+
+    /// Appends a segment, extending the last entry's repeat count when the
+    /// segment directly follows it with the same duration.
+    pub fn push_segment(&mut self, start: u64, duration: u64) {
+        if let Some(last) = self.timeline.last_mut() {
+            if let Some(repeat) = last.repeat {
+                let end = last.start + last.duration * (repeat as u64 + 1);
+
+                if last.duration == duration && end == start {
+                    last.repeat = Some(repeat + 1);
+                    return;
+                }
+            }
+        }
+
+        self.timeline.push(TimelineEntry { start, duration, repeat: Some(0) });
+    }
+
+    /// Drops segments ending at or before `time`, e.g. to honour
+    /// `time_shift_buffer_depth` in a live presentation. An open-ended last
+    /// entry reaches the live edge, so it is kept.
+    pub fn remove_before(&mut self, time: u64) {
+        let open_entry = self.timeline.pop_if(|entry| entry.repeat.is_none());
+        let segments: Vec<_> =
+            self.segments().filter(|(start, duration)| start + duration > time).collect();
+
+        self.timeline.clear();
+
+        for (start, duration) in segments {
+            self.push_segment(start, duration);
+        }
+
+        self.timeline.extend(open_entry);
+    }
+
+    /// Each segment's start time and duration, in timescale units. An
+    /// open-ended entry contributes only its first segment.
+    pub fn segments(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.timeline.iter().flat_map(|entry| {
+            (0..=entry.repeat.unwrap_or(0) as u64)
+                .map(move |i| (entry.start + i * entry.duration, entry.duration))
+        })
+    }
+
+    /// Total duration covered by the timeline.
+    pub fn duration(&self) -> Duration {
+        let ticks: u64 = self.segments().map(|(_, duration)| duration).sum();
+        Duration::from_nanos((ticks as u128 * 1_000_000_000 / self.timescale as u128) as u64)
+    }
+
+    pub fn initialization_url(&self, representation_id: &str) -> String {
+        self.initialization.replace("$RepresentationID$", representation_id)
+    }
+
+    pub fn media_url(&self, representation_id: &str, time: u64) -> String {
+        self.media
+            .replace("$RepresentationID$", representation_id)
+            .replace("$Time$", &time.to_string())
+    }
+}
+
+impl Display for Mpd {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+
+        let (mpd_type, availability_start_time) = match self.mpd_type {
+            MpdType::Static => ("static", None),
+            MpdType::Dynamic { availability_start_time } => {
+                ("dynamic", Some(availability_start_time))
+            },
+        };
+
+        write!(
+            f,
+            r#"<MPD xmlns="{}" profiles="{}" type="{}""#,
+            MPD_NAMESPACE, LIVE_PROFILE, mpd_type
+        )?;
+        write!(f, r#" minBufferTime="{}""#, format_duration(self.min_buffer_time))?;
+
+        let durations = [
+            ("mediaPresentationDuration", self.media_presentation_duration),
+            ("minimumUpdatePeriod", self.minimum_update_period),
+            ("timeShiftBufferDepth", self.time_shift_buffer_depth),
+        ];
+
+        for (name, value) in durations {
+            if let Some(value) = value {
+                write!(f, r#" {}="{}""#, name, format_duration(value))?;
+            }
+        }
+
+        let times = [
+            ("availabilityStartTime", availability_start_time),
+            ("publishTime", self.publish_time),
+        ];
+
+        for (name, value) in times {
+            if let Some(value) = value {
+                write!(f, r#" {}="{}""#, name, format_date_time(value))?;
+            }
+        }
+
+        writeln!(f, ">")?;
+
+        for period in &self.periods {
+            writeln!(
+                f,
+                r#"  <Period id="{}" start="{}">"#,
+                escape(&period.id),
+                format_duration(period.start)
+            )?;
+
+            for adaptation_set in &period.adaptation_sets {
+                write_adaptation_set(f, adaptation_set)?;
+            }
+
+            writeln!(f, "  </Period>")?;
+        }
+
+        writeln!(f, "</MPD>")
+    }
+}
+
+fn write_adaptation_set(f: &mut Formatter, adaptation_set: &AdaptationSet) -> fmt::Result {
+    writeln!(
+        f,
+        r#"    <AdaptationSet id="{}" contentType="video" mimeType="{}" segmentAlignment="true" startWithSAP="1">"#,
+        adaptation_set.id,
+        escape(&adaptation_set.mime_type)
+    )?;
+
+    let template = &adaptation_set.segment_template;
+    writeln!(
+        f,
+        r#"      <SegmentTemplate timescale="{}" initialization="{}" media="{}">"#,
+        template.timescale,
+        escape(&template.initialization),
+        escape(&template.media)
+    )?;
+    writeln!(f, "        <SegmentTimeline>")?;
+
+    for entry in &template.timeline {
+        write!(f, r#"          <S t="{}" d="{}""#, entry.start, entry.duration)?;
+
+        match entry.repeat {
+            Some(0) => {},
+            Some(repeat) => write!(f, r#" r="{}""#, repeat)?,
+            None => write!(f, r#" r="-1""#)?,
+        }
+
+        writeln!(f, "/>")?;
+    }
+
+    writeln!(f, "        </SegmentTimeline>")?;
+    writeln!(f, "      </SegmentTemplate>")?;
+
+    for representation in &adaptation_set.representations {
+        writeln!(
+            f,
+            r#"      <Representation id="{}" bandwidth="{}" codecs="{}" width="{}" height="{}"/>"#,
+            escape(&representation.id),
+            representation.bandwidth,
+            escape(&representation.codecs),
+            representation.width,
+            representation.height
+        )?;
+    }
+
+    writeln!(f, "    </AdaptationSet>")
+}
+
+fn parse_period(element: &XmlElement) -> Result<Period, DashError> {
+    Ok(Period {
+        id: element.attribute("id").unwrap_or_default().to_string(),
+        start: duration_attribute(element, "start")?.unwrap_or_default(),
+        adaptation_sets: element
+            .children("AdaptationSet")
+            .map(parse_adaptation_set)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn parse_adaptation_set(element: &XmlElement) -> Result<AdaptationSet, DashError> {
+    let template = element.child("SegmentTemplate").ok_or(DashError::Missing("SegmentTemplate"))?;
+    let mut timeline = vec![];
+
+    if let Some(segment_timeline) = template.child("SegmentTimeline") {
+        let entries: Vec<_> = segment_timeline.children("S").collect();
+        let mut next_start = 0;
+
+        for (index, s) in entries.iter().enumerate() {
+            // `t` may be omitted for segments that follow on directly.
+            let start = parsed_attribute(s, "t")?.unwrap_or(next_start);
+            let duration: u64 = parsed_attribute(s, "d")?
+                .filter(|&duration| duration > 0)
+                .ok_or(DashError::InvalidValue("d"))?;
+
+            let repeat = match parsed_attribute::<i64>(s, "r")?.unwrap_or(0) {
+                // Repeats up to the next entry, which must then give its `t`.
+                -1 => match entries.get(index + 1) {
+                    Some(next) => {
+                        let next_start =
+                            parsed_attribute(next, "t")?.ok_or(DashError::Missing("t"))?;
+                        Some(
+                            repeat_until(start, duration, next_start)
+                                .ok_or(DashError::InvalidValue("r"))?,
+                        )
+                    },
+                    None => None,
+                },
+                repeat => Some(u32::try_from(repeat).map_err(|_| DashError::InvalidValue("r"))?),
+            };
+
+            let count = repeat.map_or(1, |repeat| repeat as u64 + 1);
+            next_start = duration
+                .checked_mul(count)
+                .and_then(|length| start.checked_add(length))
+                .ok_or(DashError::InvalidValue("S"))?;
+            timeline.push(TimelineEntry { start, duration, repeat });
+        }
+    }
+
+    let representations = element
+        .children("Representation")
+        .map(|representation| {
+            Ok(Representation {
+                id: representation.attribute("id").ok_or(DashError::Missing("id"))?.to_string(),
+                bandwidth: parsed_attribute(representation, "bandwidth")?
+                    .ok_or(DashError::Missing("bandwidth"))?,
+                codecs: representation
+                    .attribute("codecs")
+                    .or(element.attribute("codecs"))
+                    .unwrap_or_default()
+                    .to_string(),
+                width: parsed_attribute(representation, "width")?.unwrap_or(0),
+                height: parsed_attribute(representation, "height")?.unwrap_or(0),
+            })
+        })
+        .collect::<Result<_, DashError>>()?;
+
+    let timescale = parsed_attribute(template, "timescale")?.unwrap_or(1);
+
+    if timescale == 0 {
+        return Err(DashError::InvalidValue("timescale"));
+    }
+
+    Ok(AdaptationSet {
+        id: parsed_attribute(element, "id")?.unwrap_or(0),
+        mime_type: element.attribute("mimeType").unwrap_or("video/mp4").to_string(),
+        segment_template: SegmentTemplate {
+            timescale,
+            initialization: template.attribute("initialization").unwrap_or_default().to_string(),
+            media: template.attribute("media").ok_or(DashError::Missing("media"))?.to_string(),
+            timeline,
+        },
+        representations,
+    })
+}
+
+/// The repeat count of segments from `start` that reach `end`.
+fn repeat_until(start: u64, duration: u64, end: u64) -> Option<u32> {
+    let count = end.checked_sub(start).filter(|&ticks| ticks > 0)?.div_ceil(duration);
+    u32::try_from(count - 1).ok()
+}
+
+fn parsed_attribute<T: FromStr>(
+    element: &XmlElement,
+    name: &'static str,
+) -> Result<Option<T>, DashError> {
+    element
+        .attribute(name)
+        .map(|value| value.parse().map_err(|_| DashError::InvalidValue(name)))
+        .transpose()
+}
+
+fn duration_attribute(
+    element: &XmlElement,
+    name: &'static str,
+) -> Result<Option<Duration>, DashError> {
+    element
+        .attribute(name)
+        .map(|value| parse_duration(value).ok_or(DashError::InvalidValue(name)))
+        .transpose()
+}
+
+fn date_time_attribute(
+    element: &XmlElement,
+    name: &'static str,
+) -> Result<Option<SystemTime>, DashError> {
+    element
+        .attribute(name)
+        .map(|value| parse_date_time(value).ok_or(DashError::InvalidValue(name)))
+        .transpose()
+}
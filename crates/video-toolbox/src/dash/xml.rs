@@ -0,0 +1,159 @@
+//! Just enough XML to read back manifests: elements, attributes and the
+//! predefined entities. Text content, comments and processing instructions
+//! are skipped.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct XmlElement {
+    pub(crate) name: String,
+    pub(crate) attributes: Vec<(String, String)>,
+    pub(crate) children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub(crate) fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// Parses a document, returning its root element.
+pub(crate) fn parse(text: &str) -> Option<XmlElement> {
+    let mut parser = Parser { text, position: 0 };
+    parser.skip_misc();
+    let root = parser.element()?;
+    parser.skip_misc();
+
+    parser.rest().is_empty().then_some(root)
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, terminator: &str) -> Option<()> {
+        let end = self.rest().find(terminator)?;
+        self.position += end + terminator.len();
+        Some(())
+    }
+
+    /// Skips whitespace, comments and `<?...?>` declarations.
+    fn skip_misc(&mut self) {
+        loop {
+            self.skip_whitespace();
+
+            let terminator = if self.rest().starts_with("<?") {
+                "?>"
+            } else if self.rest().starts_with("<!--") {
+                "-->"
+            } else {
+                return;
+            };
+
+            if self.skip_past(terminator).is_none() {
+                return;
+            }
+        }
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(rest.len());
+
+        self.position += end;
+        (end > 0).then_some(&rest[..end])
+    }
+
+    fn element(&mut self) -> Option<XmlElement> {
+        self.rest().starts_with('<').then_some(())?;
+        self.position += 1;
+
+        let name = self.name()?.to_string();
+        let mut attributes = vec![];
+
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Some(XmlElement { name, attributes, children: vec![] });
+            }
+
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+
+            let key = self.name()?.to_string();
+            self.skip_whitespace();
+            self.rest().starts_with('=').then_some(())?;
+            self.position += 1;
+            self.skip_whitespace();
+
+            let quote = self.rest().chars().next().filter(|c| matches!(c, '"' | '\''))?;
+            self.position += 1;
+            let end = self.rest().find(quote)?;
+            attributes.push((key, unescape(&self.rest()[..end])));
+            self.position += end + 1;
+        }
+
+        let mut children = vec![];
+
+        loop {
+            // Text content is not needed by any caller.
+            let next_tag = self.rest().find('<')?;
+            self.position += next_tag;
+
+            if self.rest().starts_with("</") {
+                self.position += 2;
+                (self.name()? == name).then_some(())?;
+                self.skip_past(">")?;
+                return Some(XmlElement { name, attributes, children });
+            }
+
+            if self.rest().starts_with("<!--") || self.rest().starts_with("<?") {
+                self.skip_misc();
+                continue;
+            }
+
+            children.push(self.element()?);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! ISO 8601 dates and `xs:duration` values used by HLS and DASH manifests.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Formats `time` as an ISO 8601 UTC date with millisecond precision.
+pub(crate) fn format_date_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Parses a UTC date such as `2023-11-14T22:13:20Z` or `2023-11-14T22:13:20.5Z`.
+pub(crate) fn parse_date_time(text: &str) -> Option<SystemTime> {
+    let text = text.strip_suffix('Z')?;
+    let (date, time) = text.split_once('T')?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+
+    let mut time_parts = time.splitn(3, ':');
+    let hours: u64 = time_parts.next()?.parse().ok()?;
+    let minutes: u64 = time_parts.next()?.parse().ok()?;
+    let seconds = parse_seconds(time_parts.next()?)?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch from a civil date, the inverse of the above.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).ok()?;
+
+    let whole_seconds = days * 86_400 + hours * 3600 + minutes * 60;
+    Some(UNIX_EPOCH + Duration::from_secs(whole_seconds) + seconds)
+}
+
+/// Formats an `xs:duration` such as `PT2S` or `PT1M30.5S`, to the millisecond.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let (minutes, seconds) = (duration.as_secs() / 60, duration.as_secs() % 60);
+    let millis = format!("{:03}", duration.subsec_millis());
+    let millis = millis.trim_end_matches('0');
+
+    let seconds = match millis {
+        "" => seconds.to_string(),
+        _ => format!("{}.{}", seconds, millis),
+    };
+
+    match minutes {
+        0 => format!("PT{}S", seconds),
+        _ => format!("PT{}M{}S", minutes, seconds),
+    }
+}
+
+/// Parses the day and time components of an `xs:duration`.
+pub(crate) fn parse_duration(text: &str) -> Option<Duration> {
+    let mut rest = text.strip_prefix('P')?;
+    let mut duration = Duration::ZERO;
+    let mut in_time = false;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = after;
+            continue;
+        }
+
+        let end = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let value = parse_seconds(&rest[..end])?;
+
+        duration += value
+            * match (&rest[end..end + 1], in_time) {
+                ("D", false) => 86_400,
+                ("H", true) => 3600,
+                ("M", true) => 60,
+                ("S", true) => 1,
+                _ => return None,
+            };
+
+        rest = &rest[end + 1..];
+    }
+
+    Some(duration)
+}
+
+/// Parses a decimal number of seconds without going through floating point.
+pub(crate) fn parse_seconds(text: &str) -> Option<Duration> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let nanos = format!("{:0<9}", fraction).parse().ok()?;
+    Some(Duration::new(whole.parse().ok()?, nanos))
+}
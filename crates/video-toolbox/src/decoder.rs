@@ -1,8 +1,14 @@
-use crate::{NalIterator, NalType};
+use crate::{
+    alpha::nal_layer_id,
+    color::{ColorInfo, HdrMetadata, MatrixCoefficients},
+    sps::{parse_h264_sps, parse_hevc_sps},
+    FrameBuf, FrameError, NalIterator, NalType, ParameterSets, PixelFormat, Plane, Rect, SpsInfo,
+    Timestamp, VideoCodec, VideoFrame,
+};
 use core::ffi::c_void;
 use core_foundation::{
     array::CFArrayGetValueAtIndex,
-    base::{CFIndexConvertible, OSStatus},
+    base::{CFIndexConvertible, CFRelease, CFTypeRef, OSStatus},
     boolean::CFBoolean,
     dictionary::{
         kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionaryCreate,
@@ -14,14 +20,15 @@ use core_foundation::{
 use thiserror::Error;
 use video_toolbox_sys::{
     kCMSampleAttachmentKey_DisplayImmediately, kCVPixelBufferIOSurfacePropertiesKey,
-    kCVPixelBufferLock_ReadOnly, kCVPixelBufferPixelFormatTypeKey, kCVPixelFormatType_32BGRA,
+    kCVPixelBufferLock_ReadOnly, kCVPixelBufferPixelFormatTypeKey,
     kVTVideoDecoderSpecification_RequireHardwareAcceleratedVideoDecoder,
     CMBlockBufferCreateWithMemoryBlock, CMBlockBufferRef, CMSampleBufferCreate,
     CMSampleBufferGetSampleAttachmentsArray, CMSampleBufferRef, CMTime,
+    CMVideoFormatDescriptionCreateFromH264ParameterSets,
     CMVideoFormatDescriptionCreateFromHEVCParameterSets, CMVideoFormatDescriptionRef,
-    CVImageBufferGetDisplaySize, CVImageBufferGetEncodedSize, CVImageBufferRef,
-    CVPixelBufferGetBaseAddressOfPlane, CVPixelBufferGetBytesPerRowOfPlane,
-    CVPixelBufferGetDataSize, CVPixelBufferGetHeight, CVPixelBufferGetHeightOfPlane,
+    CVImageBufferGetDisplaySize, CVImageBufferRef, CVPixelBufferGetBaseAddress,
+    CVPixelBufferGetBaseAddressOfPlane, CVPixelBufferGetBytesPerRow,
+    CVPixelBufferGetBytesPerRowOfPlane, CVPixelBufferGetHeight, CVPixelBufferGetHeightOfPlane,
     CVPixelBufferGetPixelFormatType, CVPixelBufferGetPlaneCount, CVPixelBufferGetWidth,
     CVPixelBufferIsPlanar, CVPixelBufferLockBaseAddress, CVPixelBufferUnlockBaseAddress,
     VTDecodeInfoFlags, VTDecompressionOutputCallbackRecord, VTDecompressionSessionCreate,
@@ -54,6 +61,47 @@ pub enum DecodeError {
 
     #[error("Sample Buffer Creation Error: {0}")]
     SampleBufferCreationError(i32),
+
+    #[error("Decompression Error: {0}")]
+    DecompressionError(i32),
+
+    #[error("{0:?} is not a decoder output format")]
+    UnsupportedOutputFormat(PixelFormat),
+
+    #[error(
+        "{format:?} cannot hold {bit_depth}-bit samples with chroma format {chroma_format_idc}"
+    )]
+    LossyOutputFormat { format: PixelFormat, bit_depth: u8, chroma_format_idc: u8 },
+
+    #[error("Unknown pixel format type 0x{0:x}")]
+    UnknownPixelFormat(u32),
+
+    #[error("Invalid decoded frame: {0}")]
+    InvalidFrame(#[from] FrameError),
+}
+
+#[derive(Debug, Clone)]
+pub struct DecoderConfig {
+    /// The format decoded frames are converted to, see
+    /// [`PixelFormat::is_decoder_output`].
+    pub output_format: PixelFormat,
+    /// Lets the output format drop bit depth or chroma resolution the stream
+    /// has, e.g. to show a Main10 stream as [`PixelFormat::Bgra32`]. Otherwise
+    /// such a stream fails with [`DecodeError::LossyOutputFormat`].
+    pub allow_lossy_output: bool,
+}
+
+impl DecoderConfig {
+    /// Outputs every sample of a stream, see [`PixelFormat::for_sps_info`].
+    pub fn for_stream(sps_info: &SpsInfo) -> Self {
+        Self { output_format: PixelFormat::for_sps_info(sps_info), allow_lossy_output: false }
+    }
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self { output_format: PixelFormat::Bgra32, allow_lossy_output: false }
+    }
 }
 
 pub struct Decoder {
@@ -67,12 +115,52 @@ unsafe impl Send for Decoder {}
 impl Drop for Decoder {
     fn drop(&mut self) {
         // TODO - call VTDecompressionSessionInvalidate
+        self.decoder_internal.release_session();
     }
 }
 
 impl Decoder {
+    /// Creates an HEVC decoder. H.264 streams start from their parameter
+    /// sets, see [`Decoder::from_parameter_sets`].
     pub fn new(width: u32, height: u32) -> Result<Self, DecodeError> {
-        Ok(Self { width, height, decoder_internal: DecoderInternal::new() })
+        Self::with_config(width, height, DecoderConfig::default())
+    }
+
+    pub fn with_config(
+        width: u32,
+        height: u32,
+        config: DecoderConfig,
+    ) -> Result<Self, DecodeError> {
+        let decoder_internal = DecoderInternal::new(VideoCodec::Hevc, config)?;
+        Ok(Self { width, height, decoder_internal })
+    }
+
+    /// Creates a decoder from out of band parameter sets (e.g. from SDP), so the
+    /// stream does not need to repeat them in-band before the first keyframe.
+    /// The codec and size come from the parameter sets.
+    pub fn from_parameter_sets(
+        parameter_sets: &ParameterSets,
+        config: DecoderConfig,
+    ) -> Result<Self, DecodeError> {
+        let sps_info = parameter_sets.sps_info().ok_or(DecodeError::MissingSpsNalUnit)?;
+        let mut decoder_internal = DecoderInternal::new(parameter_sets.codec(), config)?;
+
+        match parameter_sets {
+            ParameterSets::H264(parameter_sets) => {
+                decoder_internal.recreate_decoder(&[&parameter_sets.sps, &parameter_sets.pps])?
+            },
+            ParameterSets::Hevc(parameter_sets) => decoder_internal.recreate_decoder(&[
+                &parameter_sets.vps,
+                &parameter_sets.sps,
+                &parameter_sets.pps,
+            ])?,
+        }
+
+        Ok(Self { width: sps_info.width, height: sps_info.height, decoder_internal })
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.decoder_internal.codec
     }
 
     pub fn width(&self) -> u32 {
@@ -83,28 +171,72 @@ impl Decoder {
         self.height
     }
 
-    pub fn decode_blocking(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeError> {
-        self.decoder_internal.decode(src, dst)?;
-        Ok((self.width * self.height * 4) as usize)
+    /// The picture format of the stream being decoded, once its SPS has been
+    /// seen.
+    pub fn sps_info(&self) -> Option<SpsInfo> {
+        self.decoder_internal.sps_info
+    }
+
+    /// Decodes `src` into `dst`, which takes the configured output format and
+    /// the decoded size. Every plane is copied, tightly packed. The crop
+    /// rectangle is set to the stream's display size.
+    pub fn decode_blocking(&mut self, src: &[u8], dst: &mut FrameBuf) -> Result<(), DecodeError> {
+        self.decoder_internal.decode(src, dst)
     }
 }
 
 struct DecoderInternal {
+    codec: VideoCodec,
+    config: DecoderConfig,
     decode_session: Option<VTDecompressionSessionRef>,
     format_description: Option<CMVideoFormatDescriptionRef>,
+    /// From the base layer's SPS.
+    sps_info: Option<SpsInfo>,
+    /// From the SPS VUI, tagged onto every decoded frame.
+    color: ColorInfo,
+    /// From SEI messages, kept until the next SPS.
+    hdr: HdrMetadata,
 }
 
 impl DecoderInternal {
-    fn new() -> Self {
-        Self { decode_session: None, format_description: None }
+    fn new(codec: VideoCodec, config: DecoderConfig) -> Result<Self, DecodeError> {
+        if !config.output_format.is_decoder_output() {
+            return Err(DecodeError::UnsupportedOutputFormat(config.output_format));
+        }
+
+        Ok(Self {
+            codec,
+            config,
+            decode_session: None,
+            format_description: None,
+            sps_info: None,
+            color: ColorInfo::default(),
+            hdr: HdrMetadata::default(),
+        })
     }
 
-    fn recreate_decoder(
-        &mut self,
-        vps_slice: &[u8],
-        sps_slice: &[u8],
-        pps_slice: &[u8],
-    ) -> Result<(), DecodeError> {
+    /// Creates the session for `parameter_sets`: the SPS and PPS for H.264, or
+    /// the base layer's VPS, SPS and PPS for HEVC, which may go on with an
+    /// alpha layer's.
+    fn recreate_decoder(&mut self, parameter_sets: &[&[u8]]) -> Result<(), DecodeError> {
+        let sps_info = parameter_sets
+            .iter()
+            .find(|nal| is_base_layer_sps(self.codec, nal))
+            .and_then(|nal| parse_sps(self.codec, nal));
+        let output_format = self.config.output_format;
+
+        // VideoToolbox silently converts to the output format, so refuse to
+        // truncate 10-bit or 4:2:2 streams unless asked to.
+        if let Some(sps_info) = sps_info {
+            if !self.config.allow_lossy_output && !output_format.can_hold(&sps_info) {
+                return Err(DecodeError::LossyOutputFormat {
+                    format: output_format,
+                    bit_depth: sps_info.bit_depth_luma.max(sps_info.bit_depth_chroma),
+                    chroma_format_idc: sps_info.chroma_format_idc,
+                });
+            }
+        }
+
         let keys: Vec<CFStringRef> =
             unsafe { vec![kVTVideoDecoderSpecification_RequireHardwareAcceleratedVideoDecoder] };
         let values: Vec<CFBoolean> = vec![CFBoolean::true_value()];
@@ -123,18 +255,33 @@ impl DecoderInternal {
         let format_description = unsafe {
             let mut format_ref = std::mem::MaybeUninit::<CMVideoFormatDescriptionRef>::uninit();
 
-            let parameter_set_sizes = vec![vps_slice.len(), sps_slice.len(), pps_slice.len()];
-            let parameter_sets = vec![vps_slice.as_ptr(), sps_slice.as_ptr(), pps_slice.as_ptr()];
-
-            CMVideoFormatDescriptionCreateFromHEVCParameterSets(
-                std::ptr::null(),     // Allocator
-                parameter_sets.len(), // parameter set count
-                parameter_sets.as_ptr(),
-                parameter_set_sizes.as_ptr(),
-                4,                                                      // NAL unit header length
-                std::ptr::null(),                                       // extensions
-                format_ref.as_mut_ptr() as CMVideoFormatDescriptionRef, // Format ref out
-            );
+            let parameter_set_sizes: Vec<usize> = parameter_sets.iter().map(|p| p.len()).collect();
+            let parameter_set_pointers: Vec<*const u8> =
+                parameter_sets.iter().map(|p| p.as_ptr()).collect();
+
+            let status = match self.codec {
+                VideoCodec::H264 => CMVideoFormatDescriptionCreateFromH264ParameterSets(
+                    std::ptr::null(),     // Allocator
+                    parameter_sets.len(), // parameter set count
+                    parameter_set_pointers.as_ptr(),
+                    parameter_set_sizes.as_ptr(),
+                    4, // NAL unit header length
+                    format_ref.as_mut_ptr() as CMVideoFormatDescriptionRef, // Format ref out
+                ),
+                VideoCodec::Hevc => CMVideoFormatDescriptionCreateFromHEVCParameterSets(
+                    std::ptr::null(),     // Allocator
+                    parameter_sets.len(), // parameter set count
+                    parameter_set_pointers.as_ptr(),
+                    parameter_set_sizes.as_ptr(),
+                    4,                // NAL unit header length
+                    std::ptr::null(), // extensions
+                    format_ref.as_mut_ptr() as CMVideoFormatDescriptionRef, // Format ref out
+                ),
+            };
+
+            if status != 0 {
+                return Err(DecodeError::InitializationError(status));
+            }
 
             format_ref.assume_init()
         };
@@ -152,7 +299,7 @@ impl DecoderInternal {
 
         // Specify attributes for the destination image buffer.
         let dst_image_dictionary = unsafe {
-            let format_type = kCVPixelFormatType_32BGRA;
+            let format_type = output_format.os_type();
             let format_type_ptr: *const u32 = &format_type;
             let pixel_format = CFNumberCreate(
                 std::ptr::null(),
@@ -202,7 +349,7 @@ impl DecoderInternal {
         };
 
         if create_status != 0 {
-            println!("Failed to create VT Compression Session: {}", create_status);
+            unsafe { CFRelease(format_description as CFTypeRef) };
             return Err(DecodeError::InitializationError(create_status));
         }
 
@@ -210,73 +357,117 @@ impl DecoderInternal {
 
         self.decode_session = Some(decompression_session);
         self.format_description = Some(format_description);
+        self.sps_info = sps_info;
+        self.color = sps_info.map(|sps_info| sps_info.color).unwrap_or_default();
+        self.hdr = HdrMetadata::default();
+
+        // VideoToolbox converts to the output format's range, and to RGB with
+        // the stream's matrix.
+        self.color.full_range = output_format.is_full_range();
+
+        if output_format.is_rgb() {
+            self.color.matrix = MatrixCoefficients::Identity;
+        }
 
         Ok(())
     }
 
-    fn decode(&mut self, src: &[u8], dst: &mut [u8]) -> Result<(), DecodeError> {
-        let nal_iter = NalIterator::new(src);
-
-        let frame_data = if let Some(_decode_session) = self.decode_session {
-            let mut p_slice: Option<&[u8]> = None;
+    /// Waits for the current session's frames, then releases it and its
+    /// format description.
+    fn release_session(&mut self) {
+        if let Some(session) = self.decode_session.take() {
+            unsafe {
+                VTDecompressionSessionWaitForAsynchronousFrames(session);
+                CFRelease(session as CFTypeRef);
+            }
+        }
 
-            for nal in nal_iter {
-                println!("NAL Type: {:?}", nal.nal_type);
+        if let Some(format_description) = self.format_description.take() {
+            unsafe { CFRelease(format_description as CFTypeRef) };
+        }
+    }
 
-                if nal.nal_type == NalType::CodedSliceTrailR
-                    || nal.nal_type == NalType::CodedSliceIdrNLp
-                    || nal.nal_type == NalType::CodedSliceCra
-                    || nal.nal_type == NalType::CodedSliceIdrWRadl
-                {
-                    p_slice = Some(nal.data);
-                }
-            }
-            // If we have a decode session, look for P frames.
-            // TODO - Loop through NAL units and assign a P frame to frame_data.
-            p_slice.ok_or(DecodeError::MissingPFrame)?
-        } else {
-            // If we don't have a decode session, we need VPS, SPS, and PPS
-            // NAL Units, along with an I Frame NAL Unit (IDR).
-            let mut vps_slice: Option<&[u8]> = None;
-            let mut sps_slice: Option<&[u8]> = None;
-            let mut pps_slice: Option<&[u8]> = None;
-            let mut idr_slice: Option<&[u8]> = None;
-
-            for nal in nal_iter {
-                println!("NAL Type: {:?}", nal.nal_type);
-                if nal.nal_type == NalType::Vps {
-                    vps_slice = Some(nal.data);
-                }
-
-                if nal.nal_type == NalType::Sps {
-                    sps_slice = Some(nal.data);
-                }
-
-                if nal.nal_type == NalType::Pps {
-                    pps_slice = Some(nal.data);
-                }
-
-                if nal.nal_type == NalType::CodedSliceIdrNLp
-                    || nal.nal_type == NalType::CodedSliceIdrWRadl
-                {
-                    idr_slice = Some(nal.data);
-                }
+    fn decode(&mut self, src: &[u8], dst: &mut FrameBuf) -> Result<(), DecodeError> {
+        let codec = self.codec;
+        let slices = first_picture_slices(codec, src);
+
+        let mut vps_slice: Option<&[u8]> = None;
+        let mut sps_slice: Option<&[u8]> = None;
+        let mut pps_slice: Option<&[u8]> = None;
+        let mut alpha_parameter_sets = vec![];
+        let mut has_idr = false;
+
+        for nal in NalIterator::new(src) {
+            let Some(&header) = nal.data.first() else {
+                continue;
+            };
+
+            let slot = match codec {
+                VideoCodec::H264 => match header & 0b1_1111 {
+                    7 => &mut sps_slice,
+                    8 => &mut pps_slice,
+                    5 => {
+                        has_idr = true;
+                        continue;
+                    },
+                    _ => continue,
+                },
+                VideoCodec::Hevc => match nal.nal_type {
+                    NalType::Vps => &mut vps_slice,
+                    NalType::Sps => &mut sps_slice,
+                    NalType::Pps => &mut pps_slice,
+                    NalType::CodedSliceIdrNLp | NalType::CodedSliceIdrWRadl => {
+                        has_idr = true;
+                        continue;
+                    },
+                    _ => continue,
+                },
+            };
+
+            if codec == VideoCodec::H264 || nal_layer_id(nal.data) == Some(0) {
+                *slot = Some(nal.data);
+            } else {
+                alpha_parameter_sets.push(nal.data);
             }
+        }
+
+        // A new SPS may change the resolution, bit depth or chroma format, so
+        // it needs a new session that passes the output format check again.
+        let sps_changed = sps_slice.is_some_and(|sps| parse_sps(codec, sps) != self.sps_info);
 
-            let vps_slice = vps_slice.ok_or(DecodeError::MissingVpsNalUnit)?;
+        if self.decode_session.is_none() || sps_changed {
+            // Until a session accepts the new SPS, no frame goes to the old one.
+            self.release_session();
+
+            // A new session needs VPS (HEVC only), SPS, and PPS NAL Units,
+            // along with an I Frame NAL Unit (IDR).
             let sps_slice = sps_slice.ok_or(DecodeError::MissingSpsNalUnit)?;
             let pps_slice = pps_slice.ok_or(DecodeError::MissingPpsNalUnit)?;
+            let base_parameter_sets = match codec {
+                VideoCodec::H264 => vec![sps_slice, pps_slice],
+                VideoCodec::Hevc => {
+                    vec![vps_slice.ok_or(DecodeError::MissingVpsNalUnit)?, sps_slice, pps_slice]
+                },
+            };
+
+            if !has_idr {
+                return Err(DecodeError::MissingIFrame);
+            }
 
             // Recreate
-            self.recreate_decoder(vps_slice, sps_slice, pps_slice)?;
+            self.recreate_decoder(&[base_parameter_sets, alpha_parameter_sets].concat())?;
+        } else if slices.is_empty() {
+            return Err(DecodeError::MissingPFrame);
+        }
 
-            idr_slice.ok_or(DecodeError::MissingIFrame)?
-        };
+        self.hdr.update(HdrMetadata::from_access_unit(codec, src));
 
-        let mut length_prefixed_data = vec![];
-        length_prefixed_data.extend_from_slice(&(frame_data.len() as u32).to_be_bytes());
-        length_prefixed_data.extend_from_slice(frame_data);
-        let frame_data = length_prefixed_data;
+        let mut frame_data = vec![];
+
+        for slice in slices {
+            frame_data.extend_from_slice(&(slice.len() as u32).to_be_bytes());
+            frame_data.extend_from_slice(slice);
+        }
 
         let block_buffer = unsafe {
             let mut block_buffer_out = std::mem::MaybeUninit::<CMBlockBufferRef>::uninit();
@@ -294,7 +485,6 @@ impl DecoderInternal {
             );
 
             if status != 0 {
-                println!("Error creating CMBlockBuffer");
                 return Err(DecodeError::BlockBufferCreationError(status));
             }
 
@@ -321,7 +511,6 @@ impl DecoderInternal {
             );
 
             if status != 0 {
-                println!("Error creating CMSampleBuffer");
                 return Err(DecodeError::SampleBufferCreationError(status));
             }
 
@@ -338,24 +527,27 @@ impl DecoderInternal {
             );
         }
 
-        // TODO - allocate in a Box.
-        let mut dst_buffer = DstBuffer { data: dst.as_mut_ptr(), len: dst.len(), written_size: 0 };
+        let mut target = DecodeTarget { dst, color: self.color, hdr: self.hdr, result: Ok(()) };
 
-        unsafe {
+        let decode_status = unsafe {
             VTDecompressionSessionDecodeFrame(
                 self.decode_session.unwrap(),
                 sample_buffer,
-                0,                                                // Decode flags
-                &mut dst_buffer as *mut DstBuffer as *mut c_void, // User data
-                std::ptr::null_mut(),                             // Info flags out
-            );
-        }
+                0,                                               // Decode flags
+                &mut target as *mut DecodeTarget as *mut c_void, // User data
+                std::ptr::null_mut(),                            // Info flags out
+            )
+        };
 
         let _ = unsafe {
             VTDecompressionSessionWaitForAsynchronousFrames(self.decode_session.unwrap())
         };
 
-        Ok(())
+        if decode_status != 0 {
+            return Err(DecodeError::DecompressionError(decode_status));
+        }
+
+        target.result
     }
 }
 
@@ -365,52 +557,141 @@ extern "C" fn decode_callback(
     status: OSStatus,
     _info_flags: VTDecodeInfoFlags,
     image_buffer: CVImageBufferRef,
-    _presentation_timestamp: CMTime,
-    _presentation_duration: CMTime,
+    presentation_timestamp: CMTime,
+    presentation_duration: CMTime,
 ) {
-    println!("decode_callback");
-    println!("Status: {}", status);
-
-    unsafe {
-        if let Some(dst_buffer) = (source_frame_ref_con as *mut DstBuffer).as_mut() {
-            println!(
-                "We have a frame to write to, it has dimensions {}x{}",
-                CVPixelBufferGetWidth(image_buffer),
-                CVPixelBufferGetHeight(image_buffer)
-            );
-            println!("Buffer encoded size is {:?}", CVImageBufferGetEncodedSize(image_buffer));
-            println!("Buffer display size is {:?}", CVImageBufferGetDisplaySize(image_buffer));
-            println!("Data size is {:?}", CVPixelBufferGetDataSize(image_buffer));
-            println!("The buffer is planar: {}", CVPixelBufferIsPlanar(image_buffer));
-            println!("The buffer has {} planes", CVPixelBufferGetPlaneCount(image_buffer));
-            println!(
-                "The pixel format type is 0x{:x}",
-                CVPixelBufferGetPixelFormatType(image_buffer)
-            );
+    let Some(target) = (unsafe { (source_frame_ref_con as *mut DecodeTarget).as_mut() }) else {
+        return;
+    };
 
-            // Lock the buffer and copy it to our output buffer.
-            let _ = CVPixelBufferLockBaseAddress(image_buffer, kCVPixelBufferLock_ReadOnly);
+    if status != 0 {
+        target.result = Err(DecodeError::DecompressionError(status));
+        return;
+    }
 
-            let plane = 0;
-            let plane_base_address = CVPixelBufferGetBaseAddressOfPlane(image_buffer, plane);
-            let bytes_per_row = CVPixelBufferGetBytesPerRowOfPlane(image_buffer, plane);
-            let num_rows = CVPixelBufferGetHeightOfPlane(image_buffer, plane);
-            let src_len = bytes_per_row * num_rows;
+    target.result = unsafe {
+        copy_image_buffer(image_buffer, presentation_timestamp, presentation_duration, target)
+    };
+}
 
-            let src_slice: &[u8] =
-                std::slice::from_raw_parts(plane_base_address as *const u8, src_len);
-            let dst_slice = std::slice::from_raw_parts_mut(dst_buffer.data, dst_buffer.len);
+/// Copies every plane of a decoded image into the target's frame.
+unsafe fn copy_image_buffer(
+    image_buffer: CVImageBufferRef,
+    presentation_timestamp: CMTime,
+    presentation_duration: CMTime,
+    target: &mut DecodeTarget,
+) -> Result<(), DecodeError> {
+    let width = CVPixelBufferGetWidth(image_buffer);
+    let height = CVPixelBufferGetHeight(image_buffer);
+    let display_size = CVImageBufferGetDisplaySize(image_buffer);
+    let pixel_format_type = CVPixelBufferGetPixelFormatType(image_buffer);
+
+    let format = PixelFormat::from_os_type(pixel_format_type)
+        .ok_or(DecodeError::UnknownPixelFormat(pixel_format_type))?;
+
+    // Lock the buffer and copy it to our output buffer.
+    let _ = CVPixelBufferLockBaseAddress(image_buffer, kCVPixelBufferLock_ReadOnly);
+
+    // Non-planar buffers have no planes, only a base address.
+    let planes = if CVPixelBufferIsPlanar(image_buffer) {
+        (0..CVPixelBufferGetPlaneCount(image_buffer))
+            .map(|plane| {
+                let base_address = CVPixelBufferGetBaseAddressOfPlane(image_buffer, plane);
+                let bytes_per_row = CVPixelBufferGetBytesPerRowOfPlane(image_buffer, plane);
+                let num_rows = CVPixelBufferGetHeightOfPlane(image_buffer, plane);
+
+                let data =
+                    std::slice::from_raw_parts(base_address as *const u8, bytes_per_row * num_rows);
+                Plane::new(data, bytes_per_row)
+            })
+            .collect()
+    } else {
+        let base_address = CVPixelBufferGetBaseAddress(image_buffer);
+        let bytes_per_row = CVPixelBufferGetBytesPerRow(image_buffer);
+
+        let data = std::slice::from_raw_parts(base_address as *const u8, bytes_per_row * height);
+        vec![Plane::new(data, bytes_per_row)]
+    };
+
+    let display_width = (display_size.width as u32).min(width as u32);
+    let display_height = (display_size.height as u32).min(height as u32);
+
+    let pts = Timestamp::from_cm_time(presentation_timestamp).unwrap_or_default();
+    let duration = Timestamp::from_cm_time(presentation_duration);
+
+    let frame = VideoFrame::new(format, width as u32, height as u32, planes)
+        .and_then(|frame| frame.with_crop(Rect::new(0, 0, display_width, display_height)))
+        .map(|frame| {
+            let frame = frame.with_pts(pts).with_color(target.color).with_hdr_metadata(target.hdr);
+            target.dst.copy_from(&frame);
+            target.dst.set_duration(duration);
+        });
+
+    let _ = CVPixelBufferUnlockBaseAddress(image_buffer, kCVPixelBufferLock_ReadOnly);
+
+    frame.map_err(DecodeError::from)
+}
 
-            dst_slice[..src_len].copy_from_slice(src_slice);
+/// Whether `nal` is an SPS, of the base layer for HEVC.
+fn is_base_layer_sps(codec: VideoCodec, nal: &[u8]) -> bool {
+    match (codec, nal.first()) {
+        (VideoCodec::H264, Some(header)) => header & 0b1_1111 == 7,
+        (VideoCodec::Hevc, Some(header)) => {
+            nal_layer_id(nal) == Some(0) && NalType::from((header >> 1) & 0b11_1111) == NalType::Sps
+        },
+        (_, None) => false,
+    }
+}
 
-            let _ = CVPixelBufferUnlockBaseAddress(image_buffer, kCVPixelBufferLock_ReadOnly);
+fn parse_sps(codec: VideoCodec, nal: &[u8]) -> Option<SpsInfo> {
+    match codec {
+        VideoCodec::H264 => parse_h264_sps(nal),
+        VideoCodec::Hevc => parse_hevc_sps(nal),
+    }
+}
+
+/// The slices of the first picture in `src`, in every layer so HEVC with
+/// alpha keeps its alpha picture.
+fn first_picture_slices(codec: VideoCodec, src: &[u8]) -> Vec<&[u8]> {
+    let mut started_layers = vec![];
+    let mut slices = vec![];
+
+    for nal in NalIterator::new(src) {
+        // The slice's layer and the first byte of its slice header.
+        let slice = match (codec, nal.data.first()) {
+            (VideoCodec::H264, Some(header)) if (1..=5).contains(&(header & 0b1_1111)) => {
+                nal.data.get(1).map(|slice_header| (0, slice_header))
+            },
+            (VideoCodec::Hevc, Some(header)) if header >> 1 < 32 => {
+                nal_layer_id(nal.data).zip(nal.data.get(2))
+            },
+            _ => None,
+        };
+
+        let Some((layer_id, &slice_header)) = slice else {
+            continue;
+        };
+
+        // first_slice_segment_in_pic_flag (HEVC) or a first_mb_in_slice of 0
+        // (H.264) starts the next picture once a layer has been seen.
+        if slice_header & 0x80 != 0 {
+            if started_layers.contains(&layer_id) {
+                break;
+            }
+
+            started_layers.push(layer_id);
         }
+
+        slices.push(nal.data);
     }
+
+    slices
 }
 
-#[allow(unused)]
-struct DstBuffer {
-    data: *mut u8,
-    len: usize,
-    written_size: usize,
+/// Where the decode callback writes the frame for `Decoder::decode_blocking`.
+struct DecodeTarget<'a> {
+    dst: &'a mut FrameBuf,
+    color: ColorInfo,
+    hdr: HdrMetadata,
+    result: Result<(), DecodeError>,
 }
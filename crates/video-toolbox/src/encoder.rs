@@ -1,28 +1,63 @@
+use crate::{
+    color::{ColorInfo, ColorPrimaries, HdrMetadata, MatrixCoefficients, TransferCharacteristics},
+    EncodeSession, EncodedPacket, EncoderChange, EncoderConfig, EncoderConfigError,
+    EncoderProperty, EncoderUpdate, FrameBuf, FrameError, FrameHandle, HevcParameterSets,
+    HevcProfile, PacketFraming, PlaneGeometry, SessionOutput, Timestamp, VideoCodec, VideoFrame,
+};
 use core::ffi::c_void;
 use core_foundation::{
-    array::{CFArrayGetCount, CFArrayGetValueAtIndex},
-    base::{CFIndexConvertible, OSStatus},
+    array::{CFArray, CFArrayGetCount, CFArrayGetValueAtIndex},
+    base::{CFIndexConvertible, CFRelease, CFType, CFTypeRef, OSStatus, TCFType},
     boolean::CFBoolean,
+    data::CFData,
     dictionary::{
-        kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionaryCreate,
-        CFDictionaryGetValueIfPresent, CFDictionaryRef,
+        kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionary,
+        CFDictionaryCreate, CFDictionaryGetValueIfPresent, CFDictionaryRef,
     },
-    number::{CFBooleanGetValue, CFBooleanRef},
-    string::CFStringRef,
+    number::{CFBooleanGetValue, CFBooleanRef, CFNumber},
+    string::{CFString, CFStringRef},
 };
 use thiserror::Error;
 use video_toolbox_sys::{
-    kCMSampleAttachmentKey_NotSync, kCMVideoCodecType_HEVC,
+    kCMSampleAttachmentKey_NotSync, kCMVideoCodecType_HEVC, kCMVideoCodecType_HEVCWithAlpha,
+    kCVImageBufferColorPrimaries_DCI_P3, kCVImageBufferColorPrimaries_EBU_3213,
+    kCVImageBufferColorPrimaries_ITU_R_2020, kCVImageBufferColorPrimaries_ITU_R_709_2,
+    kCVImageBufferColorPrimaries_P3_D65, kCVImageBufferColorPrimaries_SMPTE_C,
+    kCVImageBufferTransferFunction_ITU_R_2020, kCVImageBufferTransferFunction_ITU_R_2100_HLG,
+    kCVImageBufferTransferFunction_ITU_R_709_2, kCVImageBufferTransferFunction_Linear,
+    kCVImageBufferTransferFunction_SMPTE_240M_1995,
+    kCVImageBufferTransferFunction_SMPTE_ST_2084_PQ, kCVImageBufferYCbCrMatrix_ITU_R_2020,
+    kCVImageBufferYCbCrMatrix_ITU_R_601_4, kCVImageBufferYCbCrMatrix_ITU_R_709_2,
+    kCVImageBufferYCbCrMatrix_SMPTE_240M_1995, kVTCompressionPropertyKey_AllowFrameReordering,
+    kVTCompressionPropertyKey_AverageBitRate, kVTCompressionPropertyKey_ColorPrimaries,
+    kVTCompressionPropertyKey_ContentLightLevelInfo, kVTCompressionPropertyKey_DataRateLimits,
+    kVTCompressionPropertyKey_ExpectedFrameRate,
+    kVTCompressionPropertyKey_MasteringDisplayColorVolume,
+    kVTCompressionPropertyKey_MaxFrameDelayCount, kVTCompressionPropertyKey_MaxKeyFrameInterval,
+    kVTCompressionPropertyKey_MaxKeyFrameIntervalDuration, kVTCompressionPropertyKey_ProfileLevel,
+    kVTCompressionPropertyKey_Quality, kVTCompressionPropertyKey_RealTime,
+    kVTCompressionPropertyKey_TargetQualityForAlpha, kVTCompressionPropertyKey_TransferFunction,
+    kVTCompressionPropertyKey_YCbCrMatrix, kVTProfileLevel_HEVC_Main10_AutoLevel,
+    kVTProfileLevel_HEVC_Main42210_AutoLevel, kVTProfileLevel_HEVC_Main_AutoLevel,
+    kVTVideoEncoderMalfunctionErr,
     kVTVideoEncoderSpecification_RequireHardwareAcceleratedVideoEncoder,
     CMBlockBufferCopyDataBytes, CMFormatDescriptionRef, CMSampleBufferGetDataBuffer,
-    CMSampleBufferGetFormatDescription, CMSampleBufferGetSampleAttachmentsArray,
-    CMSampleBufferGetTotalSampleSize, CMSampleBufferIsValid, CMSampleBufferRef, CMTime,
-    CMVideoFormatDescriptionGetHEVCParameterSetAtIndex, CVPixelBufferCreateWithBytes,
-    CVPixelBufferRef, OpaqueVTCompressionSession, VTCompressionSessionCompleteFrames,
-    VTCompressionSessionCreate, VTCompressionSessionEncodeFrame, VTCompressionSessionRef,
-    VTEncodeInfoFlags,
+    CMSampleBufferGetDecodeTimeStamp, CMSampleBufferGetDuration,
+    CMSampleBufferGetFormatDescription, CMSampleBufferGetPresentationTimeStamp,
+    CMSampleBufferGetSampleAttachmentsArray, CMSampleBufferGetTotalSampleSize, CMSampleBufferRef,
+    CMTime, CMVideoCodecType, CMVideoFormatDescriptionGetHEVCParameterSetAtIndex,
+    CVPixelBufferCreateWithBytes, CVPixelBufferCreateWithPlanarBytes, CVPixelBufferRef,
+    CVPixelBufferReleaseBytesCallback, CVPixelBufferReleasePlanarBytesCallback,
+    OpaqueVTCompressionSession, VTCompressionSessionCompleteFrames, VTCompressionSessionCreate,
+    VTCompressionSessionEncodeFrame, VTCompressionSessionRef, VTEncodeInfoFlags,
+    VTSessionSetProperties, VTSessionSetProperty,
 };
 
+/// How many [`EncoderChange`]s an encoder remembers.
+const MAX_HISTORY: usize = 100;
+
+const INVALID_TIME: CMTime = CMTime { value: 0, timescale: 0, flags: 0, epoch: 0 };
+
 #[derive(Debug, Error)]
 pub enum EncodeError {
     #[error("Initialization Error: {0}")]
@@ -30,12 +65,49 @@ pub enum EncodeError {
 
     #[error("Pixel Buffer Creation Error: {0}")]
     PixelBufferCreationError(i32),
+
+    #[error("Invalid frame: {0}")]
+    InvalidFrame(#[from] FrameError),
+
+    #[error("Set Property Error: {0}")]
+    SetPropertyError(i32),
+
+    #[error("Encode Frame Error: {0}")]
+    EncodeFrameError(i32),
+
+    #[error("The encoder dropped the frame")]
+    FrameDropped,
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(#[from] EncoderConfigError),
+
+    #[error("Encoded frame needs {required} bytes, but the output buffer has {available}")]
+    OutputTooSmall { required: usize, available: usize },
+}
+
+/// Where and when [`Encoder::encode_blocking`] wrote a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodedFrame {
+    /// Bytes written to the start of the output buffer.
+    pub size: usize,
+    pub pts: Timestamp,
+    pub dts: Timestamp,
+    pub duration: Option<Timestamp>,
 }
 
 pub struct Encoder {
     width: u32,
     height: u32,
     encode_session: *mut OpaqueVTCompressionSession,
+    has_alpha: bool,
+    config: EncoderConfig,
+    /// Frames passed to the session so far.
+    frames_submitted: u64,
+    history: Vec<EncoderChange>,
+    /// The parameter sets of the last keyframe from [`Encoder::encode`].
+    parameter_sets: Option<HevcParameterSets>,
+    color: ColorInfo,
+    hdr: HdrMetadata,
 }
 
 unsafe impl Send for Encoder {}
@@ -48,6 +120,42 @@ impl Drop for Encoder {
 
 impl Encoder {
     pub fn new(width: u32, height: u32) -> Result<Self, EncodeError> {
+        Self::create(width, height, kCMVideoCodecType_HEVC)
+    }
+
+    /// Creates an encoder with rate control, keyframe and latency settings.
+    pub fn with_config(
+        width: u32,
+        height: u32,
+        config: EncoderConfig,
+    ) -> Result<Self, EncodeError> {
+        let mut encoder = Self::create(width, height, kCMVideoCodecType_HEVC)?;
+        encoder.set_properties(&config.properties()?)?;
+        encoder.config = config;
+
+        Ok(encoder)
+    }
+
+    /// An HEVC with alpha (`muxa`) encoder. The alpha of [`PixelFormat::Argb32`]
+    /// and [`PixelFormat::Bgra32`] input is coded in a second layer at
+    /// `alpha_quality`, from 0.0 to 1.0; other input is opaque.
+    ///
+    /// [`PixelFormat::Argb32`]: crate::PixelFormat::Argb32
+    /// [`PixelFormat::Bgra32`]: crate::PixelFormat::Bgra32
+    pub fn with_alpha(width: u32, height: u32, alpha_quality: f32) -> Result<Self, EncodeError> {
+        let mut encoder = Self::create(width, height, kCMVideoCodecType_HEVCWithAlpha)?;
+        let quality = CFNumber::from(alpha_quality);
+
+        encoder.set_property(
+            unsafe { kVTCompressionPropertyKey_TargetQualityForAlpha },
+            quality.as_CFTypeRef(),
+        )?;
+        encoder.has_alpha = true;
+
+        Ok(encoder)
+    }
+
+    fn create(width: u32, height: u32, codec_type: CMVideoCodecType) -> Result<Self, EncodeError> {
         let mut encode_ref = std::mem::MaybeUninit::<VTCompressionSessionRef>::uninit();
 
         // Require hardware-accelerated encoding.
@@ -70,13 +178,13 @@ impl Encoder {
         // Create the encoder
         let create_status = unsafe {
             VTCompressionSessionCreate(
-                std::ptr::null(),       // Allocator
-                width as i32,           // Width
-                height as i32,          // Height
-                kCMVideoCodecType_HEVC, // Codec type
-                encoder_specification,  // Encoder specification,
-                std::ptr::null(),       // Src pixel buffer attributes
-                std::ptr::null(),       // Compressed data allocator
+                std::ptr::null(),      // Allocator
+                width as i32,          // Width
+                height as i32,         // Height
+                codec_type,            // Codec type
+                encoder_specification, // Encoder specification,
+                std::ptr::null(),      // Src pixel buffer attributes
+                std::ptr::null(),      // Compressed data allocator
                 Some(encode_callback), // Output callback, pass NULL if you're using VTCompressionSessionEncodeFrameWithOutputHandler
                 std::ptr::null_mut(),  // Client-defined reference value for the output callback
                 encode_ref.as_mut_ptr() as VTCompressionSessionRef,
@@ -84,13 +192,23 @@ impl Encoder {
         };
 
         if create_status != 0 {
-            println!("Failed to create VT Compression Session: {}", create_status);
             return Err(EncodeError::InitializationError(create_status));
         }
 
         let encode_session = unsafe { encode_ref.assume_init() };
 
-        Ok(Self { width, height, encode_session })
+        Ok(Self {
+            width,
+            height,
+            encode_session,
+            has_alpha: false,
+            config: EncoderConfig::default(),
+            frames_submitted: 0,
+            history: vec![],
+            parameter_sets: None,
+            color: ColorInfo::default(),
+            hdr: HdrMetadata::default(),
+        })
     }
 
     pub fn width(&self) -> u32 {
@@ -101,66 +219,449 @@ impl Encoder {
         self.height
     }
 
-    /// Encodes an uncompressed video frame from `src` into `dst`.
-    pub fn encode_blocking(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, EncodeError> {
+    /// Whether the encoder keeps alpha, see [`Encoder::with_alpha`].
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+
+    /// The session's current settings, as created and then updated.
+    pub fn config(&self) -> &EncoderConfig {
+        &self.config
+    }
+
+    /// Changes bit rate, frame rate or keyframe spacing of the running
+    /// session. Settings that need a new session fail with
+    /// [`EncoderConfigError::RequiresNewSession`] and change nothing. The
+    /// profile may still change before the first frame, as with
+    /// [`Encoder::set_profile`].
+    pub fn update(&mut self, update: &EncoderUpdate) -> Result<(), EncodeError> {
+        let mut config = self.config.clone();
+        let profile = update
+            .profile
+            .filter(|&profile| self.frames_submitted == 0 && profile != self.profile());
+
+        if profile.is_some() {
+            config.profile = profile;
+        }
+
+        let mut properties = update.apply(&mut config)?;
+        properties.splice(0..0, profile.map(EncoderProperty::ProfileLevel));
+
+        self.set_properties(&properties)?;
+        self.config = config;
+
+        if self.history.len() == MAX_HISTORY {
+            self.history.remove(0);
+        }
+
+        self.history.push(EncoderChange { frame: self.frames_submitted, properties });
+        Ok(())
+    }
+
+    /// The most recent successful updates, oldest first.
+    pub fn history(&self) -> &[EncoderChange] {
+        &self.history
+    }
+
+    pub fn profile(&self) -> HevcProfile {
+        self.config.profile.unwrap_or_default()
+    }
+
+    /// Selects the HEVC profile, [`HevcProfile::Main`] by default. Input
+    /// deeper than the profile is converted down by VideoToolbox, so pick it
+    /// with [`HevcProfile::for_format`] to keep 10-bit or 4:2:2 frames intact.
+    ///
+    /// The profile is fixed once the first frame is submitted, as for
+    /// [`EncoderUpdate::profile`].
+    pub fn set_profile(&mut self, profile: HevcProfile) -> Result<(), EncodeError> {
+        if self.frames_submitted > 0 && profile != self.profile() {
+            return Err(EncoderConfigError::RequiresNewSession("ProfileLevel").into());
+        }
+
+        self.set_properties(&[EncoderProperty::ProfileLevel(profile)])?;
+        self.config.profile = Some(profile);
+        Ok(())
+    }
+
+    pub fn color_info(&self) -> ColorInfo {
+        self.color
+    }
+
+    /// Tags the encoded stream's VUI with `color`. Code points VideoToolbox
+    /// has no constant for are left for the encoder to choose. The tags are
+    /// set together, so a failure leaves the session's tags unchanged.
+    pub fn set_color_info(&mut self, color: ColorInfo) -> Result<(), EncodeError> {
+        let properties = unsafe {
+            [
+                (kVTCompressionPropertyKey_ColorPrimaries, primaries_value(color.primaries)),
+                (kVTCompressionPropertyKey_TransferFunction, transfer_value(color.transfer)),
+                (kVTCompressionPropertyKey_YCbCrMatrix, matrix_value(color.matrix)),
+            ]
+        };
+
+        let pairs: Vec<(CFString, CFType)> = properties
+            .into_iter()
+            .filter_map(|(key, value)| unsafe {
+                Some((
+                    CFString::wrap_under_get_rule(key),
+                    CFString::wrap_under_get_rule(value?).as_CFType(),
+                ))
+            })
+            .collect();
+
+        self.set_property_pairs(&pairs)?;
+        self.color = color;
+        Ok(())
+    }
+
+    pub fn hdr_metadata(&self) -> HdrMetadata {
+        self.hdr
+    }
+
+    /// Sets the static HDR metadata for the session. Keyframes carry it in an
+    /// SEI, written by VideoToolbox where supported and by us otherwise. Both
+    /// properties are set together, so a failure changes neither.
+    pub fn set_hdr_metadata(&mut self, hdr: HdrMetadata) -> Result<(), EncodeError> {
+        let properties = unsafe {
+            [
+                (
+                    kVTCompressionPropertyKey_MasteringDisplayColorVolume,
+                    hdr.mastering_display.map(|display| CFData::from_buffer(&display.to_bytes())),
+                ),
+                (
+                    kVTCompressionPropertyKey_ContentLightLevelInfo,
+                    hdr.content_light_level.map(|level| CFData::from_buffer(&level.to_bytes())),
+                ),
+            ]
+        };
+
+        let pairs: Vec<(CFString, CFType)> = properties
+            .into_iter()
+            .filter_map(|(key, value)| unsafe {
+                Some((CFString::wrap_under_get_rule(key), value?.as_CFType()))
+            })
+            .collect();
+
+        self.set_property_pairs(&pairs)?;
+        self.hdr = hdr;
+        Ok(())
+    }
+
+    fn set_properties(&self, properties: &[EncoderProperty]) -> Result<(), EncodeError> {
+        let pairs: Vec<(CFString, CFType)> = properties
+            .iter()
+            .map(|property| unsafe {
+                (CFString::wrap_under_get_rule(property_key(property)), property_value(property))
+            })
+            .collect();
+
+        self.set_property_pairs(&pairs)
+    }
+
+    /// Sets all `pairs` with one call.
+    fn set_property_pairs(&self, pairs: &[(CFString, CFType)]) -> Result<(), EncodeError> {
+        if pairs.is_empty() {
+            return Ok(());
+        }
+
+        let dictionary = CFDictionary::from_CFType_pairs(pairs);
+
+        let status = unsafe {
+            VTSessionSetProperties(
+                self.encode_session as CFTypeRef,
+                dictionary.as_concrete_TypeRef(),
+            )
+        };
+
+        if status != 0 {
+            return Err(EncodeError::SetPropertyError(status));
+        }
+
+        Ok(())
+    }
+
+    fn set_property(&self, key: CFStringRef, value: CFTypeRef) -> Result<(), EncodeError> {
+        let status = unsafe { VTSessionSetProperty(self.encode_session as CFTypeRef, key, value) };
+
+        if status != 0 {
+            return Err(EncodeError::SetPropertyError(status));
+        }
+
+        Ok(())
+    }
+
+    /// Encodes an uncompressed video frame into `dst`, timed by the frame's
+    /// pts and duration.
+    pub fn encode_blocking(
+        &mut self,
+        frame: &VideoFrame,
+        dst: &mut [u8],
+    ) -> Result<EncodedFrame, EncodeError> {
+        let encoded = self.encode_frame(frame)?;
+
+        // 10-bit and 4:2:2 keyframes can outgrow a buffer sized for 8-bit
+        // frames, so report it rather than truncating.
+        if encoded.output.len() > dst.len() {
+            return Err(EncodeError::OutputTooSmall {
+                required: encoded.output.len(),
+                available: dst.len(),
+            });
+        }
+
+        dst[..encoded.output.len()].copy_from_slice(&encoded.output);
+
+        Ok(EncodedFrame {
+            size: encoded.output.len(),
+            pts: encoded.pts,
+            // Without reordering, frames decode in presentation order.
+            dts: encoded.dts.unwrap_or(encoded.pts),
+            duration: encoded.duration,
+        })
+    }
+
+    /// Encodes an uncompressed video frame into a packet framed as
+    /// [`EncoderConfig::framing`] asks, with the metadata a muxer needs.
+    pub fn encode(&mut self, frame: &VideoFrame) -> Result<EncodedPacket, EncodeError> {
+        let encoded = self.encode_frame(frame)?;
+        let mut packet = EncodedPacket::from_annex_b(
+            &encoded.output,
+            self.config.framing,
+            self.parameter_sets.as_ref(),
+        );
+
+        packet.pts = encoded.pts;
+        packet.dts = encoded.dts.unwrap_or(encoded.pts);
+        packet.duration = encoded.duration;
+        packet.is_keyframe = encoded.is_keyframe;
+
+        if let Some(parameter_sets) = &packet.parameter_sets {
+            self.parameter_sets = Some(parameter_sets.clone());
+        }
+
+        Ok(packet)
+    }
+
+    fn encode_frame(&mut self, frame: &VideoFrame) -> Result<DstBuffer, EncodeError> {
+        let geometry = frame.validate_encoder_input(self.width, self.height)?;
+
+        // The planes are borrowed for the duration of the blocking encode, so
+        // no release callback is needed.
+        let pixel_buffer =
+            unsafe { self.create_pixel_buffer(frame, &geometry, std::ptr::null_mut())? };
+
+        let mut dst_buffer = self.dst_buffer(frame, None);
+        let encode_status = unsafe {
+            self.encode_pixel_buffer(
+                pixel_buffer,
+                frame,
+                &mut dst_buffer as *mut DstBuffer as *mut c_void,
+            )
+        };
+
+        if encode_status != 0 {
+            unsafe { CFRelease(pixel_buffer as CFTypeRef) };
+            return Err(EncodeError::EncodeFrameError(encode_status));
+        }
+
+        self.frames_submitted += 1;
+
+        // Wait for the encode to finish.
+        let complete_status =
+            unsafe { VTCompressionSessionCompleteFrames(self.encode_session, INVALID_TIME) };
+        unsafe { CFRelease(pixel_buffer as CFTypeRef) };
+
+        if dst_buffer.status != 0 {
+            return Err(EncodeError::EncodeFrameError(dst_buffer.status));
+        }
+
+        if complete_status != 0 {
+            return Err(EncodeError::EncodeFrameError(complete_status));
+        }
+
+        if dst_buffer.output.is_empty() {
+            return Err(EncodeError::FrameDropped);
+        }
+
+        Ok(dst_buffer)
+    }
+
+    /// Wraps the planes of `frame` in a pixel buffer. With an `owner`, the
+    /// buffer frees it once VideoToolbox is done with the planes; otherwise
+    /// `frame` must outlive the encode.
+    unsafe fn create_pixel_buffer(
+        &self,
+        frame: &VideoFrame,
+        geometry: &[PlaneGeometry],
+        owner: *mut FrameBuf,
+    ) -> Result<CVPixelBufferRef, EncodeError> {
         let mut pixel_buffer_ref = std::mem::MaybeUninit::<CVPixelBufferRef>::uninit();
-        let k_cvpixel_format_type_32_argb = 0x00000020; // TODO(bschwind) - get this from CoreVideo
-        let pixel_buffer_create_status = unsafe {
+        let owned = !owner.is_null();
+
+        let pixel_buffer_create_status = if let [plane] = frame.planes() {
             CVPixelBufferCreateWithBytes(
                 std::ptr::null(),
                 self.width as usize,
                 self.height as usize,
-                k_cvpixel_format_type_32_argb,
-                src.as_ptr() as *mut c_void,
-                (4 * self.width) as usize, // bytes per row
-                None,
-                std::ptr::null_mut(),
+                frame.format().os_type(),
+                plane.data.as_ptr() as *mut c_void,
+                plane.stride, // bytes per row
+                owned.then_some(release_frame_buf as CVPixelBufferReleaseBytesCallback),
+                owner as *mut c_void,
+                std::ptr::null(),
+                pixel_buffer_ref.as_mut_ptr() as *mut CVPixelBufferRef,
+            )
+        } else {
+            let mut base_addresses: Vec<*mut c_void> =
+                frame.planes().iter().map(|plane| plane.data.as_ptr() as *mut c_void).collect();
+            let mut widths: Vec<usize> = geometry.iter().map(|plane| plane.width).collect();
+            let mut heights: Vec<usize> = geometry.iter().map(|plane| plane.height).collect();
+            let mut bytes_per_row: Vec<usize> =
+                geometry.iter().map(|plane| plane.bytes_per_row).collect();
+
+            CVPixelBufferCreateWithPlanarBytes(
+                std::ptr::null(),
+                self.width as usize,
+                self.height as usize,
+                frame.format().os_type(),
+                std::ptr::null_mut(), // Plane descriptor block
+                0,                    // Plane descriptor block size
+                geometry.len(),
+                base_addresses.as_mut_ptr(),
+                widths.as_mut_ptr(),
+                heights.as_mut_ptr(),
+                bytes_per_row.as_mut_ptr(),
+                owned
+                    .then_some(release_planar_frame_buf as CVPixelBufferReleasePlanarBytesCallback),
+                owner as *mut c_void,
                 std::ptr::null(),
                 pixel_buffer_ref.as_mut_ptr() as *mut CVPixelBufferRef,
             )
         };
 
         if pixel_buffer_create_status != 0 {
-            println!("Failed to create Pixel Buffer: {}", pixel_buffer_create_status);
+            // The release callback only runs for buffers that were created.
+            if owned {
+                drop(Box::from_raw(owner));
+            }
+
             return Err(EncodeError::PixelBufferCreationError(pixel_buffer_create_status));
         }
 
-        let pixel_buffer = unsafe { pixel_buffer_ref.assume_init() };
+        Ok(pixel_buffer_ref.assume_init())
+    }
 
-        println!("Got a pixel buffer, good to go!");
+    /// Passes `pixel_buffer` to the session, timed by `frame`. The output
+    /// callback receives `dst_buffer`.
+    unsafe fn encode_pixel_buffer(
+        &self,
+        pixel_buffer: CVPixelBufferRef,
+        frame: &VideoFrame,
+        dst_buffer: *mut c_void,
+    ) -> OSStatus {
+        let frame_time = frame.pts().to_cm_time();
+        let frame_duration = frame.duration().map_or(INVALID_TIME, Timestamp::to_cm_time);
+
+        VTCompressionSessionEncodeFrame(
+            self.encode_session,
+            pixel_buffer,
+            frame_time,           // Presentation timestamp
+            frame_duration,       // Frame duration
+            std::ptr::null(),     // Frame Properties
+            dst_buffer,           // Source frame ref con
+            std::ptr::null_mut(), // Info flags out
+        )
+    }
 
-        let frame_time = CMTime { value: 0i64, timescale: 1i32, flags: 0u32, epoch: 0i64 };
+    fn dst_buffer(&self, frame: &VideoFrame, handle: Option<FrameHandle>) -> DstBuffer {
+        DstBuffer {
+            output: vec![],
+            status: 0,
+            is_keyframe: false,
+            pts: frame.pts(),
+            dts: None,
+            duration: frame.duration(),
+            keyframe_sei: self.hdr.to_sei_nal(VideoCodec::Hevc),
+            handle,
+        }
+    }
+}
 
-        let invalid_duration = CMTime { value: 0i64, timescale: 0i32, flags: 0u32, epoch: 0i64 };
+/// Pipelined encoding, see [`EncodePipeline`](crate::EncodePipeline). Frames
+/// are copied on submission, so the caller can reuse them right away.
+impl EncodeSession for Encoder {
+    type Error = EncodeError;
+
+    fn submit(&mut self, frame: &VideoFrame, handle: FrameHandle) -> Result<(), EncodeError> {
+        let geometry = match frame.validate_encoder_input(self.width, self.height) {
+            Ok(geometry) => geometry,
+            Err(error) => {
+                handle.discard();
+                return Err(error.into());
+            },
+        };
 
-        // TODO - allocate in a Box.
-        let mut dst_buffer = DstBuffer { data: dst.as_mut_ptr(), len: dst.len(), written_size: 0 };
+        let owner = Box::into_raw(Box::new(frame.to_frame_buf()));
+        let pixel_buffer =
+            match unsafe { self.create_pixel_buffer(&(*owner).as_frame(), &geometry, owner) } {
+                Ok(pixel_buffer) => pixel_buffer,
+                Err(error) => {
+                    handle.discard();
+                    return Err(error);
+                },
+            };
 
-        // Encode the frame
-        let encode_status = unsafe {
-            VTCompressionSessionEncodeFrame(
-                self.encode_session,
-                pixel_buffer,
-                frame_time,                                       // Presentation timestamp
-                invalid_duration,                                 // Frame duration
-                std::ptr::null(),                                 // Frame Properties
-                &mut dst_buffer as *mut DstBuffer as *mut c_void, // Source frame ref con
-                std::ptr::null_mut(),                             // Info flags out
-            )
-        };
+        // Owned by the output callback once the session accepts the frame.
+        let dst_buffer = Box::into_raw(Box::new(self.dst_buffer(frame, Some(handle))));
+        let encode_status =
+            unsafe { self.encode_pixel_buffer(pixel_buffer, frame, dst_buffer as *mut c_void) };
 
-        println!("Encode status: {:?}", encode_status);
+        // The session keeps its own reference until it has encoded the frame.
+        unsafe { CFRelease(pixel_buffer as CFTypeRef) };
 
-        // Wait for the encode to finish.
-        let _ =
-            unsafe { VTCompressionSessionCompleteFrames(self.encode_session, invalid_duration) };
+        if encode_status != 0 {
+            let dst_buffer = unsafe { Box::from_raw(dst_buffer) };
+
+            if let Some(handle) = dst_buffer.handle {
+                handle.discard();
+            }
+
+            return Err(EncodeError::EncodeFrameError(encode_status));
+        }
+
+        self.frames_submitted += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), EncodeError> {
+        let status =
+            unsafe { VTCompressionSessionCompleteFrames(self.encode_session, INVALID_TIME) };
 
-        let written_size = dst_buffer.written_size;
+        if status != 0 {
+            return Err(EncodeError::EncodeFrameError(status));
+        }
+
+        Ok(())
+    }
 
-        Ok(written_size)
+    fn framing(&self) -> PacketFraming {
+        self.config.framing
     }
 }
 
+extern "C" fn release_frame_buf(release_ref_con: *mut c_void, _base_address: *const c_void) {
+    drop(unsafe { Box::from_raw(release_ref_con as *mut FrameBuf) });
+}
+
+extern "C" fn release_planar_frame_buf(
+    release_ref_con: *mut c_void,
+    _data_ptr: *const c_void,
+    _data_size: usize,
+    _number_of_planes: usize,
+    _plane_addresses: *const *const c_void,
+) {
+    drop(unsafe { Box::from_raw(release_ref_con as *mut FrameBuf) });
+}
+
 extern "C" fn encode_callback(
     _output_callback_ref_con: *mut std::os::raw::c_void,
     source_frame_ref_con: *mut std::os::raw::c_void,
@@ -168,7 +669,11 @@ extern "C" fn encode_callback(
     _info_flags: VTEncodeInfoFlags,
     sample_buffer: CMSampleBufferRef,
 ) {
-    println!("encode_callback");
+    // Failed or dropped frames have no sample buffer.
+    if status != 0 || sample_buffer.is_null() {
+        unsafe { fail_frame(source_frame_ref_con as *mut DstBuffer, status) };
+        return;
+    }
 
     let attachments = unsafe { CMSampleBufferGetSampleAttachmentsArray(sample_buffer, false) };
     let is_iframe = unsafe {
@@ -189,23 +694,15 @@ extern "C" fn encode_callback(
         }
     };
 
-    println!("Status: {}", status);
-    println!("Is I-frame: {}", is_iframe);
-
-    println!("Valid buffer: {}", unsafe { CMSampleBufferIsValid(sample_buffer) });
     // Returns the total size in bytes of sample data in a CMSampleBuffer.
     let data_length = unsafe { CMSampleBufferGetTotalSampleSize(sample_buffer) };
-    println!("Total sample size: {}", data_length);
-
     let data_buffer = unsafe { CMSampleBufferGetDataBuffer(sample_buffer) };
-    println!("Data buffer: {:?}", data_buffer);
-
     let format = unsafe { CMSampleBufferGetFormatDescription(sample_buffer) };
 
     let mut hevc_data = vec![0u8; data_length];
 
     let offset = 0;
-    let _ = unsafe {
+    let copy_status = unsafe {
         CMBlockBufferCopyDataBytes(
             data_buffer,
             offset,
@@ -214,27 +711,28 @@ extern "C" fn encode_callback(
         )
     };
 
+    if copy_status != 0 {
+        unsafe { fail_frame(source_frame_ref_con as *mut DstBuffer, copy_status) };
+        return;
+    }
+
     const HEADER: &[u8; 4] = &[0, 0, 0, 1];
 
     let mut output = vec![];
 
     if is_iframe {
-        let vps = get_hevc_param(format, HevcParam::Vps).unwrap();
-        let sps = get_hevc_param(format, HevcParam::Sps).unwrap();
-        let pps = get_hevc_param(format, HevcParam::Pps).unwrap();
-
-        output.extend_from_slice(HEADER);
-        output.extend_from_slice(&vps);
-
-        output.extend_from_slice(HEADER);
-        output.extend_from_slice(&sps);
-
-        output.extend_from_slice(HEADER);
-        output.extend_from_slice(&pps);
-
-        std::mem::forget(vps);
-        std::mem::forget(sps);
-        std::mem::forget(pps);
+        match hevc_parameter_sets(format) {
+            Ok(parameter_sets) => {
+                for parameter_set in parameter_sets {
+                    output.extend_from_slice(HEADER);
+                    output.extend_from_slice(&parameter_set);
+                }
+            },
+            Err(status) => {
+                unsafe { fail_frame(source_frame_ref_con as *mut DstBuffer, status) };
+                return;
+            },
+        }
     }
 
     let mut buffer_offset = 0;
@@ -243,63 +741,124 @@ extern "C" fn encode_callback(
     // NAL Unit.
     const LENGTH_PREFIX_SIZE: usize = 4;
 
+    let mut nals = vec![];
+
     // Convert from AVCC format to Annex B format.
     // Find each NAL unit, strip the 4 byte length prefix, replace it
     // with the HEADER, and append the data to the output buffer.
-    while buffer_offset < (hevc_data.len() - HEADER.len()) {
-        let mut nal_len = u32::from_ne_bytes([
+    while buffer_offset + LENGTH_PREFIX_SIZE <= hevc_data.len() {
+        let nal_len = u32::from_be_bytes([
             hevc_data[buffer_offset],
             hevc_data[(buffer_offset + 1)],
             hevc_data[(buffer_offset + 2)],
             hevc_data[(buffer_offset + 3)],
-        ]);
-        nal_len = u32::from_be(nal_len);
-        dbg!(nal_len);
+        ]) as usize;
 
-        output.extend_from_slice(HEADER);
         let hevc_offset = buffer_offset + LENGTH_PREFIX_SIZE; // Replace length prefix with HEADER.
-        output.extend_from_slice(&hevc_data[hevc_offset..(hevc_offset + nal_len as usize)]);
 
-        buffer_offset += LENGTH_PREFIX_SIZE;
-        buffer_offset += nal_len as usize;
+        // A length running past the sample means it is corrupt.
+        let Some(nal) = hevc_data.get(hevc_offset..hevc_offset + nal_len) else {
+            unsafe {
+                fail_frame(source_frame_ref_con as *mut DstBuffer, kVTVideoEncoderMalfunctionErr)
+            };
+            return;
+        };
+
+        nals.extend_from_slice(HEADER);
+        nals.extend_from_slice(nal);
+
+        buffer_offset = hevc_offset + nal_len;
     }
 
     unsafe {
         if let Some(dst_buffer) = (source_frame_ref_con as *mut DstBuffer).as_mut() {
-            dst_buffer.written_size = output.len();
-
-            let dst_slice = std::slice::from_raw_parts_mut(dst_buffer.data, dst_buffer.len);
-            dst_slice[..output.len()].copy_from_slice(&output);
+            if let Some(pts) =
+                Timestamp::from_cm_time(CMSampleBufferGetPresentationTimeStamp(sample_buffer))
+            {
+                dst_buffer.pts = pts;
+            }
+
+            dst_buffer.dts =
+                Timestamp::from_cm_time(CMSampleBufferGetDecodeTimeStamp(sample_buffer));
+            dst_buffer.duration = Timestamp::from_cm_time(CMSampleBufferGetDuration(sample_buffer))
+                .or(dst_buffer.duration);
+            dst_buffer.is_keyframe = is_iframe;
+
+            // Add the HDR SEI ourselves if VideoToolbox did not.
+            if let (true, Some(sei)) = (is_iframe, &dst_buffer.keyframe_sei) {
+                if HdrMetadata::from_access_unit(VideoCodec::Hevc, &nals).is_empty() {
+                    output.extend_from_slice(HEADER);
+                    output.extend_from_slice(sei);
+                }
+            }
+
+            output.extend_from_slice(&nals);
+            dst_buffer.output = output;
+
+            // Pipelined frames hand their buffer over to the callback.
+            if let Some(handle) = dst_buffer.handle.take() {
+                let dst_buffer = *Box::from_raw(dst_buffer as *mut DstBuffer);
+
+                handle.complete(SessionOutput {
+                    access_unit: dst_buffer.output,
+                    pts: Some(dst_buffer.pts),
+                    dts: dst_buffer.dts,
+                    duration: dst_buffer.duration,
+                    is_keyframe: Some(dst_buffer.is_keyframe),
+                });
+            }
         }
     }
-
-    dbg!(output.len());
 }
 
 struct DstBuffer {
-    data: *mut u8,
-    len: usize,
-    written_size: usize,
+    /// The encoded access unit, in Annex B format.
+    output: Vec<u8>,
+    /// The output callback's status, for frames that failed to encode.
+    status: OSStatus,
+    /// Whether the sample buffer is a sync sample.
+    is_keyframe: bool,
+    pts: Timestamp,
+    /// Invalid in the sample buffer when frames are not reordered.
+    dts: Option<Timestamp>,
+    duration: Option<Timestamp>,
+    /// Inserted after the parameter sets of keyframes.
+    keyframe_sei: Option<Vec<u8>>,
+    /// Set for frames from [`EncodeSession::submit`], whose buffer is boxed.
+    handle: Option<FrameHandle>,
 }
 
-#[derive(Debug)]
-enum HevcParam {
-    Vps,
-    Sps,
-    Pps,
-}
+/// Reports a frame without output: pipelined frames fail their handle, and
+/// blocking encodes find `status` in their buffer.
+unsafe fn fail_frame(dst_buffer: *mut DstBuffer, status: OSStatus) {
+    let Some(dst) = dst_buffer.as_mut() else {
+        return;
+    };
 
-impl HevcParam {
-    fn index(&self) -> usize {
-        match self {
-            HevcParam::Vps => 0,
-            HevcParam::Sps => 1,
-            HevcParam::Pps => 2,
-        }
+    match dst.handle.take() {
+        Some(handle) => {
+            drop(Box::from_raw(dst_buffer));
+            handle.fail(status);
+        },
+        None => dst.status = status,
     }
 }
 
-fn get_hevc_param(format: CMFormatDescriptionRef, param: HevcParam) -> Option<Vec<u8>> {
+/// Copies the VPS, SPS and PPS, followed by the alpha layer's SPS and PPS for
+/// HEVC with alpha.
+fn hevc_parameter_sets(format: CMFormatDescriptionRef) -> Result<Vec<Vec<u8>>, OSStatus> {
+    let (first, count) = get_hevc_param(format, 0)?;
+    let rest = (1..count)
+        .map(|index| get_hevc_param(format, index).map(|(parameter_set, _)| parameter_set));
+
+    std::iter::once(Ok(first)).chain(rest).collect()
+}
+
+/// Copies the parameter set at `index`, also returning how many there are.
+fn get_hevc_param(
+    format: CMFormatDescriptionRef,
+    index: usize,
+) -> Result<(Vec<u8>, usize), OSStatus> {
     let mut param_set_ptr: *const u8 = std::ptr::null_mut();
     let mut param_set_size: usize = 0;
     let mut param_set_count: usize = 0;
@@ -308,7 +867,7 @@ fn get_hevc_param(format: CMFormatDescriptionRef, param: HevcParam) -> Option<Ve
     let status = unsafe {
         CMVideoFormatDescriptionGetHEVCParameterSetAtIndex(
             format,
-            param.index(),
+            index,
             &mut param_set_ptr,
             &mut param_set_size,
             &mut param_set_count,
@@ -316,18 +875,103 @@ fn get_hevc_param(format: CMFormatDescriptionRef, param: HevcParam) -> Option<Ve
         )
     };
 
-    println!(
-        "{:?} - size: {}, count: {}, NAL header len: {:?}",
-        param, param_set_size, param_set_count, nal_unit_header_length
-    );
+    if status != 0 {
+        return Err(status);
+    }
 
-    if status == 0 {
-        unsafe {
-            let vec = Vec::from_raw_parts(param_set_ptr as *mut _, param_set_size, param_set_size);
-            println!("{:?}", vec);
-            Some(vec)
-        }
-    } else {
-        None
+    // The format description owns the parameter set, so copy it out.
+    let vec = unsafe { std::slice::from_raw_parts(param_set_ptr, param_set_size) }.to_vec();
+    Ok((vec, param_set_count))
+}
+
+unsafe fn property_key(property: &EncoderProperty) -> CFStringRef {
+    match property {
+        EncoderProperty::ProfileLevel(_) => kVTCompressionPropertyKey_ProfileLevel,
+        EncoderProperty::AverageBitRate(_) => kVTCompressionPropertyKey_AverageBitRate,
+        EncoderProperty::DataRateLimits(_) => kVTCompressionPropertyKey_DataRateLimits,
+        EncoderProperty::Quality(_) => kVTCompressionPropertyKey_Quality,
+        EncoderProperty::MaxKeyFrameInterval(_) => kVTCompressionPropertyKey_MaxKeyFrameInterval,
+        EncoderProperty::MaxKeyFrameIntervalDuration(_) => {
+            kVTCompressionPropertyKey_MaxKeyFrameIntervalDuration
+        },
+        EncoderProperty::RealTime(_) => kVTCompressionPropertyKey_RealTime,
+        EncoderProperty::AllowFrameReordering(_) => kVTCompressionPropertyKey_AllowFrameReordering,
+        EncoderProperty::ExpectedFrameRate(_) => kVTCompressionPropertyKey_ExpectedFrameRate,
+        EncoderProperty::MaxFrameDelayCount(_) => kVTCompressionPropertyKey_MaxFrameDelayCount,
     }
 }
+
+unsafe fn property_value(property: &EncoderProperty) -> CFType {
+    match *property {
+        EncoderProperty::ProfileLevel(profile) => {
+            let level = match profile {
+                HevcProfile::Main => kVTProfileLevel_HEVC_Main_AutoLevel,
+                HevcProfile::Main10 => kVTProfileLevel_HEVC_Main10_AutoLevel,
+                HevcProfile::Main42210 => kVTProfileLevel_HEVC_Main42210_AutoLevel,
+            };
+
+            CFString::wrap_under_get_rule(level).as_CFType()
+        },
+        EncoderProperty::AverageBitRate(value)
+        | EncoderProperty::MaxKeyFrameInterval(value)
+        | EncoderProperty::MaxFrameDelayCount(value) => CFNumber::from(value).as_CFType(),
+        EncoderProperty::Quality(value)
+        | EncoderProperty::MaxKeyFrameIntervalDuration(value)
+        | EncoderProperty::ExpectedFrameRate(value) => CFNumber::from(value).as_CFType(),
+        EncoderProperty::RealTime(value) | EncoderProperty::AllowFrameReordering(value) => {
+            CFBoolean::from(value).as_CFType()
+        },
+        EncoderProperty::DataRateLimits(ref limits) => {
+            let values: Vec<CFType> = limits
+                .iter()
+                .flat_map(|&(bytes, seconds)| {
+                    [CFNumber::from(bytes).as_CFType(), CFNumber::from(seconds).as_CFType()]
+                })
+                .collect();
+
+            CFArray::from_CFTypes(&values).as_CFType()
+        },
+    }
+}
+
+unsafe fn primaries_value(primaries: ColorPrimaries) -> Option<CFStringRef> {
+    Some(match primaries {
+        ColorPrimaries::Bt709 => kCVImageBufferColorPrimaries_ITU_R_709_2,
+        ColorPrimaries::Smpte170M | ColorPrimaries::Smpte240M => {
+            kCVImageBufferColorPrimaries_SMPTE_C
+        },
+        ColorPrimaries::Bt470Bg => kCVImageBufferColorPrimaries_EBU_3213,
+        ColorPrimaries::Bt2020 => kCVImageBufferColorPrimaries_ITU_R_2020,
+        ColorPrimaries::DciP3 => kCVImageBufferColorPrimaries_DCI_P3,
+        ColorPrimaries::DisplayP3 => kCVImageBufferColorPrimaries_P3_D65,
+        _ => return None,
+    })
+}
+
+unsafe fn transfer_value(transfer: TransferCharacteristics) -> Option<CFStringRef> {
+    Some(match transfer {
+        TransferCharacteristics::Bt709 | TransferCharacteristics::Smpte170M => {
+            kCVImageBufferTransferFunction_ITU_R_709_2
+        },
+        TransferCharacteristics::Smpte240M => kCVImageBufferTransferFunction_SMPTE_240M_1995,
+        TransferCharacteristics::Linear => kCVImageBufferTransferFunction_Linear,
+        TransferCharacteristics::Bt2020_10 | TransferCharacteristics::Bt2020_12 => {
+            kCVImageBufferTransferFunction_ITU_R_2020
+        },
+        TransferCharacteristics::Pq => kCVImageBufferTransferFunction_SMPTE_ST_2084_PQ,
+        TransferCharacteristics::Hlg => kCVImageBufferTransferFunction_ITU_R_2100_HLG,
+        _ => return None,
+    })
+}
+
+unsafe fn matrix_value(matrix: MatrixCoefficients) -> Option<CFStringRef> {
+    Some(match matrix {
+        MatrixCoefficients::Bt709 => kCVImageBufferYCbCrMatrix_ITU_R_709_2,
+        MatrixCoefficients::Bt470Bg | MatrixCoefficients::Smpte170M => {
+            kCVImageBufferYCbCrMatrix_ITU_R_601_4
+        },
+        MatrixCoefficients::Smpte240M => kCVImageBufferYCbCrMatrix_SMPTE_240M_1995,
+        MatrixCoefficients::Bt2020Ncl => kCVImageBufferYCbCrMatrix_ITU_R_2020,
+        _ => return None,
+    })
+}
@@ -0,0 +1,375 @@
+//! Encoder session settings, and their translation into VideoToolbox
+//! compression properties.
+
+use crate::{FrameRate, HevcProfile, PacketFraming};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EncoderConfigError {
+    #[error("Quality must be between 0.0 and 1.0, got {0}")]
+    InvalidQuality(f32),
+
+    #[error("Data rate limit has a zero period")]
+    InvalidDataRateLimit,
+
+    #[error("Expected frame rate {0}/{1} is not positive")]
+    InvalidFrameRate(u32, u32),
+
+    #[error("{0} cannot change without a new session")]
+    RequiresNewSession(&'static str),
+}
+
+/// A hard cap of `bytes` in any window of `period`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRateLimit {
+    pub bytes: u64,
+    pub period: Duration,
+}
+
+impl DataRateLimit {
+    pub fn new(bytes: u64, period: Duration) -> Self {
+        Self { bytes, period }
+    }
+}
+
+/// Encoder session settings. Anything left `None` is chosen by the encoder.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncoderConfig {
+    pub profile: Option<HevcProfile>,
+    /// Long term average in bits per second.
+    pub average_bit_rate: Option<u32>,
+    pub data_rate_limits: Vec<DataRateLimit>,
+    /// Constant quality from 0.0 to 1.0, for encoders not targeting a bit rate.
+    pub quality: Option<f32>,
+    /// The most frames from one keyframe to the next.
+    pub max_keyframe_interval: Option<u32>,
+    /// The longest time from one keyframe to the next.
+    pub max_keyframe_interval_duration: Option<Duration>,
+    /// Encode in real time, e.g. for live streaming, rather than as fast as
+    /// possible.
+    pub real_time: Option<bool>,
+    /// Whether B-frames may reorder output. Disable for low latency.
+    pub allow_frame_reordering: Option<bool>,
+    /// A hint for rate control, not a promise about input timing.
+    pub expected_frame_rate: Option<FrameRate>,
+    /// The most frames the encoder may hold before emitting output.
+    pub max_frame_delay: Option<u32>,
+    /// How `Encoder::encode` frames its packets.
+    /// Not a session property.
+    pub framing: PacketFraming,
+}
+
+impl EncoderConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_profile(mut self, profile: HevcProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_average_bit_rate(mut self, bits_per_second: u32) -> Self {
+        self.average_bit_rate = Some(bits_per_second);
+        self
+    }
+
+    pub fn with_data_rate_limit(mut self, limit: DataRateLimit) -> Self {
+        self.data_rate_limits.push(limit);
+        self
+    }
+
+    pub fn with_quality(mut self, quality: f32) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    pub fn with_max_keyframe_interval(mut self, frames: u32) -> Self {
+        self.max_keyframe_interval = Some(frames);
+        self
+    }
+
+    pub fn with_max_keyframe_interval_duration(mut self, duration: Duration) -> Self {
+        self.max_keyframe_interval_duration = Some(duration);
+        self
+    }
+
+    pub fn with_real_time(mut self, real_time: bool) -> Self {
+        self.real_time = Some(real_time);
+        self
+    }
+
+    pub fn with_frame_reordering(mut self, allow: bool) -> Self {
+        self.allow_frame_reordering = Some(allow);
+        self
+    }
+
+    pub fn with_expected_frame_rate(mut self, frame_rate: FrameRate) -> Self {
+        self.expected_frame_rate = Some(frame_rate);
+        self
+    }
+
+    pub fn with_max_frame_delay(mut self, frames: u32) -> Self {
+        self.max_frame_delay = Some(frames);
+        self
+    }
+
+    pub fn with_framing(mut self, framing: PacketFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Whether B-frames may reorder output, VideoToolbox allowing it when unset.
+    pub fn frame_reordering_allowed(&self) -> bool {
+        self.allow_frame_reordering.unwrap_or(true)
+    }
+
+    /// Settings for interactive streaming: real time, no reordering and no
+    /// frames held back.
+    pub fn low_latency() -> Self {
+        Self::new().with_real_time(true).with_frame_reordering(false).with_max_frame_delay(0)
+    }
+
+    /// The compression properties to set on the session, in a fixed order.
+    pub fn properties(&self) -> Result<Vec<EncoderProperty>, EncoderConfigError> {
+        let mut properties = vec![];
+
+        if let Some(profile) = self.profile {
+            properties.push(EncoderProperty::ProfileLevel(profile));
+        }
+
+        if let Some(bit_rate) = self.average_bit_rate {
+            properties.push(EncoderProperty::AverageBitRate(bit_rate as i64));
+        }
+
+        if !self.data_rate_limits.is_empty() {
+            if self.data_rate_limits.iter().any(|limit| limit.period.is_zero()) {
+                return Err(EncoderConfigError::InvalidDataRateLimit);
+            }
+
+            let limits = self
+                .data_rate_limits
+                .iter()
+                .map(|limit| (limit.bytes as i64, limit.period.as_secs_f64()))
+                .collect();
+            properties.push(EncoderProperty::DataRateLimits(limits));
+        }
+
+        if let Some(quality) = self.quality {
+            if !(0.0..=1.0).contains(&quality) {
+                return Err(EncoderConfigError::InvalidQuality(quality));
+            }
+
+            properties.push(EncoderProperty::Quality(quality as f64));
+        }
+
+        if let Some(frames) = self.max_keyframe_interval {
+            properties.push(EncoderProperty::MaxKeyFrameInterval(frames as i64));
+        }
+
+        if let Some(duration) = self.max_keyframe_interval_duration {
+            properties.push(EncoderProperty::MaxKeyFrameIntervalDuration(duration.as_secs_f64()));
+        }
+
+        if let Some(real_time) = self.real_time {
+            properties.push(EncoderProperty::RealTime(real_time));
+        }
+
+        if let Some(allow) = self.allow_frame_reordering {
+            properties.push(EncoderProperty::AllowFrameReordering(allow));
+        }
+
+        if let Some(frame_rate) = self.expected_frame_rate {
+            if frame_rate.numerator == 0 || frame_rate.denominator == 0 {
+                return Err(EncoderConfigError::InvalidFrameRate(
+                    frame_rate.numerator,
+                    frame_rate.denominator,
+                ));
+            }
+
+            properties.push(EncoderProperty::ExpectedFrameRate(frame_rate.as_f64()));
+        }
+
+        if let Some(frames) = self.max_frame_delay {
+            properties.push(EncoderProperty::MaxFrameDelayCount(frames as i64));
+        }
+
+        Ok(properties)
+    }
+}
+
+/// One `kVTCompressionPropertyKey_*` and its value, in the CoreFoundation type
+/// VideoToolbox expects: integers and floats become `CFNumber`s, flags
+/// `CFBoolean`s.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncoderProperty {
+    ProfileLevel(HevcProfile),
+    AverageBitRate(i64),
+    /// Alternating byte counts and periods in seconds, as one `CFArray`.
+    DataRateLimits(Vec<(i64, f64)>),
+    Quality(f64),
+    MaxKeyFrameInterval(i64),
+    MaxKeyFrameIntervalDuration(f64),
+    RealTime(bool),
+    AllowFrameReordering(bool),
+    ExpectedFrameRate(f64),
+    MaxFrameDelayCount(i64),
+}
+
+impl EncoderProperty {
+    /// The string value of the property's key constant.
+    pub fn key(&self) -> &'static str {
+        match self {
+            EncoderProperty::ProfileLevel(_) => "ProfileLevel",
+            EncoderProperty::AverageBitRate(_) => "AverageBitRate",
+            EncoderProperty::DataRateLimits(_) => "DataRateLimits",
+            EncoderProperty::Quality(_) => "Quality",
+            EncoderProperty::MaxKeyFrameInterval(_) => "MaxKeyFrameInterval",
+            EncoderProperty::MaxKeyFrameIntervalDuration(_) => "MaxKeyFrameIntervalDuration",
+            EncoderProperty::RealTime(_) => "RealTime",
+            EncoderProperty::AllowFrameReordering(_) => "AllowFrameReordering",
+            EncoderProperty::ExpectedFrameRate(_) => "ExpectedFrameRate",
+            EncoderProperty::MaxFrameDelayCount(_) => "MaxFrameDelayCount",
+        }
+    }
+}
+
+/// Changes to a running encoder. Rate control, keyframe spacing and frame rate
+/// apply from the next frame without restarting the GOP; the rest are fixed
+/// once the session exists.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncoderUpdate {
+    pub average_bit_rate: Option<u32>,
+    /// Replaces every limit, or removes them when empty.
+    pub data_rate_limits: Option<Vec<DataRateLimit>>,
+    pub quality: Option<f32>,
+    pub max_keyframe_interval: Option<u32>,
+    pub max_keyframe_interval_duration: Option<Duration>,
+    pub expected_frame_rate: Option<FrameRate>,
+    /// Rejected with [`EncoderConfigError::RequiresNewSession`] if it differs
+    /// from the session's, unless `Encoder::update` is called before the
+    /// first frame.
+    pub profile: Option<HevcProfile>,
+    /// Rejected with [`EncoderConfigError::RequiresNewSession`] if it differs
+    /// from the session's.
+    pub allow_frame_reordering: Option<bool>,
+    /// Rejected like `profile`.
+    pub max_frame_delay: Option<u32>,
+}
+
+impl EncoderUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_average_bit_rate(mut self, bits_per_second: u32) -> Self {
+        self.average_bit_rate = Some(bits_per_second);
+        self
+    }
+
+    pub fn with_data_rate_limits(mut self, limits: Vec<DataRateLimit>) -> Self {
+        self.data_rate_limits = Some(limits);
+        self
+    }
+
+    pub fn with_quality(mut self, quality: f32) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    pub fn with_max_keyframe_interval(mut self, frames: u32) -> Self {
+        self.max_keyframe_interval = Some(frames);
+        self
+    }
+
+    pub fn with_max_keyframe_interval_duration(mut self, duration: Duration) -> Self {
+        self.max_keyframe_interval_duration = Some(duration);
+        self
+    }
+
+    pub fn with_expected_frame_rate(mut self, frame_rate: FrameRate) -> Self {
+        self.expected_frame_rate = Some(frame_rate);
+        self
+    }
+
+    pub fn with_profile(mut self, profile: HevcProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_frame_reordering(mut self, allow: bool) -> Self {
+        self.allow_frame_reordering = Some(allow);
+        self
+    }
+
+    pub fn with_max_frame_delay(mut self, frames: u32) -> Self {
+        self.max_frame_delay = Some(frames);
+        self
+    }
+
+    /// Applies the update to `config`, returning the properties to set on the
+    /// running session. `config` is left untouched on error.
+    pub fn apply(
+        &self,
+        config: &mut EncoderConfig,
+    ) -> Result<Vec<EncoderProperty>, EncoderConfigError> {
+        let fixed = [
+            (
+                "ProfileLevel",
+                self.profile.is_some_and(|profile| config.profile.unwrap_or_default() != profile),
+            ),
+            (
+                "AllowFrameReordering",
+                self.allow_frame_reordering
+                    .is_some_and(|allow| config.frame_reordering_allowed() != allow),
+            ),
+            // An unset delay is VideoToolbox's unlimited one, which no count restates.
+            (
+                "MaxFrameDelayCount",
+                self.max_frame_delay.is_some_and(|frames| config.max_frame_delay != Some(frames)),
+            ),
+        ];
+
+        if let Some((key, _)) = fixed.into_iter().find(|(_, changed)| *changed) {
+            return Err(EncoderConfigError::RequiresNewSession(key));
+        }
+
+        let changes = EncoderConfig {
+            average_bit_rate: self.average_bit_rate,
+            data_rate_limits: self.data_rate_limits.clone().unwrap_or_default(),
+            quality: self.quality,
+            max_keyframe_interval: self.max_keyframe_interval,
+            max_keyframe_interval_duration: self.max_keyframe_interval_duration,
+            expected_frame_rate: self.expected_frame_rate,
+            ..EncoderConfig::default()
+        };
+        let mut properties = changes.properties()?;
+
+        // An empty list still has to reach the session to clear the limits.
+        if self.data_rate_limits.as_ref().is_some_and(Vec::is_empty) {
+            properties.push(EncoderProperty::DataRateLimits(vec![]));
+        }
+
+        config.average_bit_rate = self.average_bit_rate.or(config.average_bit_rate);
+        config.quality = self.quality.or(config.quality);
+        config.max_keyframe_interval = self.max_keyframe_interval.or(config.max_keyframe_interval);
+        config.max_keyframe_interval_duration =
+            self.max_keyframe_interval_duration.or(config.max_keyframe_interval_duration);
+        config.expected_frame_rate = self.expected_frame_rate.or(config.expected_frame_rate);
+
+        if let Some(limits) = &self.data_rate_limits {
+            config.data_rate_limits = limits.clone();
+        }
+
+        Ok(properties)
+    }
+}
+
+/// A successful [`EncoderUpdate`], kept for debugging.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncoderChange {
+    /// How many frames had been submitted before the change.
+    pub frame: u64,
+    pub properties: Vec<EncoderProperty>,
+}
@@ -0,0 +1,299 @@
+//! Raw Annex B elementary stream files (`.hevc`, `.h264`), with optional
+//! mkvmerge-style "timecode format v2" sidecars carrying one presentation
+//! time per access unit.
+
+use crate::{FrameRate, NalIterator, ParameterSets, VideoCodec};
+use std::{
+    fs,
+    io::{self, Write},
+    iter::Peekable,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
+
+/// The first line of a timecodes v2 file.
+pub const TIMECODES_V2_HEADER: &str = "# timecode format v2";
+
+#[derive(Debug, Error)]
+pub enum EsError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Unknown elementary stream extension: {0}")]
+    UnknownExtension(PathBuf),
+
+    #[error("Not a timecodes v2 file")]
+    UnsupportedTimecodeFormat,
+
+    #[error("Invalid timecode on line {0}")]
+    InvalidTimecode(usize),
+}
+
+/// An access unit borrowed from an `EsReader`, with its start codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EsAccessUnit<'a> {
+    pub data: &'a [u8],
+    /// From the sidecar if one was loaded, otherwise derived from the VUI
+    /// frame rate. `None` if neither is available.
+    ///
+    /// Derived timestamps count frame durations in decode order, so they are
+    /// only presentation times for streams without frame reordering.
+    pub pts: Option<Duration>,
+    pub is_keyframe: bool,
+}
+
+/// Reads an elementary stream file into memory and splits it into access units.
+pub struct EsReader {
+    codec: VideoCodec,
+    data: Vec<u8>,
+    timecodes: Option<Vec<Duration>>,
+}
+
+impl EsReader {
+    pub fn new(codec: VideoCodec, data: Vec<u8>) -> Self {
+        Self { codec, data, timecodes: None }
+    }
+
+    /// Reads a `.hevc`/`.h265` or `.h264`/`.avc` file, picking the codec
+    /// from its extension.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, EsError> {
+        let path = path.as_ref();
+        let codec =
+            codec_from_path(path).ok_or_else(|| EsError::UnknownExtension(path.to_path_buf()))?;
+
+        Ok(Self::new(codec, fs::read(path)?))
+    }
+
+    /// Uses the timestamps of a timecodes v2 sidecar file.
+    pub fn open_timecodes(&mut self, path: impl AsRef<Path>) -> Result<(), EsError> {
+        self.timecodes = Some(parse_timecodes(&fs::read_to_string(path)?)?);
+        Ok(())
+    }
+
+    pub fn set_timecodes(&mut self, timecodes: Vec<Duration>) {
+        self.timecodes = Some(timecodes);
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.codec
+    }
+
+    /// The first parameter sets in the stream.
+    pub fn parameter_sets(&self) -> Option<ParameterSets> {
+        ParameterSets::from_annex_b(self.codec, &self.data)
+    }
+
+    /// The frame rate signalled in the VUI timing info of the first SPS.
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.parameter_sets()?.sps_info()?.frame_rate
+    }
+
+    pub fn access_units(&self) -> impl Iterator<Item = EsAccessUnit<'_>> {
+        let frame_rate = self.frame_rate();
+
+        AccessUnits::new(self.codec, &self.data).enumerate().map(move |(index, data)| {
+            let pts = match &self.timecodes {
+                Some(timecodes) => timecodes.get(index).copied(),
+                None => frame_rate.map(|frame_rate| frame_rate.duration_of(index as u64)),
+            };
+
+            EsAccessUnit { data, pts, is_keyframe: self.codec.is_keyframe(data) }
+        })
+    }
+}
+
+/// Writes Annex B access units, such as `Encoder` output, back to back, with
+/// an optional timecodes v2 sidecar.
+pub struct EsWriter<W: Write> {
+    writer: W,
+    timecodes: Option<W>,
+}
+
+impl EsWriter<fs::File> {
+    /// Creates an elementary stream file, and a timecodes sidecar at
+    /// `timecodes_path` if given.
+    pub fn create(path: impl AsRef<Path>, timecodes_path: Option<&Path>) -> Result<Self, EsError> {
+        let writer = Self::new(fs::File::create(path)?);
+
+        match timecodes_path {
+            Some(timecodes_path) => writer.with_timecodes(fs::File::create(timecodes_path)?),
+            None => Ok(writer),
+        }
+    }
+}
+
+impl<W: Write> EsWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, timecodes: None }
+    }
+
+    /// Also writes the timestamp of every access unit to `timecodes`.
+    pub fn with_timecodes(mut self, mut timecodes: W) -> Result<Self, EsError> {
+        writeln!(timecodes, "{}", TIMECODES_V2_HEADER)?;
+        self.timecodes = Some(timecodes);
+        Ok(self)
+    }
+
+    pub fn write_access_unit(&mut self, access_unit: &[u8], pts: Duration) -> Result<(), EsError> {
+        self.writer.write_all(access_unit)?;
+
+        if let Some(timecodes) = &mut self.timecodes {
+            writeln!(timecodes, "{}", format_timecode(pts))?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes both files, returning the stream and sidecar writers.
+    pub fn finish(mut self) -> Result<(W, Option<W>), EsError> {
+        self.writer.flush()?;
+
+        if let Some(timecodes) = &mut self.timecodes {
+            timecodes.flush()?;
+        }
+
+        Ok((self.writer, self.timecodes))
+    }
+}
+
+/// Parses a timecodes v2 file: a header line, then one timestamp in
+/// (possibly fractional) milliseconds per frame.
+pub fn parse_timecodes(text: &str) -> Result<Vec<Duration>, EsError> {
+    let mut lines = text.lines();
+
+    // mkvmerge also accepts the newer "timestamp" spelling.
+    let header = lines.next().unwrap_or_default().trim().to_ascii_lowercase();
+    if header != TIMECODES_V2_HEADER && header != "# timestamp format v2" {
+        return Err(EsError::UnsupportedTimecodeFormat);
+    }
+
+    lines
+        .enumerate()
+        .map(|(index, line)| (index + 2, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| parse_millis(line).ok_or(EsError::InvalidTimecode(line_number)))
+        .collect()
+}
+
+/// Parses fractional milliseconds, keeping nanosecond precision.
+fn parse_millis(text: &str) -> Option<Duration> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let nanos = format!("{:0<6.6}", fraction).parse().ok()?;
+    Duration::from_millis(whole.parse().ok()?).checked_add(Duration::from_nanos(nanos))
+}
+
+/// Formats a timestamp in milliseconds with up to six decimal places.
+fn format_timecode(pts: Duration) -> String {
+    let nanos = pts.as_nanos();
+    let fraction = format!("{:06}", nanos % 1_000_000);
+    let fraction = fraction.trim_end_matches('0');
+
+    match fraction {
+        "" => format!("{}", nanos / 1_000_000),
+        _ => format!("{}.{}", nanos / 1_000_000, fraction),
+    }
+}
+
+fn codec_from_path(path: &Path) -> Option<VideoCodec> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    match extension.as_str() {
+        "hevc" | "h265" | "265" => Some(VideoCodec::Hevc),
+        "h264" | "264" | "avc" => Some(VideoCodec::H264),
+        _ => None,
+    }
+}
+
+/// Groups the NAL units of an Annex B buffer into access units (H.264
+/// section 7.4.1.2.3, HEVC section 7.4.2.4.4).
+struct AccessUnits<'a> {
+    codec: VideoCodec,
+    data: &'a [u8],
+    nals: Peekable<NalIterator<'a>>,
+}
+
+impl<'a> AccessUnits<'a> {
+    fn new(codec: VideoCodec, data: &'a [u8]) -> Self {
+        Self { codec, data, nals: NalIterator::new(data).peekable() }
+    }
+
+    /// The offset of the start code before `nal`, a subslice of `data`.
+    fn start_code_offset(&self, nal: &[u8]) -> usize {
+        let offset = nal.as_ptr() as usize - self.data.as_ptr() as usize;
+        let start = offset.saturating_sub(3);
+
+        match start.checked_sub(1) {
+            Some(zero) if self.data[zero] == 0 => zero,
+            _ => start,
+        }
+    }
+
+    fn end_offset(&self, nal: &[u8]) -> usize {
+        nal.as_ptr() as usize - self.data.as_ptr() as usize + nal.len()
+    }
+}
+
+impl<'a> Iterator for AccessUnits<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.nals.next()?.data;
+        let start = self.start_code_offset(first);
+        let mut end = self.end_offset(first);
+        let mut has_picture = is_vcl(self.codec, first);
+
+        while let Some(nal) = self.nals.peek().map(|nal| nal.data) {
+            if has_picture && starts_access_unit(self.codec, nal) {
+                break;
+            }
+
+            has_picture |= is_vcl(self.codec, nal);
+            end = self.end_offset(nal);
+            self.nals.next();
+        }
+
+        Some(&self.data[start..end])
+    }
+}
+
+/// Whether `nal` is a slice of a coded picture.
+fn is_vcl(codec: VideoCodec, nal: &[u8]) -> bool {
+    match (codec, nal.first()) {
+        (VideoCodec::Hevc, Some(header)) => (header >> 1) & 0b11_1111 < 32,
+        (VideoCodec::H264, Some(header)) => (1..=5).contains(&(header & 0b1_1111)),
+        (_, None) => false,
+    }
+}
+
+/// Whether `nal` can only appear at the start of an access unit: parameter
+/// sets, prefix SEI and delimiters, or the first slice of a picture.
+fn starts_access_unit(codec: VideoCodec, nal: &[u8]) -> bool {
+    match codec {
+        VideoCodec::Hevc => {
+            let Some(&header) = nal.first() else { return false };
+
+            match (header >> 1) & 0b11_1111 {
+                // first_slice_segment_in_pic_flag
+                0..=31 => nal.get(2).is_some_and(|byte| byte & 0x80 != 0),
+                32..=35 | 39 | 41..=44 | 48..=55 => true,
+                _ => false,
+            }
+        },
+        VideoCodec::H264 => {
+            let Some(&header) = nal.first() else { return false };
+
+            match header & 0b1_1111 {
+                // first_mb_in_slice is zero, a single set bit in Exp-Golomb.
+                1..=5 => nal.get(1).is_some_and(|byte| byte & 0x80 != 0),
+                6..=9 | 14..=18 => true,
+                _ => false,
+            }
+        },
+    }
+}
@@ -0,0 +1,546 @@
+//! Uncompressed video frames with per-plane strides.
+//!
+//! [`VideoFrame`] borrows its planes, for example from a capture buffer, while
+//! [`FrameBuf`] owns them. Both are validated on construction, so every plane
+//! is known to hold its rows at its stride, though the last row only needs
+//! its pixel bytes. Encoder input must also pad the last row to the stride.
+
+use crate::{
+    color::{ColorInfo, HdrMetadata},
+    PixelFormat, Timestamp,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FrameError {
+    #[error("Frame dimensions must be non-zero, got {width}x{height}")]
+    InvalidDimensions { width: u32, height: u32 },
+
+    #[error("{format:?} frames have {expected} planes, got {actual}")]
+    PlaneCount { format: PixelFormat, expected: usize, actual: usize },
+
+    #[error("Plane {plane} stride is {stride} bytes, at least {minimum} are needed")]
+    StrideTooSmall { plane: usize, stride: usize, minimum: usize },
+
+    #[error("Plane {plane} is {len} bytes, at least {minimum} are needed")]
+    PlaneTooSmall { plane: usize, len: usize, minimum: usize },
+
+    #[error("Crop rectangle {0:?} is outside the frame")]
+    CropOutOfBounds(Rect),
+
+    #[error("Frame is {actual:?}, expected {expected:?}")]
+    DimensionsMismatch { expected: (u32, u32), actual: (u32, u32) },
+
+    #[error("{0:?} frames can not be encoded")]
+    UnsupportedEncoderInput(PixelFormat),
+}
+
+/// A rectangle in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
+/// One borrowed plane: rows of `stride` bytes, of which the first
+/// `bytes_per_row` for the frame's format and width hold pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plane<'a> {
+    pub data: &'a [u8],
+    pub stride: usize,
+}
+
+impl<'a> Plane<'a> {
+    pub fn new(data: &'a [u8], stride: usize) -> Self {
+        Self { data, stride }
+    }
+
+    /// Row `y`, including any padding up to the stride. The last row may be
+    /// shorter than the stride.
+    pub fn row(&self, y: usize) -> &'a [u8] {
+        let start = y * self.stride;
+        &self.data[start..(start + self.stride).min(self.data.len())]
+    }
+}
+
+/// The size of a plane as `CVPixelBufferCreateWithPlanarBytes` takes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneGeometry {
+    /// Width in samples, with interleaved Cb and Cr counted once.
+    pub width: usize,
+    pub height: usize,
+    pub bytes_per_row: usize,
+}
+
+/// A borrowed, validated frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoFrame<'a> {
+    format: PixelFormat,
+    width: u32,
+    height: u32,
+    planes: Vec<Plane<'a>>,
+    crop: Rect,
+    pts: Timestamp,
+    /// How long the frame is shown, if known.
+    duration: Option<Timestamp>,
+    color: ColorInfo,
+    hdr: HdrMetadata,
+}
+
+impl<'a> VideoFrame<'a> {
+    pub fn new(
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+        planes: Vec<Plane<'a>>,
+    ) -> Result<Self, FrameError> {
+        validate(
+            format,
+            width,
+            height,
+            planes.iter().map(|plane| (plane.data.len(), plane.stride)),
+        )?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            planes,
+            crop: Rect::new(0, 0, width, height),
+            pts: Timestamp::ZERO,
+            duration: None,
+            color: ColorInfo::default(),
+            hdr: HdrMetadata::default(),
+        })
+    }
+
+    /// A frame of tightly packed planes stored back to back in `data`.
+    pub fn from_packed(
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+        data: &'a [u8],
+    ) -> Result<Self, FrameError> {
+        let mut rest = data;
+        let mut planes = vec![];
+
+        for plane in 0..format.plane_count() {
+            let stride = format.bytes_per_row(plane, width);
+            let size = format.plane_size(plane, width, height).min(rest.len());
+            let (plane_data, after) = rest.split_at(size);
+
+            planes.push(Plane::new(plane_data, stride));
+            rest = after;
+        }
+
+        Self::new(format, width, height, planes)
+    }
+
+    pub fn with_crop(mut self, crop: Rect) -> Result<Self, FrameError> {
+        self.crop = validate_crop(crop, self.width, self.height)?;
+        Ok(self)
+    }
+
+    pub fn with_pts(mut self, pts: impl Into<Timestamp>) -> Self {
+        self.pts = pts.into();
+        self
+    }
+
+    pub fn with_duration(mut self, duration: impl Into<Timestamp>) -> Self {
+        self.duration = Some(duration.into());
+        self
+    }
+
+    pub fn with_color(mut self, color: ColorInfo) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_hdr_metadata(mut self, hdr: HdrMetadata) -> Self {
+        self.hdr = hdr;
+        self
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn planes(&self) -> &[Plane<'a>] {
+        &self.planes
+    }
+
+    /// The visible region, which defaults to the whole frame.
+    pub fn crop(&self) -> Rect {
+        self.crop
+    }
+
+    pub fn pts(&self) -> Timestamp {
+        self.pts
+    }
+
+    pub fn duration(&self) -> Option<Timestamp> {
+        self.duration
+    }
+
+    pub fn color(&self) -> ColorInfo {
+        self.color
+    }
+
+    pub fn hdr_metadata(&self) -> HdrMetadata {
+        self.hdr
+    }
+
+    pub fn plane_geometry(&self) -> Vec<PlaneGeometry> {
+        self.planes
+            .iter()
+            .enumerate()
+            .map(|(index, plane)| PlaneGeometry {
+                width: self.format.plane_width(index, self.width),
+                height: self.format.plane_height(index, self.height),
+                bytes_per_row: plane.stride,
+            })
+            .collect()
+    }
+
+    /// Checks the frame can be handed to an encoder session of `width` x
+    /// `height`, returning the geometry of its planes. VideoToolbox reads
+    /// `stride * rows` bytes through raw pointers, so every plane must hold
+    /// that many, including the padding after the last row.
+    pub fn validate_encoder_input(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<PlaneGeometry>, FrameError> {
+        if (self.width, self.height) != (width, height) {
+            return Err(FrameError::DimensionsMismatch {
+                expected: (width, height),
+                actual: (self.width, self.height),
+            });
+        }
+
+        if !self.format.is_encoder_input() {
+            return Err(FrameError::UnsupportedEncoderInput(self.format));
+        }
+
+        validate(
+            self.format,
+            self.width,
+            self.height,
+            self.planes.iter().map(|plane| (plane.data.len(), plane.stride)),
+        )?;
+
+        let geometry = self.plane_geometry();
+
+        for (index, (plane, geometry)) in self.planes.iter().zip(&geometry).enumerate() {
+            let minimum = geometry.bytes_per_row * geometry.height;
+
+            if plane.data.len() < minimum {
+                return Err(FrameError::PlaneTooSmall {
+                    plane: index,
+                    len: plane.data.len(),
+                    minimum,
+                });
+            }
+        }
+
+        Ok(geometry)
+    }
+
+    /// Copies the planes into an owned frame, keeping their strides.
+    pub fn to_frame_buf(&self) -> FrameBuf {
+        let planes = self
+            .planes
+            .iter()
+            .enumerate()
+            .map(|(index, plane)| {
+                let len = self.format.plane_height(index, self.height) * plane.stride;
+                PlaneBuf {
+                    data: plane.data[..len.min(plane.data.len())].to_vec(),
+                    stride: plane.stride,
+                }
+            })
+            .collect();
+
+        FrameBuf {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            planes,
+            crop: self.crop,
+            pts: self.pts,
+            duration: self.duration,
+            color: self.color,
+            hdr: self.hdr,
+        }
+    }
+}
+
+/// One owned plane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaneBuf {
+    data: Vec<u8>,
+    stride: usize,
+}
+
+impl PlaneBuf {
+    pub fn new(data: Vec<u8>, stride: usize) -> Self {
+        Self { data, stride }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The plane's bytes. Their length can not be changed, so the frame stays valid.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn as_plane(&self) -> Plane<'_> {
+        Plane::new(&self.data, self.stride)
+    }
+}
+
+/// An owned, validated frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameBuf {
+    format: PixelFormat,
+    width: u32,
+    height: u32,
+    planes: Vec<PlaneBuf>,
+    crop: Rect,
+    pts: Timestamp,
+    /// How long the frame is shown, if known.
+    duration: Option<Timestamp>,
+    color: ColorInfo,
+    hdr: HdrMetadata,
+}
+
+impl FrameBuf {
+    /// A zeroed frame with tightly packed planes.
+    pub fn new(format: PixelFormat, width: u32, height: u32) -> Result<Self, FrameError> {
+        let planes = (0..format.plane_count())
+            .map(|plane| {
+                let stride = format.bytes_per_row(plane, width);
+                PlaneBuf::new(vec![0; format.plane_size(plane, width, height)], stride)
+            })
+            .collect();
+
+        Self::from_planes(format, width, height, planes)
+    }
+
+    pub fn from_planes(
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+        planes: Vec<PlaneBuf>,
+    ) -> Result<Self, FrameError> {
+        validate(
+            format,
+            width,
+            height,
+            planes.iter().map(|plane| (plane.data.len(), plane.stride)),
+        )?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            planes,
+            crop: Rect::new(0, 0, width, height),
+            pts: Timestamp::ZERO,
+            duration: None,
+            color: ColorInfo::default(),
+            hdr: HdrMetadata::default(),
+        })
+    }
+
+    pub fn as_frame(&self) -> VideoFrame<'_> {
+        VideoFrame {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            planes: self.planes.iter().map(PlaneBuf::as_plane).collect(),
+            crop: self.crop,
+            pts: self.pts,
+            duration: self.duration,
+            color: self.color,
+            hdr: self.hdr,
+        }
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn planes(&self) -> &[PlaneBuf] {
+        &self.planes
+    }
+
+    pub fn planes_mut(&mut self) -> &mut [PlaneBuf] {
+        &mut self.planes
+    }
+
+    pub fn crop(&self) -> Rect {
+        self.crop
+    }
+
+    pub fn set_crop(&mut self, crop: Rect) -> Result<(), FrameError> {
+        self.crop = validate_crop(crop, self.width, self.height)?;
+        Ok(())
+    }
+
+    pub fn pts(&self) -> Timestamp {
+        self.pts
+    }
+
+    pub fn duration(&self) -> Option<Timestamp> {
+        self.duration
+    }
+
+    pub fn color(&self) -> ColorInfo {
+        self.color
+    }
+
+    pub fn hdr_metadata(&self) -> HdrMetadata {
+        self.hdr
+    }
+
+    pub fn set_pts(&mut self, pts: impl Into<Timestamp>) {
+        self.pts = pts.into();
+    }
+
+    pub fn set_duration(&mut self, duration: Option<Timestamp>) {
+        self.duration = duration;
+    }
+
+    pub fn set_color(&mut self, color: ColorInfo) {
+        self.color = color;
+    }
+
+    pub fn set_hdr_metadata(&mut self, hdr: HdrMetadata) {
+        self.hdr = hdr;
+    }
+
+    /// Copies `frame` into this buffer, taking its format, size, crop, timing and
+    /// colour and HDR metadata.
+    /// Rows are tightly packed, and plane allocations are reused.
+    pub fn copy_from(&mut self, frame: &VideoFrame) {
+        let format = frame.format;
+
+        self.planes.truncate(format.plane_count());
+        self.planes.resize_with(format.plane_count(), || PlaneBuf::new(vec![], 0));
+
+        for (index, (dst, src)) in self.planes.iter_mut().zip(&frame.planes).enumerate() {
+            let row_bytes = format.bytes_per_row(index, frame.width);
+
+            dst.stride = row_bytes;
+            dst.data.clear();
+
+            for y in 0..format.plane_height(index, frame.height) {
+                dst.data.extend_from_slice(&src.row(y)[..row_bytes]);
+            }
+        }
+
+        self.format = format;
+        self.width = frame.width;
+        self.height = frame.height;
+        self.crop = frame.crop;
+        self.pts = frame.pts;
+        self.duration = frame.duration;
+        self.color = frame.color;
+        self.hdr = frame.hdr;
+    }
+
+    /// The planes' pixels back to back without row padding, the layout
+    /// `VideoFrame::from_packed` reads.
+    pub fn to_packed(&self) -> Vec<u8> {
+        let mut packed = vec![];
+
+        for (index, plane) in self.planes.iter().enumerate() {
+            let row_bytes = self.format.bytes_per_row(index, self.width);
+
+            for y in 0..self.format.plane_height(index, self.height) {
+                packed.extend_from_slice(&plane.as_plane().row(y)[..row_bytes]);
+            }
+        }
+
+        packed
+    }
+}
+
+/// Checks the plane count, strides and sizes of `(len, stride)` planes.
+fn validate(
+    format: PixelFormat,
+    width: u32,
+    height: u32,
+    planes: impl ExactSizeIterator<Item = (usize, usize)>,
+) -> Result<(), FrameError> {
+    if width == 0 || height == 0 {
+        return Err(FrameError::InvalidDimensions { width, height });
+    }
+
+    let expected = format.plane_count();
+    if planes.len() != expected {
+        return Err(FrameError::PlaneCount { format, expected, actual: planes.len() });
+    }
+
+    for (plane, (len, stride)) in planes.enumerate() {
+        let row_bytes = format.bytes_per_row(plane, width);
+
+        if stride < row_bytes {
+            return Err(FrameError::StrideTooSmall { plane, stride, minimum: row_bytes });
+        }
+
+        // The last row does not need to be padded out to the stride.
+        let minimum = stride * (format.plane_height(plane, height) - 1) + row_bytes;
+
+        if len < minimum {
+            return Err(FrameError::PlaneTooSmall { plane, len, minimum });
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_crop(crop: Rect, width: u32, height: u32) -> Result<Rect, FrameError> {
+    let right = crop.x.checked_add(crop.width);
+    let bottom = crop.y.checked_add(crop.height);
+
+    match (right, bottom) {
+        (Some(right), Some(bottom))
+            if crop.width > 0 && crop.height > 0 && right <= width && bottom <= height =>
+        {
+            Ok(crop)
+        },
+        _ => Err(FrameError::CropOutOfBounds(crop)),
+    }
+}
@@ -0,0 +1,524 @@
+//! HLS packaging of encoded access units (RFC 8216, with LL-HLS partial segments).
+
+use crate::{
+    mp4::{self, Mp4Error, Mp4Sample},
+    ts::TsWriter,
+    ParameterSets, VideoCodec,
+};
+use std::{
+    mem,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+mod playlist;
+
+pub use playlist::*;
+
+/// Timescale of fMP4 tracks and transport stream timestamps.
+pub const HLS_TIMESCALE: u32 = 90_000;
+
+/// How many completed segments keep their parts listed in the playlist.
+const SEGMENTS_WITH_PARTS: usize = 3;
+
+#[derive(Debug, Error)]
+pub enum HlsError {
+    #[error("Keyframe without parameter sets")]
+    MissingParameterSets,
+
+    #[error("MP4 error: {0}")]
+    Mp4(#[from] Mp4Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentFormat {
+    /// Fragmented MP4 segments sharing an `EXT-X-MAP` initialization section.
+    Fmp4,
+    /// MPEG-2 transport stream segments.
+    Ts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HlsMode {
+    /// A rolling playlist that keeps the most recent `window_size` segments.
+    Live { window_size: usize },
+    /// A playlist listing every segment, ended by `finish`.
+    Vod,
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsConfig {
+    pub codec: VideoCodec,
+    pub format: SegmentFormat,
+    pub mode: HlsMode,
+    /// Segments are cut at the keyframe nearest to this much media, judged by
+    /// the spacing of the previous keyframes. Fixes `EXT-X-TARGETDURATION`,
+    /// rounded up to whole seconds.
+    pub target_duration: Duration,
+    /// Enables LL-HLS partial segments of at most this duration.
+    pub part_target_duration: Option<Duration>,
+    /// Wall clock time of the first access unit, for `EXT-X-PROGRAM-DATE-TIME`.
+    pub program_date_time: Option<SystemTime>,
+}
+
+impl Default for HlsConfig {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::Hevc,
+            format: SegmentFormat::Fmp4,
+            mode: HlsMode::Live { window_size: 6 },
+            target_duration: Duration::from_secs(6),
+            part_target_duration: None,
+            program_date_time: None,
+        }
+    }
+}
+
+/// A file the segmenter produced or retired. URIs are relative to the playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HlsEvent {
+    Write {
+        uri: String,
+        data: Vec<u8>,
+    },
+    Remove {
+        uri: String,
+    },
+    /// A segment ran past `EXT-X-TARGETDURATION` for lack of a keyframe to cut
+    /// at, which RFC 8216 does not allow. The keyframe interval should be
+    /// shorter than the target duration.
+    TargetDurationExceeded {
+        uri: String,
+        duration: Duration,
+    },
+}
+
+struct Sample {
+    access_unit: Vec<u8>,
+    /// Presentation time in `HLS_TIMESCALE` units.
+    timestamp: u64,
+    duration: u64,
+    is_keyframe: bool,
+    /// Set on keyframes whose parameter sets differ from the current ones.
+    format_change: Option<ParameterSets>,
+}
+
+/// Cuts a stream of Annex B access units into HLS segments and maintains the
+/// media playlist describing them.
+///
+/// Access units must be pushed in decode order without frame reordering. A
+/// sample's duration is known once the following one arrives, so output lags
+/// input by one access unit until `finish`.
+pub struct HlsSegmenter {
+    config: HlsConfig,
+    playlist: MediaPlaylist,
+    parameter_sets: Option<ParameterSets>,
+    ts_writer: TsWriter,
+
+    pending: Option<Sample>,
+    first_timestamp: Option<u64>,
+    last_duration: u64,
+
+    format_count: u64,
+    map_uri: Option<String>,
+    segment_sequence: u64,
+    fragment_sequence: u32,
+    discontinuity: bool,
+
+    segment_start: Option<u64>,
+    segment_duration: u64,
+    last_keyframe: Option<u64>,
+    keyframe_interval: Option<u64>,
+    segment_data: Vec<u8>,
+    part_samples: Vec<Sample>,
+    part_duration: u64,
+}
+
+impl HlsSegmenter {
+    pub fn new(config: HlsConfig) -> Self {
+        let version = match (config.format, config.part_target_duration) {
+            (_, Some(_)) => 9,
+            (SegmentFormat::Fmp4, None) => 7,
+            (SegmentFormat::Ts, None) => 3,
+        };
+
+        let playlist = MediaPlaylist {
+            version,
+            target_duration: config.target_duration.as_secs_f64().ceil() as u64,
+            media_sequence: 0,
+            discontinuity_sequence: 0,
+            playlist_type: match config.mode {
+                HlsMode::Live { .. } => None,
+                HlsMode::Vod => Some(PlaylistType::Vod),
+            },
+            part_target_duration: config.part_target_duration,
+            segments: vec![],
+            partial_segment: None,
+            preload_hint: None,
+            ended: false,
+        };
+
+        Self {
+            ts_writer: TsWriter::new(config.codec),
+            config,
+            playlist,
+            parameter_sets: None,
+            pending: None,
+            first_timestamp: None,
+            last_duration: 0,
+            format_count: 0,
+            map_uri: None,
+            segment_sequence: 0,
+            fragment_sequence: 1,
+            discontinuity: false,
+            segment_start: None,
+            segment_duration: 0,
+            last_keyframe: None,
+            keyframe_interval: None,
+            segment_data: vec![],
+            part_samples: vec![],
+            part_duration: 0,
+        }
+    }
+
+    pub fn playlist(&self) -> &MediaPlaylist {
+        &self.playlist
+    }
+
+    /// Adds an access unit, as produced by `Encoder`, presented at `pts`.
+    /// Access units before the first keyframe are dropped.
+    pub fn push(&mut self, access_unit: &[u8], pts: Duration) -> Result<Vec<HlsEvent>, HlsError> {
+        let codec = self.config.codec;
+        let timestamp = duration_to_ticks(pts);
+        let is_keyframe = codec.is_keyframe(access_unit);
+
+        let format_change = if is_keyframe {
+            let parameter_sets = ParameterSets::from_annex_b(codec, access_unit)
+                .or_else(|| self.parameter_sets.clone())
+                .ok_or(HlsError::MissingParameterSets)?;
+
+            if self.parameter_sets.as_ref() != Some(&parameter_sets) {
+                self.parameter_sets = Some(parameter_sets.clone());
+                Some(parameter_sets)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if self.parameter_sets.is_none() {
+            return Ok(vec![]);
+        }
+
+        let mut events = vec![];
+
+        if let Some(mut sample) = self.pending.take() {
+            sample.duration = timestamp.saturating_sub(sample.timestamp).max(1);
+            self.last_duration = sample.duration;
+            self.add_sample(sample, &mut events)?;
+        }
+
+        self.pending = Some(Sample {
+            access_unit: access_unit.to_vec(),
+            timestamp,
+            duration: 0,
+            is_keyframe,
+            format_change,
+        });
+
+        self.update_preload_hint();
+        Ok(events)
+    }
+
+    /// Writes out the final segment and ends the playlist.
+    pub fn finish(&mut self) -> Result<Vec<HlsEvent>, HlsError> {
+        let mut events = vec![];
+
+        if let Some(mut sample) = self.pending.take() {
+            // Assume the last frame lasts as long as the one before it.
+            sample.duration =
+                if self.last_duration > 0 { self.last_duration } else { HLS_TIMESCALE as u64 / 30 };
+
+            self.add_sample(sample, &mut events)?;
+        }
+
+        self.close_segment(&mut events);
+        self.playlist.ended = true;
+        self.playlist.preload_hint = None;
+
+        Ok(events)
+    }
+
+    fn add_sample(
+        &mut self,
+        mut sample: Sample,
+        events: &mut Vec<HlsEvent>,
+    ) -> Result<(), HlsError> {
+        if sample.is_keyframe {
+            if let Some(last_keyframe) = self.last_keyframe {
+                self.keyframe_interval = sample.timestamp.checked_sub(last_keyframe);
+            }
+
+            self.last_keyframe = Some(sample.timestamp);
+
+            if self.segment_start.is_some()
+                && (sample.format_change.is_some() || self.cut_at_keyframe())
+            {
+                self.close_segment(events);
+            }
+        }
+
+        if let Some(parameter_sets) = sample.format_change.take() {
+            self.change_format(parameter_sets, events)?;
+        }
+
+        if self.segment_start.is_none() {
+            self.open_segment(sample.timestamp);
+        }
+
+        if let Some(part_target) = self.config.part_target_duration.map(duration_to_ticks) {
+            if !self.part_samples.is_empty() && self.part_duration + sample.duration > part_target {
+                self.close_part(events);
+            }
+        }
+
+        self.segment_duration += sample.duration;
+        self.part_duration += sample.duration;
+        self.part_samples.push(sample);
+
+        if let Some(part_target) = self.config.part_target_duration.map(duration_to_ticks) {
+            if self.part_duration >= part_target {
+                self.close_part(events);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether cutting at this keyframe leaves the segment closer to the target
+    /// than waiting for the next one, expected as far off as this one was from
+    /// the previous keyframe.
+    fn cut_at_keyframe(&self) -> bool {
+        let target = duration_to_ticks(self.config.target_duration);
+
+        if self.segment_duration >= target {
+            return true;
+        }
+
+        self.keyframe_interval.is_some_and(|interval| {
+            let next = self.segment_duration + interval;
+            next > target && target - self.segment_duration <= next - target
+        })
+    }
+
+    fn change_format(
+        &mut self,
+        parameter_sets: ParameterSets,
+        events: &mut Vec<HlsEvent>,
+    ) -> Result<(), HlsError> {
+        if self.config.format == SegmentFormat::Fmp4 {
+            let uri = format!("init{}.mp4", self.format_count);
+            let data = mp4::init_segment(&parameter_sets, HLS_TIMESCALE)?;
+
+            events.push(HlsEvent::Write { uri: uri.clone(), data });
+            self.map_uri = Some(uri);
+        }
+
+        self.discontinuity = self.format_count > 0;
+        self.format_count += 1;
+
+        Ok(())
+    }
+
+    fn open_segment(&mut self, timestamp: u64) {
+        let first_timestamp = *self.first_timestamp.get_or_insert(timestamp);
+        let program_date_time = self
+            .config
+            .program_date_time
+            .map(|start| start + ticks_to_duration(timestamp.saturating_sub(first_timestamp)));
+
+        self.segment_start = Some(timestamp);
+        self.segment_duration = 0;
+        self.playlist.partial_segment = Some(MediaSegment {
+            uri: segment_uri(self.config.format, self.segment_sequence, None),
+            duration: Duration::ZERO,
+            discontinuity: mem::take(&mut self.discontinuity),
+            map_uri: self.map_uri.clone(),
+            program_date_time,
+            parts: vec![],
+        });
+    }
+
+    /// Renders the buffered samples, publishing them as a part in LL-HLS mode.
+    fn close_part(&mut self, events: &mut Vec<HlsEvent>) {
+        let samples = mem::take(&mut self.part_samples);
+        let duration = mem::take(&mut self.part_duration);
+
+        let Some(first) = samples.first() else {
+            return;
+        };
+
+        let is_first_part = self.segment_data.is_empty();
+        let independent = first.is_keyframe;
+        let mut data = vec![];
+
+        match self.config.format {
+            SegmentFormat::Fmp4 => {
+                let mp4_samples: Vec<_> = samples
+                    .iter()
+                    .map(|sample| {
+                        Mp4Sample::from_annex_b(
+                            self.config.codec,
+                            &sample.access_unit,
+                            sample.duration as u32,
+                        )
+                    })
+                    .collect();
+
+                data = mp4::fragment(self.fragment_sequence, first.timestamp, &mp4_samples);
+                self.fragment_sequence += 1;
+            },
+            SegmentFormat::Ts => {
+                if is_first_part {
+                    self.ts_writer.write_tables(&mut data);
+                }
+
+                for sample in &samples {
+                    self.ts_writer.write_access_unit(
+                        &mut data,
+                        &sample.access_unit,
+                        sample.timestamp,
+                        sample.is_keyframe,
+                    );
+                }
+            },
+        }
+
+        self.segment_data.extend_from_slice(&data);
+
+        if self.config.part_target_duration.is_some() {
+            let Some(segment) = self.playlist.partial_segment.as_mut() else {
+                return;
+            };
+
+            let uri =
+                segment_uri(self.config.format, self.segment_sequence, Some(segment.parts.len()));
+            segment.parts.push(PartialSegment {
+                uri: uri.clone(),
+                duration: ticks_to_duration(duration),
+                independent,
+            });
+
+            events.push(HlsEvent::Write { uri, data });
+        }
+    }
+
+    fn close_segment(&mut self, events: &mut Vec<HlsEvent>) {
+        if self.segment_start.take().is_none() {
+            return;
+        }
+
+        self.close_part(events);
+
+        let Some(mut segment) = self.playlist.partial_segment.take() else {
+            return;
+        };
+
+        segment.duration = ticks_to_duration(self.segment_duration);
+        events.push(HlsEvent::Write {
+            uri: segment.uri.clone(),
+            data: mem::take(&mut self.segment_data),
+        });
+
+        if segment.duration.as_secs_f64().round() as u64 > self.playlist.target_duration {
+            events.push(HlsEvent::TargetDurationExceeded {
+                uri: segment.uri.clone(),
+                duration: segment.duration,
+            });
+        }
+
+        self.playlist.segments.push(segment);
+        self.segment_sequence += 1;
+
+        self.retire_parts(events);
+        self.retire_segments(events);
+    }
+
+    /// Drops parts from segments that are too old to be useful to LL-HLS clients.
+    fn retire_parts(&mut self, events: &mut Vec<HlsEvent>) {
+        let segment_count = self.playlist.segments.len();
+
+        if segment_count <= SEGMENTS_WITH_PARTS {
+            return;
+        }
+
+        let segment = &mut self.playlist.segments[segment_count - SEGMENTS_WITH_PARTS - 1];
+        events.extend(segment.parts.drain(..).map(|part| HlsEvent::Remove { uri: part.uri }));
+    }
+
+    fn retire_segments(&mut self, events: &mut Vec<HlsEvent>) {
+        let HlsMode::Live { window_size } = self.config.mode else {
+            return;
+        };
+
+        while self.playlist.segments.len() > window_size.max(1) {
+            let segment = self.playlist.segments.remove(0);
+            self.playlist.media_sequence += 1;
+
+            if segment.discontinuity {
+                self.playlist.discontinuity_sequence += 1;
+            }
+
+            events.extend(segment.parts.into_iter().map(|part| HlsEvent::Remove { uri: part.uri }));
+            events.push(HlsEvent::Remove { uri: segment.uri });
+
+            let Some(map_uri) = segment.map_uri else {
+                continue;
+            };
+
+            let still_used = self.map_uri.as_ref() == Some(&map_uri)
+                || self.playlist.segments.iter().any(|s| s.map_uri.as_ref() == Some(&map_uri));
+
+            if !still_used {
+                events.push(HlsEvent::Remove { uri: map_uri });
+            }
+        }
+    }
+
+    fn update_preload_hint(&mut self) {
+        if self.config.part_target_duration.is_none() {
+            return;
+        }
+
+        let hint = match &self.playlist.partial_segment {
+            Some(segment) => {
+                segment_uri(self.config.format, self.segment_sequence, Some(segment.parts.len()))
+            },
+            None => segment_uri(self.config.format, self.segment_sequence, Some(0)),
+        };
+
+        self.playlist.preload_hint = Some(hint);
+    }
+}
+
+fn segment_uri(format: SegmentFormat, sequence: u64, part: Option<usize>) -> String {
+    let extension = match format {
+        SegmentFormat::Fmp4 => "m4s",
+        SegmentFormat::Ts => "ts",
+    };
+
+    match part {
+        Some(part) => format!("segment{}.{}.{}", sequence, part, extension),
+        None => format!("segment{}.{}", sequence, extension),
+    }
+}
+
+/// Rounds to the nearest tick so frame durations like 1/30 s stay exact.
+fn duration_to_ticks(duration: Duration) -> u64 {
+    ((duration.as_nanos() * HLS_TIMESCALE as u128 + 500_000_000) / 1_000_000_000) as u64
+}
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos((ticks as u128 * 1_000_000_000 / HLS_TIMESCALE as u128) as u64)
+}
@@ -0,0 +1,138 @@
+use crate::date_time::format_date_time;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistType {
+    Event,
+    Vod,
+}
+
+/// An HLS media playlist (RFC 8216 section 4.3.3, plus the LL-HLS tags).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaPlaylist {
+    pub version: u8,
+    /// Upper bound on segment durations, in whole seconds.
+    pub target_duration: u64,
+    /// Sequence number of the first segment in `segments`.
+    pub media_sequence: u64,
+    pub discontinuity_sequence: u64,
+    pub playlist_type: Option<PlaylistType>,
+    /// Set for low-latency playlists, which list partial segments.
+    pub part_target_duration: Option<Duration>,
+    pub segments: Vec<MediaSegment>,
+    /// The segment being written. Only its tags and parts are listed.
+    pub partial_segment: Option<MediaSegment>,
+    /// URI of the next partial segment, announced with `EXT-X-PRELOAD-HINT`.
+    pub preload_hint: Option<String>,
+    pub ended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaSegment {
+    pub uri: String,
+    pub duration: Duration,
+    pub discontinuity: bool,
+    /// URI of the initialization section, written as `EXT-X-MAP`.
+    pub map_uri: Option<String>,
+    pub program_date_time: Option<SystemTime>,
+    pub parts: Vec<PartialSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialSegment {
+    pub uri: String,
+    pub duration: Duration,
+    /// Whether the part starts with a keyframe.
+    pub independent: bool,
+}
+
+impl Display for MediaPlaylist {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+        writeln!(f, "#EXT-X-VERSION:{}", self.version)?;
+        writeln!(f, "#EXT-X-TARGETDURATION:{}", self.target_duration)?;
+
+        if let Some(part_target_duration) = self.part_target_duration {
+            let part_hold_back = part_target_duration * 3;
+            writeln!(
+                f,
+                "#EXT-X-SERVER-CONTROL:PART-HOLD-BACK={:.5}",
+                part_hold_back.as_secs_f64()
+            )?;
+            writeln!(f, "#EXT-X-PART-INF:PART-TARGET={:.5}", part_target_duration.as_secs_f64())?;
+        }
+
+        writeln!(f, "#EXT-X-MEDIA-SEQUENCE:{}", self.media_sequence)?;
+
+        if self.discontinuity_sequence > 0 {
+            writeln!(f, "#EXT-X-DISCONTINUITY-SEQUENCE:{}", self.discontinuity_sequence)?;
+        }
+
+        match self.playlist_type {
+            Some(PlaylistType::Event) => writeln!(f, "#EXT-X-PLAYLIST-TYPE:EVENT")?,
+            Some(PlaylistType::Vod) => writeln!(f, "#EXT-X-PLAYLIST-TYPE:VOD")?,
+            None => {},
+        }
+
+        // Segments are always cut at keyframes.
+        writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+
+        let mut current_map = None;
+
+        for segment in &self.segments {
+            write_segment_tags(f, segment, &mut current_map)?;
+            writeln!(f, "#EXTINF:{:.5},", segment.duration.as_secs_f64())?;
+            writeln!(f, "{}", segment.uri)?;
+        }
+
+        if let Some(segment) = &self.partial_segment {
+            write_segment_tags(f, segment, &mut current_map)?;
+        }
+
+        if let Some(uri) = &self.preload_hint {
+            writeln!(f, "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"{}\"", uri)?;
+        }
+
+        if self.ended {
+            writeln!(f, "#EXT-X-ENDLIST")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_segment_tags<'a>(
+    f: &mut Formatter,
+    segment: &'a MediaSegment,
+    current_map: &mut Option<&'a str>,
+) -> fmt::Result {
+    if segment.discontinuity {
+        writeln!(f, "#EXT-X-DISCONTINUITY")?;
+    }
+
+    if let Some(map_uri) = segment.map_uri.as_deref() {
+        if *current_map != Some(map_uri) {
+            writeln!(f, "#EXT-X-MAP:URI=\"{}\"", map_uri)?;
+            *current_map = Some(map_uri);
+        }
+    }
+
+    if let Some(program_date_time) = segment.program_date_time {
+        writeln!(f, "#EXT-X-PROGRAM-DATE-TIME:{}", format_date_time(program_date_time))?;
+    }
+
+    for part in &segment.parts {
+        write!(f, "#EXT-X-PART:DURATION={:.5},URI=\"{}\"", part.duration.as_secs_f64(), part.uri)?;
+
+        if part.independent {
+            write!(f, ",INDEPENDENT=YES")?;
+        }
+
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
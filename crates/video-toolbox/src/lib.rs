@@ -1,10 +1,47 @@
 use thiserror::Error;
 
+pub mod alpha;
+mod base64;
+mod bitstream;
+pub mod color;
+pub mod dash;
+mod date_time;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 mod decoder;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 mod encoder;
-
+mod encoder_config;
+pub mod es;
+mod frame;
+pub mod hls;
+pub mod mkv;
+pub mod mp4;
+mod packet;
+mod parameter_sets;
+mod pipeline;
+mod pixel_format;
+pub mod rtp;
+pub mod rtsp;
+pub mod scale;
+pub mod sdp;
+mod sei;
+mod sps;
+mod timestamp;
+pub mod ts;
+pub mod y4m;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub use decoder::*;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub use encoder::*;
+pub use encoder_config::*;
+pub use frame::*;
+pub use packet::{EncodedPacket, PacketFraming, PictureType};
+pub use parameter_sets::*;
+pub use pipeline::*;
+pub use pixel_format::PixelFormat;
+pub use sps::{FrameRate, SpsInfo};
+pub use timestamp::Timestamp;
 
 #[derive(Debug, Error)]
 pub enum HevcError {
@@ -12,6 +49,83 @@ pub enum HevcError {
     InvalidNalType(u8),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    Hevc,
+}
+
+impl VideoCodec {
+    /// Whether `nal` (starting with its header) begins a random access point.
+    pub(crate) fn is_keyframe_nal(self, nal: &[u8]) -> bool {
+        match (self, nal.first()) {
+            // IRAP pictures: BLA, IDR and CRA.
+            (VideoCodec::Hevc, Some(header)) => (16..=21).contains(&((header >> 1) & 0b11_1111)),
+            // IDR slice
+            (VideoCodec::H264, Some(header)) => header & 0b1_1111 == 5,
+            (_, None) => false,
+        }
+    }
+
+    /// Whether `nal` is a VPS, SPS or PPS.
+    pub(crate) fn is_parameter_set_nal(self, nal: &[u8]) -> bool {
+        match (self, nal.first()) {
+            (VideoCodec::Hevc, Some(header)) => (32..=34).contains(&((header >> 1) & 0b11_1111)),
+            (VideoCodec::H264, Some(header)) => matches!(header & 0b1_1111, 7 | 8),
+            (_, None) => false,
+        }
+    }
+
+    /// Whether an Annex B access unit contains a random access point.
+    pub fn is_keyframe(self, access_unit: &[u8]) -> bool {
+        NalIterator::new(access_unit).any(|nal| self.is_keyframe_nal(nal.data))
+    }
+}
+
+/// Converts an Annex B access unit to four byte length-prefixed NAL units, as
+/// stored in MP4 and Matroska. Parameter sets are dropped since those formats
+/// carry them out of band. Also returns whether the access unit is a keyframe.
+pub(crate) fn length_prefixed_from_annex_b(
+    codec: VideoCodec,
+    access_unit: &[u8],
+) -> (Vec<u8>, bool) {
+    let mut data = Vec::with_capacity(access_unit.len());
+    let mut is_keyframe = false;
+
+    for nal in NalIterator::new(access_unit) {
+        if nal.data.is_empty() || codec.is_parameter_set_nal(nal.data) {
+            continue;
+        }
+
+        is_keyframe |= codec.is_keyframe_nal(nal.data);
+        data.extend_from_slice(&(nal.data.len() as u32).to_be_bytes());
+        data.extend_from_slice(nal.data);
+    }
+
+    (data, is_keyframe)
+}
+
+/// Converts length-prefixed NAL units back to Annex B, appending them to
+/// `out`. Returns `None` if a length runs past the end of `data`.
+pub(crate) fn annex_b_from_length_prefixed(
+    data: &[u8],
+    length_size: usize,
+    out: &mut Vec<u8>,
+) -> Option<()> {
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let len = rest.get(..length_size)?.iter().fold(0, |len, &byte| len << 8 | byte as usize);
+        let nal = rest.get(length_size..length_size + len)?;
+
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(nal);
+        rest = &rest[length_size + len..];
+    }
+
+    Some(())
+}
+
 pub(crate) struct NalIterator<'a> {
     hevc_bytes: &'a [u8],
 }
@@ -0,0 +1,213 @@
+//! EBML (RFC 8794) element encoding and decoding.
+
+use crate::mkv::MkvError;
+use std::io::{self, Read};
+
+pub(crate) const EBML: u32 = 0x1A45_DFA3;
+pub(crate) const EBML_VERSION: u32 = 0x4286;
+pub(crate) const EBML_READ_VERSION: u32 = 0x42F7;
+pub(crate) const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+pub(crate) const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+pub(crate) const DOC_TYPE: u32 = 0x4282;
+pub(crate) const DOC_TYPE_VERSION: u32 = 0x4287;
+pub(crate) const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+pub(crate) const VOID: u32 = 0xEC;
+
+pub(crate) const SEGMENT: u32 = 0x1853_8067;
+pub(crate) const SEEK_HEAD: u32 = 0x114D_9B74;
+pub(crate) const SEEK: u32 = 0x4DBB;
+pub(crate) const SEEK_ID: u32 = 0x53AB;
+pub(crate) const SEEK_POSITION: u32 = 0x53AC;
+
+pub(crate) const INFO: u32 = 0x1549_A966;
+pub(crate) const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+pub(crate) const DURATION: u32 = 0x4489;
+pub(crate) const MUXING_APP: u32 = 0x4D80;
+pub(crate) const WRITING_APP: u32 = 0x5741;
+
+pub(crate) const TRACKS: u32 = 0x1654_AE6B;
+pub(crate) const TRACK_ENTRY: u32 = 0xAE;
+pub(crate) const TRACK_NUMBER: u32 = 0xD7;
+pub(crate) const TRACK_UID: u32 = 0x73C5;
+pub(crate) const TRACK_TYPE: u32 = 0x83;
+pub(crate) const FLAG_LACING: u32 = 0x9C;
+pub(crate) const CODEC_ID: u32 = 0x86;
+pub(crate) const CODEC_PRIVATE: u32 = 0x63A2;
+pub(crate) const VIDEO: u32 = 0xE0;
+pub(crate) const PIXEL_WIDTH: u32 = 0xB0;
+pub(crate) const PIXEL_HEIGHT: u32 = 0xBA;
+
+pub(crate) const CLUSTER: u32 = 0x1F43_B675;
+pub(crate) const TIMESTAMP: u32 = 0xE7;
+pub(crate) const SIMPLE_BLOCK: u32 = 0xA3;
+pub(crate) const BLOCK_GROUP: u32 = 0xA0;
+pub(crate) const BLOCK: u32 = 0xA1;
+pub(crate) const REFERENCE_BLOCK: u32 = 0xFB;
+
+pub(crate) const CUES: u32 = 0x1C53_BB6B;
+pub(crate) const CUE_POINT: u32 = 0xBB;
+pub(crate) const CUE_TIME: u32 = 0xB3;
+pub(crate) const CUE_TRACK_POSITIONS: u32 = 0xB7;
+pub(crate) const CUE_TRACK: u32 = 0xF7;
+pub(crate) const CUE_CLUSTER_POSITION: u32 = 0xF1;
+
+/// The reserved all-ones size marking a master element that runs until an
+/// element which cannot be its child.
+pub(crate) const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+
+/// The largest size an 8 byte variable length integer can hold.
+const MAX_SIZE: u64 = (1 << 56) - 2;
+
+pub(crate) fn write_id(out: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().position(|&byte| byte != 0).unwrap_or(3);
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+/// Writes `size` as the shortest variable length integer that holds it.
+pub(crate) fn write_size(out: &mut Vec<u8>, size: u64) {
+    let len = (1..8).find(|len| size < (1 << (7 * len)) - 1).unwrap_or(8);
+    write_size_with_len(out, size, len);
+}
+
+/// Writes `size` using exactly `len` bytes, so it can be patched in place.
+pub(crate) fn write_size_with_len(out: &mut Vec<u8>, size: u64, len: usize) {
+    debug_assert!(size <= MAX_SIZE);
+    let marked = size | 1 << (7 * len);
+    out.extend_from_slice(&marked.to_be_bytes()[8 - len..]);
+}
+
+/// Writes an element whose contents are produced by `contents`.
+pub(crate) fn write_element(out: &mut Vec<u8>, id: u32, contents: impl FnOnce(&mut Vec<u8>)) {
+    let mut body = vec![];
+    contents(&mut body);
+    write_binary(out, id, &body);
+}
+
+pub(crate) fn write_binary(out: &mut Vec<u8>, id: u32, value: &[u8]) {
+    write_id(out, id);
+    write_size(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+pub(crate) fn write_uint(out: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().position(|&byte| byte != 0).unwrap_or(7);
+    write_binary(out, id, &bytes[skip..]);
+}
+
+pub(crate) fn write_float(out: &mut Vec<u8>, id: u32, value: f64) {
+    write_binary(out, id, &value.to_be_bytes());
+}
+
+pub(crate) fn write_string(out: &mut Vec<u8>, id: u32, value: &str) {
+    write_binary(out, id, value.as_bytes());
+}
+
+/// Writes a `Void` element of exactly `len` bytes, which must be at least 2.
+pub(crate) fn write_void(out: &mut Vec<u8>, len: usize) {
+    debug_assert!((2..=128).contains(&len));
+    write_id(out, VOID);
+    write_size(out, len as u64 - 2);
+    out.resize(out.len() + len - 2, 0);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ElementHeader {
+    pub(crate) id: u32,
+    /// `None` for an unknown-size (live) master element.
+    pub(crate) size: Option<u64>,
+    /// The number of bytes taken by the ID and size.
+    pub(crate) len: usize,
+}
+
+/// Reads an element header, returning `None` at a clean end of stream.
+pub(crate) fn read_header(reader: &mut impl Read) -> Result<Option<ElementHeader>, MkvError> {
+    let mut first = [0];
+
+    match reader.read_exact(&mut first) {
+        Ok(()) => {},
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+
+    let id_len = vint_len(first[0]).filter(|&len| len <= 4).ok_or(MkvError::Malformed)?;
+    let mut id_bytes = [0; 4];
+    id_bytes[4 - id_len] = first[0];
+    reader.read_exact(&mut id_bytes[5 - id_len..])?;
+
+    reader.read_exact(&mut first)?;
+    let size_len = vint_len(first[0]).ok_or(MkvError::Malformed)?;
+    let mut size_bytes = [0; 8];
+    size_bytes[8 - size_len] = first[0];
+    reader.read_exact(&mut size_bytes[9 - size_len..])?;
+
+    Ok(Some(ElementHeader {
+        id: u32::from_be_bytes(id_bytes),
+        size: vint_value(&size_bytes[8 - size_len..]),
+        len: id_len + size_len,
+    }))
+}
+
+/// Splits the contents of a master element into `(id, body)` pairs.
+pub(crate) fn children(mut data: &[u8]) -> Result<Vec<(u32, &[u8])>, MkvError> {
+    let mut children = vec![];
+
+    while !data.is_empty() {
+        let header = read_header(&mut data)?.ok_or(MkvError::Malformed)?;
+        let size = header.size.ok_or(MkvError::Malformed)? as usize;
+        let body = data.get(..size).ok_or(MkvError::Malformed)?;
+
+        children.push((header.id, body));
+        data = &data[size..];
+    }
+
+    Ok(children)
+}
+
+/// Reads a variable length integer such as a block's track number, returning
+/// its value and length.
+pub(crate) fn read_vint(data: &[u8]) -> Result<(u64, usize), MkvError> {
+    let len = data.first().and_then(|&first| vint_len(first)).ok_or(MkvError::Malformed)?;
+    let bytes = data.get(..len).ok_or(MkvError::Malformed)?;
+
+    Ok((vint_value(bytes).ok_or(MkvError::Malformed)?, len))
+}
+
+pub(crate) fn read_uint(body: &[u8]) -> Result<u64, MkvError> {
+    if body.len() > 8 {
+        return Err(MkvError::Malformed);
+    }
+
+    Ok(body.iter().fold(0, |value, &byte| value << 8 | byte as u64))
+}
+
+pub(crate) fn read_float(body: &[u8]) -> Result<f64, MkvError> {
+    match body.len() {
+        0 => Ok(0.0),
+        4 => Ok(f32::from_be_bytes(body.try_into().unwrap()) as f64),
+        8 => Ok(f64::from_be_bytes(body.try_into().unwrap())),
+        _ => Err(MkvError::Malformed),
+    }
+}
+
+pub(crate) fn read_string(body: &[u8]) -> String {
+    // Strings may be padded with trailing zero bytes.
+    let end = body.iter().position(|&byte| byte == 0).unwrap_or(body.len());
+    String::from_utf8_lossy(&body[..end]).into_owned()
+}
+
+/// The total length of a variable length integer, from its first byte.
+fn vint_len(first: u8) -> Option<usize> {
+    (first != 0).then_some(first.leading_zeros() as usize + 1)
+}
+
+/// The value of a complete variable length integer, or `None` if all of its
+/// value bits are set.
+fn vint_value(bytes: &[u8]) -> Option<u64> {
+    let len = bytes.len();
+    let value = bytes.iter().fold(0u64, |value, &byte| value << 8 | byte as u64);
+    let value = value & !(1 << (7 * len));
+
+    (value != (1 << (7 * len)) - 1).then_some(value)
+}
@@ -0,0 +1,73 @@
+//! Matroska and WebM muxing and demuxing of a single HEVC or H.264 track.
+//!
+//! Frames go in and come out as Annex B access units, the same format the
+//! `Encoder` produces and the `Decoder` consumes. The parameter sets travel in
+//! the track's `CodecPrivate` and are repeated in-band on every keyframe that
+//! is read back.
+
+use crate::VideoCodec;
+use thiserror::Error;
+
+mod ebml;
+mod reader;
+mod writer;
+
+pub use reader::*;
+pub use writer::*;
+
+/// Matroska codec ID of HEVC tracks, with an `hvcC` record as `CodecPrivate`.
+pub const HEVC_CODEC_ID: &str = "V_MPEGH/ISO/HEVC";
+/// Matroska codec ID of H.264 tracks, with an `avcC` record as `CodecPrivate`.
+pub const H264_CODEC_ID: &str = "V_MPEG4/ISO/AVC";
+
+/// The track number used for the single video track.
+pub const VIDEO_TRACK_NUMBER: u64 = 1;
+
+#[derive(Debug, Error)]
+pub enum MkvError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed EBML data")]
+    Malformed,
+
+    #[error("Keyframe without parameter sets")]
+    MissingParameterSets,
+
+    #[error("Could not parse the sequence parameter set")]
+    InvalidSequenceParameterSet,
+
+    #[error("Unsupported document type: {0}")]
+    UnsupportedDocType(String),
+
+    #[error("No supported video track")]
+    MissingVideoTrack,
+
+    #[error("Laced blocks are not supported")]
+    UnsupportedLacing,
+
+    #[error("The file has no cues to seek with")]
+    MissingCues,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocType {
+    Matroska,
+    WebM,
+}
+
+impl DocType {
+    fn as_str(self) -> &'static str {
+        match self {
+            DocType::Matroska => "matroska",
+            DocType::WebM => "webm",
+        }
+    }
+}
+
+fn codec_id(codec: VideoCodec) -> &'static str {
+    match codec {
+        VideoCodec::Hevc => HEVC_CODEC_ID,
+        VideoCodec::H264 => H264_CODEC_ID,
+    }
+}
@@ -0,0 +1,417 @@
+use crate::{
+    annex_b_from_length_prefixed,
+    mkv::{ebml::*, DocType, MkvError, H264_CODEC_ID, HEVC_CODEC_ID},
+    ParameterSets, VideoCodec,
+};
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+/// A decoded block as an Annex B access unit. Keyframes start with the
+/// track's parameter sets, so they can be fed straight to a decoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatroskaFrame {
+    pub data: Vec<u8>,
+    pub pts: Duration,
+    pub is_keyframe: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CuePoint {
+    pub time: Duration,
+    /// Position of the cluster relative to the start of the segment data.
+    pub cluster_position: u64,
+}
+
+/// Reads the first HEVC or H.264 video track of a Matroska or WebM stream.
+/// Clusters of unknown size, as written by live muxers, are supported.
+pub struct MatroskaReader<R: Read> {
+    input: Input<R>,
+    doc_type: DocType,
+    track: Track,
+    timestamp_scale: u64,
+    duration: Option<Duration>,
+    segment_data_offset: u64,
+    cues_position: Option<u64>,
+    cues: Vec<CuePoint>,
+    cluster_timestamp: u64,
+}
+
+struct Track {
+    number: u64,
+    codec: VideoCodec,
+    parameter_sets: ParameterSets,
+    nal_length_size: usize,
+    width: u32,
+    height: u32,
+}
+
+impl<R: Read> MatroskaReader<R> {
+    /// Reads the headers up to the first cluster.
+    pub fn new(reader: R) -> Result<Self, MkvError> {
+        let mut input = Input { reader, position: 0, pending: None };
+
+        let header = input.next_header()?.filter(|h| h.id == EBML).ok_or(MkvError::Malformed)?;
+        let body = input.read_body(header)?;
+        let mut doc_type = DocType::Matroska;
+
+        for (id, body) in children(&body)? {
+            if id == DOC_TYPE {
+                doc_type = match read_string(body).as_str() {
+                    "matroska" => DocType::Matroska,
+                    "webm" => DocType::WebM,
+                    other => return Err(MkvError::UnsupportedDocType(other.to_string())),
+                };
+            }
+        }
+
+        input.next_header()?.filter(|h| h.id == SEGMENT).ok_or(MkvError::Malformed)?;
+        let segment_data_offset = input.position;
+
+        let mut track = None;
+        let mut timestamp_scale = 1_000_000;
+        let mut duration = None;
+        let mut cues_position = None;
+        let mut cues = vec![];
+
+        loop {
+            let header = input.next_header()?.ok_or(MkvError::MissingVideoTrack)?;
+
+            match header.id {
+                SEEK_HEAD => {
+                    for (_, seek) in children(&input.read_body(header)?)? {
+                        let seek = children(seek)?;
+                        let value = |id| seek.iter().find(|(i, _)| *i == id).map(|(_, b)| *b);
+
+                        if let (Some(seek_id), Some(position)) =
+                            (value(SEEK_ID), value(SEEK_POSITION))
+                        {
+                            if read_uint(seek_id)? == CUES as u64 {
+                                cues_position = Some(read_uint(position)?);
+                            }
+                        }
+                    }
+                },
+                INFO => {
+                    let body = input.read_body(header)?;
+                    let mut raw_duration = None;
+
+                    for (id, body) in children(&body)? {
+                        match id {
+                            TIMESTAMP_SCALE => timestamp_scale = read_uint(body)?,
+                            DURATION => raw_duration = Some(read_float(body)?),
+                            _ => {},
+                        }
+                    }
+
+                    duration = raw_duration.map(|ticks| {
+                        Duration::from_nanos((ticks * timestamp_scale as f64).round() as u64)
+                    });
+                },
+                TRACKS => {
+                    for (id, entry) in children(&input.read_body(header)?)? {
+                        if id == TRACK_ENTRY && track.is_none() {
+                            track = parse_track_entry(entry)?;
+                        }
+                    }
+                },
+                CUES => cues = parse_cues(&input.read_body(header)?, timestamp_scale)?,
+                CLUSTER => {
+                    input.pending = Some(header);
+                    break;
+                },
+                _ => input.skip(header)?,
+            }
+        }
+
+        Ok(Self {
+            input,
+            doc_type,
+            track: track.ok_or(MkvError::MissingVideoTrack)?,
+            timestamp_scale,
+            duration,
+            segment_data_offset,
+            cues_position,
+            cues,
+            cluster_timestamp: 0,
+        })
+    }
+
+    pub fn doc_type(&self) -> DocType {
+        self.doc_type
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.track.codec
+    }
+
+    /// The parameter sets from the track's `CodecPrivate`.
+    pub fn parameter_sets(&self) -> &ParameterSets {
+        &self.track.parameter_sets
+    }
+
+    pub fn width(&self) -> u32 {
+        self.track.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.track.height
+    }
+
+    /// The segment duration, if the muxer recorded one.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Cues read so far. Cues after the clusters are only read once reached,
+    /// or by `seek`.
+    pub fn cues(&self) -> &[CuePoint] {
+        &self.cues
+    }
+
+    /// Reads the next frame of the video track, or `None` at the end.
+    pub fn read_frame(&mut self) -> Result<Option<MatroskaFrame>, MkvError> {
+        loop {
+            let Some(header) = self.input.next_header()? else {
+                return Ok(None);
+            };
+
+            match header.id {
+                // Descend into clusters, so unknown-size clusters simply end
+                // at the next top level element.
+                CLUSTER => self.cluster_timestamp = 0,
+                TIMESTAMP => self.cluster_timestamp = read_uint(&self.input.read_body(header)?)?,
+                SIMPLE_BLOCK => {
+                    let body = self.input.read_body(header)?;
+
+                    if let Some(frame) = self.parse_block(&body, None)? {
+                        return Ok(Some(frame));
+                    }
+                },
+                BLOCK_GROUP => {
+                    let body = self.input.read_body(header)?;
+                    let children = children(&body)?;
+                    let block = children.iter().find(|(id, _)| *id == BLOCK);
+                    let is_keyframe = !children.iter().any(|(id, _)| *id == REFERENCE_BLOCK);
+
+                    if let Some((_, block)) = block {
+                        if let Some(frame) = self.parse_block(block, Some(is_keyframe))? {
+                            return Ok(Some(frame));
+                        }
+                    }
+                },
+                CUES => {
+                    self.cues = parse_cues(&self.input.read_body(header)?, self.timestamp_scale)?;
+                },
+                _ => self.input.skip(header)?,
+            }
+        }
+    }
+
+    /// Parses a `SimpleBlock` or `Block`, returning `None` for other tracks.
+    /// SimpleBlocks carry their own keyframe flag.
+    fn parse_block(
+        &self,
+        body: &[u8],
+        is_keyframe: Option<bool>,
+    ) -> Result<Option<MatroskaFrame>, MkvError> {
+        let (track_number, len) = read_vint(body)?;
+        let block_header = body.get(len..len + 3).ok_or(MkvError::Malformed)?;
+        let flags = block_header[2];
+
+        if track_number != self.track.number {
+            return Ok(None);
+        }
+
+        if flags & 0b0110 != 0 {
+            return Err(MkvError::UnsupportedLacing);
+        }
+
+        let relative = i16::from_be_bytes([block_header[0], block_header[1]]) as i64;
+        let ticks = (self.cluster_timestamp as i64).saturating_add(relative).max(0) as u64;
+        let is_keyframe = is_keyframe.unwrap_or(flags & 0x80 != 0);
+
+        let mut data = match is_keyframe {
+            true => self.track.parameter_sets.to_annex_b(),
+            false => vec![],
+        };
+        annex_b_from_length_prefixed(&body[len + 3..], self.track.nal_length_size, &mut data)
+            .ok_or(MkvError::Malformed)?;
+
+        Ok(Some(MatroskaFrame {
+            data,
+            pts: Duration::from_nanos(ticks.saturating_mul(self.timestamp_scale)),
+            is_keyframe,
+        }))
+    }
+}
+
+impl<R: Read + Seek> MatroskaReader<R> {
+    /// Moves to the cluster of the last cue at or before `time`, reading the
+    /// cues through the `SeekHead` first if they have not been reached yet.
+    pub fn seek(&mut self, time: Duration) -> Result<(), MkvError> {
+        if self.cues.is_empty() {
+            let position = self.cues_position.ok_or(MkvError::MissingCues)?;
+            self.input.seek_to(self.segment_data_offset + position)?;
+
+            let header = self.input.next_header()?;
+            let header = header.filter(|h| h.id == CUES).ok_or(MkvError::Malformed)?;
+            self.cues = parse_cues(&self.input.read_body(header)?, self.timestamp_scale)?;
+        }
+
+        let cue = self
+            .cues
+            .iter()
+            .rev()
+            .find(|cue| cue.time <= time)
+            .or(self.cues.first())
+            .ok_or(MkvError::MissingCues)?;
+
+        self.input.seek_to(self.segment_data_offset + cue.cluster_position)?;
+        Ok(())
+    }
+}
+
+fn parse_track_entry(entry: &[u8]) -> Result<Option<Track>, MkvError> {
+    let mut number = None;
+    let mut codec = None;
+    let mut codec_private = None;
+    let (mut width, mut height) = (0, 0);
+
+    for (id, body) in children(entry)? {
+        match id {
+            TRACK_NUMBER => number = Some(read_uint(body)?),
+            CODEC_ID => {
+                codec = match read_string(body).as_str() {
+                    HEVC_CODEC_ID => Some(VideoCodec::Hevc),
+                    H264_CODEC_ID => Some(VideoCodec::H264),
+                    _ => return Ok(None),
+                }
+            },
+            CODEC_PRIVATE => codec_private = Some(body),
+            VIDEO => {
+                for (id, body) in children(body)? {
+                    match id {
+                        PIXEL_WIDTH => width = read_uint(body)? as u32,
+                        PIXEL_HEIGHT => height = read_uint(body)? as u32,
+                        _ => {},
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let (Some(number), Some(codec), Some(codec_private)) = (number, codec, codec_private) else {
+        return Ok(None);
+    };
+
+    let parameter_sets = ParameterSets::from_decoder_configuration_record(codec, codec_private)
+        .ok_or(MkvError::MissingParameterSets)?;
+
+    // lengthSizeMinusOne sits in the low bits of a fixed byte of each record.
+    let length_byte = match codec {
+        VideoCodec::H264 => codec_private[4],
+        VideoCodec::Hevc => codec_private[21],
+    };
+
+    Ok(Some(Track {
+        number,
+        codec,
+        parameter_sets,
+        nal_length_size: (length_byte & 0b11) as usize + 1,
+        width,
+        height,
+    }))
+}
+
+fn parse_cues(body: &[u8], timestamp_scale: u64) -> Result<Vec<CuePoint>, MkvError> {
+    let mut cues = vec![];
+
+    for (id, cue_point) in children(body)? {
+        if id != CUE_POINT {
+            continue;
+        }
+
+        let mut time = None;
+        let mut cluster_position = None;
+
+        for (id, body) in children(cue_point)? {
+            match id {
+                CUE_TIME => time = Some(read_uint(body)?),
+                CUE_TRACK_POSITIONS => {
+                    for (id, body) in children(body)? {
+                        if id == CUE_CLUSTER_POSITION {
+                            cluster_position = Some(read_uint(body)?);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if let (Some(time), Some(cluster_position)) = (time, cluster_position) {
+            let time = Duration::from_nanos(time.saturating_mul(timestamp_scale));
+            cues.push(CuePoint { time, cluster_position });
+        }
+    }
+
+    Ok(cues)
+}
+
+/// The underlying reader, tracking how far into the stream it is.
+struct Input<R> {
+    reader: R,
+    position: u64,
+    /// A header read ahead of its element being handled.
+    pending: Option<ElementHeader>,
+}
+
+impl<R: Read> Input<R> {
+    fn next_header(&mut self) -> Result<Option<ElementHeader>, MkvError> {
+        if let Some(header) = self.pending.take() {
+            return Ok(Some(header));
+        }
+
+        let header = read_header(&mut self.reader)?;
+        self.position += header.map_or(0, |header| header.len as u64);
+        Ok(header)
+    }
+
+    fn read_body(&mut self, header: ElementHeader) -> Result<Vec<u8>, MkvError> {
+        let size = header.size.ok_or(MkvError::Malformed)?;
+        let mut body = vec![];
+        (&mut self.reader).take(size).read_to_end(&mut body)?;
+        self.position += body.len() as u64;
+
+        if body.len() as u64 != size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(body)
+    }
+
+    fn skip(&mut self, header: ElementHeader) -> Result<(), MkvError> {
+        let size = header.size.ok_or(MkvError::Malformed)?;
+        let skipped = io::copy(&mut (&mut self.reader).take(size), &mut io::sink())?;
+        self.position += skipped;
+
+        if skipped != size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Input<R> {
+    /// Seeks to `position` bytes from where reading started.
+    fn seek_to(&mut self, position: u64) -> Result<(), MkvError> {
+        let start = self.reader.stream_position()? - self.position;
+        self.reader.seek(SeekFrom::Start(start + position))?;
+        self.position = position;
+        self.pending = None;
+        Ok(())
+    }
+}
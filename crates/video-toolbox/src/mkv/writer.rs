@@ -0,0 +1,337 @@
+use crate::{
+    length_prefixed_from_annex_b,
+    mkv::{codec_id, ebml::*, DocType, MkvError, VIDEO_TRACK_NUMBER},
+    ParameterSets, VideoCodec,
+};
+use std::{
+    io::{Seek, SeekFrom, Write},
+    time::Duration,
+};
+
+/// Nanoseconds per timestamp tick, making block timestamps milliseconds.
+pub(crate) const WRITER_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// Space reserved after the segment header for `finish_seekable` to fill in a
+/// `SeekHead`.
+const SEEK_HEAD_RESERVED: usize = 96;
+
+/// The length of a `Duration` element holding an 8 byte float.
+const DURATION_LEN: usize = 11;
+
+#[derive(Debug, Clone)]
+pub struct MatroskaConfig {
+    pub codec: VideoCodec,
+    pub doc_type: DocType,
+    /// Writes unknown-size clusters block by block as frames arrive, instead
+    /// of buffering each cluster, so the output can be streamed.
+    pub live: bool,
+    /// Clusters are started at the first keyframe after this much media.
+    pub cluster_duration: Duration,
+}
+
+impl Default for MatroskaConfig {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::Hevc,
+            doc_type: DocType::Matroska,
+            live: false,
+            cluster_duration: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Writes Annex B access units to a Matroska or WebM file with a single video
+/// track. The headers are written with the first keyframe, which must carry
+/// the parameter sets.
+pub struct MatroskaWriter<W: Write> {
+    writer: W,
+    config: MatroskaConfig,
+    /// Bytes written to `writer` so far.
+    position: u64,
+    layout: Option<Layout>,
+    cluster: Option<Cluster>,
+    /// `(timestamp, cluster position)` of every cluster starting with a keyframe.
+    cues: Vec<(u64, u64)>,
+    cues_position: Option<u64>,
+    first_timestamp: u64,
+    last_timestamp: u64,
+    frame_count: u64,
+}
+
+/// Offsets of the parts of the header that are patched by `finish_seekable`.
+/// Offsets are from the start of the output; positions are relative to the
+/// segment data, as stored in `SeekHead` and `Cues`.
+struct Layout {
+    segment_size_offset: u64,
+    segment_data_offset: u64,
+    seek_head_offset: u64,
+    duration_offset: u64,
+    info_position: u64,
+    tracks_position: u64,
+}
+
+struct Cluster {
+    timestamp: u64,
+    /// The cluster's children, when it is buffered rather than live.
+    data: Vec<u8>,
+}
+
+impl<W: Write> MatroskaWriter<W> {
+    pub fn new(writer: W, config: MatroskaConfig) -> Self {
+        Self {
+            writer,
+            config,
+            position: 0,
+            layout: None,
+            cluster: None,
+            cues: vec![],
+            cues_position: None,
+            first_timestamp: 0,
+            last_timestamp: 0,
+            frame_count: 0,
+        }
+    }
+
+    /// Writes an access unit presented at `pts`. Parameter sets are dropped
+    /// from the block since they are carried in `CodecPrivate`.
+    pub fn write_frame(&mut self, access_unit: &[u8], pts: Duration) -> Result<(), MkvError> {
+        let codec = self.config.codec;
+        let (data, is_keyframe) = length_prefixed_from_annex_b(codec, access_unit);
+        let timestamp =
+            (pts.as_nanos() as u64 + WRITER_TIMESTAMP_SCALE / 2) / WRITER_TIMESTAMP_SCALE;
+
+        if self.layout.is_none() {
+            let parameter_sets = ParameterSets::from_annex_b(codec, access_unit)
+                .filter(|_| is_keyframe)
+                .ok_or(MkvError::MissingParameterSets)?;
+
+            self.write_header(&parameter_sets)?;
+            self.first_timestamp = timestamp;
+        }
+
+        self.last_timestamp = timestamp;
+        self.frame_count += 1;
+
+        let cluster_duration = self.config.cluster_duration.as_millis() as u64;
+        let starts_cluster = self.cluster.as_ref().is_none_or(|cluster| {
+            let relative = timestamp as i64 - cluster.timestamp as i64;
+            let cluster_full = timestamp.saturating_sub(cluster.timestamp) >= cluster_duration;
+
+            (is_keyframe && cluster_full) || i16::try_from(relative).is_err()
+        });
+
+        if starts_cluster {
+            self.close_cluster()?;
+            self.open_cluster(timestamp, is_keyframe)?;
+        }
+
+        let cluster = self.cluster.as_mut().expect("a cluster was just opened");
+        let relative = (timestamp as i64 - cluster.timestamp as i64) as i16;
+
+        let mut block = vec![];
+        write_size(&mut block, VIDEO_TRACK_NUMBER);
+        block.extend_from_slice(&relative.to_be_bytes());
+        block.push(if is_keyframe { 0x80 } else { 0 });
+        block.extend_from_slice(&data);
+
+        if self.config.live {
+            let mut element = vec![];
+            write_binary(&mut element, SIMPLE_BLOCK, &block);
+            self.emit(&element)
+        } else {
+            write_binary(&mut cluster.data, SIMPLE_BLOCK, &block);
+            Ok(())
+        }
+    }
+
+    /// Writes the last cluster and the cues, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W, MkvError> {
+        self.finish_clusters()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self, parameter_sets: &ParameterSets) -> Result<(), MkvError> {
+        let sps_info = parameter_sets.sps_info().ok_or(MkvError::InvalidSequenceParameterSet)?;
+        let doc_type = self.config.doc_type;
+        let mut out = vec![];
+
+        write_element(&mut out, EBML, |out| {
+            write_uint(out, EBML_VERSION, 1);
+            write_uint(out, EBML_READ_VERSION, 1);
+            write_uint(out, EBML_MAX_ID_LENGTH, 4);
+            write_uint(out, EBML_MAX_SIZE_LENGTH, 8);
+            write_string(out, DOC_TYPE, doc_type.as_str());
+            write_uint(out, DOC_TYPE_VERSION, 4);
+            write_uint(out, DOC_TYPE_READ_VERSION, 2);
+        });
+
+        // The segment size is patched by `finish_seekable`, if at all.
+        write_id(&mut out, SEGMENT);
+        let segment_size_offset = out.len() as u64;
+        out.extend_from_slice(&UNKNOWN_SIZE);
+        let segment_data_offset = out.len() as u64;
+
+        let seek_head_offset = out.len() as u64;
+        write_void(&mut out, SEEK_HEAD_RESERVED);
+
+        let mut info = vec![];
+        write_uint(&mut info, TIMESTAMP_SCALE, WRITER_TIMESTAMP_SCALE);
+        write_string(&mut info, MUXING_APP, "video-toolbox");
+        write_string(&mut info, WRITING_APP, "video-toolbox");
+        let duration_in_info = info.len();
+        write_void(&mut info, DURATION_LEN);
+
+        let info_position = out.len() as u64 - segment_data_offset;
+        write_binary(&mut out, INFO, &info);
+        let duration_offset = (out.len() - info.len() + duration_in_info) as u64;
+
+        let tracks_position = out.len() as u64 - segment_data_offset;
+        write_element(&mut out, TRACKS, |out| {
+            write_element(out, TRACK_ENTRY, |out| {
+                write_uint(out, TRACK_NUMBER, VIDEO_TRACK_NUMBER);
+                write_uint(out, TRACK_UID, VIDEO_TRACK_NUMBER);
+                // Video
+                write_uint(out, TRACK_TYPE, 1);
+                write_uint(out, FLAG_LACING, 0);
+                write_string(out, CODEC_ID, codec_id(parameter_sets.codec()));
+                write_binary(out, CODEC_PRIVATE, &parameter_sets.to_decoder_configuration_record());
+
+                write_element(out, VIDEO, |out| {
+                    write_uint(out, PIXEL_WIDTH, sps_info.width as u64);
+                    write_uint(out, PIXEL_HEIGHT, sps_info.height as u64);
+                });
+            });
+        });
+
+        let start = self.position;
+        self.layout = Some(Layout {
+            segment_size_offset: start + segment_size_offset,
+            segment_data_offset: start + segment_data_offset,
+            seek_head_offset: start + seek_head_offset,
+            duration_offset: start + duration_offset,
+            info_position,
+            tracks_position,
+        });
+
+        self.emit(&out)
+    }
+
+    fn open_cluster(&mut self, timestamp: u64, is_keyframe: bool) -> Result<(), MkvError> {
+        let segment_data_offset = self.layout.as_ref().map_or(0, |l| l.segment_data_offset);
+
+        if is_keyframe {
+            self.cues.push((timestamp, self.position - segment_data_offset));
+        }
+
+        let mut data = vec![];
+        write_uint(&mut data, TIMESTAMP, timestamp);
+
+        if self.config.live {
+            let mut header = vec![];
+            write_id(&mut header, CLUSTER);
+            header.extend_from_slice(&UNKNOWN_SIZE);
+            header.append(&mut data);
+            self.emit(&header)?;
+        }
+
+        self.cluster = Some(Cluster { timestamp, data });
+        Ok(())
+    }
+
+    fn close_cluster(&mut self) -> Result<(), MkvError> {
+        match self.cluster.take() {
+            Some(cluster) if !self.config.live => {
+                let mut element = vec![];
+                write_binary(&mut element, CLUSTER, &cluster.data);
+                self.emit(&element)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn finish_clusters(&mut self) -> Result<(), MkvError> {
+        self.close_cluster()?;
+
+        if let Some(layout) = &self.layout {
+            self.cues_position = Some(self.position - layout.segment_data_offset);
+
+            let mut cues = vec![];
+            write_element(&mut cues, CUES, |out| {
+                for &(timestamp, position) in &self.cues {
+                    write_element(out, CUE_POINT, |out| {
+                        write_uint(out, CUE_TIME, timestamp);
+                        write_element(out, CUE_TRACK_POSITIONS, |out| {
+                            write_uint(out, CUE_TRACK, VIDEO_TRACK_NUMBER);
+                            write_uint(out, CUE_CLUSTER_POSITION, position);
+                        });
+                    });
+                }
+            });
+
+            self.emit(&cues)?;
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), MkvError> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> MatroskaWriter<W> {
+    /// Like `finish`, but also goes back to fill in the segment size, the
+    /// duration and a `SeekHead` pointing at the cues, so players can seek.
+    pub fn finish_seekable(mut self) -> Result<W, MkvError> {
+        self.finish_clusters()?;
+
+        let Some(layout) = self.layout.take() else {
+            return Ok(self.writer);
+        };
+
+        let end = self.writer.stream_position()?;
+        let base = end - self.position;
+
+        let mut segment_size = vec![];
+        write_size_with_len(&mut segment_size, self.position - layout.segment_data_offset, 8);
+        self.patch(base + layout.segment_size_offset, &segment_size)?;
+
+        let mut entries = vec![(INFO, layout.info_position), (TRACKS, layout.tracks_position)];
+        entries.extend(self.cues_position.map(|position| (CUES, position)));
+
+        let mut seek_head = vec![];
+        write_element(&mut seek_head, SEEK_HEAD, |out| {
+            for (id, position) in entries {
+                write_element(out, SEEK, |out| {
+                    let mut id_bytes = vec![];
+                    write_id(&mut id_bytes, id);
+                    write_binary(out, SEEK_ID, &id_bytes);
+                    write_uint(out, SEEK_POSITION, position);
+                });
+            }
+        });
+        let padding = SEEK_HEAD_RESERVED - seek_head.len();
+        write_void(&mut seek_head, padding);
+        self.patch(base + layout.seek_head_offset, &seek_head)?;
+
+        // The last frame is assumed to last as long as the average frame.
+        let elapsed = self.last_timestamp.saturating_sub(self.first_timestamp);
+        let duration = elapsed + elapsed / self.frame_count.saturating_sub(1).max(1);
+        let mut duration_element = vec![];
+        write_float(&mut duration_element, DURATION, duration as f64);
+        self.patch(base + layout.duration_offset, &duration_element)?;
+
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn patch(&mut self, offset: u64, bytes: &[u8]) -> Result<(), MkvError> {
+        self.writer.seek(SeekFrom::Start(offset))?;
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+}
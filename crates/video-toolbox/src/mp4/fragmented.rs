@@ -0,0 +1,67 @@
+use crate::mp4::{write_box, write_full_box, Mp4Sample, VIDEO_TRACK_ID};
+
+/// `sample_depends_on` = 2: the sample does not depend on others.
+const KEYFRAME_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// `sample_depends_on` = 1 and `sample_is_non_sync_sample` set.
+const DELTA_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+/// `tfhd` flag: sample data offsets are relative to the `moof`.
+const DEFAULT_BASE_IS_MOOF: u32 = 0x02_0000;
+/// `trun` flags: data offset plus per-sample duration, size and flags.
+const TRUN_FLAGS: u32 = 0x1 | 0x100 | 0x200 | 0x400;
+
+/// Writes a `moof` and `mdat` pair carrying `samples` of the video track.
+///
+/// `sequence_number` starts at 1 and increments with each fragment, and
+/// `base_media_decode_time` is the sum of all previous sample durations.
+pub fn fragment(
+    sequence_number: u32,
+    base_media_decode_time: u64,
+    samples: &[Mp4Sample],
+) -> Vec<u8> {
+    let mut out = vec![];
+    let mut data_offset_position = 0;
+
+    write_box(&mut out, b"moof", |out| {
+        write_full_box(out, b"mfhd", 0, 0, |out| {
+            out.extend_from_slice(&sequence_number.to_be_bytes());
+        });
+
+        write_box(out, b"traf", |out| {
+            write_full_box(out, b"tfhd", 0, DEFAULT_BASE_IS_MOOF, |out| {
+                out.extend_from_slice(&VIDEO_TRACK_ID.to_be_bytes());
+            });
+
+            write_full_box(out, b"tfdt", 1, 0, |out| {
+                out.extend_from_slice(&base_media_decode_time.to_be_bytes());
+            });
+
+            write_full_box(out, b"trun", 0, TRUN_FLAGS, |out| {
+                out.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+                data_offset_position = out.len();
+                out.extend_from_slice(&[0; 4]);
+
+                for sample in samples {
+                    let flags =
+                        if sample.is_keyframe { KEYFRAME_SAMPLE_FLAGS } else { DELTA_SAMPLE_FLAGS };
+
+                    out.extend_from_slice(&sample.duration.to_be_bytes());
+                    out.extend_from_slice(&(sample.data.len() as u32).to_be_bytes());
+                    out.extend_from_slice(&flags.to_be_bytes());
+                }
+            });
+        });
+    });
+
+    // The first sample starts right after the mdat header.
+    let data_offset = (out.len() + 8) as u32;
+    out[data_offset_position..data_offset_position + 4].copy_from_slice(&data_offset.to_be_bytes());
+
+    write_box(&mut out, b"mdat", |out| {
+        for sample in samples {
+            out.extend_from_slice(&sample.data);
+        }
+    });
+
+    out
+}
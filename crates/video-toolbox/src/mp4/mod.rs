@@ -0,0 +1,184 @@
+//! ISO Base Media File Format (ISO/IEC 14496-12) writing.
+
+use crate::{color::HdrMetadata, ParameterSets, VideoCodec};
+use thiserror::Error;
+
+mod fragmented;
+mod sample_entry;
+
+pub use fragmented::*;
+
+/// The track ID used for the single video track.
+pub const VIDEO_TRACK_ID: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum Mp4Error {
+    #[error("Could not parse the sequence parameter set")]
+    InvalidSequenceParameterSet,
+}
+
+/// One encoded picture in length-prefixed (`hvcC` / `avcC`) format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mp4Sample {
+    pub data: Vec<u8>,
+    /// Duration in track timescale units.
+    pub duration: u32,
+    pub is_keyframe: bool,
+}
+
+impl Mp4Sample {
+    /// Converts an Annex B access unit to a sample. Parameter sets are dropped
+    /// since `hvc1` and `avc1` tracks carry them in the sample entry.
+    pub fn from_annex_b(codec: VideoCodec, access_unit: &[u8], duration: u32) -> Self {
+        let (data, is_keyframe) = crate::length_prefixed_from_annex_b(codec, access_unit);
+        Self { data, duration, is_keyframe }
+    }
+}
+
+/// `ftyp` followed by a `moov` describing a single fragmented video track.
+pub fn init_segment(parameter_sets: &ParameterSets, timescale: u32) -> Result<Vec<u8>, Mp4Error> {
+    init_segment_with_hdr(parameter_sets, timescale, &HdrMetadata::default())
+}
+
+/// Like [`init_segment`], with `mdcv` and `clli` boxes for the present `hdr`
+/// fields.
+pub fn init_segment_with_hdr(
+    parameter_sets: &ParameterSets,
+    timescale: u32,
+    hdr: &HdrMetadata,
+) -> Result<Vec<u8>, Mp4Error> {
+    let sps_info = parameter_sets.sps_info().ok_or(Mp4Error::InvalidSequenceParameterSet)?;
+    let mut out = vec![];
+
+    write_box(&mut out, b"ftyp", |out| {
+        out.extend_from_slice(b"iso6");
+        out.extend_from_slice(&0u32.to_be_bytes());
+
+        for brand in [b"iso6", b"mp41", b"dash"] {
+            out.extend_from_slice(brand);
+        }
+    });
+
+    write_box(&mut out, b"moov", |out| {
+        write_full_box(out, b"mvhd", 0, 0, |out| {
+            // Creation and modification time
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&timescale.to_be_bytes());
+            // Duration is unknown for fragmented files.
+            out.extend_from_slice(&0u32.to_be_bytes());
+            // Rate 1.0, volume 1.0, reserved
+            out.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+            out.extend_from_slice(&0x0100u16.to_be_bytes());
+            out.extend_from_slice(&[0; 10]);
+            write_unity_matrix(out);
+            // pre_defined
+            out.extend_from_slice(&[0; 24]);
+            out.extend_from_slice(&(VIDEO_TRACK_ID + 1).to_be_bytes());
+        });
+
+        write_box(out, b"trak", |out| {
+            // Track enabled and in movie
+            write_full_box(out, b"tkhd", 0, 0x3, |out| {
+                out.extend_from_slice(&[0; 8]);
+                out.extend_from_slice(&VIDEO_TRACK_ID.to_be_bytes());
+                out.extend_from_slice(&[0; 4]);
+                out.extend_from_slice(&0u32.to_be_bytes());
+                // Reserved, layer, alternate group, volume, reserved
+                out.extend_from_slice(&[0; 16]);
+                write_unity_matrix(out);
+                out.extend_from_slice(&(sps_info.width << 16).to_be_bytes());
+                out.extend_from_slice(&(sps_info.height << 16).to_be_bytes());
+            });
+
+            write_box(out, b"mdia", |out| {
+                write_full_box(out, b"mdhd", 0, 0, |out| {
+                    out.extend_from_slice(&[0; 8]);
+                    out.extend_from_slice(&timescale.to_be_bytes());
+                    out.extend_from_slice(&0u32.to_be_bytes());
+                    // Packed ISO 639-2 "und"
+                    out.extend_from_slice(&0x55c4u16.to_be_bytes());
+                    out.extend_from_slice(&[0; 2]);
+                });
+
+                write_full_box(out, b"hdlr", 0, 0, |out| {
+                    out.extend_from_slice(&[0; 4]);
+                    out.extend_from_slice(b"vide");
+                    out.extend_from_slice(&[0; 12]);
+                    out.extend_from_slice(b"VideoHandler\0");
+                });
+
+                write_box(out, b"minf", |out| {
+                    write_full_box(out, b"vmhd", 0, 0x1, |out| out.extend_from_slice(&[0; 8]));
+
+                    write_box(out, b"dinf", |out| {
+                        write_full_box(out, b"dref", 0, 0, |out| {
+                            out.extend_from_slice(&1u32.to_be_bytes());
+                            // Media data is in the same file.
+                            write_full_box(out, b"url ", 0, 0x1, |_| {});
+                        });
+                    });
+
+                    write_box(out, b"stbl", |out| {
+                        write_full_box(out, b"stsd", 0, 0, |out| {
+                            out.extend_from_slice(&1u32.to_be_bytes());
+                            sample_entry::write_sample_entry(out, parameter_sets, &sps_info, hdr);
+                        });
+
+                        // Samples live in movie fragments, so the tables are empty.
+                        for kind in [b"stts", b"stsc", b"stco"] {
+                            write_full_box(out, kind, 0, 0, |out| {
+                                out.extend_from_slice(&0u32.to_be_bytes())
+                            });
+                        }
+
+                        write_full_box(out, b"stsz", 0, 0, |out| out.extend_from_slice(&[0; 8]));
+                    });
+                });
+            });
+        });
+
+        write_box(out, b"mvex", |out| {
+            write_full_box(out, b"trex", 0, 0, |out| {
+                out.extend_from_slice(&VIDEO_TRACK_ID.to_be_bytes());
+                // Default sample description index
+                out.extend_from_slice(&1u32.to_be_bytes());
+                // Default duration, size and flags
+                out.extend_from_slice(&[0; 12]);
+            });
+        });
+    });
+
+    Ok(out)
+}
+
+/// Writes a box, back-patching its 32-bit size once `contents` has run.
+pub(crate) fn write_box(out: &mut Vec<u8>, kind: &[u8; 4], contents: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(kind);
+
+    contents(out);
+
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+/// Writes a box with the version and flags header of a `FullBox`.
+pub(crate) fn write_full_box(
+    out: &mut Vec<u8>,
+    kind: &[u8; 4],
+    version: u8,
+    flags: u32,
+    contents: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, kind, |out| {
+        out.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+        contents(out);
+    });
+}
+
+fn write_unity_matrix(out: &mut Vec<u8>) {
+    for value in [0x0001_0000u32, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{color::HdrMetadata, mp4::write_box, ParameterSets, SpsInfo};
+
+/// Writes the `hvc1` or `avc1` visual sample entry for `parameter_sets`.
+pub(crate) fn write_sample_entry(
+    out: &mut Vec<u8>,
+    parameter_sets: &ParameterSets,
+    sps_info: &SpsInfo,
+    hdr: &HdrMetadata,
+) {
+    let (kind, config_kind) = match parameter_sets {
+        ParameterSets::H264(_) => (b"avc1", b"avcC"),
+        ParameterSets::Hevc(_) => (b"hvc1", b"hvcC"),
+    };
+
+    write_box(out, kind, |out| {
+        // Reserved, then data_reference_index
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&1u16.to_be_bytes());
+        // pre_defined and reserved
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&(sps_info.width as u16).to_be_bytes());
+        out.extend_from_slice(&(sps_info.height as u16).to_be_bytes());
+        // 72 dpi horizontal and vertical resolution
+        out.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        out.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        // frame_count
+        out.extend_from_slice(&1u16.to_be_bytes());
+        // compressorname
+        out.extend_from_slice(&[0; 32]);
+        out.extend_from_slice(&0x0018u16.to_be_bytes());
+        out.extend_from_slice(&(-1i16).to_be_bytes());
+
+        write_box(out, config_kind, |out| {
+            out.extend_from_slice(&parameter_sets.to_decoder_configuration_record());
+        });
+
+        if sps_info.color.is_specified() {
+            write_box(out, b"colr", |out| out.extend_from_slice(&sps_info.color.to_nclx()));
+        }
+
+        if let Some(mastering_display) = hdr.mastering_display {
+            write_box(out, b"mdcv", |out| out.extend_from_slice(&mastering_display.to_bytes()));
+        }
+
+        if let Some(content_light_level) = hdr.content_light_level {
+            write_box(out, b"clli", |out| out.extend_from_slice(&content_light_level.to_bytes()));
+        }
+    });
+}
@@ -0,0 +1,136 @@
+//! Encoded HEVC access units with the metadata muxers and network senders
+//! need, so they do not have to parse the bitstream again.
+
+use crate::{
+    alpha::nal_layer_id,
+    bitstream::{remove_emulation_prevention, BitReader},
+    length_prefixed_from_annex_b, HevcParameterSets, NalIterator, Timestamp, VideoCodec,
+};
+
+/// How the NAL units of an [`EncodedPacket`] are delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PacketFraming {
+    /// Start codes, with parameter sets repeated in-band on keyframes, as
+    /// for `.hevc` files, MPEG-TS and RTP.
+    #[default]
+    AnnexB,
+    /// Four byte big-endian lengths without parameter sets, as stored in MP4
+    /// and Matroska, which carry [`EncodedPacket::parameter_sets`] out of band.
+    LengthPrefixed,
+}
+
+/// The coding type of a picture's first slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PictureType {
+    /// Intra only, whether or not it is a keyframe.
+    I,
+    /// Predicted from earlier pictures.
+    P,
+    /// Predicted from earlier and later pictures.
+    B,
+}
+
+/// One encoded access unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedPacket {
+    pub data: Vec<u8>,
+    pub framing: PacketFraming,
+    pub pts: Timestamp,
+    pub dts: Timestamp,
+    pub duration: Option<Timestamp>,
+    /// Whether decoding can start here.
+    pub is_keyframe: bool,
+    /// `None` if the slice header could not be read.
+    pub picture_type: Option<PictureType>,
+    /// The base layer's `TemporalId`, 0 unless temporal scalability is used.
+    pub temporal_id: u8,
+    /// Set when the packet carries parameter sets different from the last
+    /// ones seen, including the first.
+    pub format_changed: bool,
+    /// The parameter sets the packet carries, on keyframes.
+    pub parameter_sets: Option<HevcParameterSets>,
+}
+
+impl EncodedPacket {
+    /// Describes an HEVC Annex B access unit and reframes it. `previous` are
+    /// the parameter sets in effect before it, used to read the slice header
+    /// and detect format changes. Timing is left at zero for the caller.
+    pub fn from_annex_b(
+        access_unit: &[u8],
+        framing: PacketFraming,
+        previous: Option<&HevcParameterSets>,
+    ) -> Self {
+        let parameter_sets = HevcParameterSets::from_annex_b(access_unit);
+        let format_changed = parameter_sets.is_some() && parameter_sets.as_ref() != previous;
+        let active = parameter_sets.as_ref().or(previous);
+
+        let first_slice = NalIterator::new(access_unit).map(|nal| nal.data).find(|nal| {
+            nal_layer_id(nal) == Some(0) && nal.first().is_some_and(|header| header >> 1 < 32)
+        });
+
+        let data = match framing {
+            PacketFraming::AnnexB => access_unit.to_vec(),
+            PacketFraming::LengthPrefixed => {
+                length_prefixed_from_annex_b(VideoCodec::Hevc, access_unit).0
+            },
+        };
+
+        Self {
+            data,
+            framing,
+            pts: Timestamp::ZERO,
+            dts: Timestamp::ZERO,
+            duration: None,
+            is_keyframe: VideoCodec::Hevc.is_keyframe(access_unit),
+            picture_type: first_slice.and_then(|slice| picture_type(slice, active)),
+            temporal_id: first_slice.and_then(temporal_id).unwrap_or(0),
+            format_changed,
+            parameter_sets,
+        }
+    }
+}
+
+/// `nuh_temporal_id_plus1` minus one.
+fn temporal_id(nal: &[u8]) -> Option<u8> {
+    (nal.get(1)? & 0b111).checked_sub(1)
+}
+
+/// Reads `slice_type` from the first slice segment of a picture (HEVC
+/// section 7.3.6.1).
+fn picture_type(slice: &[u8], parameter_sets: Option<&HevcParameterSets>) -> Option<PictureType> {
+    let nal_type = (slice.first()? >> 1) & 0b11_1111;
+    let rbsp = remove_emulation_prevention(slice.get(2..)?);
+    let mut reader = BitReader::new(&rbsp);
+
+    // Later slice segments start with fields sized by the SPS.
+    if !reader.read_bit()? {
+        return None;
+    }
+
+    // no_output_of_prior_pics_flag on IRAP pictures
+    if (16..=23).contains(&nal_type) {
+        reader.skip_bits(1)?;
+    }
+
+    let _slice_pic_parameter_set_id = reader.read_ue()?;
+    let extra_bits = parameter_sets.and_then(num_extra_slice_header_bits).unwrap_or(0);
+    reader.skip_bits(extra_bits as usize)?;
+
+    match reader.read_ue()? {
+        0 => Some(PictureType::B),
+        1 => Some(PictureType::P),
+        2 => Some(PictureType::I),
+        _ => None,
+    }
+}
+
+fn num_extra_slice_header_bits(parameter_sets: &HevcParameterSets) -> Option<u32> {
+    let rbsp = remove_emulation_prevention(parameter_sets.pps.get(2..)?);
+    let mut reader = BitReader::new(&rbsp);
+
+    let _pps_pic_parameter_set_id = reader.read_ue()?;
+    let _pps_seq_parameter_set_id = reader.read_ue()?;
+    // dependent_slice_segments_enabled_flag and output_flag_present_flag
+    reader.skip_bits(2)?;
+    reader.read_bits(3)
+}
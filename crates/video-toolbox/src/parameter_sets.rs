@@ -0,0 +1,460 @@
+use crate::{
+    alpha::nal_layer_id,
+    bitstream::{remove_emulation_prevention, BitReader},
+    color::ColorInfo,
+    sps::{parse_h264_sps, parse_hevc_sps},
+    NalIterator, NalType, PixelFormat, SpsInfo, VideoCodec,
+};
+
+const START_CODE: &[u8; 4] = &[0, 0, 0, 1];
+
+/// Parameter sets for either codec, as carried out of band in SDP or containers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterSets {
+    H264(H264ParameterSets),
+    Hevc(HevcParameterSets),
+}
+
+impl ParameterSets {
+    pub fn codec(&self) -> VideoCodec {
+        match self {
+            ParameterSets::H264(_) => VideoCodec::H264,
+            ParameterSets::Hevc(_) => VideoCodec::Hevc,
+        }
+    }
+
+    /// The parameter sets with start codes, suitable for prefixing the first
+    /// access unit passed to a decoder.
+    pub fn to_annex_b(&self) -> Vec<u8> {
+        match self {
+            ParameterSets::H264(parameter_sets) => parameter_sets.to_annex_b(),
+            ParameterSets::Hevc(parameter_sets) => parameter_sets.to_annex_b(),
+        }
+    }
+
+    pub fn sps_info(&self) -> Option<SpsInfo> {
+        match self {
+            ParameterSets::H264(parameter_sets) => parameter_sets.sps_info(),
+            ParameterSets::Hevc(parameter_sets) => parameter_sets.sps_info(),
+        }
+    }
+
+    /// The RFC 6381 `codecs` value, e.g. `hvc1.1.6.L93.B0` or `avc1.42c01f`.
+    pub fn codec_string(&self) -> Option<String> {
+        match self {
+            ParameterSets::H264(parameter_sets) => {
+                let [profile_idc, constraints, level_idc] = parameter_sets.profile_level_id()?;
+                Some(format!("avc1.{:02x}{:02x}{:02x}", profile_idc, constraints, level_idc))
+            },
+            ParameterSets::Hevc(parameter_sets) => {
+                Some(parameter_sets.profile_tier_level()?.codec_string())
+            },
+        }
+    }
+
+    /// The `avcC` or `hvcC` decoder configuration record, as used for MP4
+    /// sample entries and Matroska `CodecPrivate`.
+    pub fn to_decoder_configuration_record(&self) -> Vec<u8> {
+        match self {
+            ParameterSets::H264(parameter_sets) => parameter_sets.to_avcc(),
+            ParameterSets::Hevc(parameter_sets) => parameter_sets.to_hvcc(),
+        }
+    }
+
+    pub fn from_decoder_configuration_record(codec: VideoCodec, record: &[u8]) -> Option<Self> {
+        match codec {
+            VideoCodec::H264 => H264ParameterSets::from_avcc(record).map(Into::into),
+            VideoCodec::Hevc => HevcParameterSets::from_hvcc(record).map(Into::into),
+        }
+    }
+
+    /// Collects the parameter sets of `codec` from an Annex B buffer.
+    pub fn from_annex_b(codec: VideoCodec, bytes: &[u8]) -> Option<Self> {
+        match codec {
+            VideoCodec::H264 => H264ParameterSets::from_annex_b(bytes).map(Into::into),
+            VideoCodec::Hevc => HevcParameterSets::from_annex_b(bytes).map(Into::into),
+        }
+    }
+}
+
+impl From<H264ParameterSets> for ParameterSets {
+    fn from(parameter_sets: H264ParameterSets) -> Self {
+        ParameterSets::H264(parameter_sets)
+    }
+}
+
+impl From<HevcParameterSets> for ParameterSets {
+    fn from(parameter_sets: HevcParameterSets) -> Self {
+        ParameterSets::Hevc(parameter_sets)
+    }
+}
+
+/// H.264 sequence and picture parameter sets, without start codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct H264ParameterSets {
+    pub sps: Vec<u8>,
+    pub pps: Vec<u8>,
+}
+
+impl H264ParameterSets {
+    /// Collects the last SPS and PPS found in an Annex B buffer.
+    pub fn from_annex_b(bytes: &[u8]) -> Option<Self> {
+        let mut sps = None;
+        let mut pps = None;
+
+        for nal in NalIterator::new(bytes) {
+            match nal.data.first().map(|header| header & 0b1_1111) {
+                Some(7) => sps = Some(nal.data.to_vec()),
+                Some(8) => pps = Some(nal.data.to_vec()),
+                _ => {},
+            }
+        }
+
+        Some(Self { sps: sps?, pps: pps? })
+    }
+
+    /// `profile_idc`, the constraint flags and `level_idc` from the SPS.
+    pub fn profile_level_id(&self) -> Option<[u8; 3]> {
+        match self.sps.get(1..4)? {
+            &[profile_idc, constraints, level_idc] => Some([profile_idc, constraints, level_idc]),
+            _ => None,
+        }
+    }
+
+    /// Picture dimensions and format parsed from the SPS.
+    pub fn sps_info(&self) -> Option<SpsInfo> {
+        parse_h264_sps(&self.sps)
+    }
+
+    pub fn to_annex_b(&self) -> Vec<u8> {
+        annex_b(&[&self.sps, &self.pps])
+    }
+
+    /// `AVCDecoderConfigurationRecord` (ISO/IEC 14496-15 section 5.3.3) with
+    /// four byte NAL unit lengths.
+    pub fn to_avcc(&self) -> Vec<u8> {
+        let [profile_idc, constraints, level_idc] = self.profile_level_id().unwrap_or([66, 0, 30]);
+        let sps_info = self.sps_info().unwrap_or(DEFAULT_SPS_INFO);
+
+        let mut record = vec![1, profile_idc, constraints, level_idc, 0xfc | 3];
+
+        record.push(0xe0 | 1);
+        record.extend_from_slice(&(self.sps.len() as u16).to_be_bytes());
+        record.extend_from_slice(&self.sps);
+
+        record.push(1);
+        record.extend_from_slice(&(self.pps.len() as u16).to_be_bytes());
+        record.extend_from_slice(&self.pps);
+
+        if !matches!(profile_idc, 66 | 77 | 88) {
+            record.push(0xfc | sps_info.chroma_format_idc);
+            record.push(0xf8 | sps_info.bit_depth_luma.saturating_sub(8));
+            record.push(0xf8 | sps_info.bit_depth_chroma.saturating_sub(8));
+            // numOfSequenceParameterSetExt
+            record.push(0);
+        }
+
+        record
+    }
+
+    /// Reads the first SPS and PPS from an `AVCDecoderConfigurationRecord`.
+    pub fn from_avcc(record: &[u8]) -> Option<Self> {
+        let mut reader = RecordReader { data: record.get(5..)? };
+
+        let sps_count = reader.byte()? & 0b1_1111;
+        let sps = reader.nal_unit()?;
+        (1..sps_count).try_for_each(|_| reader.nal_unit().map(|_| ()))?;
+
+        reader.byte()?;
+        let pps = reader.nal_unit()?;
+
+        Some(Self { sps, pps })
+    }
+}
+
+/// HEVC video, sequence and picture parameter sets, without start codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HevcParameterSets {
+    pub vps: Vec<u8>,
+    pub sps: Vec<u8>,
+    pub pps: Vec<u8>,
+}
+
+impl HevcParameterSets {
+    /// Collects the last base layer VPS, SPS and PPS found in an Annex B
+    /// buffer. The alpha layer's parameter sets of HEVC with alpha are skipped.
+    pub fn from_annex_b(bytes: &[u8]) -> Option<Self> {
+        let mut vps = None;
+        let mut sps = None;
+        let mut pps = None;
+
+        for nal in NalIterator::new(bytes) {
+            if nal_layer_id(nal.data) != Some(0) {
+                continue;
+            }
+
+            match nal.nal_type {
+                NalType::Vps => vps = Some(nal.data.to_vec()),
+                NalType::Sps => sps = Some(nal.data.to_vec()),
+                NalType::Pps => pps = Some(nal.data.to_vec()),
+                _ => {},
+            }
+        }
+
+        Some(Self { vps: vps?, sps: sps?, pps: pps? })
+    }
+
+    /// The general profile, tier and level signalled in the SPS.
+    pub fn profile_tier_level(&self) -> Option<HevcProfileTierLevel> {
+        let rbsp = remove_emulation_prevention(self.sps.get(2..)?);
+        let mut reader = BitReader::new(&rbsp);
+
+        // sps_video_parameter_set_id, sps_max_sub_layers_minus1, sps_temporal_id_nesting_flag
+        reader.skip_bits(8)?;
+
+        Some(HevcProfileTierLevel {
+            profile_space: reader.read_bits(2)? as u8,
+            tier_flag: reader.read_bits(1)? == 1,
+            profile_idc: reader.read_bits(5)? as u8,
+            profile_compatibility_flags: reader.read_bits(32)?,
+            constraint_indicator_flags: ((reader.read_bits(16)? as u64) << 32)
+                | reader.read_bits(32)? as u64,
+            level_idc: reader.read_bits(8)? as u8,
+        })
+    }
+
+    /// Picture dimensions and format parsed from the SPS.
+    pub fn sps_info(&self) -> Option<SpsInfo> {
+        parse_hevc_sps(&self.sps)
+    }
+
+    pub fn to_annex_b(&self) -> Vec<u8> {
+        annex_b(&[&self.vps, &self.sps, &self.pps])
+    }
+
+    /// `HEVCDecoderConfigurationRecord` (ISO/IEC 14496-15 section 8.3.3) with
+    /// four byte NAL unit lengths.
+    pub fn to_hvcc(&self) -> Vec<u8> {
+        let profile = self.profile_tier_level().unwrap_or(HevcProfileTierLevel {
+            profile_space: 0,
+            tier_flag: false,
+            profile_idc: 1,
+            profile_compatibility_flags: 0,
+            constraint_indicator_flags: 0,
+            level_idc: 93,
+        });
+        let sps_info = self.sps_info().unwrap_or(DEFAULT_SPS_INFO);
+
+        let mut record = vec![1];
+        record.push(
+            profile.profile_space << 6 | (profile.tier_flag as u8) << 5 | profile.profile_idc,
+        );
+        record.extend_from_slice(&profile.profile_compatibility_flags.to_be_bytes());
+        record.extend_from_slice(&profile.constraint_indicator_flags.to_be_bytes()[2..]);
+        record.push(profile.level_idc);
+        // min_spatial_segmentation_idc, parallelismType
+        record.extend_from_slice(&0xf000u16.to_be_bytes());
+        record.push(0xfc);
+        record.push(0xfc | sps_info.chroma_format_idc);
+        record.push(0xf8 | sps_info.bit_depth_luma.saturating_sub(8));
+        record.push(0xf8 | sps_info.bit_depth_chroma.saturating_sub(8));
+        // avgFrameRate
+        record.extend_from_slice(&0u16.to_be_bytes());
+        // constantFrameRate 0, one temporal layer, temporal ID nested, four byte lengths
+        record.push(1 << 3 | 1 << 2 | 3);
+
+        let arrays: [(u8, &[u8]); 3] = [(32, &self.vps), (33, &self.sps), (34, &self.pps)];
+        record.push(arrays.len() as u8);
+
+        for (nal_type, nal) in arrays {
+            // array_completeness set
+            record.push(0x80 | nal_type);
+            record.extend_from_slice(&1u16.to_be_bytes());
+            record.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+            record.extend_from_slice(nal);
+        }
+
+        record
+    }
+
+    /// Reads the first VPS, SPS and PPS from an `HEVCDecoderConfigurationRecord`.
+    pub fn from_hvcc(record: &[u8]) -> Option<Self> {
+        let mut reader = RecordReader { data: record.get(22..)? };
+        let mut vps = None;
+        let mut sps = None;
+        let mut pps = None;
+
+        for _ in 0..reader.byte()? {
+            let nal_type = reader.byte()? & 0b11_1111;
+            let count = u16::from_be_bytes([reader.byte()?, reader.byte()?]);
+
+            for _ in 0..count {
+                let nal = reader.nal_unit()?;
+
+                let slot = match nal_type {
+                    32 => &mut vps,
+                    33 => &mut sps,
+                    34 => &mut pps,
+                    _ => continue,
+                };
+
+                slot.get_or_insert(nal);
+            }
+        }
+
+        Some(Self { vps: vps?, sps: sps?, pps: pps? })
+    }
+}
+
+/// The `general_*` fields of an HEVC `profile_tier_level()` structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HevcProfileTierLevel {
+    pub profile_space: u8,
+    /// `true` for the High tier.
+    pub tier_flag: bool,
+    pub profile_idc: u8,
+    pub profile_compatibility_flags: u32,
+    /// The 48 bits starting at `general_progressive_source_flag`.
+    pub constraint_indicator_flags: u64,
+    /// 30 times the level number, e.g. 93 for level 3.1.
+    pub level_idc: u8,
+}
+
+impl HevcProfileTierLevel {
+    /// The `hvc1` codecs value defined in ISO/IEC 14496-15 annex E.
+    pub fn codec_string(&self) -> String {
+        let profile_space = match self.profile_space {
+            1 => "A",
+            2 => "B",
+            3 => "C",
+            _ => "",
+        };
+        let tier = if self.tier_flag { 'H' } else { 'L' };
+
+        let mut codec = format!(
+            "hvc1.{}{}.{:X}.{}{}",
+            profile_space,
+            self.profile_idc,
+            self.profile_compatibility_flags.reverse_bits(),
+            tier,
+            self.level_idc
+        );
+
+        // Constraint bytes, omitting trailing zero bytes.
+        let constraints = &self.constraint_indicator_flags.to_be_bytes()[2..];
+        let used = constraints.iter().rposition(|&byte| byte != 0).map_or(0, |i| i + 1);
+
+        for byte in &constraints[..used] {
+            codec.push_str(&format!(".{:X}", byte));
+        }
+
+        codec
+    }
+}
+
+/// HEVC profiles the encoder can target. VideoToolbox picks the level from
+/// the resolution and frame rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HevcProfile {
+    /// 8-bit 4:2:0.
+    #[default]
+    Main,
+    /// 10-bit 4:2:0.
+    Main10,
+    /// 10-bit 4:2:2, from the range extensions.
+    Main42210,
+}
+
+impl HevcProfile {
+    /// `general_profile_idc`, 4 for every range extensions profile.
+    pub fn profile_idc(self) -> u8 {
+        match self {
+            HevcProfile::Main => 1,
+            HevcProfile::Main10 => 2,
+            HevcProfile::Main42210 => 4,
+        }
+    }
+
+    pub fn bit_depth(self) -> u8 {
+        match self {
+            HevcProfile::Main => 8,
+            HevcProfile::Main10 | HevcProfile::Main42210 => 10,
+        }
+    }
+
+    /// 1 for 4:2:0 and 2 for 4:2:2, as in [`SpsInfo::chroma_format_idc`].
+    pub fn chroma_format_idc(self) -> u8 {
+        match self {
+            HevcProfile::Main | HevcProfile::Main10 => 1,
+            HevcProfile::Main42210 => 2,
+        }
+    }
+
+    /// The smallest profile that encodes frames of `format` without dropping
+    /// bit depth or chroma, falling back to the deepest one. RGB is encoded as
+    /// 4:2:0.
+    pub fn for_format(format: PixelFormat) -> Self {
+        let chroma_420 = format.is_rgb() || format.chroma_subsampling() == (2, 2);
+
+        match (format.bit_depth(), chroma_420) {
+            (..=8, true) => HevcProfile::Main,
+            (..=10, true) => HevcProfile::Main10,
+            _ => HevcProfile::Main42210,
+        }
+    }
+
+    /// The smallest profile that decodes a stream with these parameters, if
+    /// any of these does.
+    pub fn for_sps_info(sps_info: &SpsInfo) -> Option<Self> {
+        let bit_depth = sps_info.bit_depth_luma.max(sps_info.bit_depth_chroma);
+
+        match (bit_depth, sps_info.chroma_format_idc) {
+            (..=8, 1) => Some(HevcProfile::Main),
+            (..=10, 1) => Some(HevcProfile::Main10),
+            (..=10, 2) => Some(HevcProfile::Main42210),
+            _ => None,
+        }
+    }
+}
+
+/// Assumed when an SPS cannot be parsed: 4:2:0 with 8-bit samples.
+const DEFAULT_SPS_INFO: SpsInfo = SpsInfo {
+    width: 0,
+    height: 0,
+    chroma_format_idc: 1,
+    bit_depth_luma: 8,
+    bit_depth_chroma: 8,
+    frame_rate: None,
+    color: ColorInfo::UNSPECIFIED,
+};
+
+/// Reads the 16-bit length prefixed NAL units in decoder configuration records.
+struct RecordReader<'a> {
+    data: &'a [u8],
+}
+
+impl RecordReader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(byte)
+    }
+
+    fn nal_unit(&mut self) -> Option<Vec<u8>> {
+        let len = u16::from_be_bytes([self.byte()?, self.byte()?]) as usize;
+        let nal = self.data.get(..len)?.to_vec();
+        self.data = &self.data[len..];
+        Some(nal)
+    }
+}
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for nal in nals {
+        bytes.extend_from_slice(START_CODE);
+        bytes.extend_from_slice(nal);
+    }
+
+    bytes
+}
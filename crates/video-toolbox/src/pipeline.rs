@@ -0,0 +1,324 @@
+//! Pipelined encoding: frames are submitted without waiting for their output,
+//! which arrives later through a callback or channel.
+
+use crate::{EncodedPacket, HevcParameterSets, PacketFraming, Timestamp, VideoFrame};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+};
+use thiserror::Error;
+
+/// What an [`EncodePipeline`] delivers for each submitted frame.
+pub type PipelineOutput = Result<EncodedPacket, FrameDropped>;
+
+type Sink = Box<dyn FnMut(PipelineOutput) + Send>;
+
+#[derive(Debug, Error)]
+pub enum PipelineError<E> {
+    #[error("{0} frames are already in flight")]
+    Full(usize),
+
+    #[error("Session error: {0}")]
+    Session(E),
+}
+
+/// A frame the session gave up on, or that failed to encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Frame {frame} at {pts} was dropped with status {status}")]
+pub struct FrameDropped {
+    /// The frame's index in submission order.
+    pub frame: u64,
+    pub pts: Timestamp,
+    /// The session's error code, 0 if it dropped the frame without one.
+    pub status: i32,
+}
+
+/// An encoder that finishes frames asynchronously, e.g. a VideoToolbox
+/// compression session or a mock for tests.
+pub trait EncodeSession {
+    type Error;
+
+    /// Starts encoding `frame`, which is only borrowed for the call. The
+    /// session completes `handle` once the frame's output is ready, from any
+    /// thread, or drops it if the frame is skipped. Frames must complete in
+    /// decode order.
+    ///
+    /// A session rejecting the frame outright [`FrameHandle::discard`]s the
+    /// handle and returns an error.
+    fn submit(&mut self, frame: &VideoFrame, handle: FrameHandle) -> Result<(), Self::Error>;
+
+    /// Blocks until every submitted frame has completed.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+
+    /// How the session's Annex B output is framed in packets.
+    fn framing(&self) -> PacketFraming {
+        PacketFraming::AnnexB
+    }
+}
+
+/// Encoded output for a [`FrameHandle`]. Timing left as `None` falls back to
+/// the submitted frame's, and the dts to the pts. Whether the frame is a
+/// keyframe is read from the access unit unless the session says.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionOutput {
+    /// One access unit in Annex B format.
+    pub access_unit: Vec<u8>,
+    pub pts: Option<Timestamp>,
+    pub dts: Option<Timestamp>,
+    pub duration: Option<Timestamp>,
+    pub is_keyframe: Option<bool>,
+}
+
+/// One frame in flight. Completing, failing or dropping it delivers the
+/// frame's output and frees its place in the pipeline.
+pub struct FrameHandle {
+    frame: u64,
+    pts: Timestamp,
+    duration: Option<Timestamp>,
+    shared: Option<Arc<Shared>>,
+}
+
+impl FrameHandle {
+    /// The frame's index in submission order.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn complete(mut self, output: SessionOutput) {
+        if let Some(shared) = self.shared.take() {
+            let pts = output.pts.unwrap_or(self.pts);
+            let dts = output.dts.unwrap_or(pts);
+            let duration = output.duration.or(self.duration);
+
+            shared.deliver(|output_state| {
+                let mut packet = EncodedPacket::from_annex_b(
+                    &output.access_unit,
+                    output_state.framing,
+                    output_state.parameter_sets.as_ref(),
+                );
+
+                packet.pts = pts;
+                packet.dts = dts;
+                packet.duration = duration;
+
+                if let Some(is_keyframe) = output.is_keyframe {
+                    packet.is_keyframe = is_keyframe;
+                }
+
+                if let Some(parameter_sets) = &packet.parameter_sets {
+                    output_state.parameter_sets = Some(parameter_sets.clone());
+                }
+
+                Some(Ok(packet))
+            });
+        }
+    }
+
+    /// Reports the frame as dropped with the session's error code.
+    pub fn fail(mut self, status: i32) {
+        let dropped = self.dropped(status);
+
+        if let Some(shared) = self.shared.take() {
+            shared.deliver(|_| Some(Err(dropped)));
+        }
+    }
+
+    /// Frees the frame's place without delivering anything, for frames whose
+    /// error [`EncodeSession::submit`] returns instead.
+    pub fn discard(mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.deliver(|_| None);
+        }
+    }
+
+    fn dropped(&self, status: i32) -> FrameDropped {
+        FrameDropped { frame: self.frame, pts: self.pts, status }
+    }
+}
+
+impl Drop for FrameHandle {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            let dropped = self.dropped(0);
+            shared.deliver(|_| Some(Err(dropped)));
+        }
+    }
+}
+
+struct Shared {
+    in_flight: Mutex<usize>,
+    /// Signalled whenever a frame leaves the pipeline.
+    frame_done: Condvar,
+    output: Mutex<OutputState>,
+    /// Locked before `output` is released, so packets reach the sink in the
+    /// order their output was built.
+    sink: Mutex<Sink>,
+}
+
+struct OutputState {
+    framing: PacketFraming,
+    /// The last parameter sets delivered, to detect format changes.
+    parameter_sets: Option<HevcParameterSets>,
+}
+
+impl Shared {
+    /// Hands the frame's output to the sink, then frees its place, so a
+    /// flushed pipeline has delivered everything. This runs inside the
+    /// session's output callback, so a panicking sink loses its output rather
+    /// than unwinding into the session.
+    fn deliver(&self, output: impl FnOnce(&mut OutputState) -> Option<PipelineOutput>) {
+        let output = {
+            let mut state = lock(&self.output);
+            output(&mut state).map(|output| (output, lock(&self.sink)))
+        };
+
+        if let Some((output, mut sink)) = output {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| sink(output)));
+        }
+
+        *lock(&self.in_flight) -= 1;
+        self.frame_done.notify_all();
+    }
+}
+
+/// Feeds frames to an [`EncodeSession`] without waiting for each to finish,
+/// keeping at most `max_in_flight` frames inside the session. Output arrives in
+/// decode order, on whichever thread the session completes frames.
+pub struct EncodePipeline<S: EncodeSession> {
+    session: S,
+    shared: Arc<Shared>,
+    max_in_flight: usize,
+    frames_submitted: u64,
+}
+
+impl<S: EncodeSession> EncodePipeline<S> {
+    /// Calls `sink` with each packet, or with the frames that were dropped. A
+    /// panic in `sink` is caught, and loses only that frame's output.
+    ///
+    /// # Panics
+    ///
+    /// If `max_in_flight` is zero.
+    pub fn new(
+        session: S,
+        max_in_flight: usize,
+        sink: impl FnMut(PipelineOutput) + Send + 'static,
+    ) -> Self {
+        assert!(max_in_flight > 0, "pipeline must allow a frame in flight");
+
+        let output = OutputState { framing: session.framing(), parameter_sets: None };
+
+        Self {
+            session,
+            shared: Arc::new(Shared {
+                in_flight: Mutex::new(0),
+                frame_done: Condvar::new(),
+                output: Mutex::new(output),
+                sink: Mutex::new(Box::new(sink)),
+            }),
+            max_in_flight,
+            frames_submitted: 0,
+        }
+    }
+
+    /// Like [`EncodePipeline::new`], delivering to a channel instead. Sending
+    /// stops silently once the receiver is dropped.
+    pub fn with_channel(session: S, max_in_flight: usize) -> (Self, Receiver<PipelineOutput>) {
+        let (sender, receiver) = mpsc::channel();
+        let pipeline = Self::new(session, max_in_flight, move |output| {
+            let _ = sender.send(output);
+        });
+
+        (pipeline, receiver)
+    }
+
+    pub fn session(&self) -> &S {
+        &self.session
+    }
+
+    pub fn session_mut(&mut self) -> &mut S {
+        &mut self.session
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// Frames submitted but not yet delivered.
+    pub fn frames_in_flight(&self) -> usize {
+        *lock(&self.shared.in_flight)
+    }
+
+    /// Starts encoding `frame`, first waiting for a free place if
+    /// `max_in_flight` frames are already in the session.
+    pub fn submit(&mut self, frame: &VideoFrame) -> Result<(), PipelineError<S::Error>> {
+        let mut in_flight = lock(&self.shared.in_flight);
+
+        while *in_flight >= self.max_in_flight {
+            in_flight =
+                self.shared.frame_done.wait(in_flight).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        *in_flight += 1;
+        drop(in_flight);
+
+        self.submit_reserved(frame)
+    }
+
+    /// Like [`EncodePipeline::submit`], but fails with [`PipelineError::Full`]
+    /// instead of waiting.
+    pub fn try_submit(&mut self, frame: &VideoFrame) -> Result<(), PipelineError<S::Error>> {
+        let mut in_flight = lock(&self.shared.in_flight);
+
+        if *in_flight >= self.max_in_flight {
+            return Err(PipelineError::Full(*in_flight));
+        }
+
+        *in_flight += 1;
+        drop(in_flight);
+
+        self.submit_reserved(frame)
+    }
+
+    /// Waits until every submitted frame has been delivered.
+    pub fn flush(&mut self) -> Result<(), PipelineError<S::Error>> {
+        self.session.flush().map_err(PipelineError::Session)?;
+
+        let mut in_flight = lock(&self.shared.in_flight);
+
+        while *in_flight > 0 {
+            in_flight =
+                self.shared.frame_done.wait(in_flight).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        Ok(())
+    }
+
+    /// Flushes and returns the session.
+    pub fn finish(mut self) -> Result<S, PipelineError<S::Error>> {
+        self.flush()?;
+        Ok(self.session)
+    }
+
+    /// Submits `frame` in a place already counted as in flight.
+    fn submit_reserved(&mut self, frame: &VideoFrame) -> Result<(), PipelineError<S::Error>> {
+        let handle = FrameHandle {
+            frame: self.frames_submitted,
+            pts: frame.pts(),
+            duration: frame.duration(),
+            shared: Some(self.shared.clone()),
+        };
+
+        self.frames_submitted += 1;
+        self.session.submit(frame, handle).map_err(PipelineError::Session)
+    }
+}
+
+/// Locks `mutex` even if a panic poisoned it. Every critical section leaves
+/// its state consistent, and the callers run inside session output callbacks,
+/// where a panic cannot unwind.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,377 @@
+//! Pixel formats of uncompressed frames and the layout of their planes.
+
+use crate::SpsInfo;
+
+/// The memory layout of an uncompressed frame.
+///
+/// Each format maps to a CoreVideo `OSType`. Formats with a `FullRange`
+/// variant are video range (16-235 luma for 8-bit) otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// Packed 8-bit alpha, red, green, blue.
+    Argb32,
+    /// Packed 8-bit blue, green, red, alpha.
+    Bgra32,
+    /// Packed 8-bit red, green, blue, alpha.
+    Rgba32,
+    /// Packed little-endian 2-bit padding and 10-bit red, green and blue.
+    X2Rgb10,
+    /// Packed little-endian 16-bit red, green, blue, alpha.
+    Rgba64,
+    /// 8-bit 4:2:0 with a Y plane and an interleaved CbCr plane.
+    Nv12,
+    Nv12FullRange,
+    /// [`PixelFormat::Nv12`] followed by a full resolution 8-bit alpha plane,
+    /// as decoded from HEVC with alpha.
+    Nv12Alpha,
+    /// 8-bit 4:2:0 with separate Y, Cb and Cr planes.
+    I420,
+    I420FullRange,
+    /// 8-bit 4:4:4 with a Y plane and an interleaved CbCr plane.
+    Nv24,
+    Nv24FullRange,
+    /// 10-bit 4:2:0 in the high bits of little-endian 16-bit samples, with a Y
+    /// plane and an interleaved CbCr plane.
+    P010,
+    P010FullRange,
+    /// 10-bit 4:2:2, laid out like [`PixelFormat::P010`].
+    P210,
+    P210FullRange,
+    /// 10-bit 4:4:4, laid out like [`PixelFormat::P010`].
+    P410,
+    P410FullRange,
+    /// Packed 10-bit 4:2:2, 48 pixels in every 128 bytes.
+    V210,
+    /// Packed 8-bit 4:2:2 in Y0 Cb Y1 Cr order.
+    Yuyv,
+    /// Packed 8-bit 4:2:2 in Cb Y0 Cr Y1 order.
+    Uyvy,
+    /// 8-bit luma only.
+    Gray8,
+    /// 16-bit little-endian luma only.
+    Gray16,
+}
+
+/// How a plane's rows are built: `(pixels, bytes, rows)` for `bytes` every
+/// `pixels` pixels across, and one row every `rows` pixels down.
+type PlaneLayout = (usize, usize, usize);
+
+// The sys crate is only built for Apple targets, so the FourCCs are repeated
+// above and checked against it here.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const _: () = {
+    use video_toolbox_sys::*;
+
+    assert!(PixelFormat::Argb32.os_type() == kCVPixelFormatType_32ARGB);
+    assert!(PixelFormat::Bgra32.os_type() == kCVPixelFormatType_32BGRA);
+    assert!(PixelFormat::Rgba32.os_type() == kCVPixelFormatType_32RGBA);
+    assert!(PixelFormat::X2Rgb10.os_type() == kCVPixelFormatType_ARGB2101010LEPacked);
+    assert!(PixelFormat::Rgba64.os_type() == kCVPixelFormatType_64RGBALE);
+    assert!(PixelFormat::Nv12.os_type() == kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange);
+    assert!(PixelFormat::Nv12FullRange.os_type() == kCVPixelFormatType_420YpCbCr8BiPlanarFullRange);
+    assert!(
+        PixelFormat::Nv12Alpha.os_type() == kCVPixelFormatType_420YpCbCr8VideoRange_8A_TriPlanar
+    );
+    assert!(PixelFormat::I420.os_type() == kCVPixelFormatType_420YpCbCr8Planar);
+    assert!(PixelFormat::I420FullRange.os_type() == kCVPixelFormatType_420YpCbCr8PlanarFullRange);
+    assert!(PixelFormat::Nv24.os_type() == kCVPixelFormatType_444YpCbCr8BiPlanarVideoRange);
+    assert!(PixelFormat::Nv24FullRange.os_type() == kCVPixelFormatType_444YpCbCr8BiPlanarFullRange);
+    assert!(PixelFormat::P010.os_type() == kCVPixelFormatType_420YpCbCr10BiPlanarVideoRange);
+    assert!(
+        PixelFormat::P010FullRange.os_type() == kCVPixelFormatType_420YpCbCr10BiPlanarFullRange
+    );
+    assert!(PixelFormat::P210.os_type() == kCVPixelFormatType_422YpCbCr10BiPlanarVideoRange);
+    assert!(
+        PixelFormat::P210FullRange.os_type() == kCVPixelFormatType_422YpCbCr10BiPlanarFullRange
+    );
+    assert!(PixelFormat::P410.os_type() == kCVPixelFormatType_444YpCbCr10BiPlanarVideoRange);
+    assert!(
+        PixelFormat::P410FullRange.os_type() == kCVPixelFormatType_444YpCbCr10BiPlanarFullRange
+    );
+    assert!(PixelFormat::V210.os_type() == kCVPixelFormatType_422YpCbCr10);
+    assert!(PixelFormat::Yuyv.os_type() == kCVPixelFormatType_422YpCbCr8_yuvs);
+    assert!(PixelFormat::Uyvy.os_type() == kCVPixelFormatType_422YpCbCr8);
+    assert!(PixelFormat::Gray8.os_type() == kCVPixelFormatType_OneComponent8);
+    assert!(PixelFormat::Gray16.os_type() == kCVPixelFormatType_OneComponent16);
+};
+
+const fn fourcc(data: &[u8; 4]) -> u32 {
+    ((data[0] as u32) << 24) | ((data[1] as u32) << 16) | ((data[2] as u32) << 8) | data[3] as u32
+}
+
+impl PixelFormat {
+    pub const ALL: [PixelFormat; 23] = [
+        PixelFormat::Argb32,
+        PixelFormat::Bgra32,
+        PixelFormat::Rgba32,
+        PixelFormat::X2Rgb10,
+        PixelFormat::Rgba64,
+        PixelFormat::Nv12,
+        PixelFormat::Nv12FullRange,
+        PixelFormat::Nv12Alpha,
+        PixelFormat::I420,
+        PixelFormat::I420FullRange,
+        PixelFormat::Nv24,
+        PixelFormat::Nv24FullRange,
+        PixelFormat::P010,
+        PixelFormat::P010FullRange,
+        PixelFormat::P210,
+        PixelFormat::P210FullRange,
+        PixelFormat::P410,
+        PixelFormat::P410FullRange,
+        PixelFormat::V210,
+        PixelFormat::Yuyv,
+        PixelFormat::Uyvy,
+        PixelFormat::Gray8,
+        PixelFormat::Gray16,
+    ];
+
+    /// The CoreVideo pixel format type, matching the `kCVPixelFormatType_*`
+    /// constants in `video-toolbox-sys`.
+    pub const fn os_type(self) -> u32 {
+        match self {
+            // kCVPixelFormatType_32ARGB is not a printable FourCC.
+            PixelFormat::Argb32 => 0x00000020,
+            PixelFormat::Bgra32 => fourcc(b"BGRA"),
+            PixelFormat::Rgba32 => fourcc(b"RGBA"),
+            PixelFormat::X2Rgb10 => fourcc(b"l10r"),
+            PixelFormat::Rgba64 => fourcc(b"l64r"),
+            PixelFormat::Nv12 => fourcc(b"420v"),
+            PixelFormat::Nv12FullRange => fourcc(b"420f"),
+            PixelFormat::Nv12Alpha => fourcc(b"v0a8"),
+            PixelFormat::I420 => fourcc(b"y420"),
+            PixelFormat::I420FullRange => fourcc(b"f420"),
+            PixelFormat::Nv24 => fourcc(b"444v"),
+            PixelFormat::Nv24FullRange => fourcc(b"444f"),
+            PixelFormat::P010 => fourcc(b"x420"),
+            PixelFormat::P010FullRange => fourcc(b"xf20"),
+            PixelFormat::P210 => fourcc(b"x422"),
+            PixelFormat::P210FullRange => fourcc(b"xf22"),
+            PixelFormat::P410 => fourcc(b"x444"),
+            PixelFormat::P410FullRange => fourcc(b"xf44"),
+            PixelFormat::V210 => fourcc(b"v210"),
+            PixelFormat::Yuyv => fourcc(b"yuvs"),
+            PixelFormat::Uyvy => fourcc(b"2vuy"),
+            PixelFormat::Gray8 => fourcc(b"L008"),
+            PixelFormat::Gray16 => fourcc(b"L016"),
+        }
+    }
+
+    pub fn from_os_type(os_type: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.os_type() == os_type)
+    }
+
+    pub fn is_rgb(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Argb32
+                | PixelFormat::Bgra32
+                | PixelFormat::Rgba32
+                | PixelFormat::X2Rgb10
+                | PixelFormat::Rgba64
+        )
+    }
+
+    /// Whether the format carries an alpha channel.
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Argb32
+                | PixelFormat::Bgra32
+                | PixelFormat::Rgba32
+                | PixelFormat::Rgba64
+                | PixelFormat::Nv12Alpha
+        )
+    }
+
+    /// Whether luma and chroma use the full range of sample values. RGB is
+    /// always full range.
+    pub fn is_full_range(self) -> bool {
+        self.is_rgb()
+            || matches!(
+                self,
+                PixelFormat::Nv12FullRange
+                    | PixelFormat::I420FullRange
+                    | PixelFormat::Nv24FullRange
+                    | PixelFormat::P010FullRange
+                    | PixelFormat::P210FullRange
+                    | PixelFormat::P410FullRange
+                    | PixelFormat::Gray8
+                    | PixelFormat::Gray16
+            )
+    }
+
+    /// Significant bits per sample.
+    pub fn bit_depth(self) -> u8 {
+        match self {
+            PixelFormat::X2Rgb10
+            | PixelFormat::P010
+            | PixelFormat::P010FullRange
+            | PixelFormat::P210
+            | PixelFormat::P210FullRange
+            | PixelFormat::P410
+            | PixelFormat::P410FullRange
+            | PixelFormat::V210 => 10,
+            PixelFormat::Rgba64 | PixelFormat::Gray16 => 16,
+            _ => 8,
+        }
+    }
+
+    /// Horizontal and vertical chroma subsampling factors, `(2, 2)` for 4:2:0.
+    /// RGB and luma only formats are `(1, 1)`.
+    pub fn chroma_subsampling(self) -> (u32, u32) {
+        match self {
+            PixelFormat::Nv12
+            | PixelFormat::Nv12FullRange
+            | PixelFormat::Nv12Alpha
+            | PixelFormat::I420
+            | PixelFormat::I420FullRange
+            | PixelFormat::P010
+            | PixelFormat::P010FullRange => (2, 2),
+            PixelFormat::P210
+            | PixelFormat::P210FullRange
+            | PixelFormat::V210
+            | PixelFormat::Yuyv
+            | PixelFormat::Uyvy => (2, 1),
+            _ => (1, 1),
+        }
+    }
+
+    fn layouts(self) -> &'static [PlaneLayout] {
+        match self {
+            PixelFormat::Argb32
+            | PixelFormat::Bgra32
+            | PixelFormat::Rgba32
+            | PixelFormat::X2Rgb10 => &[(1, 4, 1)],
+            PixelFormat::Rgba64 => &[(1, 8, 1)],
+            PixelFormat::Nv12 | PixelFormat::Nv12FullRange => &[(1, 1, 1), (2, 2, 2)],
+            PixelFormat::Nv12Alpha => &[(1, 1, 1), (2, 2, 2), (1, 1, 1)],
+            PixelFormat::I420 | PixelFormat::I420FullRange => &[(1, 1, 1), (2, 1, 2), (2, 1, 2)],
+            PixelFormat::Nv24 | PixelFormat::Nv24FullRange => &[(1, 1, 1), (1, 2, 1)],
+            PixelFormat::P010 | PixelFormat::P010FullRange => &[(1, 2, 1), (2, 4, 2)],
+            PixelFormat::P210 | PixelFormat::P210FullRange => &[(1, 2, 1), (2, 4, 1)],
+            PixelFormat::P410 | PixelFormat::P410FullRange => &[(1, 2, 1), (1, 4, 1)],
+            PixelFormat::V210 => &[(48, 128, 1)],
+            PixelFormat::Yuyv | PixelFormat::Uyvy => &[(2, 4, 1)],
+            PixelFormat::Gray8 => &[(1, 1, 1)],
+            PixelFormat::Gray16 => &[(1, 2, 1)],
+        }
+    }
+
+    /// Whether VideoToolbox encoders take the format as input without
+    /// converting it first.
+    pub fn is_encoder_input(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Argb32
+                | PixelFormat::Bgra32
+                | PixelFormat::Nv12
+                | PixelFormat::Nv12FullRange
+                | PixelFormat::I420
+                | PixelFormat::I420FullRange
+                | PixelFormat::P010
+                | PixelFormat::P010FullRange
+                | PixelFormat::P210
+                | PixelFormat::P210FullRange
+        )
+    }
+
+    /// Whether VideoToolbox decoders can output the format.
+    pub fn is_decoder_output(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Bgra32
+                | PixelFormat::Rgba64
+                | PixelFormat::Nv12
+                | PixelFormat::Nv12FullRange
+                | PixelFormat::Nv12Alpha
+                | PixelFormat::I420
+                | PixelFormat::I420FullRange
+                | PixelFormat::Nv24
+                | PixelFormat::Nv24FullRange
+                | PixelFormat::P010
+                | PixelFormat::P010FullRange
+                | PixelFormat::P210
+                | PixelFormat::P210FullRange
+                | PixelFormat::P410
+                | PixelFormat::P410FullRange
+        )
+    }
+
+    /// The decoder output that keeps every sample of a stream: its bit depth
+    /// and chroma format, in its range. Streams deeper than 10 bits decode to
+    /// [`PixelFormat::Rgba64`].
+    pub fn for_sps_info(sps_info: &SpsInfo) -> PixelFormat {
+        let bit_depth = sps_info.bit_depth_luma.max(sps_info.bit_depth_chroma);
+        let full_range = sps_info.color.full_range;
+
+        let (video_range, full) = match (bit_depth, sps_info.chroma_format_idc) {
+            (..=8, 0 | 1) => (PixelFormat::Nv12, PixelFormat::Nv12FullRange),
+            (..=8, 3) => (PixelFormat::Nv24, PixelFormat::Nv24FullRange),
+            (..=10, 0 | 1) => (PixelFormat::P010, PixelFormat::P010FullRange),
+            // There is no 8-bit 4:2:2 decoder output.
+            (..=10, 2) => (PixelFormat::P210, PixelFormat::P210FullRange),
+            (..=10, _) => (PixelFormat::P410, PixelFormat::P410FullRange),
+            _ => return PixelFormat::Rgba64,
+        };
+
+        if full_range {
+            full
+        } else {
+            video_range
+        }
+    }
+
+    /// Whether frames of this format hold a stream's samples without losing
+    /// bit depth or chroma resolution.
+    pub fn can_hold(self, sps_info: &SpsInfo) -> bool {
+        let bit_depth = sps_info.bit_depth_luma.max(sps_info.bit_depth_chroma);
+        let (horizontal, vertical) = self.chroma_subsampling();
+        let (stream_horizontal, stream_vertical) = match sps_info.chroma_format_idc {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+
+        self.bit_depth() >= bit_depth
+            && (sps_info.chroma_format_idc == 0
+                || (horizontal <= stream_horizontal && vertical <= stream_vertical))
+    }
+
+    pub fn plane_count(self) -> usize {
+        self.layouts().len()
+    }
+
+    /// The minimum bytes per row of `plane` in a frame `width` pixels wide.
+    /// Partial blocks (e.g. the last chroma sample of an odd width) round up.
+    pub fn bytes_per_row(self, plane: usize, width: u32) -> usize {
+        let (pixels, bytes, _) = self.layouts()[plane];
+        (width as usize).div_ceil(pixels) * bytes
+    }
+
+    /// The width in samples of `plane` in a frame `width` pixels wide, with
+    /// interleaved Cb and Cr counted as one sample.
+    pub fn plane_width(self, plane: usize, width: u32) -> usize {
+        match plane {
+            0 => width as usize,
+            2 if self == PixelFormat::Nv12Alpha => width as usize,
+            _ => width.div_ceil(self.chroma_subsampling().0) as usize,
+        }
+    }
+
+    /// The number of rows of `plane` in a frame `height` pixels high.
+    pub fn plane_height(self, plane: usize, height: u32) -> usize {
+        let (_, _, rows) = self.layouts()[plane];
+        (height as usize).div_ceil(rows)
+    }
+
+    /// The size of `plane` when tightly packed.
+    pub fn plane_size(self, plane: usize, width: u32, height: u32) -> usize {
+        self.bytes_per_row(plane, width) * self.plane_height(plane, height)
+    }
+
+    /// The size of a frame with every plane tightly packed.
+    pub fn buffer_size(self, width: u32, height: u32) -> usize {
+        (0..self.plane_count()).map(|plane| self.plane_size(plane, width, height)).sum()
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{
+    rtp::{
+        JitterBuffer, JitterBufferOutput, RtpPacket, H264_FU_A_TYPE, H264_STAP_A_TYPE,
+        HEVC_AGGREGATION_PACKET_TYPE, HEVC_FRAGMENTATION_UNIT_TYPE, HEVC_NAL_HEADER_SIZE,
+    },
+    VideoCodec,
+};
+
+const START_CODE: &[u8; 4] = &[0, 0, 0, 1];
+
+/// A complete access unit in Annex B format, ready for `Decoder::decode_blocking`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessUnit {
+    pub data: Vec<u8>,
+    /// RTP timestamp shared by every packet of the access unit.
+    pub timestamp: u32,
+    pub is_keyframe: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DepacketizerEvent {
+    AccessUnit(AccessUnit),
+    /// Packets were lost and the decoder cannot continue until the sender
+    /// produces a new keyframe (e.g. via RTCP PLI/FIR).
+    KeyframeRequest,
+}
+
+/// Reassembles RFC 7798 (HEVC) or RFC 6184 (H.264) payloads into access units.
+///
+/// After any loss, access units are dropped until the next keyframe since
+/// their references can no longer be trusted.
+#[derive(Debug)]
+pub struct Depacketizer {
+    codec: VideoCodec,
+    jitter_buffer: JitterBuffer,
+    current: Option<PendingAccessUnit>,
+    /// NAL unit being reassembled from fragmentation units.
+    fragment: Option<Vec<u8>>,
+    waiting_for_keyframe: bool,
+    keyframe_requested: bool,
+}
+
+#[derive(Debug)]
+struct PendingAccessUnit {
+    data: Vec<u8>,
+    timestamp: u32,
+    is_keyframe: bool,
+    corrupted: bool,
+}
+
+impl Depacketizer {
+    /// `jitter_buffer_depth` is the number of packets to wait for a missing one.
+    pub fn new(codec: VideoCodec, jitter_buffer_depth: usize) -> Self {
+        Self {
+            codec,
+            jitter_buffer: JitterBuffer::new(jitter_buffer_depth),
+            current: None,
+            fragment: None,
+            waiting_for_keyframe: true,
+            keyframe_requested: false,
+        }
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.codec
+    }
+
+    /// Adds a received packet and returns whatever it completes.
+    pub fn push(&mut self, packet: RtpPacket) -> Vec<DepacketizerEvent> {
+        let mut events = vec![];
+        self.jitter_buffer.push(packet);
+
+        while let Some(output) = self.jitter_buffer.pop() {
+            self.process(output, &mut events);
+        }
+
+        events
+    }
+
+    /// Drains the jitter buffer and emits the access unit in progress.
+    pub fn flush(&mut self) -> Vec<DepacketizerEvent> {
+        let mut events = vec![];
+
+        for output in self.jitter_buffer.flush() {
+            self.process(output, &mut events);
+        }
+
+        self.finish_access_unit(&mut events);
+        events
+    }
+
+    fn process(&mut self, output: JitterBufferOutput, events: &mut Vec<DepacketizerEvent>) {
+        match output {
+            JitterBufferOutput::Packet(packet) => self.process_packet(packet, events),
+            JitterBufferOutput::Lost { .. } | JitterBufferOutput::Resync => self.mark_loss(),
+        }
+    }
+
+    fn process_packet(&mut self, packet: RtpPacket, events: &mut Vec<DepacketizerEvent>) {
+        // A new timestamp means the previous access unit lost its marker packet.
+        if self.current.as_ref().is_some_and(|current| current.timestamp != packet.timestamp) {
+            self.finish_access_unit(events);
+        }
+
+        let current = self.current.get_or_insert_with(|| PendingAccessUnit {
+            data: vec![],
+            timestamp: packet.timestamp,
+            is_keyframe: false,
+            corrupted: false,
+        });
+
+        let nals = match self.codec {
+            VideoCodec::Hevc => depacketize_hevc(&packet.payload, &mut self.fragment),
+            VideoCodec::H264 => depacketize_h264(&packet.payload, &mut self.fragment),
+        };
+
+        match nals {
+            Some(nals) => {
+                for nal in nals {
+                    current.is_keyframe |= self.codec.is_keyframe_nal(&nal);
+                    current.data.extend_from_slice(START_CODE);
+                    current.data.extend_from_slice(&nal);
+                }
+            },
+            None => {
+                current.corrupted = true;
+                self.fragment = None;
+            },
+        }
+
+        if packet.marker {
+            self.finish_access_unit(events);
+        }
+    }
+
+    fn mark_loss(&mut self) {
+        // Any partially received FU can no longer be completed.
+        self.fragment = None;
+
+        if let Some(current) = self.current.as_mut() {
+            current.corrupted = true;
+        }
+
+        self.waiting_for_keyframe = true;
+        self.keyframe_requested = false;
+    }
+
+    fn finish_access_unit(&mut self, events: &mut Vec<DepacketizerEvent>) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+
+        // An FU that never saw its end bit is incomplete.
+        let corrupted = current.corrupted || self.fragment.take().is_some();
+
+        if current.is_keyframe && !corrupted {
+            self.waiting_for_keyframe = false;
+            self.keyframe_requested = false;
+        }
+
+        if corrupted {
+            self.waiting_for_keyframe = true;
+        }
+
+        if self.waiting_for_keyframe || current.data.is_empty() {
+            if !self.keyframe_requested {
+                self.keyframe_requested = true;
+                events.push(DepacketizerEvent::KeyframeRequest);
+            }
+
+            return;
+        }
+
+        events.push(DepacketizerEvent::AccessUnit(AccessUnit {
+            data: current.data,
+            timestamp: current.timestamp,
+            is_keyframe: current.is_keyframe,
+        }));
+    }
+}
+
+/// Returns the complete NAL units in an HEVC payload, or `None` if the payload
+/// is malformed or an FU arrived out of sequence.
+fn depacketize_hevc(payload: &[u8], fragment: &mut Option<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    // End of sequence and end of bitstream NAL units are only a header.
+    if payload.len() < HEVC_NAL_HEADER_SIZE {
+        return None;
+    }
+
+    let packet_type = (payload[0] >> 1) & 0b11_1111;
+
+    match packet_type {
+        HEVC_AGGREGATION_PACKET_TYPE => split_aggregate(&payload[HEVC_NAL_HEADER_SIZE..]),
+        HEVC_FRAGMENTATION_UNIT_TYPE => {
+            let fu_header = *payload.get(2)?;
+            let start = fu_header & 0b1000_0000 != 0;
+            let end = fu_header & 0b0100_0000 != 0;
+            let nal_type = fu_header & 0b11_1111;
+
+            if start {
+                let header = [(payload[0] & 0b1000_0001) | (nal_type << 1), payload[1]];
+                *fragment = Some(header.to_vec());
+            }
+
+            reassemble(&payload[3..], end, fragment)
+        },
+        // PACI packets (type 50) and reserved types are not supported.
+        50..=63 => None,
+        _ => Some(vec![payload.to_vec()]),
+    }
+}
+
+fn depacketize_h264(payload: &[u8], fragment: &mut Option<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    if payload.is_empty() {
+        return None;
+    }
+
+    let packet_type = payload[0] & 0b1_1111;
+
+    match packet_type {
+        1..=23 => Some(vec![payload.to_vec()]),
+        H264_STAP_A_TYPE => split_aggregate(&payload[1..]),
+        H264_FU_A_TYPE => {
+            if payload.len() < 3 {
+                return None;
+            }
+
+            let fu_header = payload[1];
+            let start = fu_header & 0b1000_0000 != 0;
+            let end = fu_header & 0b0100_0000 != 0;
+
+            if start {
+                *fragment = Some(vec![(payload[0] & 0b1110_0000) | (fu_header & 0b1_1111)]);
+            }
+
+            reassemble(&payload[2..], end, fragment)
+        },
+        // STAP-B, MTAP and FU-B only appear in interleaved mode.
+        _ => None,
+    }
+}
+
+fn reassemble(data: &[u8], end: bool, fragment: &mut Option<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    // A middle or end fragment without its start is useless.
+    let nal = fragment.as_mut()?;
+    nal.extend_from_slice(data);
+
+    if end {
+        fragment.take().map(|nal| vec![nal])
+    } else {
+        Some(vec![])
+    }
+}
+
+/// Splits the size-prefixed NAL units of an HEVC AP or H.264 STAP-A.
+fn split_aggregate(mut data: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut nals = vec![];
+
+    while !data.is_empty() {
+        if data.len() < 2 {
+            return None;
+        }
+
+        let size = u16::from_be_bytes([data[0], data[1]]) as usize;
+        if size == 0 || data.len() < 2 + size {
+            return None;
+        }
+
+        nals.push(data[2..2 + size].to_vec());
+        data = &data[2 + size..];
+    }
+
+    Some(nals)
+}
@@ -0,0 +1,188 @@
+use crate::{
+    rtp::{PacketizerConfig, RtpError, RtpPacket, RtpSession},
+    sdp::VideoMediaDescription,
+    H264ParameterSets, NalIterator,
+};
+use std::time::Duration;
+
+pub const H264_STAP_A_TYPE: u8 = 24;
+pub const H264_FU_A_TYPE: u8 = 28;
+
+// FU indicator + FU header
+const FU_A_OVERHEAD: usize = 2;
+
+// STAP-A NAL unit header
+const STAP_A_HEADER_SIZE: usize = 1;
+
+// Each aggregated NAL unit is prefixed with a 16 bit size.
+const STAP_A_NALU_SIZE_LEN: usize = 2;
+
+/// The `packetization-mode` SDP parameter from RFC 6184.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum H264PacketizationMode {
+    /// Every packet carries exactly one NAL unit.
+    SingleNalUnit = 0,
+    /// Single NAL unit packets, STAP-A and FU-A.
+    NonInterleaved = 1,
+}
+
+/// Splits H.264 access units into RTP packets as described in RFC 6184.
+#[derive(Debug)]
+pub struct H264Packetizer {
+    session: RtpSession,
+    mode: H264PacketizationMode,
+    parameter_sets: Option<H264ParameterSets>,
+}
+
+impl H264Packetizer {
+    pub fn new(config: PacketizerConfig, mode: H264PacketizationMode) -> Result<Self, RtpError> {
+        let session = RtpSession::new(config);
+
+        if session.max_payload_size() <= FU_A_OVERHEAD {
+            return Err(RtpError::MtuTooSmall(session.config.mtu));
+        }
+
+        Ok(Self { session, mode, parameter_sets: None })
+    }
+
+    pub fn mode(&self) -> H264PacketizationMode {
+        self.mode
+    }
+
+    /// The sequence number the next emitted packet will use.
+    pub fn next_sequence_number(&self) -> u16 {
+        self.session.next_sequence_number()
+    }
+
+    /// The most recent SPS and PPS seen in packetized access units.
+    pub fn parameter_sets(&self) -> Option<&H264ParameterSets> {
+        self.parameter_sets.as_ref()
+    }
+
+    /// The `a=fmtp` line for this stream, available once an SPS and PPS
+    /// have been packetized.
+    pub fn fmtp(&self) -> Option<String> {
+        let parameter_sets = self.parameter_sets.as_ref()?;
+        let media = VideoMediaDescription::h264(
+            self.session.config.payload_type,
+            self.mode,
+            parameter_sets,
+        );
+
+        Some(media.fmtp_line())
+    }
+
+    /// Packetizes one Annex B access unit presented at `pts`.
+    ///
+    /// In single NAL unit mode a NAL unit larger than the MTU is an error.
+    pub fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        pts: Duration,
+    ) -> Result<Vec<RtpPacket>, RtpError> {
+        let nals: Vec<&[u8]> = NalIterator::new(access_unit)
+            .map(|nal| nal.data)
+            .filter(|data| !data.is_empty())
+            .collect();
+
+        if let Some(parameter_sets) = H264ParameterSets::from_annex_b(access_unit) {
+            self.parameter_sets = Some(parameter_sets);
+        }
+
+        let max_payload_size = self.session.max_payload_size();
+
+        let payloads = match self.mode {
+            H264PacketizationMode::SingleNalUnit => {
+                if let Some(nal) = nals.iter().find(|nal| nal.len() > max_payload_size) {
+                    return Err(RtpError::NalUnitTooLarge(nal.len()));
+                }
+
+                nals.iter().map(|nal| nal.to_vec()).collect()
+            },
+            H264PacketizationMode::NonInterleaved => h264_payloads(&nals, max_payload_size),
+        };
+
+        Ok(self.session.finish_access_unit(payloads, pts))
+    }
+}
+
+/// Builds RTP payloads for a list of NAL units (without start codes).
+fn h264_payloads(nals: &[&[u8]], max_payload_size: usize) -> Vec<Vec<u8>> {
+    let mut payloads = vec![];
+    let mut i = 0;
+
+    while i < nals.len() {
+        let nal = nals[i];
+
+        if nal.len() > max_payload_size {
+            payloads.extend(fragment(nal, max_payload_size));
+            i += 1;
+            continue;
+        }
+
+        // Greedily gather following NAL units that fit in the same packet.
+        let mut aggregated_size = STAP_A_HEADER_SIZE + STAP_A_NALU_SIZE_LEN + nal.len();
+        let mut end = i + 1;
+
+        while end < nals.len() {
+            let next_size = aggregated_size + STAP_A_NALU_SIZE_LEN + nals[end].len();
+
+            if next_size > max_payload_size {
+                break;
+            }
+
+            aggregated_size = next_size;
+            end += 1;
+        }
+
+        if end - i >= 2 {
+            payloads.push(aggregate(&nals[i..end], aggregated_size));
+        } else {
+            payloads.push(nal.to_vec());
+        }
+
+        i = end;
+    }
+
+    payloads
+}
+
+fn aggregate(nals: &[&[u8]], size: usize) -> Vec<u8> {
+    // The F bit is the OR of all aggregated F bits, NRI is the maximum.
+    let forbidden = nals.iter().fold(0, |acc, nal| acc | (nal[0] & 0b1000_0000));
+    let nri = nals.iter().map(|nal| nal[0] & 0b0110_0000).max().unwrap_or(0);
+
+    let mut payload = Vec::with_capacity(size);
+    payload.push(forbidden | nri | H264_STAP_A_TYPE);
+
+    for nal in nals {
+        payload.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+        payload.extend_from_slice(nal);
+    }
+
+    payload
+}
+
+fn fragment(nal: &[u8], max_payload_size: usize) -> Vec<Vec<u8>> {
+    let fu_indicator = (nal[0] & 0b1110_0000) | H264_FU_A_TYPE;
+    let nal_type = nal[0] & 0b1_1111;
+
+    let chunks: Vec<&[u8]> = nal[1..].chunks(max_payload_size - FU_A_OVERHEAD).collect();
+    let last_index = chunks.len() - 1;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let start = (i == 0) as u8;
+            let end = (i == last_index) as u8;
+            let fu_header = (start << 7) | (end << 6) | nal_type;
+
+            let mut payload = Vec::with_capacity(FU_A_OVERHEAD + chunk.len());
+            payload.push(fu_indicator);
+            payload.push(fu_header);
+            payload.extend_from_slice(chunk);
+            payload
+        })
+        .collect()
+}
@@ -0,0 +1,161 @@
+use crate::{
+    rtp::{PacketizerConfig, RtpError, RtpPacket, RtpSession},
+    NalIterator,
+};
+use std::time::Duration;
+
+/// HEVC NAL unit header size in bytes.
+pub const HEVC_NAL_HEADER_SIZE: usize = 2;
+
+pub const HEVC_AGGREGATION_PACKET_TYPE: u8 = 48;
+pub const HEVC_FRAGMENTATION_UNIT_TYPE: u8 = 49;
+
+// PayloadHdr + FU header
+const FU_OVERHEAD: usize = HEVC_NAL_HEADER_SIZE + 1;
+
+// Each aggregated NAL unit is prefixed with a 16 bit size.
+const AP_NALU_SIZE_LEN: usize = 2;
+
+/// Splits HEVC access units into RTP packets as described in RFC 7798.
+///
+/// Only the DONL-less mode (`sprop-max-don-diff` = 0) is produced, so
+/// neither APs nor FUs carry decoding order numbers.
+#[derive(Debug)]
+pub struct HevcPacketizer {
+    session: RtpSession,
+}
+
+impl HevcPacketizer {
+    pub fn new(config: PacketizerConfig) -> Result<Self, RtpError> {
+        let session = RtpSession::new(config);
+
+        // A fragment must carry at least one byte of NAL unit payload.
+        if session.max_payload_size() <= FU_OVERHEAD {
+            return Err(RtpError::MtuTooSmall(session.config.mtu));
+        }
+
+        Ok(Self { session })
+    }
+
+    /// The sequence number the next emitted packet will use.
+    pub fn next_sequence_number(&self) -> u16 {
+        self.session.next_sequence_number()
+    }
+
+    /// Packetizes one Annex B access unit presented at `pts`.
+    ///
+    /// The last packet of the access unit has its marker bit set.
+    pub fn packetize(&mut self, access_unit: &[u8], pts: Duration) -> Vec<RtpPacket> {
+        let nals: Vec<&[u8]> = NalIterator::new(access_unit)
+            .map(|nal| nal.data)
+            .filter(|data| data.len() >= HEVC_NAL_HEADER_SIZE)
+            .collect();
+
+        let payloads = hevc_payloads(&nals, self.session.max_payload_size());
+        self.session.finish_access_unit(payloads, pts)
+    }
+}
+
+/// Builds RTP payloads for a list of NAL units (without start codes).
+fn hevc_payloads(nals: &[&[u8]], max_payload_size: usize) -> Vec<Vec<u8>> {
+    let mut payloads = vec![];
+    let mut i = 0;
+
+    while i < nals.len() {
+        let nal = nals[i];
+
+        if nal.len() > max_payload_size {
+            payloads.extend(fragment(nal, max_payload_size));
+            i += 1;
+            continue;
+        }
+
+        // Greedily gather following NAL units that fit in the same packet.
+        let mut aggregated_size = HEVC_NAL_HEADER_SIZE + AP_NALU_SIZE_LEN + nal.len();
+        let mut end = i + 1;
+
+        while end < nals.len() {
+            let next_size = aggregated_size + AP_NALU_SIZE_LEN + nals[end].len();
+
+            if next_size > max_payload_size {
+                break;
+            }
+
+            aggregated_size = next_size;
+            end += 1;
+        }
+
+        if end - i >= 2 {
+            payloads.push(aggregate(&nals[i..end], aggregated_size));
+        } else {
+            payloads.push(nal.to_vec());
+        }
+
+        i = end;
+    }
+
+    payloads
+}
+
+fn aggregate(nals: &[&[u8]], size: usize) -> Vec<u8> {
+    // The F bit is the OR of all aggregated F bits, the layer ID and TID are the minimums.
+    let forbidden = nals.iter().any(|nal| nal[0] & 0b1000_0000 != 0);
+    let layer_id = nals.iter().map(|nal| nal_layer_id(nal)).min().unwrap_or(0);
+    let temporal_id = nals.iter().map(|nal| nal[1] & 0b111).min().unwrap_or(1);
+
+    let mut payload = Vec::with_capacity(size);
+    payload.extend_from_slice(&nal_header(
+        forbidden,
+        HEVC_AGGREGATION_PACKET_TYPE,
+        layer_id,
+        temporal_id,
+    ));
+
+    for nal in nals {
+        payload.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+        payload.extend_from_slice(nal);
+    }
+
+    payload
+}
+
+fn fragment(nal: &[u8], max_payload_size: usize) -> Vec<Vec<u8>> {
+    let nal_type = (nal[0] >> 1) & 0b0011_1111;
+    let payload_header = nal_header(
+        nal[0] & 0b1000_0000 != 0,
+        HEVC_FRAGMENTATION_UNIT_TYPE,
+        nal_layer_id(nal),
+        nal[1] & 0b111,
+    );
+
+    let chunks: Vec<&[u8]> =
+        nal[HEVC_NAL_HEADER_SIZE..].chunks(max_payload_size - FU_OVERHEAD).collect();
+    let last_index = chunks.len() - 1;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let start = (i == 0) as u8;
+            let end = (i == last_index) as u8;
+            let fu_header = (start << 7) | (end << 6) | nal_type;
+
+            let mut payload = Vec::with_capacity(FU_OVERHEAD + chunk.len());
+            payload.extend_from_slice(&payload_header);
+            payload.push(fu_header);
+            payload.extend_from_slice(chunk);
+            payload
+        })
+        .collect()
+}
+
+fn nal_layer_id(nal: &[u8]) -> u8 {
+    ((nal[0] & 0b1) << 5) | (nal[1] >> 3)
+}
+
+fn nal_header(forbidden: bool, nal_type: u8, layer_id: u8, temporal_id: u8) -> [u8; 2] {
+    [
+        ((forbidden as u8) << 7) | (nal_type << 1) | (layer_id >> 5),
+        ((layer_id & 0b1_1111) << 3) | temporal_id,
+    ]
+}
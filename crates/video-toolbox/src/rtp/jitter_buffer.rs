@@ -0,0 +1,172 @@
+use crate::rtp::RtpPacket;
+use std::{collections::BTreeMap, mem};
+
+/// Consecutive packets older than the release window after which the sender
+/// is assumed to have restarted its sequence numbers.
+const MAX_LATE_PACKETS: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum JitterBufferOutput {
+    Packet(RtpPacket),
+    /// `count` packets are missing before the next packet.
+    Lost {
+        count: u64,
+    },
+    /// The sender restarted, changing its SSRC or jumping back in sequence
+    /// numbers. Packets buffered before then were dropped.
+    Resync,
+}
+
+/// Reorders packets by sequence number.
+///
+/// Packets are released strictly in order, starting from the first packet
+/// received. A missing packet is declared lost once more than `depth` later
+/// packets have been buffered behind it.
+///
+/// A packet with a new SSRC, or a run of packets that all arrive too late,
+/// restarts the buffer from that packet and reports a resync.
+#[derive(Debug)]
+pub struct JitterBuffer {
+    depth: usize,
+    packets: BTreeMap<u64, RtpPacket>,
+    ssrc: Option<u32>,
+    /// Packets discarded as late since the last accepted one.
+    late_packets: usize,
+    resynced: bool,
+    /// Extended sequence number of the next packet to release.
+    next_sequence_number: Option<u64>,
+    /// Highest extended sequence number seen, used to unwrap new packets.
+    highest_sequence_number: Option<u64>,
+}
+
+impl JitterBuffer {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            packets: BTreeMap::new(),
+            ssrc: None,
+            late_packets: 0,
+            resynced: false,
+            next_sequence_number: None,
+            highest_sequence_number: None,
+        }
+    }
+
+    /// Number of packets currently held back.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Adds a packet. Duplicates and packets arriving after their slot was
+    /// released or declared lost are discarded, returning `false`.
+    pub fn push(&mut self, packet: RtpPacket) -> bool {
+        if self.ssrc.is_some_and(|ssrc| ssrc != packet.ssrc) {
+            self.resync();
+        }
+
+        self.ssrc = Some(packet.ssrc);
+        let mut extended = self.extend_sequence_number(packet.sequence_number);
+
+        if let Some(next) = self.next_sequence_number {
+            if extended < next {
+                self.late_packets += 1;
+
+                if self.late_packets < MAX_LATE_PACKETS {
+                    return false;
+                }
+
+                self.resync();
+                extended = self.extend_sequence_number(packet.sequence_number);
+                self.next_sequence_number = Some(extended);
+            }
+        } else {
+            self.next_sequence_number = Some(extended);
+        }
+
+        self.late_packets = 0;
+
+        if self.packets.contains_key(&extended) {
+            return false;
+        }
+
+        self.highest_sequence_number =
+            Some(self.highest_sequence_number.map_or(extended, |highest| highest.max(extended)));
+        self.packets.insert(extended, packet);
+
+        true
+    }
+
+    /// Releases the next in-order packet, or reports a gap once the buffer
+    /// has waited `depth` packets for it.
+    pub fn pop(&mut self) -> Option<JitterBufferOutput> {
+        if mem::take(&mut self.resynced) {
+            return Some(JitterBufferOutput::Resync);
+        }
+
+        let next = self.next_sequence_number?;
+
+        if let Some(packet) = self.packets.remove(&next) {
+            self.next_sequence_number = Some(next + 1);
+            return Some(JitterBufferOutput::Packet(packet));
+        }
+
+        if self.packets.len() > self.depth {
+            return self.skip_gap(next);
+        }
+
+        None
+    }
+
+    /// Releases everything that is buffered, reporting any gaps.
+    pub fn flush(&mut self) -> Vec<JitterBufferOutput> {
+        let mut output = vec![];
+
+        if mem::take(&mut self.resynced) {
+            output.push(JitterBufferOutput::Resync);
+        }
+
+        while let Some(next) = self.next_sequence_number {
+            if let Some(packet) = self.packets.remove(&next) {
+                self.next_sequence_number = Some(next + 1);
+                output.push(JitterBufferOutput::Packet(packet));
+            } else if let Some(lost) = self.skip_gap(next) {
+                output.push(lost);
+            } else {
+                break;
+            }
+        }
+
+        output
+    }
+
+    /// Drops all buffered state so the next packet starts a new stream.
+    fn resync(&mut self) {
+        self.packets.clear();
+        self.late_packets = 0;
+        self.resynced = true;
+        self.next_sequence_number = None;
+        self.highest_sequence_number = None;
+    }
+
+    fn skip_gap(&mut self, next: u64) -> Option<JitterBufferOutput> {
+        let (&first_buffered, _) = self.packets.iter().next()?;
+        self.next_sequence_number = Some(first_buffered);
+
+        Some(JitterBufferOutput::Lost { count: first_buffered - next })
+    }
+
+    fn extend_sequence_number(&self, sequence_number: u16) -> u64 {
+        match self.highest_sequence_number {
+            // Start one cycle in so that slightly older packets do not underflow.
+            None => (1 << 16) + sequence_number as u64,
+            Some(highest) => {
+                let delta = sequence_number.wrapping_sub(highest as u16) as i16;
+                (highest as i64 + delta as i64) as u64
+            },
+        }
+    }
+}
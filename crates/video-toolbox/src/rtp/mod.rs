@@ -0,0 +1,198 @@
+use std::time::Duration;
+use thiserror::Error;
+
+mod depacketizer;
+mod h264;
+mod hevc;
+mod jitter_buffer;
+
+pub use depacketizer::*;
+pub use h264::*;
+pub use hevc::*;
+pub use jitter_buffer::*;
+
+/// Size of a fixed RTP header with no CSRC entries or extensions.
+pub const RTP_HEADER_SIZE: usize = 12;
+
+/// RTP clock rate used for video payloads (RFC 7798, RFC 6184).
+pub const VIDEO_CLOCK_RATE: u32 = 90_000;
+
+#[derive(Debug, Error)]
+pub enum RtpError {
+    #[error("MTU of {0} bytes is too small to carry a fragmented NAL unit")]
+    MtuTooSmall(usize),
+
+    #[error("Packet of {0} bytes is too short")]
+    PacketTooShort(usize),
+
+    #[error("Unsupported RTP version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid padding length: {0}")]
+    InvalidPadding(u8),
+
+    #[error("NAL unit of {0} bytes does not fit in a single packet")]
+    NalUnitTooLarge(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpPacket {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence_number: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub payload: Vec<u8>,
+}
+
+impl RtpPacket {
+    /// Serializes the packet with a fixed 12 byte header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RTP_HEADER_SIZE + self.payload.len());
+
+        // Version 2, no padding, no extension, no CSRCs.
+        bytes.push(0b1000_0000);
+        bytes.push(((self.marker as u8) << 7) | (self.payload_type & 0b0111_1111));
+        bytes.extend_from_slice(&self.sequence_number.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.ssrc.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+
+        bytes
+    }
+
+    /// Parses a packet, skipping any CSRC list, header extension and padding.
+    pub fn parse(bytes: &[u8]) -> Result<Self, RtpError> {
+        if bytes.len() < RTP_HEADER_SIZE {
+            return Err(RtpError::PacketTooShort(bytes.len()));
+        }
+
+        let version = bytes[0] >> 6;
+        if version != 2 {
+            return Err(RtpError::UnsupportedVersion(version));
+        }
+
+        let has_padding = bytes[0] & 0b0010_0000 != 0;
+        let has_extension = bytes[0] & 0b0001_0000 != 0;
+        let csrc_count = (bytes[0] & 0b0000_1111) as usize;
+
+        let mut payload_start = RTP_HEADER_SIZE + 4 * csrc_count;
+
+        if has_extension {
+            if bytes.len() < payload_start + 4 {
+                return Err(RtpError::PacketTooShort(bytes.len()));
+            }
+
+            let extension_words =
+                u16::from_be_bytes([bytes[payload_start + 2], bytes[payload_start + 3]]) as usize;
+            payload_start += 4 + 4 * extension_words;
+        }
+
+        let mut payload_end = bytes.len();
+
+        if has_padding {
+            let padding = bytes[bytes.len() - 1];
+            if padding == 0 || padding as usize > payload_end {
+                return Err(RtpError::InvalidPadding(padding));
+            }
+
+            payload_end -= padding as usize;
+        }
+
+        if payload_start > payload_end {
+            return Err(RtpError::PacketTooShort(bytes.len()));
+        }
+
+        Ok(Self {
+            marker: bytes[1] & 0b1000_0000 != 0,
+            payload_type: bytes[1] & 0b0111_1111,
+            sequence_number: u16::from_be_bytes([bytes[2], bytes[3]]),
+            timestamp: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            ssrc: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            payload: bytes[payload_start..payload_end].to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PacketizerConfig {
+    /// Dynamic payload type advertised in the SDP.
+    pub payload_type: u8,
+    pub ssrc: u32,
+    /// Maximum size of a whole RTP packet, header included.
+    pub mtu: usize,
+    pub initial_sequence_number: u16,
+    /// Added to every RTP timestamp, RFC 3550 recommends a random value.
+    pub timestamp_offset: u32,
+}
+
+impl Default for PacketizerConfig {
+    fn default() -> Self {
+        Self {
+            payload_type: 96,
+            ssrc: 0,
+            mtu: 1200,
+            initial_sequence_number: 0,
+            timestamp_offset: 0,
+        }
+    }
+}
+
+/// Sequence number and timestamp bookkeeping shared by the payload formats.
+#[derive(Debug)]
+pub(crate) struct RtpSession {
+    config: PacketizerConfig,
+    next_sequence_number: u16,
+}
+
+impl RtpSession {
+    pub(crate) fn new(config: PacketizerConfig) -> Self {
+        Self { next_sequence_number: config.initial_sequence_number, config }
+    }
+
+    pub(crate) fn max_payload_size(&self) -> usize {
+        self.config.mtu.saturating_sub(RTP_HEADER_SIZE)
+    }
+
+    pub(crate) fn next_sequence_number(&self) -> u16 {
+        self.next_sequence_number
+    }
+
+    pub(crate) fn rtp_timestamp(&self, pts: Duration) -> u32 {
+        rtp_timestamp(pts, VIDEO_CLOCK_RATE).wrapping_add(self.config.timestamp_offset)
+    }
+
+    /// Wraps each payload in an RTP packet, setting the marker bit on the last one.
+    pub(crate) fn finish_access_unit(
+        &mut self,
+        payloads: Vec<Vec<u8>>,
+        pts: Duration,
+    ) -> Vec<RtpPacket> {
+        let timestamp = self.rtp_timestamp(pts);
+        let last_index = payloads.len().saturating_sub(1);
+
+        payloads
+            .into_iter()
+            .enumerate()
+            .map(|(i, payload)| {
+                let sequence_number = self.next_sequence_number;
+                self.next_sequence_number = self.next_sequence_number.wrapping_add(1);
+
+                RtpPacket {
+                    marker: i == last_index,
+                    payload_type: self.config.payload_type,
+                    sequence_number,
+                    timestamp,
+                    ssrc: self.config.ssrc,
+                    payload,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Converts a presentation time to RTP clock ticks, wrapping at 32 bits.
+pub fn rtp_timestamp(pts: Duration, clock_rate: u32) -> u32 {
+    let ticks = pts.as_nanos() * clock_rate as u128 / 1_000_000_000;
+    ticks as u32
+}
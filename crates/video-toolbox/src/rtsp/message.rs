@@ -0,0 +1,275 @@
+use crate::rtsp::RtspError;
+use std::io::{BufRead, Read, Write};
+
+const RTSP_VERSION: &str = "RTSP/1.0";
+
+/// Limits message parsing so a misbehaving peer cannot exhaust memory.
+const MAX_LINE_LENGTH: usize = 4096;
+const MAX_HEADERS: usize = 64;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtspRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RtspRequest {
+    pub fn new(method: &str, url: &str, cseq: u32) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: vec![("CSeq".to_string(), cseq.to_string())],
+            body: vec![],
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub fn cseq(&self) -> Option<&str> {
+        self.header("CSeq")
+    }
+
+    /// Reads the next request. Interleaved `$` data frames sent by the client
+    /// (typically RTCP) are skipped. Returns `None` on a clean end of stream.
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Option<Self>, RtspError> {
+        if !skip_interleaved_frames(reader)? {
+            return Ok(None);
+        }
+
+        let Some(request_line) = read_line(reader)? else {
+            return Ok(None);
+        };
+
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(url), Some(RTSP_VERSION)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(RtspError::MalformedMessage(request_line));
+        };
+
+        let (method, url) = (method.to_string(), url.to_string());
+        let headers = read_headers(reader)?;
+        let body = read_body(reader, &headers)?;
+
+        Ok(Some(Self { method, url, headers, body }))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let mut message = format!("{} {} {}\r\n", self.method, self.url, RTSP_VERSION);
+        write_headers(&mut message, &self.headers, self.body.len());
+
+        writer.write_all(message.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtspResponse {
+    pub status_code: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RtspResponse {
+    pub fn new(status_code: u16, reason: &str) -> Self {
+        Self { status_code, reason: reason.to_string(), headers: vec![], body: vec![] }
+    }
+
+    /// A response echoing the CSeq of `request`.
+    pub fn for_request(request: &RtspRequest, status_code: u16, reason: &str) -> Self {
+        let response = Self::new(status_code, reason);
+
+        match request.cseq() {
+            Some(cseq) => response.with_header("CSeq", cseq),
+            None => response,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, content_type: &str, body: Vec<u8>) -> Self {
+        self.headers.push(("Content-Type".to_string(), content_type.to_string()));
+        self.body = body;
+        self
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Reads the next response, skipping interleaved data frames.
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Option<Self>, RtspError> {
+        if !skip_interleaved_frames(reader)? {
+            return Ok(None);
+        }
+
+        let Some(status_line) = read_line(reader)? else {
+            return Ok(None);
+        };
+
+        let mut parts = status_line.splitn(3, ' ');
+        let (Some(RTSP_VERSION), Some(status_code), reason) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(RtspError::MalformedMessage(status_line));
+        };
+
+        let status_code =
+            status_code.parse().map_err(|_| RtspError::MalformedMessage(status_line.clone()))?;
+        let reason = reason.unwrap_or("").to_string();
+        let headers = read_headers(reader)?;
+        let body = read_body(reader, &headers)?;
+
+        Ok(Some(Self { status_code, reason, headers, body }))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let mut message = format!("{} {} {}\r\n", RTSP_VERSION, self.status_code, self.reason);
+        write_headers(&mut message, &self.headers, self.body.len());
+
+        writer.write_all(message.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Writes an RTP or RTCP packet as an interleaved frame (RFC 2326 section 10.12).
+pub fn write_interleaved(
+    writer: &mut impl Write,
+    channel: u8,
+    packet: &[u8],
+) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(4 + packet.len());
+    frame.push(b'$');
+    frame.push(channel);
+    frame.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+    frame.extend_from_slice(packet);
+
+    writer.write_all(&frame)
+}
+
+/// Reads one interleaved frame, returning its channel and payload.
+pub fn read_interleaved(reader: &mut impl Read) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut frame_header = [0u8; 4];
+    reader.read_exact(&mut frame_header)?;
+
+    if frame_header[0] != b'$' {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Expected an interleaved frame",
+        ));
+    }
+
+    let len = u16::from_be_bytes([frame_header[2], frame_header[3]]) as usize;
+    let mut packet = vec![0u8; len];
+    reader.read_exact(&mut packet)?;
+
+    Ok((frame_header[1], packet))
+}
+
+/// Discards interleaved frames until an RTSP message starts. Returns `false` at
+/// the end of the stream.
+fn skip_interleaved_frames(reader: &mut impl BufRead) -> std::io::Result<bool> {
+    loop {
+        match reader.fill_buf()?.first() {
+            None => return Ok(false),
+            Some(b'$') => {
+                read_interleaved(reader)?;
+            },
+            Some(_) => return Ok(true),
+        }
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>, RtspError> {
+    let mut line = String::new();
+
+    if reader.take(MAX_LINE_LENGTH as u64 + 1).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    if line.len() > MAX_LINE_LENGTH {
+        return Err(RtspError::MalformedMessage(format!(
+            "Line longer than {} bytes",
+            MAX_LINE_LENGTH
+        )));
+    }
+
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn read_headers(reader: &mut impl BufRead) -> Result<Vec<(String, String)>, RtspError> {
+    let mut headers = vec![];
+
+    loop {
+        let Some(line) = read_line(reader)? else {
+            return Err(RtspError::MalformedMessage("Unexpected end of headers".to_string()));
+        };
+
+        if line.is_empty() {
+            return Ok(headers);
+        }
+
+        if headers.len() >= MAX_HEADERS {
+            return Err(RtspError::MalformedMessage("Too many headers".to_string()));
+        }
+
+        let (name, value) =
+            line.split_once(':').ok_or_else(|| RtspError::MalformedMessage(line.clone()))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+}
+
+fn read_body(
+    reader: &mut impl BufRead,
+    headers: &[(String, String)],
+) -> Result<Vec<u8>, RtspError> {
+    let content_length = match find_header(headers, "Content-Length") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| RtspError::MalformedMessage(format!("Content-Length: {}", value)))?,
+        None => 0,
+    };
+
+    if content_length > MAX_BODY_SIZE {
+        return Err(RtspError::MalformedMessage(format!("Content-Length: {}", content_length)));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(body)
+}
+
+fn write_headers(message: &mut String, headers: &[(String, String)], body_len: usize) {
+    for (name, value) in headers {
+        message.push_str(&format!("{}: {}\r\n", name, value));
+    }
+
+    if body_len > 0 {
+        message.push_str(&format!("Content-Length: {}\r\n", body_len));
+    }
+
+    message.push_str("\r\n");
+}
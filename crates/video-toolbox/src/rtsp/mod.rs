@@ -0,0 +1,683 @@
+//! A minimal RTSP (RFC 2326) server streaming a single live video track over
+//! RTP, either on UDP or interleaved in the RTSP TCP connection.
+
+use crate::{
+    rtp::{
+        H264PacketizationMode, H264Packetizer, HevcPacketizer, PacketizerConfig, RtpError,
+        RtpPacket,
+    },
+    sdp::{SessionDescription, VideoMediaDescription},
+    H264ParameterSets, HevcParameterSets, ParameterSets, VideoCodec,
+};
+use std::{
+    collections::HashMap,
+    io::BufReader,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+mod message;
+
+pub use message::*;
+
+/// How long DESCRIBE waits for the source to produce parameter sets.
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a session lives without requests, unless changed with
+/// [`RtspServer::set_session_timeout`].
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a send to one client may block before its session is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const TRACK_CONTROL: &str = "trackID=0";
+
+#[derive(Debug, Error)]
+pub enum RtspError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed RTSP message: {0}")]
+    MalformedMessage(String),
+
+    #[error("RTP Error: {0}")]
+    Rtp(#[from] RtpError),
+
+    #[error("Session {session} dropped: {source}")]
+    SessionDropped { session: String, source: std::io::Error },
+
+    #[error("Session {0} timed out")]
+    SessionTimedOut(String),
+
+    #[error("Source error: {0}")]
+    Source(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// An Annex B access unit produced by a [`MediaSource`].
+#[derive(Debug, Clone)]
+pub struct SourceAccessUnit {
+    pub data: Vec<u8>,
+    pub pts: Duration,
+}
+
+/// A live stream of encoded access units, typically backed by an `Encoder`.
+pub trait MediaSource: Send + 'static {
+    fn codec(&self) -> VideoCodec;
+
+    /// Blocks until the next access unit is ready. `None` ends the stream, as
+    /// does an error, which is passed to the server's error handler.
+    fn next_access_unit(&mut self) -> Result<Option<SourceAccessUnit>, RtspError>;
+}
+
+/// Feeds frames from `next_frame` through an [`crate::Encoder`].
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub struct EncoderSource<F> {
+    encoder: crate::Encoder,
+    next_frame: F,
+    frame_duration: Duration,
+    frame_count: u32,
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl<F: FnMut() -> Option<crate::FrameBuf> + Send + 'static> EncoderSource<F> {
+    /// The encoder must use [`crate::PacketFraming::AnnexB`], as RTP packetizers
+    /// split access units at start codes.
+    pub fn new(
+        encoder: crate::Encoder,
+        frame_duration: Duration,
+        next_frame: F,
+    ) -> Result<Self, RtspError> {
+        if encoder.config().framing != crate::PacketFraming::AnnexB {
+            return Err(RtspError::Source("encoder output is not Annex B".into()));
+        }
+
+        Ok(Self { encoder, next_frame, frame_duration, frame_count: 0 })
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl<F: FnMut() -> Option<crate::FrameBuf> + Send + 'static> MediaSource for EncoderSource<F> {
+    fn codec(&self) -> VideoCodec {
+        VideoCodec::Hevc
+    }
+
+    fn next_access_unit(&mut self) -> Result<Option<SourceAccessUnit>, RtspError> {
+        let pts = self.frame_duration * self.frame_count;
+        self.frame_count += 1;
+
+        let Some(frame) = (self.next_frame)() else {
+            return Ok(None);
+        };
+
+        let frame = frame.as_frame().with_pts(pts).with_duration(self.frame_duration);
+        let packet = self.encoder.encode(&frame).map_err(|e| RtspError::Source(e.into()))?;
+
+        Ok(Some(SourceAccessUnit { data: packet.data, pts }))
+    }
+}
+
+/// Serves one [`MediaSource`] to any number of clients. Every client receives
+/// the same RTP stream, so late joiners start decoding at the next keyframe.
+pub struct RtspServer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    codec: VideoCodec,
+    payload_type: u8,
+    state: Mutex<ServerState>,
+    parameter_sets_changed: Condvar,
+    shutdown: AtomicBool,
+    next_session_id: AtomicU64,
+    next_connection_id: AtomicU64,
+    /// Open client connections by id, so `shutdown` can close them.
+    connections: Mutex<HashMap<u64, TcpStream>>,
+    session_timeout: Mutex<Duration>,
+    error_handler: Mutex<Option<ErrorHandler>>,
+}
+
+type ErrorHandler = Box<dyn FnMut(RtspError) + Send>;
+
+impl Shared {
+    fn report(&self, error: RtspError) {
+        if let Some(handler) = self.error_handler.lock().unwrap().as_mut() {
+            handler(error);
+        }
+    }
+
+    /// Removes sessions without requests for longer than the session timeout.
+    fn expire_sessions(&self) {
+        let timeout = *self.session_timeout.lock().unwrap();
+        let mut expired = vec![];
+
+        self.state.lock().unwrap().sessions.retain(|session_id, session| {
+            let alive = session.last_activity.elapsed() <= timeout;
+
+            if !alive {
+                expired.push(session_id.clone());
+            }
+
+            alive
+        });
+
+        for session_id in expired {
+            self.report(RtspError::SessionTimedOut(session_id));
+        }
+    }
+}
+
+#[derive(Default)]
+struct ServerState {
+    parameter_sets: Option<ParameterSets>,
+    sessions: HashMap<String, Session>,
+    next_sequence_number: u16,
+    last_rtp_timestamp: u32,
+    source_finished: bool,
+}
+
+struct Session {
+    transport: Arc<SessionTransport>,
+    playing: bool,
+    last_activity: Instant,
+}
+
+enum SessionTransport {
+    Udp { socket: UdpSocket, destination: SocketAddr },
+    Interleaved { stream: Arc<Mutex<TcpStream>>, channel: u8 },
+}
+
+impl SessionTransport {
+    fn send(&self, packet: &[u8]) -> std::io::Result<()> {
+        match self {
+            SessionTransport::Udp { socket, destination } => {
+                socket.send_to(packet, destination).map(|_| ())
+            },
+            SessionTransport::Interleaved { stream, channel } => {
+                let mut stream = stream.lock().unwrap();
+                write_interleaved(&mut *stream, *channel, packet)
+            },
+        }
+    }
+
+    /// Closes an interleaved connection, whose framing a failed write may
+    /// have left half-written.
+    fn close(&self) {
+        if let SessionTransport::Interleaved { stream, .. } = self {
+            let _ = stream.lock().unwrap().shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+enum Packetizer {
+    H264(H264Packetizer),
+    Hevc(HevcPacketizer),
+}
+
+impl Packetizer {
+    fn new(codec: VideoCodec, config: PacketizerConfig) -> Result<Self, RtpError> {
+        Ok(match codec {
+            VideoCodec::H264 => Packetizer::H264(H264Packetizer::new(
+                config,
+                H264PacketizationMode::NonInterleaved,
+            )?),
+            VideoCodec::Hevc => Packetizer::Hevc(HevcPacketizer::new(config)?),
+        })
+    }
+
+    fn packetize(&mut self, access_unit: &SourceAccessUnit) -> Result<Vec<RtpPacket>, RtpError> {
+        match self {
+            Packetizer::H264(packetizer) => {
+                packetizer.packetize(&access_unit.data, access_unit.pts)
+            },
+            Packetizer::Hevc(packetizer) => {
+                Ok(packetizer.packetize(&access_unit.data, access_unit.pts))
+            },
+        }
+    }
+
+    fn next_sequence_number(&self) -> u16 {
+        match self {
+            Packetizer::H264(packetizer) => packetizer.next_sequence_number(),
+            Packetizer::Hevc(packetizer) => packetizer.next_sequence_number(),
+        }
+    }
+}
+
+impl RtspServer {
+    /// Binds the RTSP listener and starts pulling from `source`.
+    pub fn start(
+        addr: impl ToSocketAddrs,
+        source: impl MediaSource,
+        packetizer_config: PacketizerConfig,
+    ) -> Result<Self, RtspError> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+
+        let codec = source.codec();
+        let packetizer = Packetizer::new(codec, packetizer_config.clone())?;
+
+        let shared = Arc::new(Shared {
+            codec,
+            payload_type: packetizer_config.payload_type,
+            state: Mutex::new(ServerState {
+                next_sequence_number: packetizer_config.initial_sequence_number,
+                ..ServerState::default()
+            }),
+            parameter_sets_changed: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+            next_session_id: AtomicU64::new(1),
+            next_connection_id: AtomicU64::new(0),
+            connections: Mutex::new(HashMap::new()),
+            session_timeout: Mutex::new(DEFAULT_SESSION_TIMEOUT),
+            error_handler: Mutex::new(None),
+        });
+
+        let source_shared = shared.clone();
+        std::thread::spawn(move || run_source(source_shared, source, packetizer));
+
+        let accept_shared = shared.clone();
+        let accept_thread = std::thread::spawn(move || run_listener(accept_shared, listener));
+
+        Ok(Self { local_addr, shared, accept_thread: Some(accept_thread) })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The URL clients should open.
+    pub fn url(&self) -> String {
+        format!("rtsp://{}/", self.local_addr)
+    }
+
+    /// Called with connection, packetization and session errors, which are
+    /// otherwise dropped.
+    pub fn set_error_handler(&self, handler: impl FnMut(RtspError) + Send + 'static) {
+        *self.shared.error_handler.lock().unwrap() = Some(Box::new(handler));
+    }
+
+    /// Client connections currently open.
+    pub fn connection_count(&self) -> usize {
+        self.shared.connections.lock().unwrap().len()
+    }
+
+    pub fn session_timeout(&self) -> Duration {
+        *self.shared.session_timeout.lock().unwrap()
+    }
+
+    /// How long sessions live without requests, 60 seconds by default. Clients
+    /// are told the timeout in whole seconds when they set up a session.
+    pub fn set_session_timeout(&self, timeout: Duration) {
+        *self.shared.session_timeout.lock().unwrap() = timeout;
+    }
+
+    /// Stops accepting connections and closes every client connection.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+
+        // Wake the blocking accept() call.
+        let _ = TcpStream::connect(self.local_addr);
+
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+
+        for (_, connection) in self.shared.connections.lock().unwrap().drain() {
+            let _ = connection.shutdown(std::net::Shutdown::Both);
+        }
+
+        self.shared.state.lock().unwrap().sessions.clear();
+    }
+}
+
+impl Drop for RtspServer {
+    fn drop(&mut self) {
+        if self.accept_thread.is_some() {
+            self.stop();
+        }
+    }
+}
+
+fn run_listener(shared: Arc<Shared>, listener: TcpListener) {
+    for stream in listener.incoming() {
+        if shared.shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        let connection_id = shared.next_connection_id.fetch_add(1, Ordering::SeqCst);
+
+        if let Ok(clone) = stream.try_clone() {
+            shared.connections.lock().unwrap().insert(connection_id, clone);
+        }
+
+        let connection_shared = shared.clone();
+        std::thread::spawn(move || {
+            let mut connection = Connection { shared: connection_shared, sessions: vec![] };
+
+            if let Err(e) = connection.run(stream) {
+                connection.shared.report(e);
+            }
+
+            connection.close_sessions();
+            connection.shared.connections.lock().unwrap().remove(&connection_id);
+        });
+    }
+}
+
+fn run_source(shared: Arc<Shared>, mut source: impl MediaSource, mut packetizer: Packetizer) {
+    while !shared.shutdown.load(Ordering::SeqCst) {
+        let access_unit = match source.next_access_unit() {
+            Ok(Some(access_unit)) => access_unit,
+            Ok(None) => break,
+            Err(e) => {
+                shared.report(e);
+                break;
+            },
+        };
+
+        let parameter_sets: Option<ParameterSets> = match shared.codec {
+            VideoCodec::H264 => H264ParameterSets::from_annex_b(&access_unit.data).map(Into::into),
+            VideoCodec::Hevc => HevcParameterSets::from_annex_b(&access_unit.data).map(Into::into),
+        };
+
+        let packets = match packetizer.packetize(&access_unit) {
+            Ok(packets) => packets,
+            Err(e) => {
+                shared.report(e.into());
+                continue;
+            },
+        };
+
+        shared.expire_sessions();
+
+        let playing: Vec<(String, Arc<SessionTransport>)> = {
+            let mut state = shared.state.lock().unwrap();
+
+            if let Some(parameter_sets) = parameter_sets {
+                state.parameter_sets = Some(parameter_sets);
+                shared.parameter_sets_changed.notify_all();
+            }
+
+            state.next_sequence_number = packetizer.next_sequence_number();
+            if let Some(packet) = packets.first() {
+                state.last_rtp_timestamp = packet.timestamp;
+            }
+
+            state
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.playing)
+                .map(|(session_id, session)| (session_id.clone(), session.transport.clone()))
+                .collect()
+        };
+
+        let packets: Vec<Vec<u8>> = packets.iter().map(RtpPacket::to_bytes).collect();
+
+        // Sent without holding the state lock, so a slow client cannot stall
+        // requests on other connections. Sessions whose transport failed, e.g.
+        // a closed or stalled TCP connection, are dropped.
+        for (session_id, transport) in playing {
+            if let Err(e) = packets.iter().try_for_each(|packet| transport.send(packet)) {
+                transport.close();
+                shared.state.lock().unwrap().sessions.remove(&session_id);
+                shared.report(RtspError::SessionDropped { session: session_id, source: e });
+            }
+        }
+    }
+
+    shared.state.lock().unwrap().source_finished = true;
+    shared.parameter_sets_changed.notify_all();
+}
+
+/// A client connection, which may own several sessions.
+struct Connection {
+    shared: Arc<Shared>,
+    sessions: Vec<String>,
+}
+
+impl Connection {
+    fn run(&mut self, stream: TcpStream) -> Result<(), RtspError> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        let mut reader = BufReader::new(stream);
+
+        loop {
+            let request = match RtspRequest::read_from(&mut reader) {
+                Ok(Some(request)) => request,
+                Ok(None) => break,
+                // The rest of the stream cannot be parsed, so answer and close.
+                Err(e @ RtspError::MalformedMessage(_)) => {
+                    let response = RtspResponse::new(400, "Bad Request");
+                    let _ = response.write_to(&mut *writer.lock().unwrap());
+                    return Err(e);
+                },
+                Err(e) => return Err(e),
+            };
+
+            self.refresh_sessions();
+            let response = self.handle(&request, &writer);
+            response.write_to(&mut *writer.lock().unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    /// Any request keeps the connection's sessions alive.
+    fn refresh_sessions(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        for session_id in &self.sessions {
+            if let Some(session) = state.sessions.get_mut(session_id) {
+                session.last_activity = Instant::now();
+            }
+        }
+    }
+
+    fn close_sessions(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        for session_id in self.sessions.drain(..) {
+            state.sessions.remove(&session_id);
+        }
+    }
+
+    fn handle(&mut self, request: &RtspRequest, writer: &Arc<Mutex<TcpStream>>) -> RtspResponse {
+        if request.cseq().is_none() {
+            return RtspResponse::new(400, "Bad Request");
+        }
+
+        match request.method.as_str() {
+            "OPTIONS" => RtspResponse::for_request(request, 200, "OK")
+                .with_header("Public", "OPTIONS, DESCRIBE, SETUP, PLAY, TEARDOWN, GET_PARAMETER"),
+            "DESCRIBE" => self.describe(request),
+            "SETUP" => self.setup(request, writer),
+            "PLAY" => self.play(request),
+            "TEARDOWN" => self.teardown(request),
+            // Commonly used as a keep-alive.
+            "GET_PARAMETER" => RtspResponse::for_request(request, 200, "OK"),
+            _ => RtspResponse::for_request(request, 501, "Not Implemented"),
+        }
+    }
+
+    fn describe(&self, request: &RtspRequest) -> RtspResponse {
+        let state = self.shared.state.lock().unwrap();
+        let (state, _) = self
+            .shared
+            .parameter_sets_changed
+            .wait_timeout_while(state, DESCRIBE_TIMEOUT, |state| {
+                state.parameter_sets.is_none() && !state.source_finished
+            })
+            .unwrap();
+
+        let Some(parameter_sets) = state.parameter_sets.as_ref() else {
+            return RtspResponse::for_request(request, 503, "Service Unavailable");
+        };
+
+        let mut media =
+            VideoMediaDescription::from_parameter_sets(self.shared.payload_type, parameter_sets);
+        media.control = Some(TRACK_CONTROL.to_string());
+
+        let sdp = SessionDescription::new("video-toolbox", vec![media]).to_string();
+
+        RtspResponse::for_request(request, 200, "OK")
+            .with_header("Content-Base", &content_base(&request.url))
+            .with_body("application/sdp", sdp.into_bytes())
+    }
+
+    fn setup(&mut self, request: &RtspRequest, writer: &Arc<Mutex<TcpStream>>) -> RtspResponse {
+        let Some(transport) = request.header("Transport") else {
+            return RtspResponse::for_request(request, 461, "Unsupported Transport");
+        };
+
+        let Some((transport, response_transport)) = self.create_transport(transport, writer) else {
+            return RtspResponse::for_request(request, 461, "Unsupported Transport");
+        };
+
+        let session_id =
+            format!("{:016X}", self.shared.next_session_id.fetch_add(1, Ordering::SeqCst));
+        let session = Session {
+            transport: Arc::new(transport),
+            playing: false,
+            last_activity: Instant::now(),
+        };
+
+        self.shared.state.lock().unwrap().sessions.insert(session_id.clone(), session);
+        self.sessions.push(session_id.clone());
+
+        let timeout = self.shared.session_timeout.lock().unwrap().as_secs().max(1);
+
+        RtspResponse::for_request(request, 200, "OK")
+            .with_header("Transport", &response_transport)
+            .with_header("Session", &format!("{};timeout={}", session_id, timeout))
+    }
+
+    fn create_transport(
+        &self,
+        transport: &str,
+        writer: &Arc<Mutex<TcpStream>>,
+    ) -> Option<(SessionTransport, String)> {
+        let mut parameters = transport.split(';').map(str::trim);
+        let protocol = parameters.next()?;
+        let parameters: Vec<&str> = parameters.collect();
+
+        let range = |name: &str| {
+            let value = parameters.iter().find_map(|p| p.strip_prefix(name)?.strip_prefix('='))?;
+            let mut ports = value.split('-').map(|port| port.parse::<u16>());
+            let first = ports.next()?.ok()?;
+            let second = match ports.next() {
+                Some(second) => second.ok()?,
+                None => first.checked_add(1)?,
+            };
+
+            Some((first, second))
+        };
+
+        match protocol {
+            "RTP/AVP" | "RTP/AVP/UDP" => {
+                if parameters.contains(&"multicast") {
+                    return None;
+                }
+
+                let (rtp_port, rtcp_port) = range("client_port")?;
+                let stream = writer.lock().unwrap();
+                let destination = SocketAddr::new(stream.peer_addr().ok()?.ip(), rtp_port);
+                let socket =
+                    UdpSocket::bind(SocketAddr::new(stream.local_addr().ok()?.ip(), 0)).ok()?;
+                socket.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+                let server_port = socket.local_addr().ok()?.port();
+
+                let response = format!(
+                    "RTP/AVP;unicast;client_port={}-{};server_port={}-{}",
+                    rtp_port,
+                    rtcp_port,
+                    server_port,
+                    server_port.wrapping_add(1)
+                );
+
+                Some((SessionTransport::Udp { socket, destination }, response))
+            },
+            "RTP/AVP/TCP" => {
+                let (rtp_channel, rtcp_channel) = range("interleaved").unwrap_or((0, 1));
+                let channel = u8::try_from(rtp_channel).ok()?;
+
+                let response =
+                    format!("RTP/AVP/TCP;unicast;interleaved={}-{}", rtp_channel, rtcp_channel);
+
+                Some((SessionTransport::Interleaved { stream: writer.clone(), channel }, response))
+            },
+            _ => None,
+        }
+    }
+
+    fn play(&mut self, request: &RtspRequest) -> RtspResponse {
+        let mut state = self.shared.state.lock().unwrap();
+        let (sequence_number, rtp_timestamp) =
+            (state.next_sequence_number, state.last_rtp_timestamp);
+
+        let Some(session) = self.session_id(request).and_then(|id| state.sessions.get_mut(id))
+        else {
+            return RtspResponse::for_request(request, 454, "Session Not Found");
+        };
+
+        session.playing = true;
+
+        let rtp_info = format!(
+            "url={}{};seq={};rtptime={}",
+            content_base(&request.url),
+            TRACK_CONTROL,
+            sequence_number,
+            rtp_timestamp
+        );
+
+        RtspResponse::for_request(request, 200, "OK")
+            .with_header("Range", "npt=0.000-")
+            .with_header("RTP-Info", &rtp_info)
+    }
+
+    fn teardown(&mut self, request: &RtspRequest) -> RtspResponse {
+        let Some(session_id) = self.session_id(request).map(str::to_string) else {
+            return RtspResponse::for_request(request, 454, "Session Not Found");
+        };
+
+        if self.shared.state.lock().unwrap().sessions.remove(&session_id).is_none() {
+            return RtspResponse::for_request(request, 454, "Session Not Found");
+        }
+
+        self.sessions.retain(|id| *id != session_id);
+        RtspResponse::for_request(request, 200, "OK")
+    }
+
+    /// The session ID from the `Session` header, if it belongs to this connection.
+    fn session_id<'a>(&self, request: &'a RtspRequest) -> Option<&'a str> {
+        let session_id = request.header("Session")?.split(';').next()?.trim();
+        self.sessions.iter().any(|id| id == session_id).then_some(session_id)
+    }
+}
+
+/// The aggregate URL that track control URLs are relative to.
+fn content_base(url: &str) -> String {
+    let url = url.strip_suffix(TRACK_CONTROL).unwrap_or(url);
+
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    }
+}
@@ -0,0 +1,359 @@
+//! Resizing frames on the CPU, for example to encode one source at several
+//! resolutions.
+//!
+//! Each plane is scaled on its own with a separable filter, so chroma planes
+//! are resized at their subsampled resolution. Filter weights are rounded to
+//! fixed point and all pixel arithmetic is integer, which keeps results
+//! identical across platforms for golden image tests.
+
+use crate::{FrameBuf, FrameError, PixelFormat, Rect, VideoFrame};
+use std::f64::consts::PI;
+use thiserror::Error;
+
+/// Fractional bits of the fixed point filter weights.
+const WEIGHT_BITS: u32 = 14;
+
+/// Fractional bits kept between the horizontal and vertical passes.
+const INTERMEDIATE_BITS: u32 = 7;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ScaleError {
+    #[error("Scaling does not support {0:?}")]
+    UnsupportedFormat(PixelFormat),
+
+    #[error("Frame is {actual:?}, the scaler expects {expected:?}")]
+    FrameMismatch { expected: (PixelFormat, u32, u32), actual: (PixelFormat, u32, u32) },
+
+    #[error("Invalid frame: {0}")]
+    Frame(#[from] FrameError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ScaleFilter {
+    Bilinear,
+    /// Catmull-Rom.
+    #[default]
+    Bicubic,
+    /// Lanczos with three lobes.
+    Lanczos3,
+    /// Averages the source pixels each output pixel covers. Best for
+    /// downscaling by large factors, and nearest neighbour when upscaling.
+    Area,
+}
+
+impl ScaleFilter {
+    /// The filter's radius in source pixels at a scale of 1.
+    fn support(self) -> f64 {
+        match self {
+            ScaleFilter::Bilinear => 1.0,
+            ScaleFilter::Bicubic => 2.0,
+            ScaleFilter::Lanczos3 => 3.0,
+            ScaleFilter::Area => 0.5,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+
+        match self {
+            ScaleFilter::Bilinear => (1.0 - x).max(0.0),
+            ScaleFilter::Bicubic => match x {
+                x if x < 1.0 => 1.5 * x * x * x - 2.5 * x * x + 1.0,
+                x if x < 2.0 => -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0,
+                _ => 0.0,
+            },
+            ScaleFilter::Lanczos3 => match x {
+                x if x < 1e-9 => 1.0,
+                x if x < 3.0 => {
+                    let pi_x = PI * x;
+                    3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+                },
+                _ => 0.0,
+            },
+            ScaleFilter::Area => unreachable!("area weights are overlaps"),
+        }
+    }
+}
+
+/// The source samples one output sample is made of: `weights[i]` applies to
+/// source sample `start + i`.
+#[derive(Debug, Clone)]
+struct Contribution {
+    start: usize,
+    weights: Vec<i64>,
+}
+
+fn contributions(src_len: usize, dst_len: usize, filter: ScaleFilter) -> Vec<Contribution> {
+    let scale = src_len as f64 / dst_len as f64;
+    // Widen the filter when downscaling so it covers every source sample.
+    let stretch = scale.max(1.0);
+    let support = filter.support() * stretch;
+    let last = src_len as i64 - 1;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f64 + 0.5) * scale;
+            let low = (center - support).floor() as i64;
+            let high = (center + support).ceil() as i64;
+
+            let start = low.clamp(0, last);
+            let end = high.clamp(0, last);
+            let mut weights = vec![0.0; (end - start + 1) as usize];
+
+            // Taps past the edges are folded into the edge samples.
+            for j in low..=high {
+                let weight = match filter {
+                    ScaleFilter::Area => {
+                        let (from, to) = (i as f64 * scale, (i + 1) as f64 * scale);
+                        (to.min(j as f64 + 1.0) - from.max(j as f64)).max(0.0)
+                    },
+                    _ => filter.weight((j as f64 + 0.5 - center) / stretch),
+                };
+
+                weights[(j.clamp(start, end) - start) as usize] += weight;
+            }
+
+            Contribution { start: start as usize, weights: quantize(&weights) }
+        })
+        .collect()
+}
+
+/// Rounds weights to fixed point, putting any rounding error on the largest
+/// so they sum to exactly one.
+fn quantize(weights: &[f64]) -> Vec<i64> {
+    let sum: f64 = weights.iter().sum();
+    let one = 1i64 << WEIGHT_BITS;
+    let mut fixed: Vec<i64> =
+        weights.iter().map(|weight| (weight / sum * one as f64).round() as i64).collect();
+
+    let error = one - fixed.iter().sum::<i64>();
+    let largest = (0..fixed.len()).max_by_key(|&i| fixed[i]).unwrap_or_default();
+    fixed[largest] += error;
+    fixed
+}
+
+/// How samples are stored in one plane.
+#[derive(Debug, Clone, Copy)]
+struct SampleLayout {
+    /// Interleaved components per pixel, e.g. 2 for CbCr.
+    channels: usize,
+    /// Bytes per sample, 1 or 2.
+    bytes: usize,
+    /// Padding bits below the value in 16-bit samples.
+    shift: u32,
+}
+
+impl SampleLayout {
+    fn max(self) -> i64 {
+        (1 << (self.bytes as u32 * 8 - self.shift)) - 1
+    }
+
+    fn read(self, row: &[u8], index: usize) -> i64 {
+        match self.bytes {
+            1 => row[index] as i64,
+            _ => (u16::from_le_bytes([row[index * 2], row[index * 2 + 1]]) >> self.shift) as i64,
+        }
+    }
+
+    fn write(self, row: &mut [u8], index: usize, value: i64) {
+        match self.bytes {
+            1 => row[index] = value as u8,
+            _ => row[index * 2..index * 2 + 2]
+                .copy_from_slice(&((value as u16) << self.shift).to_le_bytes()),
+        }
+    }
+}
+
+fn sample_layouts(format: PixelFormat) -> Result<Vec<SampleLayout>, ScaleError> {
+    let layout = |channels, bytes, shift| SampleLayout { channels, bytes, shift };
+
+    Ok(match format {
+        PixelFormat::Argb32 | PixelFormat::Bgra32 | PixelFormat::Rgba32 => vec![layout(4, 1, 0)],
+        PixelFormat::Rgba64 => vec![layout(4, 2, 0)],
+        PixelFormat::Nv12
+        | PixelFormat::Nv12FullRange
+        | PixelFormat::Nv24
+        | PixelFormat::Nv24FullRange => vec![layout(1, 1, 0), layout(2, 1, 0)],
+        PixelFormat::Nv12Alpha => vec![layout(1, 1, 0), layout(2, 1, 0), layout(1, 1, 0)],
+        PixelFormat::I420 | PixelFormat::I420FullRange => vec![layout(1, 1, 0); 3],
+        PixelFormat::P010
+        | PixelFormat::P010FullRange
+        | PixelFormat::P210
+        | PixelFormat::P210FullRange
+        | PixelFormat::P410
+        | PixelFormat::P410FullRange => vec![layout(1, 2, 6), layout(2, 2, 6)],
+        PixelFormat::Gray8 => vec![layout(1, 1, 0)],
+        PixelFormat::Gray16 => vec![layout(1, 2, 0)],
+        format => return Err(ScaleError::UnsupportedFormat(format)),
+    })
+}
+
+/// One plane's filter weights.
+struct PlaneScaler {
+    layout: SampleLayout,
+    src_size: (usize, usize),
+    dst_size: (usize, usize),
+    horizontal: Vec<Contribution>,
+    vertical: Vec<Contribution>,
+}
+
+impl PlaneScaler {
+    fn scale(&self, src: &[u8], src_stride: usize, dst: &mut [u8], dst_stride: usize) {
+        let channels = self.layout.channels;
+        let (_, src_height) = self.src_size;
+        let row_len = self.dst_size.0 * channels;
+
+        // Horizontal pass into fixed point rows.
+        let mut intermediate = vec![0i64; row_len * src_height];
+        let round = 1 << (WEIGHT_BITS - INTERMEDIATE_BITS - 1);
+
+        for (y, out) in intermediate.chunks_exact_mut(row_len).enumerate() {
+            let row = &src[y * src_stride..];
+
+            for (x, contribution) in self.horizontal.iter().enumerate() {
+                for channel in 0..channels {
+                    let mut sum = 0;
+
+                    for (i, weight) in contribution.weights.iter().enumerate() {
+                        let index = (contribution.start + i) * channels + channel;
+                        sum += weight * self.layout.read(row, index);
+                    }
+
+                    out[x * channels + channel] =
+                        (sum + round) >> (WEIGHT_BITS - INTERMEDIATE_BITS);
+                }
+            }
+        }
+
+        // Vertical pass, rounding back to samples.
+        let shift = WEIGHT_BITS + INTERMEDIATE_BITS;
+        let round = 1 << (shift - 1);
+        let max = self.layout.max();
+        let mut sums = vec![0i64; row_len];
+
+        for (y, contribution) in self.vertical.iter().enumerate() {
+            sums.fill(0);
+
+            for (i, weight) in contribution.weights.iter().enumerate() {
+                let row = &intermediate[(contribution.start + i) * row_len..][..row_len];
+
+                for (sum, value) in sums.iter_mut().zip(row) {
+                    *sum += weight * value;
+                }
+            }
+
+            let out = &mut dst[y * dst_stride..];
+            for (index, sum) in sums.iter().enumerate() {
+                self.layout.write(out, index, ((sum + round) >> shift).clamp(0, max));
+            }
+        }
+    }
+}
+
+/// Scales frames of one format and size to another size. The filter weights
+/// are computed once, so reuse a scaler for every frame of a stream.
+pub struct Scaler {
+    format: PixelFormat,
+    src_size: (u32, u32),
+    dst_size: (u32, u32),
+    planes: Vec<PlaneScaler>,
+}
+
+impl Scaler {
+    pub fn new(
+        format: PixelFormat,
+        src_size: (u32, u32),
+        dst_size: (u32, u32),
+        filter: ScaleFilter,
+    ) -> Result<Self, ScaleError> {
+        for (width, height) in [src_size, dst_size] {
+            if width == 0 || height == 0 {
+                return Err(FrameError::InvalidDimensions { width, height }.into());
+            }
+        }
+
+        let planes = sample_layouts(format)?
+            .into_iter()
+            .enumerate()
+            .map(|(plane, layout)| {
+                let plane_size = |(width, height)| {
+                    (format.plane_width(plane, width), format.plane_height(plane, height))
+                };
+                let (src_size, dst_size) = (plane_size(src_size), plane_size(dst_size));
+
+                PlaneScaler {
+                    layout,
+                    src_size,
+                    dst_size,
+                    horizontal: contributions(src_size.0, dst_size.0, filter),
+                    vertical: contributions(src_size.1, dst_size.1, filter),
+                }
+            })
+            .collect();
+
+        Ok(Self { format, src_size, dst_size, planes })
+    }
+
+    pub fn dst_size(&self) -> (u32, u32) {
+        self.dst_size
+    }
+
+    pub fn scale(&self, src: &VideoFrame) -> Result<FrameBuf, ScaleError> {
+        let mut dst = FrameBuf::new(self.format, self.dst_size.0, self.dst_size.1)?;
+        self.scale_into(src, &mut dst)?;
+        Ok(dst)
+    }
+
+    /// Scales `src` into `dst`, which must have the scaler's format and output
+    /// size. The crop rectangle is scaled too, and the pts copied.
+    pub fn scale_into(&self, src: &VideoFrame, dst: &mut FrameBuf) -> Result<(), ScaleError> {
+        let frames = [
+            ((src.format(), src.width(), src.height()), self.src_size),
+            ((dst.format(), dst.width(), dst.height()), self.dst_size),
+        ];
+
+        for (actual, (width, height)) in frames {
+            let expected = (self.format, width, height);
+
+            if actual != expected {
+                return Err(ScaleError::FrameMismatch { expected, actual });
+            }
+        }
+
+        for ((scaler, src), dst) in self.planes.iter().zip(src.planes()).zip(dst.planes_mut()) {
+            let stride = dst.stride();
+            scaler.scale(src.data, src.stride, dst.data_mut(), stride);
+        }
+
+        dst.set_crop(self.scale_crop(src.crop()))?;
+        dst.set_pts(src.pts());
+        dst.set_duration(src.duration());
+        dst.set_color(src.color());
+        dst.set_hdr_metadata(src.hdr_metadata());
+        Ok(())
+    }
+
+    /// Scales a crop rectangle outwards to whole output pixels.
+    fn scale_crop(&self, crop: Rect) -> Rect {
+        let axis = |start: u32, len: u32, src: u32, dst: u32| {
+            let (src, dst) = (src as u64, dst as u64);
+            let from = start as u64 * dst / src;
+            let to = ((start + len) as u64 * dst).div_ceil(src).min(dst);
+            (from as u32, (to - from).max(1) as u32)
+        };
+
+        let (x, width) = axis(crop.x, crop.width, self.src_size.0, self.dst_size.0);
+        let (y, height) = axis(crop.y, crop.height, self.src_size.1, self.dst_size.1);
+        Rect::new(x, y, width, height)
+    }
+}
+
+/// Scales one frame to `width` x `height`. Use a [`Scaler`] for streams.
+pub fn scale(
+    src: &VideoFrame,
+    width: u32,
+    height: u32,
+    filter: ScaleFilter,
+) -> Result<FrameBuf, ScaleError> {
+    Scaler::new(src.format(), (src.width(), src.height()), (width, height), filter)?.scale(src)
+}
@@ -0,0 +1,456 @@
+//! SDP (RFC 4566) session descriptions for HEVC (RFC 7798) and H.264 (RFC 6184)
+//! RTP streams.
+
+use crate::{
+    base64,
+    rtp::{H264PacketizationMode, VIDEO_CLOCK_RATE},
+    H264ParameterSets, HevcParameterSets, HevcProfileTierLevel, ParameterSets, VideoCodec,
+};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SdpError {
+    #[error("Missing or unsupported SDP version")]
+    UnsupportedVersion,
+
+    #[error("Malformed SDP line: {0}")]
+    MalformedLine(String),
+
+    #[error("No supported video media section")]
+    MissingVideoMedia,
+}
+
+/// A session description containing one or more video media sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionDescription {
+    pub session_id: u64,
+    pub session_version: u64,
+    pub origin_address: String,
+    pub session_name: String,
+    /// Session level `c=` address, if any.
+    pub connection_address: Option<String>,
+    pub media: Vec<VideoMediaDescription>,
+}
+
+impl SessionDescription {
+    pub fn new(session_name: &str, media: Vec<VideoMediaDescription>) -> Self {
+        Self {
+            session_id: 0,
+            session_version: 0,
+            origin_address: "127.0.0.1".to_string(),
+            session_name: session_name.to_string(),
+            connection_address: Some("0.0.0.0".to_string()),
+            media,
+        }
+    }
+
+    /// Parses a session description, keeping only the HEVC and H.264 video
+    /// sections. Unknown lines are ignored.
+    pub fn parse(text: &str) -> Result<Self, SdpError> {
+        let mut lines =
+            text.lines().map(|line| line.trim_end_matches('\r')).filter(|l| !l.is_empty());
+
+        if lines.next() != Some("v=0") {
+            return Err(SdpError::UnsupportedVersion);
+        }
+
+        let mut description = Self {
+            session_id: 0,
+            session_version: 0,
+            origin_address: String::new(),
+            session_name: String::new(),
+            connection_address: None,
+            media: vec![],
+        };
+
+        let mut section: Option<MediaSection> = None;
+
+        for line in lines {
+            let (kind, value) =
+                line.split_once('=').ok_or_else(|| SdpError::MalformedLine(line.to_string()))?;
+
+            match (kind, section.as_mut()) {
+                ("o", None) => {
+                    let fields: Vec<&str> = value.split_whitespace().collect();
+                    if fields.len() != 6 {
+                        return Err(SdpError::MalformedLine(line.to_string()));
+                    }
+
+                    description.session_id = fields[1].parse().unwrap_or(0);
+                    description.session_version = fields[2].parse().unwrap_or(0);
+                    description.origin_address = fields[5].to_string();
+                },
+                ("s", None) => description.session_name = value.to_string(),
+                ("c", None) => description.connection_address = parse_connection(line, value)?,
+                ("c", Some(_)) if description.connection_address.is_none() => {
+                    description.connection_address = parse_connection(line, value)?;
+                },
+                ("m", _) => {
+                    if let Some(media) = section.take().and_then(MediaSection::finish) {
+                        description.media.push(media);
+                    }
+
+                    section = MediaSection::parse(line, value)?;
+                },
+                ("a", Some(section)) => section.parse_attribute(line, value)?,
+                _ => {},
+            }
+        }
+
+        if let Some(media) = section.take().and_then(MediaSection::finish) {
+            description.media.push(media);
+        }
+
+        if description.media.is_empty() {
+            return Err(SdpError::MissingVideoMedia);
+        }
+
+        Ok(description)
+    }
+}
+
+impl fmt::Display for SessionDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v=0\r\n")?;
+        write!(
+            f,
+            "o=- {} {} IN IP4 {}\r\n",
+            self.session_id, self.session_version, self.origin_address
+        )?;
+        write!(f, "s={}\r\n", self.session_name)?;
+
+        if let Some(address) = &self.connection_address {
+            write!(f, "c=IN IP4 {}\r\n", address)?;
+        }
+
+        write!(f, "t=0 0\r\n")?;
+
+        for media in &self.media {
+            write!(f, "{}", media)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An `m=video` section carrying a single HEVC or H.264 payload type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoMediaDescription {
+    pub port: u16,
+    pub protocol: String,
+    pub payload_type: u8,
+    pub codec: VideoCodec,
+    pub clock_rate: u32,
+    /// `a=fmtp` parameters in their original order.
+    pub fmtp: Vec<(String, String)>,
+    /// `a=control` URL, used by RTSP.
+    pub control: Option<String>,
+}
+
+impl VideoMediaDescription {
+    fn new(codec: VideoCodec, payload_type: u8) -> Self {
+        Self {
+            port: 0,
+            protocol: "RTP/AVP".to_string(),
+            payload_type,
+            codec,
+            clock_rate: VIDEO_CLOCK_RATE,
+            fmtp: vec![],
+            control: None,
+        }
+    }
+
+    /// Describes an H.264 stream using the given parameter sets.
+    pub fn h264(
+        payload_type: u8,
+        mode: H264PacketizationMode,
+        parameter_sets: &H264ParameterSets,
+    ) -> Self {
+        let mut media = Self::new(VideoCodec::H264, payload_type);
+        media.set_fmtp("packetization-mode", (mode as u8).to_string());
+
+        if let Some([profile_idc, constraints, level_idc]) = parameter_sets.profile_level_id() {
+            let profile_level_id =
+                format!("{:02x}{:02x}{:02x}", profile_idc, constraints, level_idc);
+            media.set_fmtp("profile-level-id", profile_level_id);
+        }
+
+        let sprop = format!(
+            "{},{}",
+            base64::encode(&parameter_sets.sps),
+            base64::encode(&parameter_sets.pps)
+        );
+        media.set_fmtp("sprop-parameter-sets", sprop);
+
+        media
+    }
+
+    /// Describes an HEVC stream using the given parameter sets.
+    pub fn hevc(payload_type: u8, parameter_sets: &HevcParameterSets) -> Self {
+        let mut media = Self::new(VideoCodec::Hevc, payload_type);
+
+        if let Some(profile) = parameter_sets.profile_tier_level() {
+            media.set_fmtp("profile-space", profile.profile_space.to_string());
+            media.set_fmtp("profile-id", profile.profile_idc.to_string());
+            media.set_fmtp("tier-flag", (profile.tier_flag as u8).to_string());
+            media.set_fmtp("level-id", profile.level_idc.to_string());
+            media.set_fmtp(
+                "interop-constraints",
+                format!("{:012X}", profile.constraint_indicator_flags),
+            );
+        }
+
+        media.set_fmtp("sprop-vps", base64::encode(&parameter_sets.vps));
+        media.set_fmtp("sprop-sps", base64::encode(&parameter_sets.sps));
+        media.set_fmtp("sprop-pps", base64::encode(&parameter_sets.pps));
+
+        media
+    }
+
+    /// Describes a stream with the default packetization for its codec.
+    pub fn from_parameter_sets(payload_type: u8, parameter_sets: &ParameterSets) -> Self {
+        match parameter_sets {
+            ParameterSets::H264(parameter_sets) => {
+                Self::h264(payload_type, H264PacketizationMode::NonInterleaved, parameter_sets)
+            },
+            ParameterSets::Hevc(parameter_sets) => Self::hevc(payload_type, parameter_sets),
+        }
+    }
+
+    pub fn fmtp_parameter(&self, name: &str) -> Option<&str> {
+        self.fmtp.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_fmtp(&mut self, name: &str, value: String) {
+        match self.fmtp.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.fmtp.push((name.to_string(), value)),
+        }
+    }
+
+    /// The `a=fmtp` line, without a trailing line break.
+    pub fn fmtp_line(&self) -> String {
+        let parameters: Vec<String> =
+            self.fmtp.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+
+        format!("a=fmtp:{} {}", self.payload_type, parameters.join(";"))
+    }
+
+    /// Decodes the out of band parameter sets (`sprop-*`), if all are present.
+    pub fn parameter_sets(&self) -> Option<ParameterSets> {
+        match self.codec {
+            VideoCodec::H264 => {
+                let mut sps = None;
+                let mut pps = None;
+
+                for encoded in self.fmtp_parameter("sprop-parameter-sets")?.split(',') {
+                    let nal = base64::decode(encoded)?;
+
+                    match nal.first().map(|header| header & 0b1_1111) {
+                        Some(7) if sps.is_none() => sps = Some(nal),
+                        Some(8) if pps.is_none() => pps = Some(nal),
+                        _ => {},
+                    }
+                }
+
+                Some(ParameterSets::H264(H264ParameterSets { sps: sps?, pps: pps? }))
+            },
+            VideoCodec::Hevc => {
+                let decode = |name| base64::decode(self.fmtp_parameter(name)?.split(',').next()?);
+
+                Some(ParameterSets::Hevc(HevcParameterSets {
+                    vps: decode("sprop-vps")?,
+                    sps: decode("sprop-sps")?,
+                    pps: decode("sprop-pps")?,
+                }))
+            },
+        }
+    }
+
+    /// `packetization-mode` for H.264, defaulting to single NAL unit mode.
+    pub fn packetization_mode(&self) -> Option<H264PacketizationMode> {
+        if self.codec != VideoCodec::H264 {
+            return None;
+        }
+
+        match self.fmtp_parameter("packetization-mode").unwrap_or("0") {
+            "0" => Some(H264PacketizationMode::SingleNalUnit),
+            "1" => Some(H264PacketizationMode::NonInterleaved),
+            _ => None,
+        }
+    }
+
+    /// The HEVC profile, tier and level, using the RFC 7798 defaults for
+    /// absent parameters.
+    ///
+    /// SDP does not carry the profile compatibility flags, so only the flag
+    /// matching `profile-id` is set.
+    pub fn hevc_profile_tier_level(&self) -> Option<HevcProfileTierLevel> {
+        if self.codec != VideoCodec::Hevc {
+            return None;
+        }
+
+        let parameter = |name: &str, default: u8| match self.fmtp_parameter(name) {
+            Some(value) => value.parse::<u8>().ok(),
+            None => Some(default),
+        };
+
+        let profile_idc = parameter("profile-id", 1)?;
+        let constraint_indicator_flags = match self.fmtp_parameter("interop-constraints") {
+            Some(value) => u64::from_str_radix(value, 16).ok()?,
+            None => 0,
+        };
+
+        // profile_space is a 2 bit field and tier_flag a single bit.
+        let profile_space = parameter("profile-space", 0).filter(|&space| space <= 3)?;
+        let tier_flag = parameter("tier-flag", 0).filter(|&tier| tier <= 1)?;
+
+        // Out of range profiles have no compatibility flag.
+        let profile_compatibility_flags = 31u32
+            .checked_sub(profile_idc as u32)
+            .and_then(|shift| 1u32.checked_shl(shift))
+            .unwrap_or(0);
+
+        Some(HevcProfileTierLevel {
+            profile_space,
+            tier_flag: tier_flag == 1,
+            profile_idc,
+            profile_compatibility_flags,
+            constraint_indicator_flags,
+            level_idc: parameter("level-id", 93)?,
+        })
+    }
+}
+
+impl fmt::Display for VideoMediaDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoding_name = match self.codec {
+            VideoCodec::H264 => "H264",
+            VideoCodec::Hevc => "H265",
+        };
+
+        write!(f, "m=video {} {} {}\r\n", self.port, self.protocol, self.payload_type)?;
+        write!(f, "a=rtpmap:{} {}/{}\r\n", self.payload_type, encoding_name, self.clock_rate)?;
+
+        if !self.fmtp.is_empty() {
+            write!(f, "{}\r\n", self.fmtp_line())?;
+        }
+
+        if let Some(control) = &self.control {
+            write!(f, "a=control:{}\r\n", control)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A media section being parsed. It may list several payload types, the
+/// first one with a supported `rtpmap` is kept.
+struct MediaSection {
+    port: u16,
+    protocol: String,
+    payload_types: Vec<u8>,
+    rtpmaps: Vec<(u8, VideoCodec, u32)>,
+    fmtps: Vec<(u8, Vec<(String, String)>)>,
+    control: Option<String>,
+}
+
+impl MediaSection {
+    /// Returns `None` for non-video sections, which are skipped.
+    fn parse(line: &str, value: &str) -> Result<Option<Self>, SdpError> {
+        let malformed = || SdpError::MalformedLine(line.to_string());
+        let mut fields = value.split_whitespace();
+
+        if fields.next() != Some("video") {
+            return Ok(None);
+        }
+
+        let port = fields.next().and_then(|port| port.split('/').next()).ok_or_else(malformed)?;
+        let port = port.parse().map_err(|_| malformed())?;
+        let protocol = fields.next().ok_or_else(malformed)?.to_string();
+        let payload_types = fields.filter_map(|format| format.parse().ok()).collect();
+
+        Ok(Some(Self {
+            port,
+            protocol,
+            payload_types,
+            rtpmaps: vec![],
+            fmtps: vec![],
+            control: None,
+        }))
+    }
+
+    fn parse_attribute(&mut self, line: &str, value: &str) -> Result<(), SdpError> {
+        let malformed = || SdpError::MalformedLine(line.to_string());
+        let (name, value) = value.split_once(':').unwrap_or((value, ""));
+
+        match name {
+            "rtpmap" => {
+                let (payload_type, encoding) = value.split_once(' ').ok_or_else(malformed)?;
+                let payload_type = payload_type.parse().map_err(|_| malformed())?;
+
+                let mut encoding = encoding.split('/');
+                let codec = match encoding.next().map(|name| name.to_ascii_uppercase()) {
+                    Some(name) if name == "H264" => VideoCodec::H264,
+                    Some(name) if name == "H265" => VideoCodec::Hevc,
+                    _ => return Ok(()),
+                };
+                let clock_rate =
+                    encoding.next().and_then(|rate| rate.parse().ok()).unwrap_or(VIDEO_CLOCK_RATE);
+
+                self.rtpmaps.push((payload_type, codec, clock_rate));
+            },
+            "fmtp" => {
+                let (payload_type, parameters) = value.split_once(' ').ok_or_else(malformed)?;
+                let payload_type = payload_type.parse().map_err(|_| malformed())?;
+
+                let parameters = parameters
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|parameter| !parameter.is_empty())
+                    .map(|parameter| {
+                        let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+                        (key.trim().to_string(), value.trim().to_string())
+                    })
+                    .collect();
+
+                self.fmtps.push((payload_type, parameters));
+            },
+            "control" => self.control = Some(value.to_string()),
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Option<VideoMediaDescription> {
+        let (payload_type, codec, clock_rate) = self.payload_types.iter().find_map(|pt| {
+            self.rtpmaps.iter().find(|(rtpmap_pt, _, _)| rtpmap_pt == pt).copied()
+        })?;
+
+        let fmtp = self
+            .fmtps
+            .into_iter()
+            .find(|(fmtp_pt, _)| *fmtp_pt == payload_type)
+            .map(|(_, parameters)| parameters)
+            .unwrap_or_default();
+
+        Some(VideoMediaDescription {
+            port: self.port,
+            protocol: self.protocol,
+            payload_type,
+            codec,
+            clock_rate,
+            fmtp,
+            control: self.control,
+        })
+    }
+}
+
+fn parse_connection(line: &str, value: &str) -> Result<Option<String>, SdpError> {
+    // c=<nettype> <addrtype> <connection-address>[/ttl]
+    let address =
+        value.split_whitespace().nth(2).ok_or_else(|| SdpError::MalformedLine(line.to_string()))?;
+    Ok(Some(address.split('/').next().unwrap_or(address).to_string()))
+}
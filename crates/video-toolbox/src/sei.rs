@@ -0,0 +1,77 @@
+//! SEI message framing (H.264 section 7.3.2.3, HEVC section 7.3.5).
+
+use crate::{
+    bitstream::{add_emulation_prevention, remove_emulation_prevention},
+    VideoCodec,
+};
+
+/// Builds a prefix SEI NAL unit, header included, from `(payload_type,
+/// payload)` messages.
+pub(crate) fn write_sei_nal(codec: VideoCodec, messages: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut rbsp = vec![];
+
+    for (payload_type, payload) in messages {
+        for mut value in [*payload_type as usize, payload.len()] {
+            while value >= 255 {
+                rbsp.push(0xff);
+                value -= 255;
+            }
+
+            rbsp.push(value as u8);
+        }
+
+        rbsp.extend_from_slice(payload);
+    }
+
+    // rbsp_trailing_bits
+    rbsp.push(0x80);
+
+    let mut nal = match codec {
+        VideoCodec::Hevc => vec![39 << 1, 1],
+        VideoCodec::H264 => vec![6],
+    };
+
+    nal.extend_from_slice(&add_emulation_prevention(&rbsp));
+    nal
+}
+
+/// Splits a prefix SEI NAL unit, header included, into `(payload_type,
+/// payload)` messages. Returns `None` if `nal` is not an SEI or is malformed.
+pub(crate) fn read_sei_nal(codec: VideoCodec, nal: &[u8]) -> Option<Vec<(u32, Vec<u8>)>> {
+    let header_len = match codec {
+        VideoCodec::Hevc => ((nal.first()? >> 1) & 0b11_1111 == 39).then_some(2)?,
+        VideoCodec::H264 => (nal.first()? & 0b1_1111 == 6).then_some(1)?,
+    };
+
+    let rbsp = remove_emulation_prevention(nal.get(header_len..)?);
+    let mut rest = &rbsp[..];
+    let mut messages = vec![];
+
+    // Stop at rbsp_trailing_bits.
+    while rest.len() > 1 || rest.first().is_some_and(|&byte| byte != 0x80) {
+        let (payload_type, used) = read_sei_value(rest)?;
+        rest = &rest[used..];
+        let (payload_size, used) = read_sei_value(rest)?;
+        rest = &rest[used..];
+
+        messages.push((payload_type, rest.get(..payload_size as usize)?.to_vec()));
+        rest = &rest[payload_size as usize..];
+    }
+
+    Some(messages)
+}
+
+/// Reads an SEI payload type or size, returning it and the bytes used.
+fn read_sei_value(data: &[u8]) -> Option<(u32, usize)> {
+    let mut value = 0;
+
+    for (index, &byte) in data.iter().enumerate() {
+        value += byte as u32;
+
+        if byte != 0xff {
+            return Some((value, index + 1));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,460 @@
+//! Sequence parameter set parsing (H.264 section 7.3.2.1, HEVC section 7.3.2.2).
+
+use crate::{
+    bitstream::{remove_emulation_prevention, BitReader},
+    color::{ChromaSiting, ColorInfo},
+    Timestamp,
+};
+use std::time::Duration;
+
+/// H.264 profiles whose SPS carries chroma format and bit depth fields.
+const H264_HIGH_PROFILES: &[u32] = &[100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// Picture format fields shared by H.264 and HEVC sequence parameter sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpsInfo {
+    /// Width in pixels after the conformance (cropping) window is applied.
+    pub width: u32,
+    /// Height in pixels after the conformance (cropping) window is applied.
+    pub height: u32,
+    /// 0 for monochrome, 1 for 4:2:0, 2 for 4:2:2 and 3 for 4:4:4.
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+    /// From the VUI timing info, when present.
+    pub frame_rate: Option<FrameRate>,
+    /// From the VUI video signal and chroma location info, unspecified when
+    /// absent.
+    pub color: ColorInfo,
+}
+
+/// A frame rate as an exact fraction, e.g. 30000/1001.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameRate {
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self { numerator, denominator }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// The duration of one frame, rounded to the nanosecond.
+    pub fn frame_duration(self) -> Duration {
+        self.duration_of(1)
+    }
+
+    /// The exact presentation time of frame `index`, counting from zero, in
+    /// units of `1 / numerator` seconds.
+    pub fn timestamp_of(self, frames: u64) -> Timestamp {
+        Timestamp::new(frames as i64 * self.denominator as i64, self.numerator.max(1))
+    }
+
+    /// The presentation time of frame `index`, counting from zero, without
+    /// accumulating rounding errors.
+    pub fn duration_of(self, frames: u64) -> Duration {
+        let nanos = frames as u128 * self.denominator as u128 * 1_000_000_000;
+        let numerator = self.numerator.max(1) as u128;
+        Duration::from_nanos(((nanos + numerator / 2) / numerator) as u64)
+    }
+}
+
+/// Parses an H.264 SPS NAL unit, including its one byte header.
+pub(crate) fn parse_h264_sps(nal: &[u8]) -> Option<SpsInfo> {
+    let rbsp = remove_emulation_prevention(nal.get(1..)?);
+    let mut reader = BitReader::new(&rbsp);
+
+    let profile_idc = reader.read_bits(8)?;
+    // constraint_set flags and level_idc
+    reader.skip_bits(16)?;
+    let _seq_parameter_set_id = reader.read_ue()?;
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    let mut bit_depth_luma = 8;
+    let mut bit_depth_chroma = 8;
+
+    if H264_HIGH_PROFILES.contains(&profile_idc) {
+        chroma_format_idc = read_ue_max(&mut reader, 3)?;
+
+        if chroma_format_idc == 3 {
+            separate_colour_plane = reader.read_bit()?;
+        }
+
+        bit_depth_luma = read_ue_max(&mut reader, 6)? + 8;
+        bit_depth_chroma = read_ue_max(&mut reader, 6)? + 8;
+        // qpprime_y_zero_transform_bypass_flag
+        reader.skip_bits(1)?;
+
+        if reader.read_bit()? {
+            let scaling_list_count = if chroma_format_idc == 3 { 12 } else { 8 };
+
+            for i in 0..scaling_list_count {
+                if reader.read_bit()? {
+                    skip_scaling_list(&mut reader, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    let _log2_max_frame_num_minus4 = reader.read_ue()?;
+
+    match reader.read_ue()? {
+        0 => {
+            let _log2_max_pic_order_cnt_lsb_minus4 = reader.read_ue()?;
+        },
+        1 => {
+            // delta_pic_order_always_zero_flag
+            reader.skip_bits(1)?;
+            let _offset_for_non_ref_pic = reader.read_se()?;
+            let _offset_for_top_to_bottom_field = reader.read_se()?;
+
+            for _ in 0..reader.read_ue()? {
+                let _offset_for_ref_frame = reader.read_se()?;
+            }
+        },
+        _ => {},
+    }
+
+    let _max_num_ref_frames = reader.read_ue()?;
+    // gaps_in_frame_num_value_allowed_flag
+    reader.skip_bits(1)?;
+
+    let width_in_mbs = reader.read_ue()? + 1;
+    let height_in_map_units = reader.read_ue()? + 1;
+    let frame_mbs_only = reader.read_bit()?;
+
+    if !frame_mbs_only {
+        // mb_adaptive_frame_field_flag
+        reader.skip_bits(1)?;
+    }
+
+    // direct_8x8_inference_flag
+    reader.skip_bits(1)?;
+
+    let field_factor = if frame_mbs_only { 1 } else { 2 };
+    let mut width = width_in_mbs.checked_mul(16)?;
+    let mut height = height_in_map_units.checked_mul(16 * field_factor)?;
+
+    if reader.read_bit()? {
+        let (left, right, top, bottom) =
+            (reader.read_ue()?, reader.read_ue()?, reader.read_ue()?, reader.read_ue()?);
+
+        let chroma_array_type = if separate_colour_plane { 0 } else { chroma_format_idc };
+        let (crop_unit_x, crop_unit_y) = match chroma_array_type {
+            0 => (1, field_factor),
+            1 => (2, 2 * field_factor),
+            2 => (2, field_factor),
+            _ => (1, field_factor),
+        };
+
+        width = width.checked_sub(left.checked_add(right)?.checked_mul(crop_unit_x)?)?;
+        height = height.checked_sub(top.checked_add(bottom)?.checked_mul(crop_unit_y)?)?;
+    }
+
+    // A truncated VUI only loses the optional fields.
+    let mut frame_rate = None;
+    let mut color = ColorInfo::default();
+
+    if reader.read_bit() == Some(true) {
+        frame_rate = read_vui_prefix(&mut reader, &mut color).and_then(|()| {
+            let (num_units_in_tick, time_scale) = read_timing_info(&mut reader)?;
+            // Ticks are fields, so a frame is two of them.
+            frame_rate_from_timing(time_scale, num_units_in_tick.checked_mul(2)?)
+        });
+    }
+
+    Some(SpsInfo {
+        width,
+        height,
+        chroma_format_idc,
+        bit_depth_luma,
+        bit_depth_chroma,
+        frame_rate,
+        color,
+    })
+}
+
+/// Parses an HEVC SPS NAL unit, including its two byte header.
+pub(crate) fn parse_hevc_sps(nal: &[u8]) -> Option<SpsInfo> {
+    let rbsp = remove_emulation_prevention(nal.get(2..)?);
+    let mut reader = BitReader::new(&rbsp);
+
+    // sps_video_parameter_set_id
+    reader.skip_bits(4)?;
+    let max_sub_layers_minus1 = reader.read_bits(3)?;
+    // sps_temporal_id_nesting_flag
+    reader.skip_bits(1)?;
+
+    skip_hevc_profile_tier_level(&mut reader, max_sub_layers_minus1)?;
+
+    let _sps_seq_parameter_set_id = reader.read_ue()?;
+    let chroma_format_idc = read_ue_max(&mut reader, 3)?;
+    let separate_colour_plane = chroma_format_idc == 3 && reader.read_bit()?;
+
+    let mut width = reader.read_ue()?;
+    let mut height = reader.read_ue()?;
+
+    if reader.read_bit()? {
+        let (left, right, top, bottom) =
+            (reader.read_ue()?, reader.read_ue()?, reader.read_ue()?, reader.read_ue()?);
+
+        let chroma_array_type = if separate_colour_plane { 0 } else { chroma_format_idc };
+        let (sub_width, sub_height) = match chroma_array_type {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+
+        width = width.checked_sub(left.checked_add(right)?.checked_mul(sub_width)?)?;
+        height = height.checked_sub(top.checked_add(bottom)?.checked_mul(sub_height)?)?;
+    }
+
+    let bit_depth_luma = read_ue_max(&mut reader, 8)? + 8;
+    let bit_depth_chroma = read_ue_max(&mut reader, 8)? + 8;
+
+    // A truncated VUI only loses the optional fields.
+    let mut color = ColorInfo::default();
+    let frame_rate = skip_hevc_sps_to_vui(&mut reader, max_sub_layers_minus1).and_then(|()| {
+        reader.read_bit()?.then_some(())?;
+        read_vui_prefix(&mut reader, &mut color)?;
+
+        // neutral_chroma_indication_flag, field_seq_flag, frame_field_info_present_flag
+        reader.skip_bits(3)?;
+
+        if reader.read_bit()? {
+            // default display window offsets
+            (0..4).try_for_each(|_| reader.read_ue().map(|_| ()))?;
+        }
+
+        let (num_units_in_tick, time_scale) = read_timing_info(&mut reader)?;
+        frame_rate_from_timing(time_scale, num_units_in_tick)
+    });
+
+    Some(SpsInfo {
+        width,
+        height,
+        chroma_format_idc,
+        bit_depth_luma,
+        bit_depth_chroma,
+        frame_rate,
+        color,
+    })
+}
+
+/// Reads an Exp-Golomb value, rejecting anything above the spec limit `max`.
+fn read_ue_max(reader: &mut BitReader, max: u8) -> Option<u8> {
+    u8::try_from(reader.read_ue()?).ok().filter(|&value| value <= max)
+}
+
+/// Skips the HEVC SPS fields between the bit depths and
+/// `vui_parameters_present_flag`.
+fn skip_hevc_sps_to_vui(reader: &mut BitReader, max_sub_layers_minus1: u32) -> Option<()> {
+    let log2_max_pic_order_cnt_lsb = read_ue_max(reader, 12)? + 4;
+    let sub_layer_ordering_info_present = reader.read_bit()?;
+    let first_sub_layer = if sub_layer_ordering_info_present { 0 } else { max_sub_layers_minus1 };
+
+    for _ in first_sub_layer..=max_sub_layers_minus1 {
+        // max_dec_pic_buffering, max_num_reorder_pics and max_latency_increase
+        (0..3).try_for_each(|_| reader.read_ue().map(|_| ()))?;
+    }
+
+    // Coding block and transform block sizes and hierarchy depths
+    (0..6).try_for_each(|_| reader.read_ue().map(|_| ()))?;
+
+    if reader.read_bit()? && reader.read_bit()? {
+        skip_hevc_scaling_list_data(reader)?;
+    }
+
+    // amp_enabled_flag, sample_adaptive_offset_enabled_flag
+    reader.skip_bits(2)?;
+
+    if reader.read_bit()? {
+        // PCM sample bit depths
+        reader.skip_bits(8)?;
+        let _log2_min_pcm_luma_coding_block_size_minus3 = reader.read_ue()?;
+        let _log2_diff_max_min_pcm_luma_coding_block_size = reader.read_ue()?;
+        // pcm_loop_filter_disabled_flag
+        reader.skip_bits(1)?;
+    }
+
+    let num_short_term_ref_pic_sets = read_ue_max(reader, 64)? as usize;
+    let mut num_delta_pocs = Vec::with_capacity(num_short_term_ref_pic_sets);
+
+    for index in 0..num_short_term_ref_pic_sets {
+        let count = skip_st_ref_pic_set(reader, index, &num_delta_pocs)?;
+        num_delta_pocs.push(count);
+    }
+
+    if reader.read_bit()? {
+        for _ in 0..reader.read_ue()? {
+            // lt_ref_pic_poc_lsb_sps and used_by_curr_pic_lt_sps_flag
+            reader.skip_bits(log2_max_pic_order_cnt_lsb as usize + 1)?;
+        }
+    }
+
+    // sps_temporal_mvp_enabled_flag, strong_intra_smoothing_enabled_flag
+    reader.skip_bits(2)
+}
+
+/// Skips `st_ref_pic_set(index)` in an SPS, returning its `NumDeltaPocs`.
+fn skip_st_ref_pic_set(
+    reader: &mut BitReader,
+    index: usize,
+    num_delta_pocs: &[u32],
+) -> Option<u32> {
+    let inter_ref_pic_set_prediction = index != 0 && reader.read_bit()?;
+
+    if inter_ref_pic_set_prediction {
+        // Within an SPS the reference is always the previous set.
+        // delta_rps_sign
+        reader.skip_bits(1)?;
+        let _abs_delta_rps_minus1 = reader.read_ue()?;
+        let mut count = 0;
+
+        for _ in 0..=num_delta_pocs[index - 1] {
+            let used_by_curr_pic = reader.read_bit()?;
+
+            if used_by_curr_pic || reader.read_bit()? {
+                count += 1;
+            }
+        }
+
+        Some(count)
+    } else {
+        let num_negative_pics = reader.read_ue()?;
+        let num_positive_pics = reader.read_ue()?;
+
+        for _ in 0..num_negative_pics.checked_add(num_positive_pics)? {
+            let _delta_poc_minus1 = reader.read_ue()?;
+            // used_by_curr_pic_flag
+            reader.skip_bits(1)?;
+        }
+
+        Some(num_negative_pics + num_positive_pics)
+    }
+}
+
+fn skip_hevc_scaling_list_data(reader: &mut BitReader) -> Option<()> {
+    for size_id in 0..4 {
+        let matrix_step = if size_id == 3 { 3 } else { 1 };
+
+        for _ in (0..6).step_by(matrix_step) {
+            if !reader.read_bit()? {
+                let _scaling_list_pred_matrix_id_delta = reader.read_ue()?;
+                continue;
+            }
+
+            if size_id > 1 {
+                let _scaling_list_dc_coef_minus8 = reader.read_se()?;
+            }
+
+            for _ in 0..64.min(1 << (4 + (size_id << 1))) {
+                let _scaling_list_delta_coef = reader.read_se()?;
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// Reads the VUI fields H.264 and HEVC share, up to the codec specific ones,
+/// filling in `color` as far as the VUI gets.
+fn read_vui_prefix(reader: &mut BitReader, color: &mut ColorInfo) -> Option<()> {
+    // aspect_ratio_info_present_flag
+    if reader.read_bit()? && reader.read_bits(8)? == 255 {
+        // sar_width and sar_height
+        reader.skip_bits(32)?;
+    }
+
+    // overscan_info_present_flag
+    if reader.read_bit()? {
+        reader.skip_bits(1)?;
+    }
+
+    // video_signal_type_present_flag
+    if reader.read_bit()? {
+        let _video_format = reader.read_bits(3)?;
+        color.full_range = reader.read_bit()?;
+
+        // colour_description_present_flag
+        if reader.read_bit()? {
+            color.primaries = (reader.read_bits(8)? as u8).into();
+            color.transfer = (reader.read_bits(8)? as u8).into();
+            color.matrix = (reader.read_bits(8)? as u8).into();
+        }
+    }
+
+    // chroma_loc_info_present_flag
+    if reader.read_bit()? {
+        let chroma_sample_loc_type_top_field = reader.read_ue()?;
+        let _chroma_sample_loc_type_bottom_field = reader.read_ue()?;
+        color.chroma_location =
+            ChromaSiting::from_code(chroma_sample_loc_type_top_field).unwrap_or_default();
+    }
+
+    Some(())
+}
+
+/// Reads `timing_info_present_flag` and, if set, `(num_units_in_tick, time_scale)`.
+fn read_timing_info(reader: &mut BitReader) -> Option<(u32, u32)> {
+    reader.read_bit()?.then_some(())?;
+    Some((reader.read_bits(32)?, reader.read_bits(32)?))
+}
+
+fn frame_rate_from_timing(numerator: u32, denominator: u32) -> Option<FrameRate> {
+    (numerator != 0 && denominator != 0).then_some(FrameRate { numerator, denominator })
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Option<()> {
+    let mut last_scale = 8;
+    let mut next_scale = 8;
+
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = reader.read_se()?;
+            next_scale = (last_scale + delta_scale + 256) % 256;
+        }
+
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+
+    Some(())
+}
+
+fn skip_hevc_profile_tier_level(reader: &mut BitReader, max_sub_layers_minus1: u32) -> Option<()> {
+    // General profile space through general_level_idc.
+    reader.skip_bits(96)?;
+
+    let mut sub_layers_present = vec![];
+
+    for _ in 0..max_sub_layers_minus1 {
+        let profile_present = reader.read_bit()?;
+        let level_present = reader.read_bit()?;
+        sub_layers_present.push((profile_present, level_present));
+    }
+
+    if max_sub_layers_minus1 > 0 {
+        // reserved_zero_2bits
+        reader.skip_bits(2 * (8 - max_sub_layers_minus1 as usize))?;
+    }
+
+    for (profile_present, level_present) in sub_layers_present {
+        if profile_present {
+            reader.skip_bits(88)?;
+        }
+
+        if level_present {
+            reader.skip_bits(8)?;
+        }
+    }
+
+    Some(())
+}
@@ -0,0 +1,264 @@
+//! Rational media timestamps, the safe counterpart of CoreMedia's `CMTime`.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign},
+    time::Duration,
+};
+
+const NANOS_PER_SECOND: u32 = 1_000_000_000;
+
+/// A media time of `value / timescale` seconds, e.g. `3003/30000` for one
+/// frame of 29.97 fps video. Timestamps compare and hash by their exact
+/// rational value, so `1/2` equals `45000/90000`.
+///
+/// Arithmetic between different timescales is exact when their least common
+/// multiple fits a `u32`, and rounds to the finer timescale otherwise. The
+/// operators panic on overflow, like integer arithmetic; the `checked_*`
+/// methods return `None` instead.
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp {
+    value: i64,
+    timescale: u32,
+}
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp { value: 0, timescale: 1 };
+
+    /// # Panics
+    ///
+    /// If `timescale` is zero.
+    pub const fn new(value: i64, timescale: u32) -> Self {
+        assert!(timescale > 0, "timestamp timescale must be positive");
+        Self { value, timescale }
+    }
+
+    pub fn from_secs(seconds: i64) -> Self {
+        Self::new(seconds, 1)
+    }
+
+    pub fn value(self) -> i64 {
+        self.value
+    }
+
+    /// Ticks per second.
+    pub fn timescale(self) -> u32 {
+        self.timescale
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.value < 0
+    }
+
+    pub fn as_secs_f64(self) -> f64 {
+        self.value as f64 / self.timescale as f64
+    }
+
+    /// The time in nanoseconds, rounded to the nearest one. Returns `None` if
+    /// negative.
+    pub fn to_duration(self) -> Option<Duration> {
+        let nanos = self.checked_rescale(NANOS_PER_SECOND)?.value;
+        Some(Duration::from_nanos(u64::try_from(nanos).ok()?))
+    }
+
+    /// The same time in units of `1 / timescale` seconds, rounded to the
+    /// nearest tick with halves away from zero. Returns `None` if the value
+    /// overflows.
+    ///
+    /// # Panics
+    ///
+    /// If `timescale` is zero.
+    pub fn checked_rescale(self, timescale: u32) -> Option<Self> {
+        assert!(timescale > 0, "timestamp timescale must be positive");
+
+        let value = div_round(self.value as i128 * timescale as i128, self.timescale as i128);
+        Some(Self::new(i64::try_from(value).ok()?, timescale))
+    }
+
+    /// See [`Timestamp::checked_rescale`].
+    ///
+    /// # Panics
+    ///
+    /// If the value overflows.
+    pub fn rescale(self, timescale: u32) -> Self {
+        self.checked_rescale(timescale).expect("timestamp overflow")
+    }
+
+    /// The same time with the smallest timescale that represents it exactly.
+    pub fn reduced(self) -> Self {
+        let divisor = gcd(self.value.unsigned_abs(), self.timescale as u64);
+        Self::new(self.value / divisor as i64, self.timescale / divisor as u32)
+    }
+
+    pub fn checked_add(self, other: Timestamp) -> Option<Self> {
+        let timescale = common_timescale(self.timescale, other.timescale);
+        let value = self.checked_rescale(timescale)?.value;
+        Some(Self::new(value.checked_add(other.checked_rescale(timescale)?.value)?, timescale))
+    }
+
+    pub fn checked_sub(self, other: Timestamp) -> Option<Self> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Self::new(self.value.checked_neg()?, self.timescale))
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Option<Self> {
+        Some(Self::new(self.value.checked_mul(factor)?, self.timescale))
+    }
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+/// Rounds `numerator / denominator` to the nearest integer, halves away from
+/// zero. `denominator` must be positive.
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+
+    if remainder.abs() * 2 >= denominator {
+        quotient + numerator.signum()
+    } else {
+        quotient
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a
+}
+
+/// The least common multiple if it fits, otherwise the finer of the two.
+fn common_timescale(a: u32, b: u32) -> u32 {
+    let lcm = a as u64 / gcd(a as u64, b as u64) * b as u64;
+    u32::try_from(lcm).unwrap_or(a.max(b))
+}
+
+impl From<Duration> for Timestamp {
+    /// Exact to the nanosecond, saturating past about 292 years.
+    fn from(duration: Duration) -> Self {
+        let nanos = i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX);
+        Self::new(nanos, NANOS_PER_SECOND)
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Both products fit an i128, so this is exact.
+        let left = self.value as i128 * other.timescale as i128;
+        let right = other.value as i128 * self.timescale as i128;
+        left.cmp(&right)
+    }
+}
+
+impl Hash for Timestamp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let reduced = self.reduced();
+        reduced.value.hash(state);
+        reduced.timescale.hash(state);
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.value, self.timescale)
+    }
+}
+
+impl Add for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, other: Timestamp) -> Timestamp {
+        self.checked_add(other).expect("timestamp overflow")
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, other: Timestamp) -> Timestamp {
+        self.checked_sub(other).expect("timestamp overflow")
+    }
+}
+
+impl Neg for Timestamp {
+    type Output = Timestamp;
+
+    fn neg(self) -> Timestamp {
+        self.checked_neg().expect("timestamp overflow")
+    }
+}
+
+impl Mul<i64> for Timestamp {
+    type Output = Timestamp;
+
+    fn mul(self, factor: i64) -> Timestamp {
+        self.checked_mul(factor).expect("timestamp overflow")
+    }
+}
+
+impl AddAssign for Timestamp {
+    fn add_assign(&mut self, other: Timestamp) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Timestamp {
+    fn sub_assign(&mut self, other: Timestamp) {
+        *self = *self - other;
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl Timestamp {
+    /// `None` for invalid, indefinite or infinite times.
+    pub(crate) fn from_cm_time(time: video_toolbox_sys::CMTime) -> Option<Self> {
+        // kCMTimeFlags_Valid, without kCMTimeFlags_PositiveInfinity,
+        // kCMTimeFlags_NegativeInfinity or kCMTimeFlags_Indefinite.
+        if time.flags & 0b11101 != 1 || time.timescale <= 0 {
+            return None;
+        }
+
+        Some(Self::new(time.value, time.timescale as u32))
+    }
+
+    /// Rescaled to nanoseconds if the timescale does not fit a `CMTime`.
+    pub(crate) fn to_cm_time(self) -> video_toolbox_sys::CMTime {
+        let time = match i32::try_from(self.timescale) {
+            Ok(_) => self,
+            Err(_) => self.rescale(NANOS_PER_SECOND),
+        };
+
+        video_toolbox_sys::CMTime {
+            value: time.value,
+            timescale: time.timescale as i32,
+            // kCMTimeFlags_Valid
+            flags: 1,
+            epoch: 0,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! MPEG-2 transport stream (ISO/IEC 13818-1) writing for a single video stream.
+
+use crate::VideoCodec;
+
+pub const TS_PACKET_SIZE: usize = 188;
+
+const TS_HEADER_SIZE: usize = 4;
+const SYNC_BYTE: u8 = 0x47;
+
+const PAT_PID: u16 = 0;
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x100;
+
+const PROGRAM_NUMBER: u16 = 1;
+const VIDEO_STREAM_ID: u8 = 0xe0;
+
+/// Writes a single-program transport stream, keeping continuity counters
+/// across calls so consecutive segments form one continuous stream.
+pub struct TsWriter {
+    codec: VideoCodec,
+    pat_continuity: u8,
+    pmt_continuity: u8,
+    video_continuity: u8,
+}
+
+impl TsWriter {
+    pub fn new(codec: VideoCodec) -> Self {
+        Self { codec, pat_continuity: 0, pmt_continuity: 0, video_continuity: 0 }
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.codec
+    }
+
+    /// Writes the PAT and PMT, which must start every independently decodable
+    /// segment.
+    pub fn write_tables(&mut self, out: &mut Vec<u8>) {
+        let mut pat = vec![];
+        pat.extend_from_slice(&PROGRAM_NUMBER.to_be_bytes());
+        pat.extend_from_slice(&(0xe000 | PMT_PID).to_be_bytes());
+        let section = psi_section(0x00, 1, &pat);
+        write_psi_packet(out, PAT_PID, &mut self.pat_continuity, &section);
+
+        let stream_type = match self.codec {
+            VideoCodec::H264 => 0x1b,
+            VideoCodec::Hevc => 0x24,
+        };
+
+        let mut pmt = vec![];
+        // PCR PID, then an empty program_info loop
+        pmt.extend_from_slice(&(0xe000 | VIDEO_PID).to_be_bytes());
+        pmt.extend_from_slice(&0xf000u16.to_be_bytes());
+        pmt.push(stream_type);
+        pmt.extend_from_slice(&(0xe000 | VIDEO_PID).to_be_bytes());
+        pmt.extend_from_slice(&0xf000u16.to_be_bytes());
+        let section = psi_section(0x02, PROGRAM_NUMBER, &pmt);
+        write_psi_packet(out, PMT_PID, &mut self.pmt_continuity, &section);
+    }
+
+    /// Writes an Annex B access unit as one PES packet. `pts` is in 90 kHz
+    /// units and also drives the PCR, so frames must be in decode order with
+    /// no reordering.
+    pub fn write_access_unit(
+        &mut self,
+        out: &mut Vec<u8>,
+        access_unit: &[u8],
+        pts: u64,
+        is_keyframe: bool,
+    ) {
+        let mut pes = Vec::with_capacity(access_unit.len() + 14);
+        pes.extend_from_slice(&[0, 0, 1, VIDEO_STREAM_ID]);
+        // A zero PES_packet_length is allowed for video.
+        pes.extend_from_slice(&[0, 0]);
+        // data_alignment_indicator, PTS only, five header bytes
+        pes.extend_from_slice(&[0x84, 0x80, 5]);
+        pes.extend_from_slice(&encode_pts(pts));
+        pes.extend_from_slice(access_unit);
+
+        let mut remaining = &pes[..];
+        let mut first = true;
+
+        while !remaining.is_empty() {
+            let adaptation =
+                Adaptation { pcr: first.then_some(pts), random_access: first && is_keyframe };
+
+            let consumed = write_packet(
+                out,
+                VIDEO_PID,
+                first,
+                &mut self.video_continuity,
+                adaptation,
+                remaining,
+            );
+
+            remaining = &remaining[consumed..];
+            first = false;
+        }
+    }
+}
+
+struct Adaptation {
+    pcr: Option<u64>,
+    random_access: bool,
+}
+
+/// Writes one packet carrying as much of `payload` as fits, padding with
+/// adaptation field stuffing. Returns the number of payload bytes written.
+fn write_packet(
+    out: &mut Vec<u8>,
+    pid: u16,
+    payload_unit_start: bool,
+    continuity: &mut u8,
+    adaptation: Adaptation,
+    payload: &[u8],
+) -> usize {
+    let required_adaptation = match (adaptation.pcr, adaptation.random_access) {
+        (Some(_), _) => 8,
+        (None, true) => 2,
+        (None, false) => 0,
+    };
+
+    let capacity = TS_PACKET_SIZE - TS_HEADER_SIZE;
+    let consumed = payload.len().min(capacity - required_adaptation);
+    let adaptation_size = capacity - consumed;
+
+    let start_flag = if payload_unit_start { 0x40 } else { 0 };
+    let control = if adaptation_size > 0 { 0x30 } else { 0x10 };
+
+    out.push(SYNC_BYTE);
+    out.push(start_flag | (pid >> 8) as u8);
+    out.push(pid as u8);
+    out.push(control | *continuity);
+    *continuity = (*continuity + 1) & 0x0f;
+
+    if adaptation_size > 0 {
+        out.push((adaptation_size - 1) as u8);
+
+        if adaptation_size > 1 {
+            let mut flags = 0;
+
+            if adaptation.random_access {
+                flags |= 0x40;
+            }
+
+            if adaptation.pcr.is_some() {
+                flags |= 0x10;
+            }
+
+            out.push(flags);
+
+            if let Some(pcr) = adaptation.pcr {
+                out.extend_from_slice(&encode_pcr(pcr));
+            }
+
+            let written = if adaptation.pcr.is_some() { 8 } else { 2 };
+            out.extend(std::iter::repeat_n(0xff, adaptation_size.saturating_sub(written)));
+        }
+    }
+
+    out.extend_from_slice(&payload[..consumed]);
+    consumed
+}
+
+fn write_psi_packet(out: &mut Vec<u8>, pid: u16, continuity: &mut u8, section: &[u8]) {
+    let start = out.len();
+
+    out.push(SYNC_BYTE);
+    out.push(0x40 | (pid >> 8) as u8);
+    out.push(pid as u8);
+    out.push(0x10 | *continuity);
+    *continuity = (*continuity + 1) & 0x0f;
+
+    // pointer_field
+    out.push(0);
+    out.extend_from_slice(section);
+    out.resize(start + TS_PACKET_SIZE, 0xff);
+}
+
+/// A long-form PSI section with a single table entry and trailing CRC.
+fn psi_section(table_id: u8, table_id_extension: u16, body: &[u8]) -> Vec<u8> {
+    // Extension, version, section numbers, body and CRC
+    let section_length = 5 + body.len() + 4;
+
+    let mut section = vec![table_id];
+    section.extend_from_slice(&(0xb000 | section_length as u16).to_be_bytes());
+    section.extend_from_slice(&table_id_extension.to_be_bytes());
+    // Version 0, current_next_indicator set, section 0 of 0
+    section.extend_from_slice(&[0xc1, 0, 0]);
+    section.extend_from_slice(body);
+
+    let crc = crc32_mpeg2(&section);
+    section.extend_from_slice(&crc.to_be_bytes());
+    section
+}
+
+fn encode_pts(pts: u64) -> [u8; 5] {
+    let pts = pts & 0x1_ffff_ffff;
+
+    [
+        0x21 | ((pts >> 29) & 0x0e) as u8,
+        (pts >> 22) as u8,
+        0x01 | ((pts >> 14) & 0xfe) as u8,
+        (pts >> 7) as u8,
+        0x01 | ((pts << 1) & 0xfe) as u8,
+    ]
+}
+
+fn encode_pcr(base: u64) -> [u8; 6] {
+    let base = base & 0x1_ffff_ffff;
+
+    // Six reserved bits set and a zero 27 MHz extension.
+    [
+        (base >> 25) as u8,
+        (base >> 17) as u8,
+        (base >> 9) as u8,
+        (base >> 1) as u8,
+        ((base & 1) << 7) as u8 | 0x7e,
+        0,
+    ]
+}
+
+fn crc32_mpeg2(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04c1_1db7 } else { crc << 1 };
+        }
+    }
+
+    crc
+}
@@ -0,0 +1,659 @@
+//! YUV4MPEG2 (`.y4m`) raw video files.
+//!
+//! Frames are stored planar and tightly packed: Y, then U and V (if any), then
+//! alpha for `C444alpha`. Samples deeper than 8 bits are little-endian `u16`s.
+
+use crate::{FrameBuf, FrameError, FrameRate, PixelFormat, VideoFrame};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    mem,
+    str::FromStr,
+};
+use thiserror::Error;
+
+const SIGNATURE: &str = "YUV4MPEG2";
+
+/// Header and frame lines are short; anything longer is not Y4M.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Frames are allocated from the header, so its dimensions are capped.
+const MAX_DIMENSION: u32 = 16384;
+
+#[derive(Debug, Error)]
+pub enum Y4mError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Missing YUV4MPEG2 signature")]
+    MissingSignature,
+
+    #[error("Missing {0} header parameter")]
+    MissingParameter(&'static str),
+
+    #[error("Invalid header parameter: {0}")]
+    InvalidParameter(String),
+
+    #[error("Unsupported colorspace: {0}")]
+    UnsupportedColorspace(String),
+
+    #[error("Missing FRAME marker")]
+    MissingFrameMarker,
+
+    #[error("Line longer than {MAX_LINE_LENGTH} bytes")]
+    LineTooLong,
+
+    #[error("Frame is {actual} bytes, expected {expected}")]
+    FrameSizeMismatch { expected: usize, actual: usize },
+
+    #[error("Frame dimensions {0}x{1} exceed {MAX_DIMENSION}x{MAX_DIMENSION}")]
+    DimensionsTooLarge(u32, u32),
+
+    #[error("Frame is {actual:?}, the stream is {expected:?}")]
+    FrameDimensionsMismatch { expected: (u32, u32), actual: (u32, u32) },
+
+    #[error("No pixel format for the stream's colorspace")]
+    UnsupportedPixelFormat,
+
+    #[error("Invalid frame: {0}")]
+    Frame(#[from] FrameError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Y4mChroma {
+    Mono,
+    C420,
+    C422,
+    C444,
+    /// 4:4:4 with a fourth, alpha, plane.
+    C444Alpha,
+}
+
+/// Position of 4:2:0 chroma samples relative to luma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Y4mChromaSiting {
+    /// Centred between luma samples, `C420jpeg` (the default).
+    Center,
+    /// Co-sited horizontally with the left luma sample, `C420mpeg2`.
+    Left,
+    /// PAL DV, with the chroma planes sited differently, `C420paldv`.
+    PalDv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Y4mColorspace {
+    pub chroma: Y4mChroma,
+    pub bit_depth: u8,
+    /// Only written for 8-bit 4:2:0.
+    pub siting: Y4mChromaSiting,
+}
+
+impl Y4mColorspace {
+    pub fn new(chroma: Y4mChroma, bit_depth: u8) -> Self {
+        Self { chroma, bit_depth, siting: Y4mChromaSiting::Center }
+    }
+
+    /// Bytes per sample: 1 up to 8 bits, 2 above.
+    pub fn bytes_per_sample(&self) -> usize {
+        if self.bit_depth > 8 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Horizontal and vertical chroma subsampling shifts.
+    fn chroma_shift(&self) -> (u32, u32) {
+        match self.chroma {
+            Y4mChroma::C420 => (1, 1),
+            Y4mChroma::C422 => (1, 0),
+            Y4mChroma::Mono | Y4mChroma::C444 | Y4mChroma::C444Alpha => (0, 0),
+        }
+    }
+}
+
+impl Default for Y4mColorspace {
+    fn default() -> Self {
+        Self::new(Y4mChroma::C420, 8)
+    }
+}
+
+impl fmt::Display for Y4mColorspace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let chroma = match self.chroma {
+            Y4mChroma::Mono => "mono",
+            Y4mChroma::C420 => "420",
+            Y4mChroma::C422 => "422",
+            Y4mChroma::C444 => "444",
+            Y4mChroma::C444Alpha => "444alpha",
+        };
+
+        write!(f, "C{}", chroma)?;
+
+        match (self.chroma, self.bit_depth, self.siting) {
+            (Y4mChroma::C420, 8, Y4mChromaSiting::Center) => write!(f, "jpeg"),
+            (Y4mChroma::C420, 8, Y4mChromaSiting::Left) => write!(f, "mpeg2"),
+            (Y4mChroma::C420, 8, Y4mChromaSiting::PalDv) => write!(f, "paldv"),
+            (_, 8, _) => Ok(()),
+            (Y4mChroma::Mono, bit_depth, _) => write!(f, "{}", bit_depth),
+            (_, bit_depth, _) => write!(f, "p{}", bit_depth),
+        }
+    }
+}
+
+impl FromStr for Y4mColorspace {
+    type Err = Y4mError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let unsupported = || Y4mError::UnsupportedColorspace(text.to_string());
+
+        let (chroma, rest) = [
+            ("mono", Y4mChroma::Mono),
+            ("420", Y4mChroma::C420),
+            ("422", Y4mChroma::C422),
+            ("444alpha", Y4mChroma::C444Alpha),
+            ("444", Y4mChroma::C444),
+        ]
+        .into_iter()
+        .find_map(|(prefix, chroma)| Some((chroma, text.strip_prefix(prefix)?)))
+        .ok_or_else(unsupported)?;
+
+        let mut colorspace = Self::new(chroma, 8);
+
+        match rest {
+            "" | "jpeg" => {},
+            "mpeg2" => colorspace.siting = Y4mChromaSiting::Left,
+            "paldv" => colorspace.siting = Y4mChromaSiting::PalDv,
+            // `Cmono10` has no `p`.
+            _ => {
+                let depth = rest.strip_prefix('p').unwrap_or(rest);
+                colorspace.bit_depth = depth.parse().map_err(|_| unsupported())?;
+
+                if !(8..=16).contains(&colorspace.bit_depth) {
+                    return Err(unsupported());
+                }
+            },
+        }
+
+        Ok(colorspace)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Y4mInterlacing {
+    Progressive,
+    TopFieldFirst,
+    BottomFieldFirst,
+    /// Signalled per frame.
+    Mixed,
+}
+
+/// The stream header. Fields match the single letter header tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Y4mHeader {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: FrameRate,
+    pub interlacing: Y4mInterlacing,
+    /// Pixel aspect ratio, with `0:0` meaning unknown.
+    pub pixel_aspect: (u32, u32),
+    pub colorspace: Y4mColorspace,
+    /// `X` tags, without the `X`, kept as is.
+    pub extensions: Vec<String>,
+}
+
+impl Y4mHeader {
+    pub fn new(width: u32, height: u32, frame_rate: FrameRate, colorspace: Y4mColorspace) -> Self {
+        Self {
+            width,
+            height,
+            frame_rate,
+            interlacing: Y4mInterlacing::Progressive,
+            pixel_aspect: (1, 1),
+            colorspace,
+            extensions: vec![],
+        }
+    }
+
+    /// Width and height in samples of each plane, in file order.
+    pub fn plane_dimensions(&self) -> Vec<(u32, u32)> {
+        let (shift_x, shift_y) = self.colorspace.chroma_shift();
+        let chroma = (self.width.div_ceil(1 << shift_x), self.height.div_ceil(1 << shift_y));
+        let luma = (self.width, self.height);
+
+        match self.colorspace.chroma {
+            Y4mChroma::Mono => vec![luma],
+            Y4mChroma::C444Alpha => vec![luma, chroma, chroma, luma],
+            _ => vec![luma, chroma, chroma],
+        }
+    }
+
+    /// The size in bytes of each plane, in file order.
+    pub fn plane_sizes(&self) -> Vec<usize> {
+        let bytes_per_sample = self.colorspace.bytes_per_sample();
+
+        self.plane_dimensions()
+            .into_iter()
+            .map(|(width, height)| {
+                (width as usize).saturating_mul(height as usize).saturating_mul(bytes_per_sample)
+            })
+            .collect()
+    }
+
+    /// The size in bytes of a frame's data, excluding the `FRAME` line.
+    /// Saturates for dimensions beyond what a reader or writer accepts.
+    pub fn frame_size(&self) -> usize {
+        self.plane_sizes().into_iter().fold(0, usize::saturating_add)
+    }
+
+    /// Rejects dimensions whose frames would be too large to allocate.
+    fn check_dimensions(&self) -> Result<(), Y4mError> {
+        if self.width > MAX_DIMENSION || self.height > MAX_DIMENSION {
+            return Err(Y4mError::DimensionsTooLarge(self.width, self.height));
+        }
+
+        Ok(())
+    }
+
+    /// The [`PixelFormat`] of frames from [`Y4mReader::read_video_frame`], if
+    /// there is one for the colorspace. Formats deeper than the stream hold
+    /// its samples in their high bits, and 8-bit 4:2:2 widens to
+    /// [`PixelFormat::P210`], as VideoToolbox has no 8-bit 4:2:2 planes.
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        match (self.colorspace.chroma, self.colorspace.bit_depth) {
+            (Y4mChroma::Mono, 8) => Some(PixelFormat::Gray8),
+            (Y4mChroma::Mono, _) => Some(PixelFormat::Gray16),
+            (Y4mChroma::C420, 8) => Some(PixelFormat::I420),
+            (Y4mChroma::C420, 9..=10) => Some(PixelFormat::P010),
+            (Y4mChroma::C422, ..=10) => Some(PixelFormat::P210),
+            (Y4mChroma::C444, 8) => Some(PixelFormat::Nv24),
+            (Y4mChroma::C444, 9..=10) => Some(PixelFormat::P410),
+            _ => None,
+        }
+    }
+
+    /// The file planes making up each plane of a `format` frame, interleaved
+    /// sample by sample when there are two.
+    fn frame_planes(&self, format: PixelFormat) -> Vec<Vec<usize>> {
+        let plane_count = self.plane_dimensions().len();
+
+        if format.plane_count() == plane_count {
+            (0..plane_count).map(|plane| vec![plane]).collect()
+        } else {
+            vec![vec![0], vec![1, 2]]
+        }
+    }
+
+    /// How far samples are shifted up in a `format` frame.
+    fn sample_shift(&self, format: PixelFormat) -> u32 {
+        frame_sample_bytes(format) as u32 * 8 - self.colorspace.bit_depth as u32
+    }
+
+    /// Splits frame data into its planes.
+    pub fn planes<'a>(&self, frame: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut rest = frame;
+
+        self.plane_sizes()
+            .into_iter()
+            .map(|size| {
+                let (plane, after) = rest.split_at(size.min(rest.len()));
+                rest = after;
+                plane
+            })
+            .collect()
+    }
+
+    /// Peak signal to noise ratio of each plane of `decoded` against
+    /// `reference`, in dB. Identical planes give infinity.
+    pub fn psnr(&self, reference: &[u8], decoded: &[u8]) -> Vec<f64> {
+        let bit_depth = self.colorspace.bit_depth;
+
+        self.planes(reference)
+            .into_iter()
+            .zip(self.planes(decoded))
+            .map(|(reference, decoded)| plane_psnr(reference, decoded, bit_depth))
+            .collect()
+    }
+
+    fn parse(line: &str) -> Result<Self, Y4mError> {
+        let mut tags = line.split(' ').filter(|tag| !tag.is_empty());
+
+        if tags.next() != Some(SIGNATURE) {
+            return Err(Y4mError::MissingSignature);
+        }
+
+        let mut width = None;
+        let mut height = None;
+        let mut frame_rate = None;
+        let mut header = Self::new(0, 0, FrameRate::new(0, 0), Y4mColorspace::default());
+        header.pixel_aspect = (0, 0);
+
+        for tag in tags {
+            let invalid = || Y4mError::InvalidParameter(tag.to_string());
+            let value = tag.get(1..).ok_or_else(invalid)?;
+
+            match tag.as_bytes()[0] {
+                b'W' => width = Some(value.parse().map_err(|_| invalid())?),
+                b'H' => height = Some(value.parse().map_err(|_| invalid())?),
+                b'F' => {
+                    let (numerator, denominator) = parse_ratio(value).ok_or_else(invalid)?;
+                    frame_rate = Some(FrameRate::new(numerator, denominator));
+                },
+                b'I' => {
+                    header.interlacing = match value {
+                        "p" | "?" => Y4mInterlacing::Progressive,
+                        "t" => Y4mInterlacing::TopFieldFirst,
+                        "b" => Y4mInterlacing::BottomFieldFirst,
+                        "m" => Y4mInterlacing::Mixed,
+                        _ => return Err(invalid()),
+                    }
+                },
+                b'A' => header.pixel_aspect = parse_ratio(value).ok_or_else(invalid)?,
+                b'C' => header.colorspace = value.parse()?,
+                b'X' => header.extensions.push(value.to_string()),
+                _ => return Err(invalid()),
+            }
+        }
+
+        header.width = width.ok_or(Y4mError::MissingParameter("W"))?;
+        header.height = height.ok_or(Y4mError::MissingParameter("H"))?;
+        header.frame_rate = frame_rate.ok_or(Y4mError::MissingParameter("F"))?;
+        header.check_dimensions()?;
+
+        Ok(header)
+    }
+}
+
+impl fmt::Display for Y4mHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let interlacing = match self.interlacing {
+            Y4mInterlacing::Progressive => 'p',
+            Y4mInterlacing::TopFieldFirst => 't',
+            Y4mInterlacing::BottomFieldFirst => 'b',
+            Y4mInterlacing::Mixed => 'm',
+        };
+
+        write!(
+            f,
+            "{} W{} H{} F{}:{} I{} A{}:{} {}",
+            SIGNATURE,
+            self.width,
+            self.height,
+            self.frame_rate.numerator,
+            self.frame_rate.denominator,
+            interlacing,
+            self.pixel_aspect.0,
+            self.pixel_aspect.1,
+            self.colorspace
+        )?;
+
+        for extension in &self.extensions {
+            write!(f, " X{}", extension)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Y4mReader<R: Read> {
+    reader: R,
+    header: Y4mHeader,
+    frame_count: u64,
+}
+
+impl<R: Read> Y4mReader<R> {
+    /// Reads the stream header.
+    pub fn new(mut reader: R) -> Result<Self, Y4mError> {
+        let line = read_line(&mut reader)?.ok_or(Y4mError::MissingSignature)?;
+        let header = Y4mHeader::parse(&line)?;
+
+        Ok(Self { reader, header, frame_count: 0 })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// Reads the next frame as a [`FrameBuf`], with its pts and duration from the frame
+    /// rate. The colorspace must have a [`Y4mHeader::pixel_format`].
+    pub fn read_video_frame(&mut self) -> Result<Option<FrameBuf>, Y4mError> {
+        let format = self.header.pixel_format().ok_or(Y4mError::UnsupportedPixelFormat)?;
+        let frame_rate = self.header.frame_rate;
+        let pts = frame_rate.timestamp_of(self.frame_count);
+
+        let Some(data) = self.read_frame()? else {
+            return Ok(None);
+        };
+
+        let header = &self.header;
+        let mut frame = FrameBuf::new(format, header.width, header.height)?;
+        let file_planes = header.planes(&data);
+        let file_sample_bytes = header.colorspace.bytes_per_sample();
+        let frame_sample_bytes = frame_sample_bytes(format);
+        let shift = header.sample_shift(format);
+
+        for (plane, sources) in frame.planes_mut().iter_mut().zip(header.frame_planes(format)) {
+            let (width, height) = header.plane_dimensions()[sources[0]];
+            let stride = plane.stride();
+
+            for (y, row) in plane.data_mut().chunks_mut(stride).take(height as usize).enumerate() {
+                for x in 0..width as usize {
+                    for (index, &source) in sources.iter().enumerate() {
+                        let sample = read_sample(
+                            file_planes[source],
+                            y * width as usize + x,
+                            file_sample_bytes,
+                        );
+                        let frame_index = x * sources.len() + index;
+                        write_sample(row, frame_index, frame_sample_bytes, sample << shift);
+                    }
+                }
+            }
+        }
+
+        frame.set_pts(pts);
+        frame.set_duration(Some(frame_rate.timestamp_of(1)));
+        Ok(Some(frame))
+    }
+
+    /// Reads the next frame's data, or `None` at the end of the stream.
+    pub fn read_frame(&mut self) -> Result<Option<Vec<u8>>, Y4mError> {
+        let mut frame = vec![0; self.header.frame_size()];
+        Ok(self.read_frame_into(&mut frame)?.then_some(frame))
+    }
+
+    /// Reads the next frame into `frame`, which must be `frame_size` bytes.
+    /// Returns `false` at the end of the stream.
+    pub fn read_frame_into(&mut self, frame: &mut [u8]) -> Result<bool, Y4mError> {
+        let expected = self.header.frame_size();
+
+        if frame.len() != expected {
+            return Err(Y4mError::FrameSizeMismatch { expected, actual: frame.len() });
+        }
+
+        let Some(line) = read_line(&mut self.reader)? else {
+            return Ok(false);
+        };
+
+        // Per frame parameters (e.g. interlacing for `Im`) are ignored.
+        if line.split(' ').next() != Some("FRAME") {
+            return Err(Y4mError::MissingFrameMarker);
+        }
+
+        self.reader.read_exact(frame)?;
+        self.frame_count += 1;
+        Ok(true)
+    }
+}
+
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    header: Y4mHeader,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream header.
+    pub fn new(mut writer: W, header: Y4mHeader) -> Result<Self, Y4mError> {
+        header.check_dimensions()?;
+        writeln!(writer, "{}", header)?;
+        Ok(Self { writer, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<(), Y4mError> {
+        let expected = self.header.frame_size();
+
+        if frame.len() != expected {
+            return Err(Y4mError::FrameSizeMismatch { expected, actual: frame.len() });
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(frame)?;
+        Ok(())
+    }
+
+    /// Writes a frame in the header's [`Y4mHeader::pixel_format`], dropping
+    /// any row padding and rounding samples to the stream's bit depth.
+    pub fn write_video_frame(&mut self, frame: &VideoFrame) -> Result<(), Y4mError> {
+        let format = frame.format();
+
+        if self.header.pixel_format() != Some(format) {
+            return Err(Y4mError::UnsupportedPixelFormat);
+        }
+
+        let (width, height) = (self.header.width, self.header.height);
+
+        if (frame.width(), frame.height()) != (width, height) {
+            return Err(Y4mError::FrameDimensionsMismatch {
+                expected: (width, height),
+                actual: (frame.width(), frame.height()),
+            });
+        }
+
+        let header = &self.header;
+        let mut data = vec![0; header.frame_size()];
+        let file_sample_bytes = header.colorspace.bytes_per_sample();
+        let frame_sample_bytes = frame_sample_bytes(format);
+        let shift = header.sample_shift(format);
+        let max_sample = (1u32 << header.colorspace.bit_depth) - 1;
+
+        let mut rest = &mut data[..];
+        let mut file_planes = vec![];
+        for size in header.plane_sizes() {
+            let (plane, after) = mem::take(&mut rest).split_at_mut(size);
+            file_planes.push(plane);
+            rest = after;
+        }
+
+        for (plane, sources) in frame.planes().iter().zip(header.frame_planes(format)) {
+            let (plane_width, plane_height) = header.plane_dimensions()[sources[0]];
+
+            for y in 0..plane_height as usize {
+                let row = plane.row(y);
+
+                for x in 0..plane_width as usize {
+                    for (index, &source) in sources.iter().enumerate() {
+                        let sample =
+                            read_sample(row, x * sources.len() + index, frame_sample_bytes) as u32;
+                        let rounded = ((sample + ((1 << shift) >> 1)) >> shift).min(max_sample);
+                        let file_index = y * plane_width as usize + x;
+
+                        write_sample(
+                            file_planes[source],
+                            file_index,
+                            file_sample_bytes,
+                            rounded as u16,
+                        );
+                    }
+                }
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W, Y4mError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// PSNR in dB of one plane, with samples as `u8` or little-endian `u16`.
+pub fn plane_psnr(reference: &[u8], decoded: &[u8], bit_depth: u8) -> f64 {
+    let samples = |plane: &[u8]| -> Vec<f64> {
+        match bit_depth {
+            0..=8 => plane.iter().map(|&sample| sample as f64).collect(),
+            _ => plane.chunks_exact(2).map(|s| u16::from_le_bytes([s[0], s[1]]) as f64).collect(),
+        }
+    };
+
+    let (reference, decoded) = (samples(reference), samples(decoded));
+    let count = reference.len().min(decoded.len()).max(1) as f64;
+    let squared_error: f64 = reference.iter().zip(&decoded).map(|(a, b)| (a - b) * (a - b)).sum();
+
+    if squared_error == 0.0 {
+        return f64::INFINITY;
+    }
+
+    let peak = ((1u32 << bit_depth) - 1) as f64;
+    10.0 * (peak * peak / (squared_error / count)).log10()
+}
+
+/// Bytes per sample of a planar YUV or luma only format.
+fn frame_sample_bytes(format: PixelFormat) -> usize {
+    if format.bit_depth() > 8 {
+        2
+    } else {
+        1
+    }
+}
+
+/// Reads the `index`th sample, as `u8` or little-endian `u16`.
+fn read_sample(data: &[u8], index: usize, bytes: usize) -> u16 {
+    match bytes {
+        1 => data[index] as u16,
+        _ => u16::from_le_bytes([data[index * 2], data[index * 2 + 1]]),
+    }
+}
+
+fn write_sample(data: &mut [u8], index: usize, bytes: usize, sample: u16) {
+    match bytes {
+        1 => data[index] = sample as u8,
+        _ => data[index * 2..index * 2 + 2].copy_from_slice(&sample.to_le_bytes()),
+    }
+}
+
+fn parse_ratio(text: &str) -> Option<(u32, u32)> {
+    let (numerator, denominator) = text.split_once(':')?;
+    Some((numerator.parse().ok()?, denominator.parse().ok()?))
+}
+
+/// Reads a `\n` terminated line, returning `None` at a clean end of stream.
+fn read_line(reader: &mut impl Read) -> Result<Option<String>, Y4mError> {
+    let mut line = vec![];
+    let mut byte = [0];
+
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) if line.is_empty() => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        }
+
+        if line.len() > MAX_LINE_LENGTH {
+            return Err(Y4mError::LineTooLong);
+        }
+    }
+
+    String::from_utf8(line).map(Some).map_err(|error| {
+        Y4mError::InvalidParameter(String::from_utf8_lossy(error.as_bytes()).into())
+    })
+}
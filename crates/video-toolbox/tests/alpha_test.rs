@@ -0,0 +1,100 @@
+use video_toolbox::{
+    alpha::{has_alpha_layer, nal_layer_id, split_layers, AlphaChannelInfo, AlphaUse},
+    scale::{scale, ScaleFilter},
+    HevcParameterSets, PixelFormat, VideoFrame,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+/// Alpha layer (`nuh_layer_id` 1) NAL headers.
+const ALPHA_SPS: &[u8] = &[0x42, 0x09, 0x01, 0x02];
+const ALPHA_PPS: &[u8] = &[0x44, 0x09, 0x03];
+const ALPHA_SLICE: &[u8] = &[0x26, 0x09, 0xaf];
+
+/// An alpha channel information SEI: straight 8-bit alpha from 0 to 255.
+const ALPHA_SEI: &[u8] = &[0x4e, 0x01, 0xa5, 0x04, 0x10, 0x00, 0x7f, 0x90, 0x80];
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    nals.iter().flat_map(|nal| [&[0, 0, 0, 1][..], nal].concat()).collect()
+}
+
+#[test]
+fn test_nal_layer_id() {
+    assert_eq!(nal_layer_id(&[0x42, 0x01]), Some(0));
+    assert_eq!(nal_layer_id(ALPHA_SPS), Some(1));
+    // The top bit of the layer ID is in the first header byte.
+    assert_eq!(nal_layer_id(&[0x43, 0x01]), Some(32));
+    assert_eq!(nal_layer_id(&[0x42]), None);
+}
+
+#[test]
+fn test_split_layers() {
+    let base = annex_b(&[&[0x26, 0x01, 0xaf], &[0x02, 0x01, 0xd0]]);
+    let access_unit = annex_b(&[&[0x26, 0x01, 0xaf], ALPHA_SLICE, &[0x02, 0x01, 0xd0]]);
+
+    assert!(has_alpha_layer(&access_unit));
+    assert!(!has_alpha_layer(HEVC_BYTES));
+
+    let layers = split_layers(&access_unit);
+    assert_eq!(layers.base, base);
+    assert_eq!(layers.alpha, annex_b(&[ALPHA_SLICE]));
+
+    assert!(split_layers(HEVC_BYTES).alpha.is_empty());
+}
+
+#[test]
+fn test_base_layer_parameter_sets() {
+    let with_alpha = [HEVC_BYTES, &annex_b(&[ALPHA_SPS, ALPHA_PPS])].concat();
+
+    assert_eq!(
+        HevcParameterSets::from_annex_b(&with_alpha),
+        HevcParameterSets::from_annex_b(HEVC_BYTES)
+    );
+}
+
+#[test]
+fn test_alpha_channel_info() {
+    let expected = AlphaChannelInfo {
+        alpha_use: AlphaUse::Premultiplied,
+        bit_depth: 8,
+        transparent_value: 0,
+        opaque_value: 255,
+    };
+
+    assert_eq!(AlphaChannelInfo::from_sei_nal(ALPHA_SEI), Some(expected));
+    assert_eq!(
+        AlphaChannelInfo::from_access_unit(&annex_b(&[ALPHA_SPS, ALPHA_SEI, ALPHA_SLICE])),
+        Some(expected)
+    );
+
+    // alpha_channel_use_idc 0 and 2 in otherwise identical messages.
+    let straight =
+        AlphaChannelInfo::from_sei_nal(&[0x4e, 0x01, 0xa5, 0x04, 0x00, 0x00, 0x7f, 0x90, 0x80]);
+    assert_eq!(straight, Some(AlphaChannelInfo { alpha_use: AlphaUse::Straight, ..expected }));
+    let unspecified =
+        AlphaChannelInfo::from_sei_nal(&[0x4e, 0x01, 0xa5, 0x04, 0x20, 0x00, 0x7f, 0x90, 0x80]);
+    assert_eq!(unspecified.unwrap().alpha_use, AlphaUse::Unspecified);
+
+    // Cancelled, and an SEI without alpha information.
+    assert_eq!(AlphaChannelInfo::from_sei_nal(&[0x4e, 0x01, 0xa5, 0x01, 0x80, 0x80]), None);
+    assert_eq!(AlphaChannelInfo::from_sei_nal(&[0x4e, 0x01, 0x90, 0x01, 0x00, 0x80]), None);
+}
+
+#[test]
+fn test_nv12_alpha_layout() {
+    let format = PixelFormat::Nv12Alpha;
+
+    assert_eq!(PixelFormat::from_os_type(u32::from_be_bytes(*b"v0a8")), Some(format));
+    assert!(format.has_alpha() && format.is_decoder_output());
+    assert!(!PixelFormat::Nv12.has_alpha());
+    assert_eq!(format.plane_count(), 3);
+    assert_eq!((0..3).map(|plane| format.plane_width(plane, 5)).collect::<Vec<_>>(), [5, 3, 5]);
+    assert_eq!(format.buffer_size(5, 3), 15 + 6 * 2 + 15);
+
+    // The alpha plane scales like luma.
+    let mut data = vec![0; format.buffer_size(4, 4)];
+    data[24..].fill(255);
+    let frame = VideoFrame::from_packed(format, 4, 4, &data).unwrap();
+    let scaled = scale(&frame, 2, 2, ScaleFilter::Area).unwrap();
+    assert_eq!(scaled.planes()[2].data(), [255; 4]);
+}
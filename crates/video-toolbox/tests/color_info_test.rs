@@ -0,0 +1,114 @@
+use video_toolbox::{
+    color::{
+        ChromaSiting, ColorConversion, ColorInfo, ColorPrimaries, Matrix, MatrixCoefficients,
+        TransferCharacteristics,
+    },
+    mp4::init_segment,
+    scale::{scale, ScaleFilter},
+    H264ParameterSets, ParameterSets, PixelFormat, VideoFrame,
+};
+
+/// A 1280x720 baseline SPS whose VUI signals BT.2020 PQ, limited range, with
+/// top-left chroma siting.
+const H264_SPS_WITH_COLOR: &[u8] =
+    &[0x67, 0x42, 0xc0, 0x1f, 0xf4, 0x02, 0x80, 0x2d, 0xd3, 0x50, 0x91, 0x00, 0x9b, 0x60, 0x80];
+const H264_PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+const HDR10: ColorInfo = ColorInfo {
+    primaries: ColorPrimaries::Bt2020,
+    transfer: TransferCharacteristics::Pq,
+    matrix: MatrixCoefficients::Bt2020Ncl,
+    full_range: false,
+    chroma_location: ChromaSiting::TopLeft,
+};
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    data.windows(4).position(|window| window == kind).map(|start| {
+        let size = u32::from_be_bytes(data[start - 4..start].try_into().unwrap()) as usize;
+        &data[start + 4..start - 4 + size]
+    })
+}
+
+#[test]
+fn test_code_points_round_trip() {
+    for code in 0..=255u8 {
+        assert_eq!(u8::from(ColorPrimaries::from(code)), code);
+        assert_eq!(u8::from(TransferCharacteristics::from(code)), code);
+        assert_eq!(u8::from(MatrixCoefficients::from(code)), code);
+    }
+
+    assert_eq!(TransferCharacteristics::from(16), TransferCharacteristics::Pq);
+    assert_eq!(TransferCharacteristics::from(18), TransferCharacteristics::Hlg);
+    assert_eq!(MatrixCoefficients::Bt2020Ncl.conversion_matrix(), Some(Matrix::Bt2020));
+    assert_eq!(MatrixCoefficients::from(Matrix::Bt601), MatrixCoefficients::Smpte170M);
+}
+
+#[test]
+fn test_nclx_round_trip() {
+    let nclx = HDR10.to_nclx();
+    assert_eq!(nclx, *b"nclx\x00\x09\x00\x10\x00\x09\x00");
+
+    // The siting is not stored in `colr`.
+    let parsed = ColorInfo::from_nclx(&nclx).unwrap();
+    assert_eq!(parsed, ColorInfo { chroma_location: ChromaSiting::Left, ..HDR10 });
+    assert!(parsed.is_hdr());
+
+    let full_range = ColorInfo { full_range: true, ..ColorInfo::BT709 };
+    assert_eq!(ColorInfo::from_nclx(&full_range.to_nclx()), Some(full_range));
+    assert_eq!(ColorInfo::from_nclx(b"nclc\x00\x01\x00\x01\x00\x01\x00"), None);
+}
+
+#[test]
+fn test_sps_vui_color() {
+    let h264 = H264ParameterSets { sps: H264_SPS_WITH_COLOR.to_vec(), pps: H264_PPS.to_vec() };
+    let sps_info = h264.sps_info().unwrap();
+
+    assert_eq!((sps_info.width, sps_info.height), (1280, 720));
+    assert_eq!(sps_info.color, HDR10);
+    assert!(sps_info.color.is_specified());
+}
+
+#[test]
+fn test_mp4_colr_box() {
+    let parameter_sets: ParameterSets =
+        H264ParameterSets { sps: H264_SPS_WITH_COLOR.to_vec(), pps: H264_PPS.to_vec() }.into();
+    let init = init_segment(&parameter_sets, 90_000).unwrap();
+    let avc1 = find_box(&init, b"avc1").unwrap();
+    assert_eq!(find_box(avc1, b"colr").unwrap(), HDR10.to_nclx());
+
+    // Streams without colour description get no `colr` box.
+    let parameter_sets: ParameterSets = H264ParameterSets {
+        sps: vec![0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8],
+        pps: H264_PPS.to_vec(),
+    }
+    .into();
+    let init = init_segment(&parameter_sets, 90_000).unwrap();
+    assert!(find_box(&init, b"colr").is_none());
+}
+
+#[test]
+fn test_frames_carry_color() {
+    let rgba = [255, 0, 0, 255].repeat(16);
+    let frame = VideoFrame::from_packed(PixelFormat::Rgba32, 4, 4, &rgba)
+        .unwrap()
+        .with_color(ColorInfo { matrix: MatrixCoefficients::Identity, ..ColorInfo::BT709 });
+
+    let conversion = ColorConversion::new(Matrix::Bt2020, ChromaSiting::Center);
+    let yuv = conversion.convert(&frame, PixelFormat::P010FullRange).unwrap();
+    assert_eq!(
+        yuv.color(),
+        ColorInfo {
+            matrix: MatrixCoefficients::Bt2020Ncl,
+            full_range: true,
+            chroma_location: ChromaSiting::Center,
+            ..ColorInfo::BT709
+        }
+    );
+
+    let scaled = scale(&yuv.as_frame(), 2, 2, ScaleFilter::Bilinear).unwrap();
+    assert_eq!(scaled.color(), yuv.color());
+
+    let rgb = yuv.color().color_conversion().convert(&yuv.as_frame(), PixelFormat::Bgra32).unwrap();
+    assert_eq!(rgb.color().matrix, MatrixCoefficients::Identity);
+    assert_eq!(rgb.color().primaries, ColorPrimaries::Bt709);
+}
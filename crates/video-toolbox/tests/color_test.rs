@@ -0,0 +1,165 @@
+use video_toolbox::{
+    color::{ChromaSiting, ColorConversion, ConvertError, Matrix},
+    FrameBuf, PixelFormat, VideoFrame,
+};
+
+/// A 2x2 frame of one RGBA colour.
+fn solid_rgba(r: u8, g: u8, b: u8) -> Vec<u8> {
+    [r, g, b, 255].repeat(4)
+}
+
+fn convert(data: &[u8], from: PixelFormat, to: PixelFormat, matrix: Matrix) -> Vec<u8> {
+    let frame = VideoFrame::from_packed(from, 2, 2, data).unwrap();
+    let conversion = ColorConversion::new(matrix, ChromaSiting::Left);
+    conversion.convert(&frame, to).unwrap().to_packed()
+}
+
+#[test]
+fn test_reference_colours() {
+    // Y, Cb, Cr of an I420 2x2 frame.
+    let yuv = |data: Vec<u8>| (data[0], data[4], data[5]);
+
+    let cases = [
+        (Matrix::Bt709, (255, 255, 255), (235, 128, 128)),
+        (Matrix::Bt709, (0, 0, 0), (16, 128, 128)),
+        (Matrix::Bt709, (255, 0, 0), (63, 102, 240)),
+        (Matrix::Bt709, (0, 255, 0), (173, 42, 26)),
+        (Matrix::Bt709, (0, 0, 255), (32, 240, 118)),
+        (Matrix::Bt601, (255, 0, 0), (81, 90, 240)),
+        (Matrix::Bt601, (0, 255, 0), (145, 54, 34)),
+        (Matrix::Bt601, (0, 0, 255), (41, 240, 110)),
+    ];
+
+    for (matrix, (r, g, b), expected) in cases {
+        let data = solid_rgba(r, g, b);
+        assert_eq!(
+            yuv(convert(&data, PixelFormat::Rgba32, PixelFormat::I420, matrix)),
+            expected,
+            "{:?} {:?}",
+            matrix,
+            (r, g, b)
+        );
+    }
+
+    // Full range keeps the whole 0-255 scale.
+    let data = solid_rgba(255, 0, 0);
+    let full = convert(&data, PixelFormat::Rgba32, PixelFormat::I420FullRange, Matrix::Bt709);
+    assert_eq!(yuv(full), (54, 99, 255));
+}
+
+#[test]
+fn test_p010_bt2020() {
+    let data = solid_rgba(255, 0, 0);
+    let p010 = convert(&data, PixelFormat::Rgba32, PixelFormat::P010, Matrix::Bt2020);
+
+    // 10-bit values in the high bits of little-endian 16-bit samples.
+    let sample = |index: usize| u16::from_le_bytes([p010[index * 2], p010[index * 2 + 1]]) >> 6;
+    assert_eq!((sample(0), sample(4), sample(5)), (294, 387, 960));
+    assert_eq!(p010[..2], [0x80, 0x49]);
+
+    // Back to 8-bit RGB.
+    let rgba = convert(&p010, PixelFormat::P010, PixelFormat::Rgba32, Matrix::Bt2020);
+    assert_eq!(rgba, data);
+}
+
+#[test]
+fn test_packed_rgb_orders() {
+    let rgba = [1, 2, 3, 4].repeat(4);
+    let bgra = convert(&rgba, PixelFormat::Rgba32, PixelFormat::Bgra32, Matrix::Bt709);
+    assert_eq!(bgra[..4], [3, 2, 1, 4]);
+
+    let argb = convert(&bgra, PixelFormat::Bgra32, PixelFormat::Argb32, Matrix::Bt709);
+    assert_eq!(argb[..4], [4, 1, 2, 3]);
+
+    // Opaque alpha from YUV.
+    let nv12 = [16, 16, 16, 16, 128, 128];
+    let from_yuv = convert(&nv12, PixelFormat::Nv12, PixelFormat::Argb32, Matrix::Bt709);
+    assert_eq!(from_yuv[..4], [255, 0, 0, 0]);
+}
+
+#[test]
+fn test_yuv_to_yuv_keeps_chroma() {
+    let width = 6;
+    let height = 4;
+    let luma: Vec<u8> = (0..24).map(|i| 16 + i * 9).collect();
+    let cb: Vec<u8> = (0..6).map(|i| 40 + i * 30).collect();
+    let cr: Vec<u8> = (0..6).map(|i| 220 - i * 30).collect();
+
+    let mut i420 = luma.clone();
+    i420.extend_from_slice(&cb);
+    i420.extend_from_slice(&cr);
+
+    let conversion = ColorConversion::default();
+    let frame = VideoFrame::from_packed(PixelFormat::I420, width, height, &i420).unwrap();
+    let nv12 = conversion.convert(&frame, PixelFormat::Nv12).unwrap();
+
+    let interleaved: Vec<u8> = cb.iter().zip(&cr).flat_map(|(&cb, &cr)| [cb, cr]).collect();
+    assert_eq!(nv12.planes()[0].data(), luma);
+    assert_eq!(nv12.planes()[1].data(), interleaved);
+
+    // 8 to 10 bits is a lossless shift in video range.
+    let p010 = conversion.convert(&nv12.as_frame(), PixelFormat::P010).unwrap();
+    let back = conversion.convert(&p010.as_frame(), PixelFormat::I420).unwrap();
+    assert_eq!(back.to_packed(), i420);
+}
+
+#[test]
+fn test_gradient_round_trip() {
+    let (width, height) = (33, 17);
+    let mut rgba = vec![];
+
+    for y in 0..height {
+        for x in 0..width {
+            rgba.extend_from_slice(&[(x * 5) as u8, (y * 9) as u8, 128, 255]);
+        }
+    }
+
+    let frame = VideoFrame::from_packed(PixelFormat::Rgba32, width, height, &rgba).unwrap();
+
+    for siting in [ChromaSiting::Left, ChromaSiting::Center, ChromaSiting::TopLeft] {
+        for format in [PixelFormat::Nv12FullRange, PixelFormat::I420, PixelFormat::P010] {
+            let conversion = ColorConversion::new(Matrix::Bt709, siting);
+            let yuv = conversion.convert(&frame, format).unwrap();
+            let back = conversion.convert(&yuv.as_frame(), PixelFormat::Rgba32).unwrap();
+
+            // A smooth gradient survives chroma subsampling almost unchanged,
+            // apart from the edges where chroma is extrapolated.
+            let packed = back.to_packed();
+            let error = |x: u32, y: u32| {
+                let index = ((y * width + x) * 4) as usize;
+                (0..4).map(|c| packed[index + c].abs_diff(rgba[index + c])).max().unwrap()
+            };
+
+            let interior = (1..height - 1)
+                .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+                .map(|(x, y)| error(x, y))
+                .max()
+                .unwrap();
+            let edges = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| error(x, y))
+                .max()
+                .unwrap();
+
+            assert!(interior <= 2, "{:?} {:?}: {}", siting, format, interior);
+            assert!(edges <= 5, "{:?} {:?}: {}", siting, format, edges);
+        }
+    }
+}
+
+#[test]
+fn test_errors() {
+    let conversion = ColorConversion::default();
+    let frame = FrameBuf::new(PixelFormat::Nv12, 4, 4).unwrap();
+
+    assert_eq!(
+        conversion.convert(&frame.as_frame(), PixelFormat::Yuyv).unwrap_err(),
+        ConvertError::UnsupportedFormat(PixelFormat::Yuyv)
+    );
+
+    let mut small = FrameBuf::new(PixelFormat::Bgra32, 2, 2).unwrap();
+    assert!(matches!(
+        conversion.convert_into(&frame.as_frame(), &mut small),
+        Err(ConvertError::Frame(_))
+    ));
+}
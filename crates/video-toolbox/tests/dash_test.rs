@@ -0,0 +1,215 @@
+use std::time::{Duration, UNIX_EPOCH};
+use video_toolbox::{
+    dash::{
+        AdaptationSet, DashError, Mpd, MpdType, Representation, SegmentTemplate, TimelineEntry,
+    },
+    H264ParameterSets, ParameterSets, VideoCodec,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+fn hevc_parameter_sets() -> ParameterSets {
+    ParameterSets::from_annex_b(VideoCodec::Hevc, HEVC_BYTES).unwrap()
+}
+
+#[test]
+fn test_codec_strings() {
+    assert_eq!(hevc_parameter_sets().codec_string().as_deref(), Some("hvc1.1.6.L150.B0"));
+
+    let h264: ParameterSets = H264ParameterSets {
+        sps: vec![0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8],
+        pps: vec![0x68, 0xce, 0x3c, 0x80],
+    }
+    .into();
+    assert_eq!(h264.codec_string().as_deref(), Some("avc1.42c01f"));
+}
+
+#[test]
+fn test_static_mpd_round_trip() {
+    let mut template = SegmentTemplate::new(90_000);
+    for (start, duration) in
+        [(0, 180_000), (180_000, 180_000), (360_000, 180_000), (540_000, 90_000)]
+    {
+        template.push_segment(start, duration);
+    }
+
+    assert_eq!(
+        template.timeline,
+        [
+            TimelineEntry { start: 0, duration: 180_000, repeat: Some(2) },
+            TimelineEntry { start: 540_000, duration: 90_000, repeat: Some(0) },
+        ]
+    );
+    assert_eq!(template.duration(), Duration::from_secs(7));
+
+    let parameter_sets = hevc_parameter_sets();
+    let mut adaptation_set = AdaptationSet::new(0, template);
+    adaptation_set.representations = vec![
+        Representation::new("high", 6_000_000, &parameter_sets).unwrap(),
+        Representation::new("low", 1_500_000, &parameter_sets).unwrap(),
+    ];
+
+    let mut mpd = Mpd::new(MpdType::Static, adaptation_set);
+    mpd.media_presentation_duration = Some(Duration::from_secs(7));
+
+    let text = mpd.to_string();
+    assert!(
+        text.contains(r#"type="static" minBufferTime="PT2S" mediaPresentationDuration="PT7S">"#)
+    );
+    assert!(text.contains(r#"<S t="0" d="180000" r="2"/>"#));
+    assert!(text.contains(
+        r#"<Representation id="high" bandwidth="6000000" codecs="hvc1.1.6.L150.B0" width="1280" height="720"/>"#
+    ));
+
+    assert_eq!(Mpd::parse(&text).unwrap(), mpd);
+}
+
+#[test]
+fn test_dynamic_mpd_window() {
+    let mut template = SegmentTemplate::new(90_000);
+    for i in 0..10 {
+        template.push_segment(i * 180_000, 180_000);
+    }
+
+    // Keep a 6 second time shift buffer behind the live edge at 20 seconds.
+    template.remove_before(20 * 90_000 - 6 * 90_000);
+    assert_eq!(
+        template.timeline,
+        [TimelineEntry { start: 1_260_000, duration: 180_000, repeat: Some(2) }]
+    );
+
+    let first = template.segments().next().unwrap();
+    assert_eq!(template.media_url("high", first.0), "segment-high-1260000.m4s");
+    assert_eq!(template.initialization_url("high"), "init-high.mp4");
+
+    let mut adaptation_set = AdaptationSet::new(0, template);
+    adaptation_set.representations =
+        vec![Representation::new("high", 6_000_000, &hevc_parameter_sets()).unwrap()];
+
+    let availability_start_time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut mpd = Mpd::new(MpdType::Dynamic { availability_start_time }, adaptation_set);
+    mpd.publish_time = Some(availability_start_time + Duration::from_millis(20_500));
+    mpd.minimum_update_period = Some(Duration::from_secs(2));
+    mpd.time_shift_buffer_depth = Some(Duration::from_secs(90));
+
+    let text = mpd.to_string();
+    assert!(text.contains(r#"type="dynamic""#));
+    assert!(text.contains(r#"timeShiftBufferDepth="PT1M30S""#));
+    assert!(text.contains(r#"availabilityStartTime="2023-11-14T22:13:20.000Z""#));
+    assert!(text.contains(r#"publishTime="2023-11-14T22:13:40.500Z""#));
+
+    assert_eq!(Mpd::parse(&text).unwrap(), mpd);
+}
+
+#[test]
+fn test_parse_external_mpd() {
+    let text = r#"<?xml version="1.0"?>
+        <!-- Generated elsewhere -->
+        <MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" minBufferTime="PT1.500S"
+             mediaPresentationDuration="PT0H0M12.0S">
+          <Period id="p0">
+            <AdaptationSet mimeType="video/mp4" codecs="avc1.64001f">
+              <SegmentTemplate timescale="1000" media="v_$Time$.m4s?a=1&amp;b=2"
+                               initialization="v_init.mp4">
+                <SegmentTimeline>
+                  <S t="1000" d="4000" r="1"/>
+                  <S d="4000"/>
+                </SegmentTimeline>
+              </SegmentTemplate>
+              <Representation id="v1" bandwidth="800000" width="640" height="360">
+                <BaseURL>ignored/</BaseURL>
+              </Representation>
+            </AdaptationSet>
+          </Period>
+        </MPD>"#;
+
+    let mpd = Mpd::parse(text).unwrap();
+    assert_eq!(mpd.min_buffer_time, Duration::from_millis(1500));
+    assert_eq!(mpd.media_presentation_duration, Some(Duration::from_secs(12)));
+
+    let adaptation_set = &mpd.periods[0].adaptation_sets[0];
+    let template = &adaptation_set.segment_template;
+    assert_eq!(template.media, "v_$Time$.m4s?a=1&b=2");
+    assert_eq!(template.segments().collect::<Vec<_>>(), [(1000, 4000), (5000, 4000), (9000, 4000)]);
+
+    let representation = &adaptation_set.representations[0];
+    assert_eq!(representation.codecs, "avc1.64001f");
+    assert_eq!((representation.width, representation.height), (640, 360));
+
+    assert!(Mpd::parse("<MPD><Period></MPD>").is_err());
+    assert!(Mpd::parse(r#"<MPD type="static"/>"#).is_err());
+}
+
+/// A dynamic MPD holding `timeline` in a template with `timescale`.
+fn live_mpd(timescale: &str, timeline: &str) -> String {
+    format!(
+        r#"<MPD type="dynamic" minBufferTime="PT2S" availabilityStartTime="2023-11-14T22:13:20Z">
+          <Period id="0">
+            <AdaptationSet>
+              <SegmentTemplate timescale="{}" media="$Time$.m4s">
+                <SegmentTimeline>{}</SegmentTimeline>
+              </SegmentTemplate>
+            </AdaptationSet>
+          </Period>
+        </MPD>"#,
+        timescale, timeline
+    )
+}
+
+#[test]
+fn test_open_ended_timeline() {
+    let text = live_mpd("90000", r#"<S t="0" d="180000" r="-1"/><S t="900000" d="90000" r="-1"/>"#);
+    let mpd = Mpd::parse(&text).unwrap();
+    assert_eq!(
+        mpd.mpd_type,
+        MpdType::Dynamic {
+            availability_start_time: UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        }
+    );
+
+    // The first entry repeats up to the next one's start, the last to the live edge.
+    let template = &mpd.periods[0].adaptation_sets[0].segment_template;
+    assert_eq!(
+        template.timeline,
+        [
+            TimelineEntry { start: 0, duration: 180_000, repeat: Some(4) },
+            TimelineEntry { start: 900_000, duration: 90_000, repeat: None },
+        ]
+    );
+
+    let mut template = template.clone();
+    template.remove_before(360_000);
+    assert_eq!(
+        template.timeline[0],
+        TimelineEntry { start: 360_000, duration: 180_000, repeat: Some(2) }
+    );
+    assert_eq!(template.timeline[1].repeat, None);
+
+    let text = mpd.to_string();
+    assert!(text.contains(r#"<S t="900000" d="90000" r="-1"/>"#));
+    assert_eq!(Mpd::parse(&text).unwrap(), mpd);
+}
+
+#[test]
+fn test_invalid_live_mpd() {
+    let text = r#"<MPD type="dynamic" minBufferTime="PT2S"><Period id="0"/></MPD>"#;
+    assert!(matches!(Mpd::parse(text), Err(DashError::Missing("availabilityStartTime"))));
+
+    assert!(matches!(
+        Mpd::parse(&live_mpd("0", r#"<S t="0" d="1"/>"#)),
+        Err(DashError::InvalidValue("timescale"))
+    ));
+
+    for (timeline, error) in [
+        (r#"<S t="0" d="0"/>"#, DashError::InvalidValue("d")),
+        (r#"<S t="0" d="1" r="-2"/>"#, DashError::InvalidValue("r")),
+        // An open-ended entry must be followed by an explicit start.
+        (r#"<S t="0" d="1" r="-1"/><S d="1"/>"#, DashError::Missing("t")),
+        (r#"<S t="10" d="1" r="-1"/><S t="10" d="1"/>"#, DashError::InvalidValue("r")),
+        (r#"<S t="0" d="18446744073709551615" r="1"/>"#, DashError::InvalidValue("S")),
+        (r#"<S t="18446744073709551615" d="1"/>"#, DashError::InvalidValue("S")),
+    ] {
+        let result = Mpd::parse(&live_mpd("1000", timeline));
+        assert_eq!(result.unwrap_err().to_string(), error.to_string(), "{}", timeline);
+    }
+}
@@ -1,4 +1,9 @@
-use video_toolbox::Decoder;
+#![cfg(any(target_os = "macos", target_os = "ios"))]
+
+use video_toolbox::{
+    sdp::VideoMediaDescription, Decoder, DecoderConfig, Encoder, FrameBuf, HevcParameterSets,
+    PixelFormat, VideoCodec,
+};
 
 #[test]
 fn test_decode() {
@@ -7,9 +12,73 @@ fn test_decode() {
     let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
 
     let mut decoder = Decoder::new(width, height).unwrap();
-    let mut dst = vec![0u8; width as usize * height as usize * 4];
+    let mut dst = FrameBuf::new(PixelFormat::Bgra32, width, height).unwrap();
+
+    decoder.decode_blocking(hevc_bytes, &mut dst).unwrap();
+
+    assert_eq!((dst.width(), dst.height()), (width, height));
+    assert_eq!(decoder.sps_info().map(|sps_info| sps_info.bit_depth_luma), Some(8));
+    println!("Decoded stride: {}", dst.planes()[0].stride());
+}
+
+#[test]
+fn test_decode_nv12() {
+    let width = 1280;
+    let height = 720;
+    let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+    let config = DecoderConfig { output_format: PixelFormat::Nv12, ..Default::default() };
+    let mut decoder = Decoder::with_config(width, height, config).unwrap();
+    let mut dst = FrameBuf::new(PixelFormat::Bgra32, width, height).unwrap();
+
+    decoder.decode_blocking(hevc_bytes, &mut dst).unwrap();
+
+    assert_eq!(dst.format(), PixelFormat::Nv12);
+    assert_eq!(dst.planes().len(), 2);
+    assert_eq!(dst.planes()[1].data().len(), 1280 * 360);
+}
+
+#[test]
+fn test_decode_alpha() {
+    let width = 1280;
+    let height = 720;
+
+    // Transparent left half, opaque right half.
+    let mut src = FrameBuf::new(PixelFormat::Bgra32, width, height).unwrap();
+    for row in src.planes_mut()[0].data_mut().chunks_exact_mut(width as usize * 4) {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            pixel.copy_from_slice(&[0, 128, 255, if x < 640 { 0 } else { 255 }]);
+        }
+    }
+
+    let mut encoder = Encoder::with_alpha(width, height, 1.0).unwrap();
+    let mut encoded = vec![0u8; width as usize * height as usize * 4];
+    let encoded_size = encoder.encode_blocking(&src.as_frame(), &mut encoded).unwrap().size;
+
+    let config = DecoderConfig { output_format: PixelFormat::Nv12Alpha, ..Default::default() };
+    let mut decoder = Decoder::with_config(width, height, config).unwrap();
+    let mut dst = FrameBuf::new(PixelFormat::Bgra32, width, height).unwrap();
+
+    decoder.decode_blocking(&encoded[..encoded_size], &mut dst).unwrap();
+
+    assert_eq!(dst.format(), PixelFormat::Nv12Alpha);
+    let alpha = dst.planes()[2].data();
+    assert!(alpha[100] < 16 && alpha[1200] > 240);
+}
+
+#[test]
+fn test_decoder_from_sdp() {
+    let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
+    let parameter_sets = HevcParameterSets::from_annex_b(hevc_bytes).unwrap();
+    let media = VideoMediaDescription::hevc(96, &parameter_sets);
 
-    let decoded_size = decoder.decode_blocking(hevc_bytes, &mut dst).unwrap();
+    let mut decoder =
+        Decoder::from_parameter_sets(&media.parameter_sets().unwrap(), DecoderConfig::default())
+            .unwrap();
+    assert_eq!(decoder.codec(), VideoCodec::Hevc);
+    assert_eq!((decoder.width(), decoder.height()), (1280, 720));
 
-    println!("Decoded size: {}", decoded_size);
+    let mut dst = FrameBuf::new(PixelFormat::Bgra32, 1280, 720).unwrap();
+    decoder.decode_blocking(hevc_bytes, &mut dst).unwrap();
+    assert_eq!((dst.width(), dst.height()), (1280, 720));
 }
@@ -1,4 +1,10 @@
-use video_toolbox::Encoder;
+#![cfg(any(target_os = "macos", target_os = "ios"))]
+
+use video_toolbox::{
+    alpha::has_alpha_layer, EncodeError, EncodePipeline, Encoder, EncoderConfig,
+    EncoderConfigError, EncoderUpdate, FrameBuf, FrameRate, HevcProfile, PacketFraming,
+    PictureType, PixelFormat, Plane, Timestamp, VideoFrame,
+};
 
 #[test]
 fn test_encode() {
@@ -7,22 +13,220 @@ fn test_encode() {
 
     let mut encoder = Encoder::new(width, height).unwrap();
 
-    let src_frame = make_image_frame(width as usize, height as usize);
+    let src_frame = make_image_frame(width, height);
     let mut dst = vec![0u8; width as usize * height as usize * 4];
 
-    let encoded_size = encoder.encode_blocking(&src_frame, &mut dst).unwrap();
+    let encoded_size = encoder.encode_blocking(&src_frame.as_frame(), &mut dst).unwrap().size;
     println!("Encoded size for frame 1: {}", encoded_size);
 
-    let encoded_size = encoder.encode_blocking(&src_frame, &mut dst).unwrap();
+    let encoded_size = encoder.encode_blocking(&src_frame.as_frame(), &mut dst).unwrap().size;
     println!("Encoded size for frame 2: {}", encoded_size);
 }
 
-fn make_image_frame(width: usize, height: usize) -> Vec<u8> {
-    let mut frame = vec![0u8; width * height * 4];
+#[test]
+fn test_encode_timestamps() {
+    let width = 1280;
+    let height = 720;
+
+    let mut encoder = Encoder::with_config(width, height, EncoderConfig::low_latency()).unwrap();
+    let src_frame = make_image_frame(width, height);
+    let mut dst = vec![0u8; width as usize * height as usize * 4];
+    let frame_rate = FrameRate::new(30000, 1001);
+
+    for index in 0..3 {
+        let frame = src_frame
+            .as_frame()
+            .with_pts(frame_rate.timestamp_of(index))
+            .with_duration(frame_rate.timestamp_of(1));
+        let encoded = encoder.encode_blocking(&frame, &mut dst).unwrap();
+
+        assert_eq!(encoded.pts, Timestamp::new(1001 * index as i64, 30000));
+        assert_eq!(encoded.dts, encoded.pts);
+        assert_eq!(encoded.duration, Some(Timestamp::new(1001, 30000)));
+    }
+}
+
+#[test]
+fn test_encode_packets() {
+    let width = 1280;
+    let height = 720;
+
+    let config = EncoderConfig::low_latency().with_framing(PacketFraming::LengthPrefixed);
+    let mut encoder = Encoder::with_config(width, height, config).unwrap();
+    let src_frame = make_image_frame(width, height);
+    let frame_rate = FrameRate::new(30, 1);
+
+    let packets: Vec<_> = (0..3)
+        .map(|index| {
+            let frame = src_frame.as_frame().with_pts(frame_rate.timestamp_of(index));
+            encoder.encode(&frame).unwrap()
+        })
+        .collect();
+
+    let first = &packets[0];
+    assert_eq!(first.framing, PacketFraming::LengthPrefixed);
+    assert!(first.is_keyframe);
+    assert_eq!(first.picture_type, Some(PictureType::I));
+    assert!(first.format_changed);
+    assert!(first.parameter_sets.is_some());
+    // Length-prefixed packets carry no start codes.
+    assert_ne!(first.data[..4], [0, 0, 0, 1]);
+
+    for (index, packet) in packets.iter().enumerate().skip(1) {
+        assert!(!packet.format_changed);
+        assert_eq!(packet.temporal_id, 0);
+        assert_eq!(packet.pts, frame_rate.timestamp_of(index as u64));
+        assert_eq!(packet.dts, packet.pts);
+    }
+}
+
+#[test]
+fn test_encode_pipeline() {
+    let width = 1280;
+    let height = 720;
+
+    let encoder = Encoder::with_config(width, height, EncoderConfig::low_latency()).unwrap();
+    let (mut pipeline, packets) = EncodePipeline::with_channel(encoder, 4);
+    let frame_rate = FrameRate::new(30, 1);
+
+    for index in 0..10 {
+        // Submitted frames are copied, so this one can be dropped right away.
+        let src_frame = make_image_frame(width, height);
+        let frame = src_frame.as_frame().with_pts(frame_rate.timestamp_of(index));
+        pipeline.submit(&frame).unwrap();
+        assert!(pipeline.frames_in_flight() <= 4);
+    }
+
+    let encoder = pipeline.finish().unwrap();
+    assert_eq!(encoder.width(), width);
+
+    let packets: Vec<_> = packets.try_iter().map(Result::unwrap).collect();
+    assert_eq!(packets.len(), 10);
+    assert!(packets[0].is_keyframe);
+
+    for pair in packets.windows(2) {
+        assert!(pair[0].dts < pair[1].dts);
+    }
+}
+
+#[test]
+fn test_encode_with_config() {
+    let width = 1280;
+    let height = 720;
+
+    let config = EncoderConfig::low_latency()
+        .with_profile(HevcProfile::Main)
+        .with_average_bit_rate(2_000_000)
+        .with_max_keyframe_interval(30)
+        .with_expected_frame_rate(FrameRate::new(30, 1));
+    let mut encoder = Encoder::with_config(width, height, config.clone()).unwrap();
+    assert_eq!(encoder.config(), &config);
+
+    let src_frame = make_image_frame(width, height);
+    let mut dst = vec![0u8; width as usize * height as usize * 4];
+
+    let encoded_size = encoder.encode_blocking(&src_frame.as_frame(), &mut dst).unwrap().size;
+    assert!(encoded_size > 0);
+}
+
+#[test]
+fn test_encoder_update() {
+    let width = 1280;
+    let height = 720;
+
+    let config = EncoderConfig::low_latency().with_average_bit_rate(4_000_000);
+    let mut encoder = Encoder::with_config(width, height, config).unwrap();
+
+    // The profile can change until the first frame, as with `set_profile`.
+    encoder.update(&EncoderUpdate::new().with_profile(HevcProfile::Main10)).unwrap();
+    assert_eq!(encoder.profile(), HevcProfile::Main10);
+    encoder.update(&EncoderUpdate::new().with_profile(HevcProfile::Main)).unwrap();
+
+    let src_frame = make_image_frame(width, height);
+    let mut dst = vec![0u8; width as usize * height as usize * 4];
+    encoder.encode_blocking(&src_frame.as_frame(), &mut dst).unwrap();
+
+    let update = EncoderUpdate::new()
+        .with_average_bit_rate(1_000_000)
+        .with_expected_frame_rate(FrameRate::new(15, 1));
+    encoder.update(&update).unwrap();
+    encoder.encode_blocking(&src_frame.as_frame(), &mut dst).unwrap();
+
+    assert_eq!(encoder.config().average_bit_rate, Some(1_000_000));
+    assert_eq!(encoder.history().len(), 3);
+    assert_eq!(encoder.history()[2].frame, 1);
+
+    let error = encoder.update(&EncoderUpdate::new().with_frame_reordering(true)).unwrap_err();
+    assert!(matches!(
+        error,
+        EncodeError::InvalidConfig(EncoderConfigError::RequiresNewSession("AllowFrameReordering"))
+    ));
+
+    let error =
+        encoder.update(&EncoderUpdate::new().with_profile(HevcProfile::Main10)).unwrap_err();
+    assert!(matches!(
+        error,
+        EncodeError::InvalidConfig(EncoderConfigError::RequiresNewSession("ProfileLevel"))
+    ));
+    assert_eq!(encoder.history().len(), 3);
+}
+
+#[test]
+fn test_encode_nv12() {
+    let width = 1280;
+    let height = 720;
+
+    let mut encoder = Encoder::new(width, height).unwrap();
+
+    // Luma rows padded out to a 64 byte aligned stride.
+    let luma = vec![128u8; 1344 * 720];
+    let chroma = vec![128u8; 1280 * 360];
+    let frame = VideoFrame::new(
+        PixelFormat::Nv12,
+        width,
+        height,
+        vec![Plane::new(&luma, 1344), Plane::new(&chroma, 1280)],
+    )
+    .unwrap();
+
+    let mut dst = vec![0u8; width as usize * height as usize * 4];
+    let encoded_size = encoder.encode_blocking(&frame, &mut dst).unwrap().size;
+    assert!(encoded_size > 0);
+}
+
+#[test]
+fn test_encode_alpha() {
+    let width = 1280;
+    let height = 720;
+
+    let mut encoder = Encoder::with_alpha(width, height, 0.9).unwrap();
+    assert!(encoder.has_alpha());
+
+    let mut src_frame = make_image_frame(width, height);
+    let plane = &mut src_frame.planes_mut()[0];
+
+    // Fade the alpha out across each row.
+    for row in plane.data_mut().chunks_exact_mut(width as usize * 4) {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            pixel[0] = (255 - x * 255 / width as usize) as u8;
+        }
+    }
+
+    let mut dst = vec![0u8; width as usize * height as usize * 4];
+    let encoded_size = encoder.encode_blocking(&src_frame.as_frame(), &mut dst).unwrap().size;
+    assert!(has_alpha_layer(&dst[..encoded_size]));
+}
+
+fn make_image_frame(width: u32, height: u32) -> FrameBuf {
+    let mut frame_buf = FrameBuf::new(PixelFormat::Argb32, width, height).unwrap();
+    let plane = &mut frame_buf.planes_mut()[0];
+    let stride = plane.stride();
+    let frame = plane.data_mut();
+    let (width, height) = (width as usize, height as usize);
 
     for y in 0..height {
         for x in 0..width {
-            let pixel_offset = (y * width * 4) + (x * 4);
+            let pixel_offset = (y * stride) + (x * 4);
 
             let width_factor = x as f32 / width as f32;
             let height_factor = y as f32 / height as f32;
@@ -34,5 +238,5 @@ fn make_image_frame(width: usize, height: usize) -> Vec<u8> {
         }
     }
 
-    frame
+    frame_buf
 }
@@ -0,0 +1,153 @@
+use std::time::Duration;
+use video_toolbox::{
+    DataRateLimit, EncoderConfig, EncoderConfigError, EncoderProperty, EncoderUpdate, FrameRate,
+    HevcProfile,
+};
+
+#[test]
+fn test_default_sets_nothing() {
+    assert_eq!(EncoderConfig::default().properties(), Ok(vec![]));
+}
+
+#[test]
+fn test_properties() {
+    let config = EncoderConfig::new()
+        .with_profile(HevcProfile::Main10)
+        .with_average_bit_rate(4_000_000)
+        .with_data_rate_limit(DataRateLimit::new(1_000_000, Duration::from_secs(1)))
+        .with_data_rate_limit(DataRateLimit::new(300_000, Duration::from_millis(250)))
+        .with_quality(0.75)
+        .with_max_keyframe_interval(60)
+        .with_max_keyframe_interval_duration(Duration::from_secs(2))
+        .with_real_time(true)
+        .with_frame_reordering(false)
+        .with_expected_frame_rate(FrameRate::new(30000, 1001))
+        .with_max_frame_delay(0);
+
+    let properties = config.properties().unwrap();
+
+    assert_eq!(
+        properties,
+        [
+            EncoderProperty::ProfileLevel(HevcProfile::Main10),
+            EncoderProperty::AverageBitRate(4_000_000),
+            EncoderProperty::DataRateLimits(vec![(1_000_000, 1.0), (300_000, 0.25)]),
+            EncoderProperty::Quality(0.75),
+            EncoderProperty::MaxKeyFrameInterval(60),
+            EncoderProperty::MaxKeyFrameIntervalDuration(2.0),
+            EncoderProperty::RealTime(true),
+            EncoderProperty::AllowFrameReordering(false),
+            EncoderProperty::ExpectedFrameRate(30000.0 / 1001.0),
+            EncoderProperty::MaxFrameDelayCount(0),
+        ]
+    );
+
+    let keys: Vec<&str> = properties.iter().map(EncoderProperty::key).collect();
+    assert_eq!(keys[..3], ["ProfileLevel", "AverageBitRate", "DataRateLimits"]);
+    assert_eq!(keys[9], "MaxFrameDelayCount");
+}
+
+#[test]
+fn test_low_latency() {
+    let config = EncoderConfig::low_latency();
+
+    assert_eq!(config.real_time, Some(true));
+    assert_eq!(config.allow_frame_reordering, Some(false));
+    assert_eq!(config.max_frame_delay, Some(0));
+    assert_eq!(config.average_bit_rate, None);
+}
+
+#[test]
+fn test_invalid_config() {
+    assert_eq!(
+        EncoderConfig::new().with_quality(1.5).properties(),
+        Err(EncoderConfigError::InvalidQuality(1.5))
+    );
+    assert_eq!(
+        EncoderConfig::new()
+            .with_data_rate_limit(DataRateLimit::new(1, Duration::ZERO))
+            .properties(),
+        Err(EncoderConfigError::InvalidDataRateLimit)
+    );
+    assert_eq!(
+        EncoderConfig::new().with_expected_frame_rate(FrameRate::new(30, 0)).properties(),
+        Err(EncoderConfigError::InvalidFrameRate(30, 0))
+    );
+}
+
+#[test]
+fn test_update() {
+    let mut config = EncoderConfig::low_latency()
+        .with_average_bit_rate(4_000_000)
+        .with_data_rate_limit(DataRateLimit::new(1_000_000, Duration::from_secs(1)));
+
+    let update = EncoderUpdate::new()
+        .with_average_bit_rate(1_500_000)
+        .with_expected_frame_rate(FrameRate::new(15, 1))
+        .with_max_keyframe_interval(30)
+        // Unchanged fixed settings are fine.
+        .with_frame_reordering(false);
+
+    assert_eq!(
+        update.apply(&mut config),
+        Ok(vec![
+            EncoderProperty::AverageBitRate(1_500_000),
+            EncoderProperty::MaxKeyFrameInterval(30),
+            EncoderProperty::ExpectedFrameRate(15.0),
+        ])
+    );
+    assert_eq!(config.average_bit_rate, Some(1_500_000));
+    assert_eq!(config.expected_frame_rate, Some(FrameRate::new(15, 1)));
+    assert_eq!(config.data_rate_limits.len(), 1);
+    assert_eq!(config.real_time, Some(true));
+
+    // An empty list clears the limits.
+    let update = EncoderUpdate::new().with_data_rate_limits(vec![]);
+    assert_eq!(update.apply(&mut config), Ok(vec![EncoderProperty::DataRateLimits(vec![])]));
+    assert!(config.data_rate_limits.is_empty());
+}
+
+#[test]
+fn test_update_rejects_fixed_settings() {
+    let mut config = EncoderConfig::low_latency().with_profile(HevcProfile::Main);
+    let before = config.clone();
+
+    let cases = [
+        (EncoderUpdate::new().with_profile(HevcProfile::Main10), "ProfileLevel"),
+        (EncoderUpdate::new().with_frame_reordering(true), "AllowFrameReordering"),
+        (EncoderUpdate::new().with_max_frame_delay(2), "MaxFrameDelayCount"),
+    ];
+
+    for (update, key) in cases {
+        let update = update.with_average_bit_rate(1_000_000);
+        assert_eq!(update.apply(&mut config), Err(EncoderConfigError::RequiresNewSession(key)));
+    }
+
+    assert_eq!(
+        EncoderUpdate::new().with_average_bit_rate(1).with_quality(2.0).apply(&mut config),
+        Err(EncoderConfigError::InvalidQuality(2.0))
+    );
+    assert_eq!(config, before);
+
+    // Restating the default profile is not a change.
+    let mut config = EncoderConfig::default();
+    let update = EncoderUpdate::new().with_profile(HevcProfile::Main);
+    assert_eq!(update.apply(&mut config), Ok(vec![]));
+
+    let update = EncoderUpdate::new().with_profile(HevcProfile::Main10);
+    assert_eq!(
+        update.apply(&mut config),
+        Err(EncoderConfigError::RequiresNewSession("ProfileLevel"))
+    );
+
+    // Nor is restating that VideoToolbox reorders frames by default.
+    let update = EncoderUpdate::new().with_frame_reordering(true);
+    assert_eq!(update.apply(&mut config), Ok(vec![]));
+
+    let update = EncoderUpdate::new().with_frame_reordering(false);
+    assert_eq!(
+        update.apply(&mut config),
+        Err(EncoderConfigError::RequiresNewSession("AllowFrameReordering"))
+    );
+    assert!(config.frame_reordering_allowed());
+}
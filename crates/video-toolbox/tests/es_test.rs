@@ -0,0 +1,150 @@
+use std::time::Duration;
+use video_toolbox::{
+    es::{parse_timecodes, EsError, EsReader, EsWriter},
+    FrameRate, H264ParameterSets, VideoCodec,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 30);
+
+/// The SPS of `out.hevc` with VUI timing info for 60000/1001 fps added.
+const HEVC_SPS_WITH_TIMING: &[u8] = &[
+    0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0xb0, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03,
+    0x00, 0x96, 0xa0, 0x02, 0x80, 0x80, 0x2d, 0x16, 0x20, 0x57, 0xb9, 0x16, 0x55, 0x00, 0x80, 0x00,
+    0x01, 0xf4, 0x80, 0x00, 0x75, 0x30, 0x04,
+];
+
+/// A 1280x720 Baseline SPS with VUI timing info for 30000/1001 fps.
+const H264_SPS_WITH_TIMING: &[u8] = &[
+    0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8, 0x40, 0x00, 0x00, 0xfa, 0x40, 0x00, 0x3a,
+    0x98, 0x21,
+];
+const H264_PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+fn delta_frame() -> Vec<u8> {
+    let mut frame = vec![0, 0, 0, 1, 0x02, 0x01];
+    frame.extend(std::iter::repeat_n(0xab, 500));
+    frame
+}
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    nals.iter().flat_map(|nal| [&[0, 0, 0, 1][..], nal].concat()).collect()
+}
+
+#[test]
+fn test_access_units() {
+    let frames = [HEVC_BYTES.to_vec(), delta_frame(), delta_frame(), HEVC_BYTES.to_vec()];
+    let reader = EsReader::new(VideoCodec::Hevc, frames.concat());
+
+    assert_eq!(reader.frame_rate(), None);
+
+    let access_units: Vec<_> = reader.access_units().collect();
+    assert_eq!(access_units.len(), 4);
+
+    for (access_unit, frame) in access_units.iter().zip(&frames) {
+        assert_eq!(access_unit.data, &frame[..]);
+        assert_eq!(access_unit.pts, None);
+    }
+
+    let keyframes: Vec<bool> = access_units.iter().map(|au| au.is_keyframe).collect();
+    assert_eq!(keyframes, [true, false, false, true]);
+
+    // H.264 pictures can span several slices.
+    let idr = annex_b(&[H264_SPS_WITH_TIMING, H264_PPS, &[0x65, 0x88, 0x80]]);
+    let first_slice = annex_b(&[&[0x41, 0x9a, 0x01]]);
+    let second_slice = annex_b(&[&[0x41, 0x40, 0x02]]);
+    let stream = [idr.clone(), first_slice.clone(), second_slice.clone()].concat();
+
+    let reader = EsReader::new(VideoCodec::H264, stream);
+    let data: Vec<&[u8]> = reader.access_units().map(|au| au.data).collect();
+    assert_eq!(data, [&idr[..], &[first_slice, second_slice].concat()[..]]);
+}
+
+#[test]
+fn test_vui_frame_rate() {
+    let sps_start = HEVC_BYTES.windows(5).position(|w| w == [0, 0, 0, 1, 0x42]).unwrap() + 4;
+    let pps_start = HEVC_BYTES.windows(5).position(|w| w == [0, 0, 0, 1, 0x44]).unwrap();
+    let keyframe =
+        [&HEVC_BYTES[..sps_start], HEVC_SPS_WITH_TIMING, &HEVC_BYTES[pps_start..]].concat();
+
+    let reader = EsReader::new(VideoCodec::Hevc, [keyframe, delta_frame(), delta_frame()].concat());
+    let frame_rate = reader.frame_rate().unwrap();
+    assert_eq!(frame_rate, FrameRate::new(60_000, 1001));
+
+    let sps_info = reader.parameter_sets().unwrap().sps_info().unwrap();
+    assert_eq!((sps_info.width, sps_info.height), (1280, 720));
+
+    let pts: Vec<_> = reader.access_units().map(|au| au.pts.unwrap()).collect();
+    assert_eq!(pts, [0, 16_683_333, 33_366_667].map(Duration::from_nanos));
+
+    // H.264 ticks are fields, so the frame rate is half the tick rate.
+    let h264 = H264ParameterSets { sps: H264_SPS_WITH_TIMING.to_vec(), pps: H264_PPS.to_vec() };
+    let sps_info = h264.sps_info().unwrap();
+    assert_eq!((sps_info.width, sps_info.height), (1280, 720));
+    assert_eq!(sps_info.frame_rate, Some(FrameRate::new(60_000, 2002)));
+}
+
+#[test]
+fn test_timecodes_round_trip() {
+    let mut writer = EsWriter::new(vec![]).with_timecodes(vec![]).unwrap();
+
+    for i in 0..3 {
+        let frame = if i == 0 { HEVC_BYTES.to_vec() } else { delta_frame() };
+        writer.write_access_unit(&frame, FRAME_DURATION * i).unwrap();
+    }
+
+    let (stream, timecodes) = writer.finish().unwrap();
+    let timecodes = String::from_utf8(timecodes.unwrap()).unwrap();
+    assert_eq!(timecodes, "# timecode format v2\n0\n33.333333\n66.666666\n");
+
+    let mut reader = EsReader::new(VideoCodec::Hevc, stream);
+    reader.set_timecodes(parse_timecodes(&timecodes).unwrap());
+
+    let pts: Vec<_> = reader.access_units().map(|au| au.pts.unwrap()).collect();
+    assert_eq!(pts, [Duration::ZERO, FRAME_DURATION, FRAME_DURATION * 2]);
+
+    assert_eq!(
+        parse_timecodes("# timestamp format v2\n# comment\n\n0\n40.5\n").unwrap(),
+        [Duration::ZERO, Duration::from_micros(40_500)]
+    );
+    // Fractions beyond a nanosecond are truncated.
+    assert_eq!(
+        parse_timecodes("# timecode format v2\n0.000001\n33.3333339\n").unwrap(),
+        [Duration::from_nanos(1), Duration::from_nanos(33_333_333)]
+    );
+    assert!(matches!(
+        parse_timecodes("# timecode format v1\n"),
+        Err(EsError::UnsupportedTimecodeFormat)
+    ));
+    assert!(matches!(
+        parse_timecodes("# timecode format v2\n0\nx\n"),
+        Err(EsError::InvalidTimecode(3))
+    ));
+    assert!(matches!(
+        parse_timecodes("# timecode format v2\n1.5e3\n"),
+        Err(EsError::InvalidTimecode(2))
+    ));
+}
+
+#[test]
+fn test_files() {
+    let directory = std::env::temp_dir().join(format!("es_test_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let stream_path = directory.join("out.h265");
+    let timecodes_path = directory.join("out.txt");
+
+    let mut writer = EsWriter::create(&stream_path, Some(&timecodes_path)).unwrap();
+    writer.write_access_unit(HEVC_BYTES, Duration::from_millis(40)).unwrap();
+    writer.write_access_unit(&delta_frame(), Duration::from_millis(80)).unwrap();
+    writer.finish().unwrap();
+
+    let mut reader = EsReader::open(&stream_path).unwrap();
+    assert_eq!(reader.codec(), VideoCodec::Hevc);
+    reader.open_timecodes(&timecodes_path).unwrap();
+
+    let pts: Vec<_> = reader.access_units().map(|au| au.pts).collect();
+    assert_eq!(pts, [Some(Duration::from_millis(40)), Some(Duration::from_millis(80))]);
+
+    assert!(matches!(EsReader::open(&timecodes_path), Err(EsError::UnknownExtension(_))));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
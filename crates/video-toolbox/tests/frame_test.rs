@@ -0,0 +1,227 @@
+use std::time::Duration;
+use video_toolbox::{
+    y4m::{Y4mColorspace, Y4mHeader, Y4mReader, Y4mWriter},
+    FrameBuf, FrameError, FrameRate, PixelFormat, Plane, PlaneBuf, PlaneGeometry, Rect, Timestamp,
+    VideoFrame,
+};
+
+#[test]
+fn test_plane_layout() {
+    assert_eq!(PixelFormat::Bgra32.plane_count(), 1);
+    assert_eq!(PixelFormat::Bgra32.bytes_per_row(0, 1280), 5120);
+
+    // Odd dimensions round the chroma planes up.
+    assert_eq!(PixelFormat::Nv12.bytes_per_row(1, 33), 34);
+    assert_eq!(PixelFormat::Nv12.plane_height(1, 17), 9);
+    assert_eq!(PixelFormat::I420.bytes_per_row(2, 33), 17);
+
+    let frame = FrameBuf::new(PixelFormat::I420, 33, 17).unwrap();
+    let lengths: Vec<usize> = frame.planes().iter().map(|plane| plane.data().len()).collect();
+    assert_eq!(lengths, [33 * 17, 17 * 9, 17 * 9]);
+    assert_eq!(frame.crop(), Rect::new(0, 0, 33, 17));
+}
+
+#[test]
+fn test_validation() {
+    let data = vec![0u8; 64 * 4 * 4];
+    let plane = Plane::new(&data, 64 * 4);
+
+    assert!(VideoFrame::new(PixelFormat::Bgra32, 64, 4, vec![plane]).is_ok());
+
+    assert_eq!(
+        VideoFrame::new(PixelFormat::Nv12, 64, 4, vec![plane]).unwrap_err(),
+        FrameError::PlaneCount { format: PixelFormat::Nv12, expected: 2, actual: 1 }
+    );
+    assert_eq!(
+        VideoFrame::new(PixelFormat::Bgra32, 65, 4, vec![plane]).unwrap_err(),
+        FrameError::StrideTooSmall { plane: 0, stride: 256, minimum: 260 }
+    );
+    assert_eq!(
+        VideoFrame::new(PixelFormat::Bgra32, 64, 5, vec![plane]).unwrap_err(),
+        FrameError::PlaneTooSmall { plane: 0, len: 1024, minimum: 1280 }
+    );
+    assert_eq!(
+        VideoFrame::new(PixelFormat::Bgra32, 0, 4, vec![plane]).unwrap_err(),
+        FrameError::InvalidDimensions { width: 0, height: 4 }
+    );
+
+    // The last row does not need padding out to the stride.
+    let padded = vec![0u8; 128 * 3 + 64];
+    assert!(VideoFrame::new(
+        PixelFormat::I420,
+        64,
+        4,
+        vec![
+            Plane::new(&padded, 128),
+            Plane::new(&padded[..64 + 32], 64),
+            Plane::new(&padded[..64 + 32], 64),
+        ]
+    )
+    .is_ok());
+
+    let frame = VideoFrame::new(PixelFormat::Bgra32, 64, 4, vec![plane]).unwrap();
+    assert!(frame.clone().with_crop(Rect::new(8, 0, 56, 4)).is_ok());
+    assert_eq!(
+        frame.with_crop(Rect::new(8, 0, 57, 4)).unwrap_err(),
+        FrameError::CropOutOfBounds(Rect::new(8, 0, 57, 4))
+    );
+}
+
+#[test]
+fn test_strided_round_trip() {
+    // A 3x2 NV12 frame with 8 byte strides, padding filled with 0xff.
+    let luma = [1, 2, 3, 0xff, 0xff, 0xff, 0xff, 0xff, 4, 5, 6];
+    let chroma = [7, 8, 9, 10];
+
+    let frame = FrameBuf::from_planes(
+        PixelFormat::Nv12,
+        3,
+        2,
+        vec![PlaneBuf::new(luma.to_vec(), 8), PlaneBuf::new(chroma.to_vec(), 4)],
+    )
+    .unwrap();
+
+    assert_eq!(frame.planes()[0].as_plane().row(1), [4, 5, 6]);
+    assert_eq!(frame.to_packed(), [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+    let packed = frame.to_packed();
+    let repacked = VideoFrame::from_packed(PixelFormat::Nv12, 3, 2, &packed).unwrap();
+    assert_eq!(repacked.planes()[0].stride, 3);
+    assert_eq!(repacked.to_frame_buf().to_packed(), packed);
+
+    assert!(matches!(
+        VideoFrame::from_packed(PixelFormat::Nv12, 3, 2, &packed[..9]),
+        Err(FrameError::PlaneTooSmall { plane: 1, len: 3, minimum: 4 })
+    ));
+}
+
+#[test]
+fn test_y4m_video_frames() {
+    let header = Y4mHeader::new(4, 2, FrameRate::new(25, 1), Y4mColorspace::default());
+    assert_eq!(header.pixel_format(), Some(PixelFormat::I420));
+
+    // Padded luma rows are written tightly packed.
+    let luma: Vec<u8> = (0..16).collect();
+    let frame = VideoFrame::new(
+        PixelFormat::I420,
+        4,
+        2,
+        vec![Plane::new(&luma, 8), Plane::new(&[20, 21], 2), Plane::new(&[30, 31], 2)],
+    )
+    .unwrap();
+
+    let mut writer = Y4mWriter::new(vec![], header).unwrap();
+    writer.write_video_frame(&frame).unwrap();
+    writer.write_video_frame(&frame).unwrap();
+    assert!(writer
+        .write_video_frame(&FrameBuf::new(PixelFormat::Nv12, 4, 2).unwrap().as_frame())
+        .is_err());
+    let data = writer.finish().unwrap();
+
+    let mut reader = Y4mReader::new(&data[..]).unwrap();
+    let first = reader.read_video_frame().unwrap().unwrap();
+    assert_eq!(first.to_packed(), [0, 1, 2, 3, 8, 9, 10, 11, 20, 21, 30, 31]);
+    assert_eq!(first.pts(), Timestamp::ZERO);
+
+    let second = reader.read_video_frame().unwrap().unwrap();
+    assert_eq!(second.pts(), Timestamp::new(1, 25));
+    assert_eq!(second.duration(), Some(Duration::from_millis(40).into()));
+    assert!(reader.read_video_frame().unwrap().is_none());
+}
+
+#[test]
+fn test_encoder_input_validation() {
+    // A 5x3 NV12 frame with 8 byte strides.
+    let luma = [0u8; 8 * 3];
+    let chroma = [0u8; 8 * 2];
+    let frame = VideoFrame::new(
+        PixelFormat::Nv12,
+        5,
+        3,
+        vec![Plane::new(&luma, 8), Plane::new(&chroma, 8)],
+    )
+    .unwrap();
+
+    assert_eq!(
+        frame.validate_encoder_input(5, 3).unwrap(),
+        [
+            PlaneGeometry { width: 5, height: 3, bytes_per_row: 8 },
+            PlaneGeometry { width: 3, height: 2, bytes_per_row: 8 },
+        ]
+    );
+
+    assert_eq!(
+        frame.validate_encoder_input(6, 3).unwrap_err(),
+        FrameError::DimensionsMismatch { expected: (6, 3), actual: (5, 3) }
+    );
+
+    // Frames may leave the last row unpadded, but VideoToolbox reads it in full.
+    let unpadded = VideoFrame::new(
+        PixelFormat::Nv12,
+        5,
+        3,
+        vec![Plane::new(&luma[..8 * 2 + 5], 8), Plane::new(&chroma, 8)],
+    )
+    .unwrap();
+    assert_eq!(
+        unpadded.validate_encoder_input(5, 3).unwrap_err(),
+        FrameError::PlaneTooSmall { plane: 0, len: 21, minimum: 24 }
+    );
+
+    let i420 = FrameBuf::new(PixelFormat::I420, 5, 3).unwrap();
+    let geometry = i420.as_frame().validate_encoder_input(5, 3).unwrap();
+    assert_eq!(geometry.iter().map(|plane| plane.width).collect::<Vec<_>>(), [5, 3, 3]);
+
+    let p010 = FrameBuf::new(PixelFormat::P010, 5, 3).unwrap();
+    let geometry = p010.as_frame().validate_encoder_input(5, 3).unwrap();
+    assert_eq!(geometry[1], PlaneGeometry { width: 3, height: 2, bytes_per_row: 12 });
+
+    let gray = FrameBuf::new(PixelFormat::Gray8, 5, 3).unwrap();
+    assert_eq!(
+        gray.as_frame().validate_encoder_input(5, 3).unwrap_err(),
+        FrameError::UnsupportedEncoderInput(PixelFormat::Gray8)
+    );
+}
+
+#[test]
+fn test_copy_from_strided_planes() {
+    // A 4x2 I420 frame as a decoder might return it, with 16 byte aligned rows.
+    let mut luma = vec![0xee; 16 + 4];
+    luma[..4].copy_from_slice(&[1, 2, 3, 4]);
+    luma[16..].copy_from_slice(&[5, 6, 7, 8]);
+
+    let frame = VideoFrame::new(
+        PixelFormat::I420,
+        4,
+        2,
+        vec![
+            Plane::new(&luma, 16),
+            Plane::new(&[9, 10, 0xee, 0xee], 16),
+            Plane::new(&[11, 12], 16),
+        ],
+    )
+    .unwrap()
+    .with_crop(Rect::new(0, 0, 3, 2))
+    .unwrap()
+    .with_pts(Duration::from_millis(40))
+    .with_duration(Timestamp::new(1, 25));
+
+    // The destination changes format and size to match.
+    let mut dst = FrameBuf::new(PixelFormat::Bgra32, 64, 64).unwrap();
+    dst.copy_from(&frame);
+
+    assert_eq!(dst.format(), PixelFormat::I420);
+    assert_eq!((dst.width(), dst.height()), (4, 2));
+    assert_eq!(dst.crop(), Rect::new(0, 0, 3, 2));
+    assert_eq!(dst.pts(), Timestamp::new(1, 25));
+    assert_eq!(dst.duration(), Some(Timestamp::new(1, 25)));
+
+    let strides: Vec<usize> = dst.planes().iter().map(|plane| plane.stride()).collect();
+    assert_eq!(strides, [4, 2, 2]);
+    assert_eq!(dst.to_packed(), [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+
+    // Back to a single plane format.
+    let bgra = FrameBuf::new(PixelFormat::Bgra32, 2, 2).unwrap();
+    dst.copy_from(&bgra.as_frame());
+    assert_eq!(dst, bgra);
+}
@@ -0,0 +1,111 @@
+use video_toolbox::{
+    color::{ContentLightLevel, HdrMetadata, MasteringDisplay, TransferCharacteristics},
+    mp4::{init_segment, init_segment_with_hdr},
+    scale::{scale, ScaleFilter},
+    ParameterSets, PixelFormat, VideoCodec, VideoFrame,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+const HDR10: HdrMetadata = HdrMetadata {
+    mastering_display: Some(MasteringDisplay::P3_D65_1000_NITS),
+    content_light_level: Some(ContentLightLevel {
+        max_content_light_level: 1000,
+        max_frame_average_light_level: 400,
+    }),
+    alternative_transfer: None,
+};
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    data.windows(4).position(|window| window == kind).map(|start| {
+        let size = u32::from_be_bytes(data[start - 4..start].try_into().unwrap()) as usize;
+        &data[start + 4..start - 4 + size]
+    })
+}
+
+#[test]
+fn test_payload_bytes() {
+    let display = MasteringDisplay::P3_D65_1000_NITS;
+    let bytes = display.to_bytes();
+
+    assert_eq!(
+        bytes,
+        [
+            0x33, 0xc2, 0x86, 0xc4, 0x1d, 0x4c, 0x0b, 0xb8, 0x84, 0xd0, 0x3e, 0x80, 0x3d, 0x13,
+            0x40, 0x42, 0x00, 0x98, 0x96, 0x80, 0x00, 0x00, 0x00, 0x01,
+        ]
+    );
+    assert_eq!(MasteringDisplay::from_bytes(&bytes), Some(display));
+    assert_eq!(MasteringDisplay::from_bytes(&bytes[..23]), None);
+
+    let level = ContentLightLevel::new(1000, 400);
+    assert_eq!(level.to_bytes(), [0x03, 0xe8, 0x01, 0x90]);
+    assert_eq!(ContentLightLevel::from_bytes(&level.to_bytes()), Some(level));
+}
+
+#[test]
+fn test_sei_round_trip() {
+    for codec in [VideoCodec::Hevc, VideoCodec::H264] {
+        let nal = HDR10.to_sei_nal(codec).unwrap();
+        assert_eq!(HdrMetadata::from_sei_nal(codec, &nal), Some(HDR10));
+    }
+
+    let hlg = HdrMetadata {
+        content_light_level: Some(ContentLightLevel::new(0, 0)),
+        alternative_transfer: Some(TransferCharacteristics::Hlg),
+        ..HdrMetadata::default()
+    };
+
+    // The zero light levels need emulation prevention.
+    let nal = hlg.to_sei_nal(VideoCodec::Hevc).unwrap();
+    assert_eq!(nal, [0x4e, 0x01, 0x90, 0x04, 0x00, 0x00, 0x03, 0x00, 0x00, 0x93, 0x01, 0x12, 0x80]);
+    assert_eq!(HdrMetadata::from_sei_nal(VideoCodec::Hevc, &nal), Some(hlg));
+
+    assert_eq!(HdrMetadata::default().to_sei_nal(VideoCodec::Hevc), None);
+    // Not an SEI, and a truncated one.
+    assert_eq!(HdrMetadata::from_sei_nal(VideoCodec::Hevc, &[0x42, 0x01, 0x90]), None);
+    assert_eq!(HdrMetadata::from_sei_nal(VideoCodec::Hevc, &nal[..6]), None);
+}
+
+#[test]
+fn test_access_unit_metadata() {
+    let sei = HDR10.to_sei_nal(VideoCodec::Hevc).unwrap();
+    let access_unit = [&[0, 0, 0, 1][..], &sei, HEVC_BYTES].concat();
+    assert_eq!(HdrMetadata::from_access_unit(VideoCodec::Hevc, &access_unit), HDR10);
+    assert!(HdrMetadata::from_access_unit(VideoCodec::Hevc, HEVC_BYTES).is_empty());
+
+    // Later messages override earlier ones field by field.
+    let mut metadata = HDR10;
+    metadata.update(HdrMetadata {
+        content_light_level: Some(ContentLightLevel::new(600, 200)),
+        ..HdrMetadata::default()
+    });
+    assert_eq!(metadata.mastering_display, HDR10.mastering_display);
+    assert_eq!(metadata.content_light_level, Some(ContentLightLevel::new(600, 200)));
+}
+
+#[test]
+fn test_mp4_hdr_boxes() {
+    let parameter_sets = ParameterSets::from_annex_b(VideoCodec::Hevc, HEVC_BYTES).unwrap();
+
+    let init = init_segment_with_hdr(&parameter_sets, 90_000, &HDR10).unwrap();
+    let hvc1 = find_box(&init, b"hvc1").unwrap();
+    assert_eq!(find_box(hvc1, b"mdcv").unwrap(), MasteringDisplay::P3_D65_1000_NITS.to_bytes());
+    assert_eq!(find_box(hvc1, b"clli").unwrap(), [0x03, 0xe8, 0x01, 0x90]);
+
+    let init = init_segment(&parameter_sets, 90_000).unwrap();
+    assert!(find_box(&init, b"mdcv").is_none());
+    assert!(find_box(&init, b"clli").is_none());
+}
+
+#[test]
+fn test_frames_carry_hdr_metadata() {
+    let data = vec![0; PixelFormat::P010.buffer_size(4, 4)];
+    let frame =
+        VideoFrame::from_packed(PixelFormat::P010, 4, 4, &data).unwrap().with_hdr_metadata(HDR10);
+
+    assert_eq!(frame.to_frame_buf().hdr_metadata(), HDR10);
+
+    let scaled = scale(&frame, 2, 2, ScaleFilter::Area).unwrap();
+    assert_eq!(scaled.hdr_metadata(), HDR10);
+}
@@ -0,0 +1,313 @@
+use std::time::{Duration, UNIX_EPOCH};
+use video_toolbox::hls::{HlsConfig, HlsEvent, HlsMode, HlsSegmenter, SegmentFormat};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 30);
+
+fn delta_frame() -> Vec<u8> {
+    let mut frame = vec![0, 0, 0, 1, 0x02, 0x01];
+    frame.extend(std::iter::repeat_n(0xab, 500));
+    frame
+}
+
+/// Encodes `frame_count` frames at 30 fps with a keyframe every `gop` frames.
+fn run(segmenter: &mut HlsSegmenter, frame_count: u32, gop: u32) -> Vec<HlsEvent> {
+    let mut events = vec![];
+
+    for i in 0..frame_count {
+        let frame = if i % gop == 0 { HEVC_BYTES.to_vec() } else { delta_frame() };
+        events.extend(segmenter.push(&frame, FRAME_DURATION * i).unwrap());
+    }
+
+    events.extend(segmenter.finish().unwrap());
+    events
+}
+
+fn written(events: &[HlsEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            HlsEvent::Write { uri, .. } => Some(uri.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn removed(events: &[HlsEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            HlsEvent::Remove { uri } => Some(uri.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn data<'a>(events: &'a [HlsEvent], name: &str) -> &'a [u8] {
+    events
+        .iter()
+        .find_map(|event| match event {
+            HlsEvent::Write { uri, data } if uri == name => Some(&data[..]),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_fmp4_vod_playlist() {
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Vod,
+        target_duration: Duration::from_secs(2),
+        ..Default::default()
+    });
+
+    // Five seconds with a keyframe every second.
+    let events = run(&mut segmenter, 150, 30);
+    assert_eq!(written(&events), ["init0.mp4", "segment0.m4s", "segment1.m4s", "segment2.m4s"]);
+    assert!(removed(&events).is_empty());
+
+    // Segments start with a movie fragment.
+    assert_eq!(&data(&events, "segment1.m4s")[4..8], b"moof");
+
+    assert_eq!(
+        segmenter.playlist().to_string(),
+        "#EXTM3U\n\
+         #EXT-X-VERSION:7\n\
+         #EXT-X-TARGETDURATION:2\n\
+         #EXT-X-MEDIA-SEQUENCE:0\n\
+         #EXT-X-PLAYLIST-TYPE:VOD\n\
+         #EXT-X-INDEPENDENT-SEGMENTS\n\
+         #EXT-X-MAP:URI=\"init0.mp4\"\n\
+         #EXTINF:2.00000,\n\
+         segment0.m4s\n\
+         #EXTINF:2.00000,\n\
+         segment1.m4s\n\
+         #EXTINF:1.00000,\n\
+         segment2.m4s\n\
+         #EXT-X-ENDLIST\n"
+    );
+}
+
+#[test]
+fn test_segments_cut_at_nearest_keyframe() {
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Vod,
+        target_duration: Duration::from_secs(2),
+        ..Default::default()
+    });
+
+    // Five seconds with a keyframe every 1.67 seconds, which is nearer to the
+    // target than the following one at 3.33 seconds.
+    let events = run(&mut segmenter, 150, 50);
+    assert_eq!(written(&events), ["init0.mp4", "segment0.m4s", "segment1.m4s", "segment2.m4s"]);
+
+    let playlist = segmenter.playlist();
+    assert_eq!(playlist.target_duration, 2);
+    let durations: Vec<_> =
+        playlist.segments.iter().map(|segment| segment.duration.as_millis()).collect();
+    assert_eq!(durations, [1666, 1666, 1666]);
+}
+
+#[test]
+fn test_overlong_segments_are_reported() {
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Vod,
+        target_duration: Duration::from_secs(1),
+        ..Default::default()
+    });
+
+    // A keyframe every two seconds cannot meet a one second target.
+    let events = run(&mut segmenter, 120, 60);
+    let exceeded: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            HlsEvent::TargetDurationExceeded { uri, duration } => {
+                Some((uri.as_str(), duration.as_millis()))
+            },
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(exceeded, [("segment0.m4s", 2000), ("segment1.m4s", 2000)]);
+
+    // The advertised target duration never changes mid-stream.
+    assert!(segmenter.playlist().to_string().contains("#EXT-X-TARGETDURATION:1\n"));
+}
+
+#[test]
+fn test_live_window_and_program_date_time() {
+    let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Live { window_size: 2 },
+        target_duration: Duration::from_secs(1),
+        program_date_time: Some(start),
+        ..Default::default()
+    });
+
+    let mut events = vec![];
+    for i in 0..90 {
+        let frame = if i % 30 == 0 { HEVC_BYTES.to_vec() } else { delta_frame() };
+        events.extend(segmenter.push(&frame, FRAME_DURATION * i).unwrap());
+    }
+
+    // The third segment is still open, so only the first two are complete.
+    assert_eq!(written(&events), ["init0.mp4", "segment0.m4s", "segment1.m4s"]);
+
+    events.extend(segmenter.finish().unwrap());
+    assert_eq!(removed(&events), ["segment0.m4s"]);
+
+    let playlist = segmenter.playlist();
+    assert_eq!(playlist.media_sequence, 1);
+    assert_eq!(playlist.segments.len(), 2);
+    assert_eq!(playlist.segments[0].program_date_time, Some(start + Duration::from_secs(1)));
+
+    let text = playlist.to_string();
+    assert!(text.contains("#EXT-X-MEDIA-SEQUENCE:1\n"));
+    assert!(text.contains("#EXT-X-PROGRAM-DATE-TIME:2023-11-14T22:13:21.000Z\n"));
+    assert!(text.contains("#EXT-X-PROGRAM-DATE-TIME:2023-11-14T22:13:22.000Z\n"));
+    assert!(!text.contains("#EXT-X-PLAYLIST-TYPE"));
+}
+
+#[test]
+fn test_ts_segments() {
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        format: SegmentFormat::Ts,
+        mode: HlsMode::Vod,
+        target_duration: Duration::from_secs(1),
+        ..Default::default()
+    });
+
+    let events = run(&mut segmenter, 60, 30);
+    assert_eq!(written(&events), ["segment0.ts", "segment1.ts"]);
+
+    let text = segmenter.playlist().to_string();
+    assert!(text.contains("#EXT-X-VERSION:3\n"));
+    assert!(!text.contains("#EXT-X-MAP"));
+
+    let mut video_continuity = vec![];
+
+    for name in ["segment0.ts", "segment1.ts"] {
+        let segment = data(&events, name);
+        assert_eq!(segment.len() % 188, 0);
+
+        let packets: Vec<&[u8]> = segment.chunks(188).collect();
+        assert!(packets.iter().all(|packet| packet[0] == 0x47));
+
+        // PAT then PMT, then the keyframe with PCR and random access set.
+        let pid = |packet: &[u8]| u16::from_be_bytes([packet[1] & 0x1f, packet[2]]);
+        assert_eq!(pid(packets[0]), 0);
+        assert_eq!(pid(packets[1]), 0x1000);
+        assert_eq!(packets[1][5 + 12], 0x24);
+        assert_eq!(pid(packets[2]), 0x100);
+        assert_eq!(packets[2][1] & 0x40, 0x40);
+        assert_eq!(packets[2][5] & 0x50, 0x50);
+
+        video_continuity
+            .extend(packets.iter().filter(|p| pid(p) == 0x100).map(|packet| packet[3] & 0x0f));
+    }
+
+    // Continuity counters carry across segments.
+    for pair in video_continuity.windows(2) {
+        assert_eq!(pair[1], (pair[0] + 1) % 16);
+    }
+}
+
+#[test]
+fn test_low_latency_parts() {
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Live { window_size: 10 },
+        target_duration: Duration::from_secs(1),
+        part_target_duration: Some(Duration::from_millis(500)),
+        ..Default::default()
+    });
+
+    let mut events = vec![];
+    for i in 0..40 {
+        let frame = if i % 30 == 0 { HEVC_BYTES.to_vec() } else { delta_frame() };
+        events.extend(segmenter.push(&frame, FRAME_DURATION * i).unwrap());
+    }
+
+    assert_eq!(written(&events), ["init0.mp4", "segment0.0.m4s", "segment0.1.m4s", "segment0.m4s"]);
+
+    // A complete segment is the concatenation of its parts.
+    let mut joined = data(&events, "segment0.0.m4s").to_vec();
+    joined.extend_from_slice(data(&events, "segment0.1.m4s"));
+    assert_eq!(data(&events, "segment0.m4s"), &joined[..]);
+
+    let text = segmenter.playlist().to_string();
+    assert!(text.contains("#EXT-X-VERSION:9\n"));
+    assert!(text.contains("#EXT-X-SERVER-CONTROL:PART-HOLD-BACK=1.50000\n"));
+    assert!(text.contains("#EXT-X-PART-INF:PART-TARGET=0.50000\n"));
+    assert!(text.contains(
+        "#EXT-X-PART:DURATION=0.50000,URI=\"segment0.0.m4s\",INDEPENDENT=YES\n\
+         #EXT-X-PART:DURATION=0.50000,URI=\"segment0.1.m4s\"\n\
+         #EXTINF:1.00000,\n\
+         segment0.m4s\n"
+    ));
+
+    // The second segment is in progress with no complete parts yet.
+    assert!(text.ends_with("#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment1.0.m4s\"\n"));
+}
+
+#[test]
+fn test_discontinuity_on_format_change() {
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Vod,
+        target_duration: Duration::from_secs(10),
+        ..Default::default()
+    });
+
+    // Same stream, but with an extra trailing byte in the PPS.
+    let pps_start = HEVC_BYTES.windows(5).position(|w| w == [0, 0, 0, 1, 0x44]).unwrap();
+    let sei_start = pps_start
+        + 4
+        + HEVC_BYTES[pps_start + 4..].windows(4).position(|w| w == [0, 0, 0, 1]).unwrap();
+    let mut changed = HEVC_BYTES[..sei_start].to_vec();
+    changed.push(0x80);
+    changed.extend_from_slice(&HEVC_BYTES[sei_start..]);
+
+    let mut events = vec![];
+    for i in 0..60 {
+        let frame = match i {
+            0 => HEVC_BYTES.to_vec(),
+            30 => changed.clone(),
+            _ => delta_frame(),
+        };
+
+        events.extend(segmenter.push(&frame, FRAME_DURATION * i).unwrap());
+    }
+    events.extend(segmenter.finish().unwrap());
+
+    assert_eq!(written(&events), ["init0.mp4", "segment0.m4s", "init1.mp4", "segment1.m4s"]);
+    assert!(segmenter.playlist().to_string().contains(
+        "segment0.m4s\n\
+         #EXT-X-DISCONTINUITY\n\
+         #EXT-X-MAP:URI=\"init1.mp4\"\n\
+         #EXTINF:1.00000,\n\
+         segment1.m4s\n"
+    ));
+}
+
+#[test]
+fn test_long_and_backward_timestamps() {
+    let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut segmenter = HlsSegmenter::new(HlsConfig {
+        mode: HlsMode::Vod,
+        target_duration: Duration::from_secs(1),
+        program_date_time: Some(start),
+        ..Default::default()
+    });
+
+    // A three day gap, then a pts before the first, as after an encoder restart.
+    let three_days = Duration::from_secs(3 * 24 * 60 * 60);
+    let timestamps = [10, 3 * 24 * 60 * 60, 3 * 24 * 60 * 60 + 2, 0, 5, 7];
+    for pts in timestamps.map(Duration::from_secs) {
+        segmenter.push(HEVC_BYTES, pts).unwrap();
+    }
+    segmenter.finish().unwrap();
+
+    let segments = &segmenter.playlist().segments;
+    assert_eq!(segments[0].duration, three_days - Duration::from_secs(10));
+    assert_eq!(segments[1].program_date_time, Some(start + three_days - Duration::from_secs(10)));
+    assert_eq!(segments.last().unwrap().program_date_time, Some(start));
+}
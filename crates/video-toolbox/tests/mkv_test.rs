@@ -0,0 +1,200 @@
+use std::{io::Cursor, time::Duration};
+use video_toolbox::{
+    mkv::{DocType, MatroskaConfig, MatroskaReader, MatroskaWriter, MkvError},
+    H264ParameterSets, ParameterSets, VideoCodec,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 30);
+
+const H264_SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8];
+const H264_PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+fn delta_frame() -> Vec<u8> {
+    let mut frame = vec![0, 0, 0, 1, 0x02, 0x01];
+    frame.extend(std::iter::repeat_n(0xab, 500));
+    frame
+}
+
+fn h264_frame(nal: &[u8]) -> Vec<u8> {
+    let mut frame = vec![];
+    for nal in [H264_SPS, H264_PPS, nal] {
+        frame.extend_from_slice(&[0, 0, 0, 1]);
+        frame.extend_from_slice(nal);
+    }
+    frame
+}
+
+/// Writes `frame_count` HEVC frames at 30 fps with a keyframe every 30 frames.
+fn write_hevc(config: MatroskaConfig, frame_count: u32) -> MatroskaWriter<Cursor<Vec<u8>>> {
+    let mut writer = MatroskaWriter::new(Cursor::new(vec![]), config);
+
+    for i in 0..frame_count {
+        let frame = if i % 30 == 0 { HEVC_BYTES.to_vec() } else { delta_frame() };
+        writer.write_frame(&frame, FRAME_DURATION * i).unwrap();
+    }
+
+    writer
+}
+
+fn read_all<R: std::io::Read>(reader: &mut MatroskaReader<R>) -> Vec<(Duration, bool, Vec<u8>)> {
+    std::iter::from_fn(|| reader.read_frame().unwrap())
+        .map(|frame| (frame.pts, frame.is_keyframe, frame.data))
+        .collect()
+}
+
+#[test]
+fn test_hevc_round_trip() {
+    let config = MatroskaConfig { cluster_duration: Duration::from_secs(1), ..Default::default() };
+    let bytes = write_hevc(config, 90).finish_seekable().unwrap().into_inner();
+
+    assert_eq!(&bytes[..4], &[0x1a, 0x45, 0xdf, 0xa3]);
+
+    let mut reader = MatroskaReader::new(Cursor::new(bytes)).unwrap();
+    let parameter_sets = ParameterSets::from_annex_b(VideoCodec::Hevc, HEVC_BYTES).unwrap();
+
+    assert_eq!(reader.doc_type(), DocType::Matroska);
+    assert_eq!(reader.codec(), VideoCodec::Hevc);
+    assert_eq!(reader.parameter_sets(), &parameter_sets);
+    assert_eq!((reader.width(), reader.height()), (1280, 720));
+    assert_eq!(reader.duration(), Some(Duration::from_millis(3000)));
+
+    let frames = read_all(&mut reader);
+    assert_eq!(frames.len(), 90);
+    assert_eq!(frames[1].0, Duration::from_millis(33));
+    assert_eq!(frames[1].2, delta_frame());
+
+    let keyframes: Vec<usize> = (0..90).filter(|&i| frames[i].1).collect();
+    assert_eq!(keyframes, [0, 30, 60]);
+
+    // Keyframes are ready for a decoder, with the parameter sets in-band.
+    assert!(frames[30].2.starts_with(&parameter_sets.to_annex_b()));
+    assert!(VideoCodec::Hevc.is_keyframe(&frames[30].2));
+
+    // The cues were read along with the last cluster.
+    let cue_times: Vec<Duration> = reader.cues().iter().map(|cue| cue.time).collect();
+    assert_eq!(cue_times, [0, 1000, 2000].map(Duration::from_millis));
+}
+
+#[test]
+fn test_seek_with_cues() {
+    let config = MatroskaConfig { cluster_duration: Duration::from_secs(1), ..Default::default() };
+    let bytes = write_hevc(config, 90).finish_seekable().unwrap().into_inner();
+
+    let mut reader = MatroskaReader::new(Cursor::new(bytes)).unwrap();
+    assert!(reader.cues().is_empty());
+
+    // The cues at the end are found through the SeekHead.
+    reader.seek(Duration::from_millis(1500)).unwrap();
+    assert_eq!(reader.cues().len(), 3);
+
+    let frame = reader.read_frame().unwrap().unwrap();
+    assert_eq!(frame.pts, Duration::from_millis(1000));
+    assert!(frame.is_keyframe);
+
+    reader.seek(Duration::ZERO).unwrap();
+    assert_eq!(read_all(&mut reader).len(), 90);
+
+    // Without `finish_seekable` there is nothing pointing at the cues.
+    let bytes = write_hevc(MatroskaConfig::default(), 30).finish().unwrap().into_inner();
+    let mut reader = MatroskaReader::new(Cursor::new(bytes)).unwrap();
+    assert!(matches!(reader.seek(Duration::ZERO), Err(MkvError::MissingCues)));
+}
+
+#[test]
+fn test_live_unknown_size_clusters() {
+    let config = MatroskaConfig {
+        doc_type: DocType::WebM,
+        live: true,
+        cluster_duration: Duration::from_secs(1),
+        ..Default::default()
+    };
+    let writer = write_hevc(config, 45);
+
+    // Everything so far has already been written, with unknown sizes.
+    let streamed = writer.finish().unwrap().into_inner();
+    let cluster_header = [0x1f, 0x43, 0xb6, 0x75, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    let cluster_count = streamed.windows(12).filter(|w| *w == cluster_header).count();
+    assert_eq!(cluster_count, 2);
+
+    let mut reader = MatroskaReader::new(Cursor::new(streamed)).unwrap();
+    assert_eq!(reader.doc_type(), DocType::WebM);
+    assert_eq!(reader.duration(), None);
+
+    let frames = read_all(&mut reader);
+    assert_eq!(frames.len(), 45);
+    assert_eq!(frames[44].0, Duration::from_millis(1467));
+    assert_eq!(reader.cues().len(), 2);
+}
+
+#[test]
+fn test_h264_block_groups() {
+    let config = MatroskaConfig { codec: VideoCodec::H264, live: true, ..Default::default() };
+    let mut writer = MatroskaWriter::new(vec![], config);
+
+    // The first frame must be a keyframe carrying the parameter sets.
+    let error = writer.write_frame(&[0, 0, 0, 1, 0x41, 0x9a], Duration::ZERO);
+    assert!(matches!(error, Err(MkvError::MissingParameterSets)));
+
+    writer.write_frame(&h264_frame(&[0x65, 0x88, 0x84]), Duration::ZERO).unwrap();
+    let mut bytes = writer.finish().unwrap();
+
+    // Append a cluster of BlockGroups, as other muxers write them.
+    let block = |relative: i16, nal: &[u8]| {
+        let mut block = vec![0x81];
+        block.extend_from_slice(&relative.to_be_bytes());
+        block.push(0);
+        block.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+        block.extend_from_slice(nal);
+        block
+    };
+
+    let mut cluster = vec![0xe7, 0x82, 0x03, 0xe8];
+    for (relative, reference, nal) in
+        [(0, None, &[0x65, 0x11][..]), (40, Some(-40i8), &[0x41, 0x22])]
+    {
+        let block = block(relative, nal);
+        let mut group = vec![0xa1, 0x80 | block.len() as u8];
+        group.extend_from_slice(&block);
+
+        if let Some(reference) = reference {
+            group.extend_from_slice(&[0xfb, 0x81, reference as u8]);
+        }
+
+        cluster.extend_from_slice(&[0xa0, 0x80 | group.len() as u8]);
+        cluster.extend_from_slice(&group);
+    }
+
+    bytes.extend_from_slice(&[0x1f, 0x43, 0xb6, 0x75, 0x80 | cluster.len() as u8]);
+    bytes.extend_from_slice(&cluster);
+
+    let mut reader = MatroskaReader::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(reader.codec(), VideoCodec::H264);
+    assert_eq!(
+        reader.parameter_sets(),
+        &ParameterSets::from(H264ParameterSets { sps: H264_SPS.to_vec(), pps: H264_PPS.to_vec() })
+    );
+
+    let frames = read_all(&mut reader);
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].2, h264_frame(&[0x65, 0x88, 0x84]));
+    assert_eq!(frames[1], (Duration::from_secs(1), true, h264_frame(&[0x65, 0x11])));
+    assert_eq!(frames[2], (Duration::from_millis(1040), false, vec![0, 0, 0, 1, 0x41, 0x22]));
+}
+
+#[test]
+fn test_decoder_configuration_records() {
+    let h264 = H264ParameterSets { sps: H264_SPS.to_vec(), pps: H264_PPS.to_vec() };
+    assert_eq!(H264ParameterSets::from_avcc(&h264.to_avcc()), Some(h264));
+
+    let hevc = ParameterSets::from_annex_b(VideoCodec::Hevc, HEVC_BYTES).unwrap();
+    let record = hevc.to_decoder_configuration_record();
+    assert_eq!(
+        ParameterSets::from_decoder_configuration_record(VideoCodec::Hevc, &record),
+        Some(hevc)
+    );
+    assert_eq!(
+        ParameterSets::from_decoder_configuration_record(VideoCodec::Hevc, &record[..30]),
+        None
+    );
+}
@@ -0,0 +1,162 @@
+use video_toolbox::{
+    color::{ChromaSiting, ColorInfo},
+    mp4::{fragment, init_segment, Mp4Sample},
+    H264ParameterSets, HevcParameterSets, ParameterSets, SpsInfo, VideoCodec,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+const H264_SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8];
+const H264_PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+/// Returns the payload of the first box of type `kind` directly inside `data`.
+fn find_box<'a>(mut data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+
+        if &data[4..8] == kind {
+            return Some(&data[8..size]);
+        }
+
+        data = &data[size..];
+    }
+
+    None
+}
+
+fn find_path<'a>(data: &'a [u8], path: &[(&[u8; 4], usize)]) -> &'a [u8] {
+    path.iter().fold(data, |data, (kind, skip)| &find_box(data, kind).unwrap()[*skip..])
+}
+
+#[test]
+fn test_sps_info() {
+    let hevc = HevcParameterSets::from_annex_b(HEVC_BYTES).unwrap();
+    assert_eq!(
+        hevc.sps_info(),
+        Some(SpsInfo {
+            width: 1280,
+            height: 720,
+            chroma_format_idc: 1,
+            bit_depth_luma: 8,
+            bit_depth_chroma: 8,
+            frame_rate: None,
+            color: ColorInfo { chroma_location: ChromaSiting::Center, ..ColorInfo::default() },
+        })
+    );
+
+    let h264 = H264ParameterSets { sps: H264_SPS.to_vec(), pps: H264_PPS.to_vec() };
+    let sps_info = h264.sps_info().unwrap();
+    assert_eq!((sps_info.width, sps_info.height), (1280, 720));
+}
+
+#[test]
+fn test_hevc_init_segment() {
+    let parameter_sets = ParameterSets::from_annex_b(VideoCodec::Hevc, HEVC_BYTES).unwrap();
+    let init = init_segment(&parameter_sets, 90_000).unwrap();
+
+    assert_eq!(&find_box(&init, b"ftyp").unwrap()[..4], b"iso6");
+
+    let tkhd = find_path(&init, &[(b"moov", 0), (b"trak", 0), (b"tkhd", 0)]);
+    assert_eq!(&tkhd[tkhd.len() - 8..], &[0x05, 0x00, 0, 0, 0x02, 0xd0, 0, 0]);
+
+    let mdhd = find_path(&init, &[(b"moov", 0), (b"trak", 0), (b"mdia", 0), (b"mdhd", 0)]);
+    assert_eq!(&mdhd[12..16], &90_000u32.to_be_bytes());
+
+    // Skip the stsd header and the fixed visual sample entry fields.
+    let hvcc = find_path(
+        &init,
+        &[
+            (b"moov", 0),
+            (b"trak", 0),
+            (b"mdia", 0),
+            (b"minf", 0),
+            (b"stbl", 0),
+            (b"stsd", 8),
+            (b"hvc1", 78),
+            (b"hvcC", 0),
+        ],
+    );
+
+    // Main profile, level 5, 4:2:0 8-bit and three parameter set arrays.
+    assert_eq!(&hvcc[..2], &[1, 1]);
+    assert_eq!(hvcc[12], 150);
+    assert_eq!(&hvcc[16..19], &[0xfd, 0xf8, 0xf8]);
+    assert_eq!(hvcc[22], 3);
+
+    let ParameterSets::Hevc(hevc) = &parameter_sets else { unreachable!() };
+    assert!(hvcc.windows(hevc.sps.len()).any(|window| window == hevc.sps));
+}
+
+#[test]
+fn test_h264_init_segment() {
+    let parameter_sets: ParameterSets =
+        H264ParameterSets { sps: H264_SPS.to_vec(), pps: H264_PPS.to_vec() }.into();
+    let init = init_segment(&parameter_sets, 90_000).unwrap();
+
+    let avcc = find_path(
+        &init,
+        &[
+            (b"moov", 0),
+            (b"trak", 0),
+            (b"mdia", 0),
+            (b"minf", 0),
+            (b"stbl", 0),
+            (b"stsd", 8),
+            (b"avc1", 78),
+            (b"avcC", 0),
+        ],
+    );
+
+    let mut expected = vec![1, 0x42, 0xc0, 0x1f, 0xff, 0xe1, 0, 9];
+    expected.extend_from_slice(H264_SPS);
+    expected.extend_from_slice(&[1, 0, 4]);
+    expected.extend_from_slice(H264_PPS);
+    assert_eq!(avcc, &expected[..]);
+}
+
+#[test]
+fn test_fragment_data_offsets() {
+    let keyframe = Mp4Sample::from_annex_b(VideoCodec::Hevc, HEVC_BYTES, 3000);
+    assert!(keyframe.is_keyframe);
+
+    // Parameter sets are stripped, leaving the SEI and IDR slice.
+    let nal_types: Vec<u8> = {
+        let mut data = &keyframe.data[..];
+        let mut types = vec![];
+
+        while !data.is_empty() {
+            let len = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+            types.push((data[4] >> 1) & 0b11_1111);
+            data = &data[4 + len..];
+        }
+
+        types
+    };
+    assert_eq!(nal_types, vec![39, 20]);
+
+    let delta = Mp4Sample::from_annex_b(VideoCodec::Hevc, &[0, 0, 0, 1, 0x02, 0x01, 0xab], 3000);
+    assert!(!delta.is_keyframe);
+    assert_eq!(delta.data, vec![0, 0, 0, 3, 0x02, 0x01, 0xab]);
+
+    let samples = [keyframe, delta];
+    let output = fragment(7, 90_000, &samples);
+
+    let moof = find_box(&output, b"moof").unwrap();
+    assert_eq!(find_box(moof, b"mfhd").unwrap(), &[0, 0, 0, 0, 0, 0, 0, 7]);
+
+    let traf = find_box(moof, b"traf").unwrap();
+    let tfdt = find_box(traf, b"tfdt").unwrap();
+    assert_eq!(&tfdt[4..], &90_000u64.to_be_bytes());
+
+    let trun = find_box(traf, b"trun").unwrap();
+    assert_eq!(&trun[4..8], &2u32.to_be_bytes());
+
+    let data_offset = u32::from_be_bytes(trun[8..12].try_into().unwrap()) as usize;
+    let first_size = u32::from_be_bytes(trun[16..20].try_into().unwrap()) as usize;
+    assert_eq!(&output[data_offset..data_offset + first_size], &samples[0].data[..]);
+    assert_eq!(&output[data_offset + first_size..], &samples[1].data[..]);
+
+    // Sync sample flags on the keyframe only.
+    assert_eq!(&trun[20..24], &0x0200_0000u32.to_be_bytes());
+    assert_eq!(&trun[32..36], &0x0101_0000u32.to_be_bytes());
+}
@@ -0,0 +1,93 @@
+use video_toolbox::{EncodedPacket, HevcParameterSets, PacketFraming, PictureType, Timestamp};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+/// Slice headers for PPS 0 without extra slice header bits.
+const P_SLICE: &[u8] = &[0x02, 0x01, 0xd4];
+const B_SLICE: &[u8] = &[0x02, 0x01, 0xf0];
+/// A P slice in temporal sub-layer 2.
+const P_SLICE_TEMPORAL_2: &[u8] = &[0x02, 0x03, 0xd4];
+/// A second slice segment, whose header has no `slice_type` at a fixed position.
+const LATER_SLICE: &[u8] = &[0x02, 0x01, 0x7f];
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    nals.iter().flat_map(|nal| [&[0, 0, 0, 1][..], nal].concat()).collect()
+}
+
+#[test]
+fn test_keyframe_packet() {
+    let packet = EncodedPacket::from_annex_b(HEVC_BYTES, PacketFraming::AnnexB, None);
+
+    assert_eq!(packet.data, HEVC_BYTES);
+    assert_eq!(packet.framing, PacketFraming::AnnexB);
+    assert!(packet.is_keyframe);
+    assert_eq!(packet.picture_type, Some(PictureType::I));
+    assert_eq!(packet.temporal_id, 0);
+    assert!(packet.format_changed);
+    assert_eq!(packet.parameter_sets, HevcParameterSets::from_annex_b(HEVC_BYTES));
+    assert_eq!(packet.pts, Timestamp::ZERO);
+    assert_eq!(packet.duration, None);
+}
+
+#[test]
+fn test_format_changed() {
+    let parameter_sets = HevcParameterSets::from_annex_b(HEVC_BYTES).unwrap();
+
+    let repeated =
+        EncodedPacket::from_annex_b(HEVC_BYTES, PacketFraming::AnnexB, Some(&parameter_sets));
+    assert!(!repeated.format_changed);
+
+    let other = HevcParameterSets { pps: vec![0x44, 0x01, 0xc1], ..parameter_sets.clone() };
+    let changed = EncodedPacket::from_annex_b(HEVC_BYTES, PacketFraming::AnnexB, Some(&other));
+    assert!(changed.format_changed);
+
+    let delta = EncodedPacket::from_annex_b(
+        &annex_b(&[P_SLICE]),
+        PacketFraming::AnnexB,
+        Some(&parameter_sets),
+    );
+    assert!(!delta.format_changed);
+    assert_eq!(delta.parameter_sets, None);
+}
+
+#[test]
+fn test_length_prefixed_framing() {
+    let packet = EncodedPacket::from_annex_b(HEVC_BYTES, PacketFraming::LengthPrefixed, None);
+
+    // The parameter sets move out of band, leaving the SEI and the slice.
+    assert!(packet.parameter_sets.is_some());
+    assert_eq!(packet.picture_type, Some(PictureType::I));
+
+    let mut nals = Vec::new();
+    let mut rest = &packet.data[..];
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        nals.push((rest[4] >> 1) & 0b11_1111);
+        rest = &rest[4 + len..];
+    }
+    assert_eq!(nals, [39, 20]);
+}
+
+#[test]
+fn test_picture_types() {
+    let picture_type = |nals: &[&[u8]]| {
+        EncodedPacket::from_annex_b(&annex_b(nals), PacketFraming::AnnexB, None).picture_type
+    };
+
+    assert_eq!(picture_type(&[P_SLICE]), Some(PictureType::P));
+    assert_eq!(picture_type(&[B_SLICE]), Some(PictureType::B));
+    assert_eq!(picture_type(&[LATER_SLICE]), None);
+    assert_eq!(picture_type(&[&[0x4e, 0x01, 0x05]]), None);
+
+    let packet = EncodedPacket::from_annex_b(&annex_b(&[P_SLICE]), PacketFraming::AnnexB, None);
+    assert!(!packet.is_keyframe);
+}
+
+#[test]
+fn test_temporal_id() {
+    let packet =
+        EncodedPacket::from_annex_b(&annex_b(&[P_SLICE_TEMPORAL_2]), PacketFraming::AnnexB, None);
+
+    assert_eq!(packet.temporal_id, 2);
+    assert_eq!(packet.picture_type, Some(PictureType::P));
+}
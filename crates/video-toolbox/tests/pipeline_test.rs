@@ -0,0 +1,262 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use video_toolbox::{
+    EncodePipeline, EncodeSession, FrameBuf, FrameDropped, FrameHandle, PacketFraming, PictureType,
+    PipelineError, PixelFormat, SessionOutput, Timestamp, VideoFrame,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+const P_SLICE: &[u8] = &[0, 0, 0, 1, 0x02, 0x01, 0xd4];
+
+/// Holds frames until told to finish them, like a hardware encoder with a
+/// frame delay. The first frame is a keyframe, the rest P-frames.
+#[derive(Default)]
+struct MockSession {
+    pending: Arc<Mutex<VecDeque<FrameHandle>>>,
+    framing: PacketFraming,
+    reject: bool,
+    flushes: usize,
+}
+
+impl MockSession {
+    fn complete(handle: FrameHandle) {
+        let access_unit = if handle.frame() == 0 { HEVC_BYTES } else { P_SLICE };
+        handle.complete(SessionOutput { access_unit: access_unit.to_vec(), ..Default::default() });
+    }
+
+    fn complete_oldest(pending: &Mutex<VecDeque<FrameHandle>>) {
+        let handle = pending.lock().unwrap().pop_front().unwrap();
+        Self::complete(handle);
+    }
+}
+
+impl EncodeSession for MockSession {
+    type Error = &'static str;
+
+    fn submit(&mut self, _frame: &VideoFrame, handle: FrameHandle) -> Result<(), Self::Error> {
+        if self.reject {
+            handle.discard();
+            return Err("rejected");
+        }
+
+        self.pending.lock().unwrap().push_back(handle);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flushes += 1;
+
+        while let Some(handle) = self.pending.lock().unwrap().pop_front() {
+            Self::complete(handle);
+        }
+
+        Ok(())
+    }
+
+    fn framing(&self) -> PacketFraming {
+        self.framing
+    }
+}
+
+fn frame_buf() -> FrameBuf {
+    FrameBuf::new(PixelFormat::Nv12, 16, 16).unwrap()
+}
+
+fn timed_frame(frame: &FrameBuf, index: i64) -> VideoFrame<'_> {
+    frame.as_frame().with_pts(Timestamp::new(index, 30)).with_duration(Timestamp::new(1, 30))
+}
+
+#[test]
+fn test_submit_returns_before_output() {
+    let (mut pipeline, packets) = EncodePipeline::with_channel(MockSession::default(), 4);
+    let frame = frame_buf();
+
+    for index in 0..3 {
+        pipeline.submit(&timed_frame(&frame, index)).unwrap();
+    }
+
+    assert_eq!(pipeline.frames_in_flight(), 3);
+    assert!(packets.try_recv().is_err());
+
+    pipeline.flush().unwrap();
+    assert_eq!(pipeline.frames_in_flight(), 0);
+    assert_eq!(pipeline.session().flushes, 1);
+
+    let packets: Vec<_> = packets.try_iter().map(Result::unwrap).collect();
+    assert_eq!(packets.len(), 3);
+
+    assert!(packets[0].is_keyframe);
+    assert!(packets[0].format_changed);
+    assert_eq!(packets[0].picture_type, Some(PictureType::I));
+
+    for (index, packet) in packets.iter().enumerate() {
+        // Timing falls back to the submitted frame's.
+        assert_eq!(packet.pts, Timestamp::new(index as i64, 30));
+        assert_eq!(packet.dts, packet.pts);
+        assert_eq!(packet.duration, Some(Timestamp::new(1, 30)));
+    }
+
+    assert!(!packets[1].is_keyframe);
+    assert!(!packets[1].format_changed);
+    assert_eq!(packets[2].picture_type, Some(PictureType::P));
+}
+
+#[test]
+fn test_callback_and_framing() {
+    let session = MockSession { framing: PacketFraming::LengthPrefixed, ..Default::default() };
+    let delivered = Arc::new(Mutex::new(vec![]));
+    let sink = delivered.clone();
+    let mut pipeline =
+        EncodePipeline::new(session, 2, move |output| sink.lock().unwrap().push(output));
+
+    pipeline.submit(&frame_buf().as_frame()).unwrap();
+    let session = pipeline.finish().unwrap();
+    assert_eq!(session.flushes, 1);
+
+    let delivered = delivered.lock().unwrap();
+    let packet = delivered[0].as_ref().unwrap();
+    assert_eq!(packet.framing, PacketFraming::LengthPrefixed);
+    assert_ne!(packet.data[..4], [0, 0, 0, 1]);
+    assert!(packet.parameter_sets.is_some());
+}
+
+#[test]
+fn test_session_output_timing() {
+    let (mut pipeline, packets) = EncodePipeline::with_channel(MockSession::default(), 4);
+    let pending = pipeline.session().pending.clone();
+    let frame = frame_buf();
+
+    pipeline.submit(&timed_frame(&frame, 2)).unwrap();
+    let handle = pending.lock().unwrap().pop_front().unwrap();
+    handle.complete(SessionOutput {
+        access_unit: HEVC_BYTES.to_vec(),
+        pts: Some(Timestamp::new(2002, 30000)),
+        dts: Some(Timestamp::new(1001, 30000)),
+        duration: None,
+        is_keyframe: None,
+    });
+
+    let packet = packets.try_recv().unwrap().unwrap();
+    assert_eq!(packet.pts, Timestamp::new(2002, 30000));
+    assert_eq!(packet.dts, Timestamp::new(1001, 30000));
+    assert_eq!(packet.duration, Some(Timestamp::new(1, 30)));
+    assert!(packet.is_keyframe);
+
+    // The session's sync sample flag wins over the access unit's NAL types.
+    pipeline.submit(&timed_frame(&frame, 3)).unwrap();
+    let handle = pending.lock().unwrap().pop_front().unwrap();
+    handle.complete(SessionOutput {
+        access_unit: HEVC_BYTES.to_vec(),
+        is_keyframe: Some(false),
+        ..Default::default()
+    });
+
+    assert!(!packets.try_recv().unwrap().unwrap().is_keyframe);
+}
+
+#[test]
+fn test_try_submit_when_full() {
+    let (mut pipeline, packets) = EncodePipeline::with_channel(MockSession::default(), 2);
+    let frame = frame_buf();
+
+    pipeline.try_submit(&frame.as_frame()).unwrap();
+    pipeline.try_submit(&frame.as_frame()).unwrap();
+    assert!(matches!(pipeline.try_submit(&frame.as_frame()), Err(PipelineError::Full(2))));
+
+    MockSession::complete_oldest(&pipeline.session().pending);
+    assert_eq!(pipeline.frames_in_flight(), 1);
+    pipeline.try_submit(&frame.as_frame()).unwrap();
+
+    pipeline.flush().unwrap();
+    assert_eq!(packets.try_iter().count(), 3);
+}
+
+#[test]
+fn test_submit_waits_for_free_place() {
+    let (mut pipeline, packets) = EncodePipeline::with_channel(MockSession::default(), 1);
+    let pending = pipeline.session().pending.clone();
+    let frame = frame_buf();
+
+    pipeline.submit(&frame.as_frame()).unwrap();
+
+    // The hardware finishes the first frame a little later on its own thread.
+    let completer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        MockSession::complete_oldest(&pending);
+    });
+
+    pipeline.submit(&frame.as_frame()).unwrap();
+    assert!(packets.try_recv().unwrap().is_ok());
+    assert_eq!(pipeline.frames_in_flight(), 1);
+
+    completer.join().unwrap();
+    pipeline.flush().unwrap();
+    assert_eq!(packets.try_iter().count(), 1);
+}
+
+#[test]
+fn test_dropped_frames() {
+    let (mut pipeline, packets) = EncodePipeline::with_channel(MockSession::default(), 4);
+    let pending = pipeline.session().pending.clone();
+    let frame = frame_buf();
+
+    pipeline.submit(&timed_frame(&frame, 0)).unwrap();
+    pipeline.submit(&timed_frame(&frame, 1)).unwrap();
+
+    let first = pending.lock().unwrap().pop_front().unwrap();
+    first.fail(-12902);
+    drop(pending.lock().unwrap().pop_front());
+
+    assert_eq!(pipeline.frames_in_flight(), 0);
+    assert_eq!(
+        packets.try_recv().unwrap(),
+        Err(FrameDropped { frame: 0, pts: Timestamp::ZERO, status: -12902 })
+    );
+    assert_eq!(
+        packets.try_recv().unwrap(),
+        Err(FrameDropped { frame: 1, pts: Timestamp::new(1, 30), status: 0 })
+    );
+}
+
+#[test]
+fn test_rejected_frame() {
+    let session = MockSession { reject: true, ..Default::default() };
+    let (mut pipeline, packets) = EncodePipeline::with_channel(session, 1);
+
+    assert!(matches!(
+        pipeline.submit(&frame_buf().as_frame()),
+        Err(PipelineError::Session("rejected"))
+    ));
+    assert_eq!(pipeline.frames_in_flight(), 0);
+    assert!(packets.try_recv().is_err());
+}
+
+#[test]
+fn test_panicking_sink() {
+    let delivered = Arc::new(Mutex::new(vec![]));
+    let sink = delivered.clone();
+    let mut pipeline = EncodePipeline::new(MockSession::default(), 4, move |output| {
+        let packet = output.unwrap();
+        assert!(!packet.is_keyframe, "sink rejects keyframes");
+        sink.lock().unwrap().push(packet);
+    });
+    let frame = frame_buf();
+
+    for index in 0..3 {
+        pipeline.submit(&timed_frame(&frame, index)).unwrap();
+    }
+
+    // The keyframe's output is lost, but the pipeline keeps delivering.
+    pipeline.flush().unwrap();
+    assert_eq!(pipeline.frames_in_flight(), 0);
+
+    pipeline.submit(&timed_frame(&frame, 3)).unwrap();
+    pipeline.flush().unwrap();
+
+    let pts: Vec<_> = delivered.lock().unwrap().iter().map(|packet| packet.pts).collect();
+    assert_eq!(pts, [Timestamp::new(1, 30), Timestamp::new(2, 30), Timestamp::new(3, 30)]);
+}
@@ -0,0 +1,60 @@
+use video_toolbox::PixelFormat;
+
+#[test]
+fn test_os_type_round_trip() {
+    for format in PixelFormat::ALL {
+        assert_eq!(PixelFormat::from_os_type(format.os_type()), Some(format));
+    }
+
+    assert_eq!(PixelFormat::Argb32.os_type(), 0x20);
+    assert_eq!(PixelFormat::Nv12.os_type(), u32::from_be_bytes(*b"420v"));
+    assert_eq!(PixelFormat::P010FullRange.os_type(), u32::from_be_bytes(*b"xf20"));
+    assert_eq!(PixelFormat::from_os_type(u32::from_be_bytes(*b"abcd")), None);
+}
+
+#[test]
+fn test_plane_layouts() {
+    // (format, subsampling, bytes per row of each plane at 1920 pixels wide)
+    let cases: [(PixelFormat, (u32, u32), &[usize]); 9] = [
+        (PixelFormat::Bgra32, (1, 1), &[7680]),
+        (PixelFormat::Nv12, (2, 2), &[1920, 1920]),
+        (PixelFormat::I420FullRange, (2, 2), &[1920, 960, 960]),
+        (PixelFormat::Nv24, (1, 1), &[1920, 3840]),
+        (PixelFormat::P010, (2, 2), &[3840, 3840]),
+        (PixelFormat::P210, (2, 1), &[3840, 3840]),
+        (PixelFormat::P410FullRange, (1, 1), &[3840, 7680]),
+        (PixelFormat::Uyvy, (2, 1), &[3840]),
+        (PixelFormat::Gray16, (1, 1), &[3840]),
+    ];
+
+    for (format, subsampling, rows) in cases {
+        let plane_count = format.plane_count();
+        assert_eq!(plane_count, rows.len(), "{:?}", format);
+        assert_eq!(format.chroma_subsampling(), subsampling, "{:?}", format);
+
+        let actual: Vec<usize> =
+            (0..plane_count).map(|plane| format.bytes_per_row(plane, 1920)).collect();
+        assert_eq!(actual, rows, "{:?}", format);
+    }
+
+    assert_eq!(PixelFormat::P010.bit_depth(), 10);
+    assert!(PixelFormat::Nv12FullRange.is_full_range());
+    assert!(!PixelFormat::Nv12.is_full_range());
+    assert!(PixelFormat::X2Rgb10.is_rgb());
+}
+
+#[test]
+fn test_buffer_sizes() {
+    assert_eq!(PixelFormat::Bgra32.buffer_size(1280, 720), 1280 * 720 * 4);
+    assert_eq!(PixelFormat::Nv12.buffer_size(1920, 1080), 1920 * 1080 * 3 / 2);
+    assert_eq!(PixelFormat::P210.buffer_size(1920, 1080), 1920 * 1080 * 4);
+
+    // Odd sizes round chroma up.
+    assert_eq!(PixelFormat::I420.plane_size(1, 33, 17), 17 * 9);
+    assert_eq!(PixelFormat::P010.plane_size(1, 33, 17), 17 * 4 * 9);
+    assert_eq!(PixelFormat::Yuyv.bytes_per_row(0, 33), 68);
+
+    // v210 packs 48 pixels into each 128 byte block.
+    assert_eq!(PixelFormat::V210.bytes_per_row(0, 1920), 5120);
+    assert_eq!(PixelFormat::V210.bytes_per_row(0, 1280), 27 * 128);
+}
@@ -0,0 +1,103 @@
+use video_toolbox::{
+    color::ColorInfo,
+    scale::{scale, ScaleFilter},
+    HevcParameterSets, HevcProfile, PixelFormat, SpsInfo, VideoFrame,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+fn sps_info(bit_depth: u8, chroma_format_idc: u8, full_range: bool) -> SpsInfo {
+    SpsInfo {
+        width: 1920,
+        height: 1080,
+        chroma_format_idc,
+        bit_depth_luma: bit_depth,
+        bit_depth_chroma: bit_depth,
+        frame_rate: None,
+        color: ColorInfo { full_range, ..ColorInfo::default() },
+    }
+}
+
+#[test]
+fn test_decoder_output_for_stream() {
+    let cases = [
+        (sps_info(8, 1, false), PixelFormat::Nv12),
+        (sps_info(8, 0, true), PixelFormat::Nv12FullRange),
+        (sps_info(8, 2, false), PixelFormat::P210),
+        (sps_info(8, 3, false), PixelFormat::Nv24),
+        (sps_info(10, 1, true), PixelFormat::P010FullRange),
+        (sps_info(10, 2, false), PixelFormat::P210),
+        (sps_info(10, 3, false), PixelFormat::P410),
+        (sps_info(12, 1, false), PixelFormat::Rgba64),
+    ];
+
+    for (sps_info, format) in cases {
+        assert_eq!(PixelFormat::for_sps_info(&sps_info), format, "{:?}", sps_info);
+        assert!(format.is_decoder_output(), "{:?}", format);
+        assert!(format.can_hold(&sps_info), "{:?}", format);
+    }
+
+    let hevc_sps_info = HevcParameterSets::from_annex_b(HEVC_BYTES).unwrap().sps_info().unwrap();
+    assert_eq!(PixelFormat::for_sps_info(&hevc_sps_info), PixelFormat::Nv12);
+}
+
+#[test]
+fn test_can_hold() {
+    let main10 = sps_info(10, 1, false);
+
+    // 8-bit outputs would truncate 10-bit samples.
+    assert!(!PixelFormat::Bgra32.can_hold(&main10));
+    assert!(!PixelFormat::Nv12.can_hold(&main10));
+    assert!(PixelFormat::P010.can_hold(&main10));
+    assert!(PixelFormat::Rgba64.can_hold(&main10));
+
+    // 4:2:0 outputs would halve 4:2:2 chroma vertically.
+    assert!(!PixelFormat::P010.can_hold(&sps_info(10, 2, false)));
+    assert!(PixelFormat::P410.can_hold(&sps_info(10, 2, false)));
+    assert!(PixelFormat::Bgra32.can_hold(&sps_info(8, 3, false)));
+
+    // Monochrome fits any chroma layout.
+    assert!(PixelFormat::Nv12.can_hold(&sps_info(8, 0, false)));
+}
+
+#[test]
+fn test_hevc_profile() {
+    assert_eq!(HevcProfile::default(), HevcProfile::Main);
+    assert_eq!(HevcProfile::Main10.profile_idc(), 2);
+    assert_eq!(HevcProfile::Main42210.profile_idc(), 4);
+    assert_eq!(HevcProfile::Main42210.bit_depth(), 10);
+    assert_eq!(HevcProfile::Main42210.chroma_format_idc(), 2);
+
+    assert_eq!(HevcProfile::for_format(PixelFormat::Nv12), HevcProfile::Main);
+    assert_eq!(HevcProfile::for_format(PixelFormat::Bgra32), HevcProfile::Main);
+    assert_eq!(HevcProfile::for_format(PixelFormat::P010), HevcProfile::Main10);
+    assert_eq!(HevcProfile::for_format(PixelFormat::X2Rgb10), HevcProfile::Main10);
+    assert_eq!(HevcProfile::for_format(PixelFormat::P210FullRange), HevcProfile::Main42210);
+    assert!(PixelFormat::P210.is_encoder_input());
+
+    assert_eq!(HevcProfile::for_sps_info(&sps_info(8, 1, false)), Some(HevcProfile::Main));
+    assert_eq!(HevcProfile::for_sps_info(&sps_info(10, 1, false)), Some(HevcProfile::Main10));
+    assert_eq!(HevcProfile::for_sps_info(&sps_info(8, 2, false)), Some(HevcProfile::Main42210));
+    assert_eq!(HevcProfile::for_sps_info(&sps_info(10, 3, false)), None);
+    assert_eq!(HevcProfile::for_sps_info(&sps_info(12, 1, false)), None);
+
+    let parameter_sets = HevcParameterSets::from_annex_b(HEVC_BYTES).unwrap();
+    let profile = HevcProfile::for_sps_info(&parameter_sets.sps_info().unwrap()).unwrap();
+    assert_eq!(profile.profile_idc(), parameter_sets.profile_tier_level().unwrap().profile_idc);
+}
+
+#[test]
+fn test_rgba64_layout() {
+    let format = PixelFormat::Rgba64;
+
+    assert_eq!(PixelFormat::from_os_type(u32::from_be_bytes(*b"l64r")), Some(format));
+    assert!(format.is_rgb() && format.has_alpha() && format.is_full_range());
+    assert_eq!(format.bit_depth(), 16);
+    assert_eq!(format.buffer_size(1920, 1080), 1920 * 1080 * 8);
+
+    let pixel = [0x00, 0x10, 0x00, 0x20, 0x00, 0x30, 0xff, 0xff];
+    let data: Vec<u8> = pixel.iter().copied().cycle().take(format.buffer_size(4, 4)).collect();
+    let frame = VideoFrame::from_packed(format, 4, 4, &data).unwrap();
+    let scaled = scale(&frame, 2, 2, ScaleFilter::Bilinear).unwrap();
+    assert_eq!(scaled.planes()[0].data(), pixel.repeat(4));
+}
@@ -0,0 +1,315 @@
+use std::{net::UdpSocket, time::Duration};
+use video_toolbox::{
+    rtp::{
+        Depacketizer, DepacketizerEvent, HevcPacketizer, JitterBuffer, JitterBufferOutput,
+        PacketizerConfig, RtpPacket,
+    },
+    VideoCodec,
+};
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for nal in nals {
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+        bytes.extend_from_slice(nal);
+    }
+
+    bytes
+}
+
+fn hevc_nal(nal_type: u8, len: usize) -> Vec<u8> {
+    let mut nal = vec![nal_type << 1, 1];
+    nal.extend((0..len - 2).map(|i| (i % 251) as u8 + 2));
+    nal
+}
+
+fn packet(sequence_number: u16, timestamp: u32, marker: bool, payload: Vec<u8>) -> RtpPacket {
+    RtpPacket { marker, payload_type: 96, sequence_number, timestamp, ssrc: 1, payload }
+}
+
+fn access_units(events: &[DepacketizerEvent]) -> Vec<Vec<u8>> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            DepacketizerEvent::AccessUnit(access_unit) => Some(access_unit.data.clone()),
+            DepacketizerEvent::KeyframeRequest => None,
+        })
+        .collect()
+}
+
+fn keyframe_requests(events: &[DepacketizerEvent]) -> usize {
+    events.iter().filter(|event| **event == DepacketizerEvent::KeyframeRequest).count()
+}
+
+#[test]
+fn test_parse_round_trip() {
+    let original = packet(1234, 5678, true, vec![1, 2, 3, 4]);
+    assert_eq!(RtpPacket::parse(&original.to_bytes()).unwrap(), original);
+}
+
+#[test]
+fn test_parse_skips_csrcs_extension_and_padding() {
+    let mut bytes = vec![0b1011_0001, 0x60, 0, 7, 0, 0, 0, 9, 0, 0, 0, 1];
+    bytes.extend_from_slice(&[0xaa; 4]); // CSRC
+    bytes.extend_from_slice(&[0xbe, 0xde, 0, 1, 1, 2, 3, 4]); // One word extension
+    bytes.extend_from_slice(&[5, 6, 7]); // Payload
+    bytes.extend_from_slice(&[0, 0, 3]); // Padding
+
+    let packet = RtpPacket::parse(&bytes).unwrap();
+    assert!(!packet.marker);
+    assert_eq!(packet.payload_type, 96);
+    assert_eq!(packet.sequence_number, 7);
+    assert_eq!(packet.timestamp, 9);
+    assert_eq!(packet.payload, vec![5, 6, 7]);
+
+    assert!(RtpPacket::parse(&bytes[..8]).is_err());
+    bytes[0] = 0b0100_0000;
+    assert!(RtpPacket::parse(&bytes).is_err());
+}
+
+#[test]
+fn test_jitter_buffer_reorders_across_wrap() {
+    let mut jitter_buffer = JitterBuffer::new(4);
+
+    for sequence_number in [65534, 0, 65535, 1] {
+        jitter_buffer.push(packet(sequence_number, 0, false, vec![]));
+    }
+
+    // Duplicates and packets that were already released are dropped.
+    assert!(!jitter_buffer.push(packet(0, 0, false, vec![])));
+
+    let mut released = vec![];
+    while let Some(JitterBufferOutput::Packet(packet)) = jitter_buffer.pop() {
+        released.push(packet.sequence_number);
+    }
+
+    assert_eq!(released, vec![65534, 65535, 0, 1]);
+    assert!(!jitter_buffer.push(packet(65535, 0, false, vec![])));
+}
+
+#[test]
+fn test_jitter_buffer_reports_loss_when_full() {
+    let mut jitter_buffer = JitterBuffer::new(2);
+
+    jitter_buffer.push(packet(10, 0, false, vec![]));
+    assert!(matches!(jitter_buffer.pop(), Some(JitterBufferOutput::Packet(_))));
+
+    jitter_buffer.push(packet(13, 0, false, vec![]));
+    jitter_buffer.push(packet(14, 0, false, vec![]));
+    assert_eq!(jitter_buffer.pop(), None);
+
+    jitter_buffer.push(packet(15, 0, false, vec![]));
+    assert_eq!(jitter_buffer.pop(), Some(JitterBufferOutput::Lost { count: 2 }));
+    assert_eq!(jitter_buffer.flush().len(), 3);
+    assert!(jitter_buffer.is_empty());
+}
+
+#[test]
+fn test_jitter_buffer_resyncs() {
+    let mut jitter_buffer = JitterBuffer::new(2);
+
+    jitter_buffer.push(packet(30_000, 0, false, vec![]));
+    assert!(matches!(jitter_buffer.pop(), Some(JitterBufferOutput::Packet(_))));
+
+    // A sender restarting its sequence numbers is out of the window at first.
+    for sequence_number in 100..115 {
+        assert!(!jitter_buffer.push(packet(sequence_number, 0, false, vec![])));
+    }
+
+    for sequence_number in 115..117 {
+        assert!(jitter_buffer.push(packet(sequence_number, 0, false, vec![])));
+    }
+
+    assert_eq!(jitter_buffer.pop(), Some(JitterBufferOutput::Resync));
+    assert!(
+        matches!(jitter_buffer.pop(), Some(JitterBufferOutput::Packet(p)) if p.sequence_number == 115)
+    );
+    assert!(
+        matches!(jitter_buffer.pop(), Some(JitterBufferOutput::Packet(p)) if p.sequence_number == 116)
+    );
+
+    // A new SSRC restarts the buffer straight away, dropping what it held.
+    jitter_buffer.push(packet(118, 0, false, vec![]));
+    jitter_buffer.push(RtpPacket { ssrc: 2, ..packet(5, 0, false, vec![]) });
+    assert_eq!(jitter_buffer.len(), 1);
+    assert_eq!(jitter_buffer.pop(), Some(JitterBufferOutput::Resync));
+    assert!(matches!(jitter_buffer.pop(), Some(JitterBufferOutput::Packet(p)) if p.ssrc == 2));
+}
+
+#[test]
+fn test_restarted_sender_requests_keyframe() {
+    let mut packetizer = HevcPacketizer::new(PacketizerConfig::default()).unwrap();
+    let keyframe = annex_b(&[&hevc_nal(32, 20), &hevc_nal(33, 30), &hevc_nal(19, 100)]);
+    let delta = annex_b(&[&hevc_nal(1, 100)]);
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 4);
+    let mut events = vec![];
+    for packet in packetizer.packetize(&keyframe, Duration::ZERO) {
+        events.extend(depacketizer.push(packet));
+    }
+    assert_eq!(access_units(&events), vec![keyframe]);
+
+    // The restarted sender continues with a delta frame under a new SSRC.
+    let mut events = vec![];
+    for packet in packetizer.packetize(&delta, Duration::from_millis(40)) {
+        events.extend(depacketizer.push(RtpPacket { ssrc: 2, ..packet }));
+    }
+    events.extend(depacketizer.flush());
+    assert!(access_units(&events).is_empty());
+    assert_eq!(keyframe_requests(&events), 1);
+}
+
+#[test]
+fn test_hevc_reordered_packets_reassemble() {
+    let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+    let config = PacketizerConfig { mtu: 300, ..PacketizerConfig::default() };
+    let mut packetizer = HevcPacketizer::new(config).unwrap();
+    let mut packets = packetizer.packetize(hevc_bytes, Duration::ZERO);
+    assert!(packets.len() > 10);
+
+    // Swap neighbouring pairs after the first packet, which anchors the sequence.
+    for pair in packets[1..].chunks_mut(2) {
+        pair.reverse();
+    }
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 8);
+    let mut events = vec![];
+    for packet in packets {
+        events.extend(depacketizer.push(packet));
+    }
+
+    assert_eq!(events.len(), 1);
+    match &events[0] {
+        DepacketizerEvent::AccessUnit(access_unit) => {
+            assert!(access_unit.is_keyframe);
+            assert_eq!(access_unit.data, hevc_bytes.to_vec());
+        },
+        event => panic!("Unexpected event {:?}", event),
+    }
+}
+
+#[test]
+fn test_hevc_loss_requests_keyframe() {
+    let config = PacketizerConfig { mtu: 200, ..PacketizerConfig::default() };
+    let mut packetizer = HevcPacketizer::new(config).unwrap();
+
+    let keyframe = annex_b(&[&hevc_nal(32, 20), &hevc_nal(33, 30), &hevc_nal(19, 600)]);
+    let delta = annex_b(&[&hevc_nal(1, 600)]);
+
+    let mut packets = vec![];
+    packets.extend(packetizer.packetize(&keyframe, Duration::from_millis(0)));
+    packets.extend(packetizer.packetize(&delta, Duration::from_millis(40)));
+    let lost_index = packets.len() - 2;
+    packets.extend(packetizer.packetize(&delta, Duration::from_millis(80)));
+    packets.extend(packetizer.packetize(&keyframe, Duration::from_millis(120)));
+    packets.extend(packetizer.packetize(&delta, Duration::from_millis(160)));
+
+    // Drop a middle fragment of the first delta frame.
+    packets.remove(lost_index);
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 2);
+    let mut events = vec![];
+    for packet in packets {
+        events.extend(depacketizer.push(packet));
+    }
+    events.extend(depacketizer.flush());
+
+    assert_eq!(access_units(&events), vec![keyframe.clone(), keyframe, delta]);
+    assert_eq!(keyframe_requests(&events), 1);
+}
+
+#[test]
+fn test_hevc_header_only_nal_units() {
+    let config = PacketizerConfig { mtu: 200, ..PacketizerConfig::default() };
+    let mut packetizer = HevcPacketizer::new(config).unwrap();
+
+    let keyframe = annex_b(&[&hevc_nal(32, 20), &hevc_nal(33, 30), &hevc_nal(19, 600)]);
+    let end_of_bitstream = annex_b(&[&hevc_nal(37, 2)]);
+
+    let mut packets = packetizer.packetize(&keyframe, Duration::ZERO);
+    packets.extend(packetizer.packetize(&end_of_bitstream, Duration::from_millis(40)));
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 2);
+    let mut events = vec![];
+    for packet in packets {
+        events.extend(depacketizer.push(packet));
+    }
+    events.extend(depacketizer.flush());
+
+    assert_eq!(access_units(&events), vec![keyframe, end_of_bitstream]);
+}
+
+#[test]
+fn test_stream_starting_without_keyframe() {
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 2);
+
+    let events = depacketizer.push(packet(0, 0, true, hevc_nal(1, 20)));
+    assert_eq!(events, vec![DepacketizerEvent::KeyframeRequest]);
+
+    // Only one request is raised while waiting.
+    let events = depacketizer.push(packet(1, 3000, true, hevc_nal(1, 20)));
+    assert!(events.is_empty());
+}
+
+#[test]
+fn test_h264_stap_a_and_fu_a() {
+    let sps = vec![0x67, 0x42, 0xc0, 0x1f, 0xaa];
+    let pps = vec![0x68, 0xce, 0x3c, 0x80];
+    let idr: Vec<u8> = std::iter::once(0x65).chain((0..100).map(|i| i as u8)).collect();
+    let slice: Vec<u8> = vec![0x41, 1, 2, 3];
+
+    let mut stap_a = vec![0x78];
+    for nal in [&sps, &pps] {
+        stap_a.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+        stap_a.extend_from_slice(nal);
+    }
+
+    let fu_indicator = (idr[0] & 0b1110_0000) | 28;
+    let mut fu_start = vec![fu_indicator, 0b1000_0000 | 5];
+    fu_start.extend_from_slice(&idr[1..50]);
+    let mut fu_end = vec![fu_indicator, 0b0100_0000 | 5];
+    fu_end.extend_from_slice(&idr[50..]);
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::H264, 4);
+    let mut events = vec![];
+    events.extend(depacketizer.push(packet(100, 0, false, stap_a)));
+    events.extend(depacketizer.push(packet(102, 0, true, fu_end)));
+    events.extend(depacketizer.push(packet(101, 0, false, fu_start)));
+    events.extend(depacketizer.push(packet(103, 3000, true, slice.clone())));
+
+    assert_eq!(access_units(&events), vec![annex_b(&[&sps, &pps, &idr]), annex_b(&[&slice])]);
+    assert_eq!(keyframe_requests(&events), 0);
+}
+
+#[test]
+fn test_udp_loopback() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.connect(receiver.local_addr().unwrap()).unwrap();
+
+    let keyframe = annex_b(&[&hevc_nal(32, 20), &hevc_nal(33, 30), &hevc_nal(19, 5000)]);
+    let delta = annex_b(&[&hevc_nal(1, 3000)]);
+
+    let mut packetizer = HevcPacketizer::new(PacketizerConfig::default()).unwrap();
+    let mut packets = vec![];
+    for (i, access_unit) in [&keyframe, &delta, &delta].into_iter().enumerate() {
+        packets.extend(packetizer.packetize(access_unit, Duration::from_millis(40 * i as u64)));
+    }
+
+    for packet in &packets {
+        sender.send(&packet.to_bytes()).unwrap();
+    }
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 16);
+    let mut events = vec![];
+    let mut buffer = [0u8; 2048];
+    for _ in 0..packets.len() {
+        let len = receiver.recv(&mut buffer).unwrap();
+        events.extend(depacketizer.push(RtpPacket::parse(&buffer[..len]).unwrap()));
+    }
+
+    assert_eq!(access_units(&events), vec![keyframe, delta.clone(), delta]);
+}
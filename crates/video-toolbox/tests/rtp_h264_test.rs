@@ -0,0 +1,131 @@
+use std::time::Duration;
+use video_toolbox::{
+    rtp::{
+        Depacketizer, DepacketizerEvent, H264PacketizationMode, H264Packetizer, PacketizerConfig,
+    },
+    VideoCodec,
+};
+
+const SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8];
+const PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for nal in nals {
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+        bytes.extend_from_slice(nal);
+    }
+
+    bytes
+}
+
+fn h264_nal(header: u8, len: usize) -> Vec<u8> {
+    std::iter::once(header).chain((0..len - 1).map(|i| (i % 251) as u8 + 2)).collect()
+}
+
+#[test]
+fn test_non_interleaved_round_trip() {
+    let idr = h264_nal(0x65, 3000);
+    let slice = h264_nal(0x41, 800);
+
+    let config = PacketizerConfig { mtu: 500, ..PacketizerConfig::default() };
+    let mut packetizer =
+        H264Packetizer::new(config, H264PacketizationMode::NonInterleaved).unwrap();
+
+    let keyframe = annex_b(&[SPS, PPS, &idr]);
+    let delta = annex_b(&[&slice]);
+
+    let mut packets = packetizer.packetize(&keyframe, Duration::ZERO).unwrap();
+
+    // SPS and PPS share a STAP-A, the IDR slice is split into FU-A packets.
+    assert_eq!(packets[0].payload[0] & 0b1_1111, 24);
+    assert!(packets[1..].iter().all(|packet| packet.payload[0] & 0b1_1111 == 28));
+    assert!(packets.iter().all(|packet| packet.to_bytes().len() <= 500));
+    assert!(packets.last().unwrap().marker);
+
+    packets.extend(packetizer.packetize(&delta, Duration::from_millis(33)).unwrap());
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::H264, 4);
+    let mut access_units = vec![];
+    for packet in packets {
+        for event in depacketizer.push(packet) {
+            if let DepacketizerEvent::AccessUnit(access_unit) = event {
+                access_units.push(access_unit);
+            }
+        }
+    }
+
+    assert_eq!(access_units.len(), 2);
+    assert!(access_units[0].is_keyframe);
+    assert_eq!(access_units[0].data, keyframe);
+    assert_eq!(access_units[1].data, delta);
+    assert_eq!(access_units[1].timestamp, 2970);
+}
+
+#[test]
+fn test_single_nal_unit_mode() {
+    let config = PacketizerConfig { mtu: 500, ..PacketizerConfig::default() };
+    let mut packetizer = H264Packetizer::new(config, H264PacketizationMode::SingleNalUnit).unwrap();
+
+    let idr = h264_nal(0x65, 400);
+    let packets = packetizer.packetize(&annex_b(&[SPS, PPS, &idr]), Duration::ZERO).unwrap();
+
+    let payloads: Vec<&[u8]> = packets.iter().map(|packet| packet.payload.as_slice()).collect();
+    assert_eq!(payloads, vec![SPS, PPS, &idr[..]]);
+    assert_eq!(packets.iter().filter(|packet| packet.marker).count(), 1);
+
+    let too_large = h264_nal(0x65, 1000);
+    assert!(packetizer.packetize(&annex_b(&[&too_large]), Duration::ZERO).is_err());
+}
+
+#[test]
+fn test_header_only_nal_units_are_sent() {
+    let idr = h264_nal(0x65, 300);
+    let end_of_sequence = [0x0a];
+    let end_of_stream = [0x0b];
+    let access_unit = annex_b(&[&idr, &end_of_sequence, &end_of_stream]);
+
+    let mut packetizer =
+        H264Packetizer::new(PacketizerConfig::default(), H264PacketizationMode::SingleNalUnit)
+            .unwrap();
+    let packets = packetizer.packetize(&access_unit, Duration::ZERO).unwrap();
+
+    let payloads: Vec<&[u8]> = packets.iter().map(|packet| packet.payload.as_slice()).collect();
+    assert_eq!(payloads, vec![&idr[..], &end_of_sequence, &end_of_stream]);
+
+    let mut packetizer =
+        H264Packetizer::new(PacketizerConfig::default(), H264PacketizationMode::NonInterleaved)
+            .unwrap();
+    let packets = packetizer.packetize(&access_unit, Duration::ZERO).unwrap();
+
+    let mut depacketizer = Depacketizer::new(VideoCodec::H264, 4);
+    let access_units: Vec<_> = packets
+        .into_iter()
+        .flat_map(|packet| depacketizer.push(packet))
+        .filter_map(|event| match event {
+            DepacketizerEvent::AccessUnit(access_unit) => Some(access_unit),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(access_units.len(), 1);
+    assert_eq!(access_units[0].data, access_unit);
+}
+
+#[test]
+fn test_fmtp_from_encoder_parameter_sets() {
+    let config = PacketizerConfig { payload_type: 97, ..PacketizerConfig::default() };
+    let mut packetizer =
+        H264Packetizer::new(config, H264PacketizationMode::NonInterleaved).unwrap();
+    assert_eq!(packetizer.fmtp(), None);
+
+    packetizer.packetize(&annex_b(&[SPS, PPS, &h264_nal(0x65, 100)]), Duration::ZERO).unwrap();
+
+    assert_eq!(packetizer.parameter_sets().unwrap().sps, SPS);
+    assert_eq!(
+        packetizer.fmtp().unwrap(),
+        "a=fmtp:97 packetization-mode=1;profile-level-id=42c01f;\
+         sprop-parameter-sets=Z0LAH9oBQBbo,aM48gA=="
+    );
+}
@@ -0,0 +1,190 @@
+use std::time::Duration;
+use video_toolbox::rtp::{HevcPacketizer, PacketizerConfig, RtpPacket};
+
+fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for nal in nals {
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+        bytes.extend_from_slice(nal);
+    }
+
+    bytes
+}
+
+fn hevc_nal(nal_type: u8, len: usize) -> Vec<u8> {
+    let mut nal = vec![nal_type << 1, 1];
+    nal.extend((0..len - 2).map(|i| (i % 251) as u8 + 2));
+    nal
+}
+
+/// Reassembles the NAL units carried by single NAL, AP and FU packets.
+fn depacketize(packets: &[RtpPacket]) -> Vec<Vec<u8>> {
+    let mut nals = vec![];
+    let mut fragment = vec![];
+
+    for packet in packets {
+        let payload = &packet.payload;
+        let packet_type = (payload[0] >> 1) & 0b11_1111;
+
+        match packet_type {
+            48 => {
+                let mut offset = 2;
+                while offset < payload.len() {
+                    let size = u16::from_be_bytes([payload[offset], payload[offset + 1]]) as usize;
+                    offset += 2;
+                    nals.push(payload[offset..offset + size].to_vec());
+                    offset += size;
+                }
+            },
+            49 => {
+                let fu_header = payload[2];
+                if fu_header & 0b1000_0000 != 0 {
+                    let nal_type = fu_header & 0b11_1111;
+                    fragment = vec![(payload[0] & 0b1000_0001) | (nal_type << 1), payload[1]];
+                }
+                fragment.extend_from_slice(&payload[3..]);
+                if fu_header & 0b0100_0000 != 0 {
+                    nals.push(std::mem::take(&mut fragment));
+                }
+            },
+            _ => nals.push(payload.clone()),
+        }
+    }
+
+    nals
+}
+
+#[test]
+fn test_parameter_sets_are_aggregated() {
+    let vps = hevc_nal(32, 24);
+    let sps = hevc_nal(33, 40);
+    let pps = hevc_nal(34, 8);
+    let idr = hevc_nal(19, 500);
+
+    let mut packetizer = HevcPacketizer::new(PacketizerConfig::default()).unwrap();
+    let packets = packetizer.packetize(&annex_b(&[&vps, &sps, &pps, &idr]), Duration::ZERO);
+
+    // VPS, SPS, PPS and the slice all fit in a single aggregation packet.
+    assert_eq!(packets.len(), 1);
+    assert_eq!((packets[0].payload[0] >> 1) & 0b11_1111, 48);
+    assert!(packets[0].marker);
+    assert_eq!(depacketize(&packets), vec![vps, sps, pps, idr]);
+}
+
+#[test]
+fn test_single_nal_unit_packet() {
+    let config = PacketizerConfig { mtu: 400, ..PacketizerConfig::default() };
+    let mut packetizer = HevcPacketizer::new(config).unwrap();
+
+    let slice = hevc_nal(1, 300);
+    let packets = packetizer.packetize(&annex_b(&[&slice]), Duration::ZERO);
+
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].payload, slice);
+}
+
+#[test]
+fn test_header_only_nal_units_are_sent() {
+    let slice = hevc_nal(1, 300);
+    let end_of_sequence = hevc_nal(36, 2);
+    let end_of_bitstream = hevc_nal(37, 2);
+
+    let mut packetizer = HevcPacketizer::new(PacketizerConfig::default()).unwrap();
+    let packets = packetizer
+        .packetize(&annex_b(&[&slice, &end_of_sequence, &end_of_bitstream]), Duration::ZERO);
+    assert_eq!(depacketize(&packets), vec![slice, end_of_sequence.clone(), end_of_bitstream]);
+
+    let packets = packetizer.packetize(&annex_b(&[&end_of_sequence]), Duration::ZERO);
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].payload, end_of_sequence);
+}
+
+#[test]
+fn test_large_nal_is_fragmented() {
+    let config = PacketizerConfig { mtu: 200, ..PacketizerConfig::default() };
+    let mut packetizer = HevcPacketizer::new(config).unwrap();
+
+    let sps = hevc_nal(33, 40);
+    let slice = hevc_nal(19, 1000);
+    let packets = packetizer.packetize(&annex_b(&[&sps, &slice]), Duration::ZERO);
+
+    assert!(packets.len() > 2);
+    assert!(packets.iter().all(|packet| packet.to_bytes().len() <= 200));
+
+    for packet in &packets[1..] {
+        assert_eq!((packet.payload[0] >> 1) & 0b11_1111, 49);
+    }
+
+    assert_eq!(packets[1].payload[2] & 0b1100_0000, 0b1000_0000);
+    assert_eq!(packets.last().unwrap().payload[2] & 0b1100_0000, 0b0100_0000);
+
+    let markers: Vec<bool> = packets.iter().map(|packet| packet.marker).collect();
+    assert_eq!(markers.iter().filter(|marker| **marker).count(), 1);
+    assert!(markers.last().unwrap());
+
+    assert_eq!(depacketize(&packets), vec![sps, slice]);
+}
+
+#[test]
+fn test_sequence_numbers_and_timestamps() {
+    let config = PacketizerConfig {
+        mtu: 100,
+        initial_sequence_number: u16::MAX - 1,
+        timestamp_offset: 1000,
+        ..PacketizerConfig::default()
+    };
+    let mut packetizer = HevcPacketizer::new(config).unwrap();
+
+    let slice = hevc_nal(1, 250);
+    let first = packetizer.packetize(&annex_b(&[&slice]), Duration::ZERO);
+    let second = packetizer.packetize(&annex_b(&[&slice]), Duration::from_millis(40));
+
+    let sequence_numbers: Vec<u16> =
+        first.iter().chain(second.iter()).map(|packet| packet.sequence_number).collect();
+    assert_eq!(sequence_numbers, vec![65534, 65535, 0, 1, 2, 3]);
+
+    assert!(first.iter().all(|packet| packet.timestamp == 1000));
+    assert!(second.iter().all(|packet| packet.timestamp == 1000 + 3600));
+    assert_eq!(packetizer.next_sequence_number(), 4);
+}
+
+#[test]
+fn test_header_serialization() {
+    let mut packetizer = HevcPacketizer::new(PacketizerConfig {
+        payload_type: 98,
+        ssrc: 0xdead_beef,
+        ..PacketizerConfig::default()
+    })
+    .unwrap();
+
+    let slice = hevc_nal(1, 10);
+    let bytes = packetizer.packetize(&annex_b(&[&slice]), Duration::from_secs(1))[0].to_bytes();
+
+    assert_eq!(bytes[0], 0x80);
+    assert_eq!(bytes[1], 0x80 | 98);
+    assert_eq!(&bytes[4..8], &90_000u32.to_be_bytes());
+    assert_eq!(&bytes[8..12], &0xdead_beefu32.to_be_bytes());
+    assert_eq!(&bytes[12..], &slice[..]);
+}
+
+#[test]
+fn test_encoder_output_round_trip() {
+    let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+    let mut packetizer = HevcPacketizer::new(PacketizerConfig::default()).unwrap();
+    let packets = packetizer.packetize(hevc_bytes, Duration::ZERO);
+
+    let nals = depacketize(&packets);
+    let nal_types: Vec<u8> = nals.iter().map(|nal| (nal[0] >> 1) & 0b11_1111).collect();
+    assert_eq!(&nal_types[..3], &[32, 33, 34]);
+
+    let rebuilt: Vec<&[u8]> = nals.iter().map(|nal| nal.as_slice()).collect();
+    assert_eq!(annex_b(&rebuilt), hevc_bytes.to_vec());
+}
+
+#[test]
+fn test_tiny_mtu_is_rejected() {
+    let config = PacketizerConfig { mtu: 15, ..PacketizerConfig::default() };
+    assert!(HevcPacketizer::new(config).is_err());
+}
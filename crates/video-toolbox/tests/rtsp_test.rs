@@ -0,0 +1,354 @@
+use std::{
+    io::{BufReader, Write},
+    net::{TcpStream, UdpSocket},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use video_toolbox::{
+    rtp::{Depacketizer, DepacketizerEvent, PacketizerConfig, RtpPacket},
+    rtsp::{
+        read_interleaved, MediaSource, RtspError, RtspRequest, RtspResponse, RtspServer,
+        SourceAccessUnit,
+    },
+    sdp::SessionDescription,
+    HevcParameterSets, ParameterSets, VideoCodec,
+};
+
+const HEVC_BYTES: &[u8] = include_bytes!("../../video-toolbox-sys/out.hevc");
+
+/// Emits the keyframe from `out.hevc` followed by small delta frames.
+struct SyntheticSource {
+    frame_count: u32,
+}
+
+impl MediaSource for SyntheticSource {
+    fn codec(&self) -> VideoCodec {
+        VideoCodec::Hevc
+    }
+
+    fn next_access_unit(&mut self) -> Result<Option<SourceAccessUnit>, RtspError> {
+        std::thread::sleep(Duration::from_millis(5));
+
+        let data = if self.frame_count.is_multiple_of(5) {
+            HEVC_BYTES.to_vec()
+        } else {
+            let mut delta = vec![0, 0, 0, 1, 0x02, 0x01];
+            delta.extend(std::iter::repeat_n(0xab, 2000));
+            delta
+        };
+
+        let pts = Duration::from_millis(40) * self.frame_count;
+        self.frame_count += 1;
+
+        Ok(Some(SourceAccessUnit { data, pts }))
+    }
+}
+
+/// Fails once told to after its first access unit, like an encoder that
+/// stopped working.
+struct FailingSource {
+    started: bool,
+    fail: std::sync::mpsc::Receiver<()>,
+}
+
+impl MediaSource for FailingSource {
+    fn codec(&self) -> VideoCodec {
+        VideoCodec::Hevc
+    }
+
+    fn next_access_unit(&mut self) -> Result<Option<SourceAccessUnit>, RtspError> {
+        if std::mem::replace(&mut self.started, true) {
+            let _ = self.fail.recv();
+            return Err(RtspError::Source("encoder failed".into()));
+        }
+
+        Ok(Some(SourceAccessUnit { data: HEVC_BYTES.to_vec(), pts: Duration::ZERO }))
+    }
+}
+
+struct Client {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+    cseq: u32,
+}
+
+impl Client {
+    fn connect(server: &RtspServer) -> Self {
+        let writer = TcpStream::connect(server.local_addr()).unwrap();
+        writer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let reader = BufReader::new(writer.try_clone().unwrap());
+
+        Self { writer, reader, cseq: 0 }
+    }
+
+    fn send(&mut self, method: &str, url: &str, headers: &[(&str, &str)]) -> RtspResponse {
+        self.cseq += 1;
+
+        let mut request = RtspRequest::new(method, url, self.cseq);
+        for (name, value) in headers {
+            request = request.with_header(name, value);
+        }
+
+        request.write_to(&mut self.writer).unwrap();
+
+        let response = RtspResponse::read_from(&mut self.reader).unwrap().unwrap();
+        assert_eq!(response.header("CSeq"), Some(self.cseq.to_string().as_str()));
+        response
+    }
+}
+
+fn session_id(response: &RtspResponse) -> String {
+    response.header("Session").unwrap().split(';').next().unwrap().to_string()
+}
+
+fn wait_for_keyframe(mut next_packet: impl FnMut() -> RtpPacket) -> Vec<u8> {
+    let mut depacketizer = Depacketizer::new(VideoCodec::Hevc, 8);
+
+    loop {
+        for event in depacketizer.push(next_packet()) {
+            if let DepacketizerEvent::AccessUnit(access_unit) = event {
+                if access_unit.is_keyframe {
+                    return access_unit.data;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_describe_setup_play_over_udp() {
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        SyntheticSource { frame_count: 0 },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+    let url = server.url();
+    let mut client = Client::connect(&server);
+
+    let response = client.send("OPTIONS", &url, &[]);
+    assert_eq!(response.status_code, 200);
+    assert!(response.header("Public").unwrap().contains("DESCRIBE"));
+
+    let response = client.send("DESCRIBE", &url, &[("Accept", "application/sdp")]);
+    assert_eq!(response.status_code, 200);
+    assert_eq!(response.header("Content-Type"), Some("application/sdp"));
+
+    let description =
+        SessionDescription::parse(std::str::from_utf8(&response.body).unwrap()).unwrap();
+    let media = &description.media[0];
+    assert_eq!(
+        media.parameter_sets(),
+        Some(ParameterSets::Hevc(HevcParameterSets::from_annex_b(HEVC_BYTES).unwrap()))
+    );
+
+    let track_url = format!("{}{}", response.header("Content-Base").unwrap(), "trackID=0");
+    assert_eq!(media.control.as_deref(), Some("trackID=0"));
+
+    let rtp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    rtp_socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let rtp_port = rtp_socket.local_addr().unwrap().port();
+    let transport = format!("RTP/AVP;unicast;client_port={}-{}", rtp_port, rtp_port + 1);
+
+    let response = client.send("SETUP", &track_url, &[("Transport", &transport)]);
+    assert_eq!(response.status_code, 200);
+    assert!(response.header("Transport").unwrap().contains("server_port="));
+    let session = session_id(&response);
+
+    let response = client.send("PLAY", &url, &[("Session", &session)]);
+    assert_eq!(response.status_code, 200);
+    assert!(response.header("RTP-Info").unwrap().contains("seq="));
+
+    let keyframe = wait_for_keyframe(|| {
+        let mut buffer = [0u8; 2048];
+        let len = rtp_socket.recv(&mut buffer).unwrap();
+        RtpPacket::parse(&buffer[..len]).unwrap()
+    });
+    assert_eq!(keyframe, HEVC_BYTES);
+
+    let response = client.send("TEARDOWN", &url, &[("Session", &session)]);
+    assert_eq!(response.status_code, 200);
+
+    let response = client.send("PLAY", &url, &[("Session", &session)]);
+    assert_eq!(response.status_code, 454);
+
+    server.shutdown();
+}
+
+#[test]
+fn test_interleaved_tcp() {
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        SyntheticSource { frame_count: 0 },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+    let url = server.url();
+    let mut client = Client::connect(&server);
+
+    let response = client.send(
+        "SETUP",
+        &format!("{}trackID=0", url),
+        &[("Transport", "RTP/AVP/TCP;unicast;interleaved=0-1")],
+    );
+    assert_eq!(response.status_code, 200);
+    assert_eq!(response.header("Transport"), Some("RTP/AVP/TCP;unicast;interleaved=0-1"));
+    let session = session_id(&response);
+
+    let response = client.send("PLAY", &url, &[("Session", &session)]);
+    assert_eq!(response.status_code, 200);
+
+    let keyframe = wait_for_keyframe(|| {
+        let (channel, packet) = read_interleaved(&mut client.reader).unwrap();
+        assert_eq!(channel, 0);
+        RtpPacket::parse(&packet).unwrap()
+    });
+    assert_eq!(keyframe, HEVC_BYTES);
+
+    server.shutdown();
+}
+
+#[test]
+fn test_error_responses() {
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        SyntheticSource { frame_count: 0 },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+    let url = server.url();
+    let mut client = Client::connect(&server);
+
+    assert_eq!(client.send("RECORD", &url, &[]).status_code, 501);
+    assert_eq!(client.send("PLAY", &url, &[("Session", "1234")]).status_code, 454);
+    assert_eq!(client.send("SETUP", &url, &[("Transport", "RTP/AVP;multicast")]).status_code, 461);
+
+    // Requests without a CSeq are rejected.
+    client.writer.write_all(b"OPTIONS * RTSP/1.0\r\n\r\n").unwrap();
+    let response = RtspResponse::read_from(&mut client.reader).unwrap().unwrap();
+    assert_eq!(response.status_code, 400);
+
+    server.shutdown();
+}
+
+#[test]
+fn test_oversized_requests_are_rejected() {
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        SyntheticSource { frame_count: 0 },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+
+    let long_line = format!("OPTIONS {} RTSP/1.0\r\nCSeq: 1\r\n\r\n", "a".repeat(10_000));
+    let many_headers = format!("OPTIONS * RTSP/1.0\r\n{}\r\n", "X-Header: 1\r\n".repeat(100));
+
+    for request in [long_line, many_headers] {
+        let mut client = Client::connect(&server);
+        client.writer.write_all(request.as_bytes()).unwrap();
+
+        let response = RtspResponse::read_from(&mut client.reader).unwrap().unwrap();
+        assert_eq!(response.status_code, 400);
+    }
+
+    server.shutdown();
+}
+
+#[test]
+fn test_idle_sessions_time_out() {
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        SyntheticSource { frame_count: 0 },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+    server.set_session_timeout(Duration::from_millis(500));
+
+    let errors = Arc::new(Mutex::new(vec![]));
+    let handler_errors = errors.clone();
+    server.set_error_handler(move |error| handler_errors.lock().unwrap().push(error));
+
+    let url = server.url();
+    let mut client = Client::connect(&server);
+
+    let rtp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let rtp_port = rtp_socket.local_addr().unwrap().port();
+    let transport = format!("RTP/AVP;unicast;client_port={}-{}", rtp_port, rtp_port + 1);
+
+    let response = client.send("SETUP", &format!("{}trackID=0", url), &[("Transport", &transport)]);
+    // The timeout is advertised in whole seconds.
+    assert!(response.header("Session").unwrap().ends_with(";timeout=1"));
+    let session = session_id(&response);
+
+    assert_eq!(client.send("PLAY", &url, &[("Session", &session)]).status_code, 200);
+
+    // Keep-alives hold the session open past the timeout.
+    for _ in 0..4 {
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(client.send("GET_PARAMETER", &url, &[("Session", &session)]).status_code, 200);
+    }
+
+    std::thread::sleep(Duration::from_millis(1000));
+    assert_eq!(client.send("PLAY", &url, &[("Session", &session)]).status_code, 454);
+
+    let errors = errors.lock().unwrap();
+    assert!(errors
+        .iter()
+        .any(|error| matches!(error, RtspError::SessionTimedOut(id) if *id == session)));
+
+    server.shutdown();
+}
+
+#[test]
+fn test_closed_connections_are_forgotten() {
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        SyntheticSource { frame_count: 0 },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+
+    let mut client = Client::connect(&server);
+    assert_eq!(client.send("OPTIONS", &server.url(), &[]).status_code, 200);
+    assert_eq!(server.connection_count(), 1);
+
+    drop(client);
+
+    for _ in 0..50 {
+        if server.connection_count() == 0 {
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    assert_eq!(server.connection_count(), 0);
+    server.shutdown();
+}
+
+#[test]
+fn test_source_errors_are_reported() {
+    let (fail, fail_receiver) = std::sync::mpsc::channel();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let server = RtspServer::start(
+        "127.0.0.1:0",
+        FailingSource { started: false, fail: fail_receiver },
+        PacketizerConfig::default(),
+    )
+    .unwrap();
+
+    server.set_error_handler(move |error| {
+        let _ = sender.send(error.to_string());
+    });
+    fail.send(()).unwrap();
+
+    let error = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(error, "Source error: encoder failed");
+
+    // The stream ended, but DESCRIBE still has the parameter sets it saw.
+    let mut client = Client::connect(&server);
+    let response = client.send("DESCRIBE", &server.url(), &[]);
+    assert_eq!(response.status_code, 200);
+
+    server.shutdown();
+}
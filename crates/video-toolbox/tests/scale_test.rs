@@ -0,0 +1,104 @@
+use std::time::Duration;
+use video_toolbox::{
+    scale::{scale, ScaleError, ScaleFilter, Scaler},
+    FrameBuf, PixelFormat, Rect, Timestamp, VideoFrame,
+};
+
+const FILTERS: [ScaleFilter; 4] =
+    [ScaleFilter::Bilinear, ScaleFilter::Bicubic, ScaleFilter::Lanczos3, ScaleFilter::Area];
+
+fn gray(width: u32, height: u32, data: &[u8]) -> VideoFrame<'_> {
+    VideoFrame::from_packed(PixelFormat::Gray8, width, height, data).unwrap()
+}
+
+#[test]
+fn test_identity_and_constant() {
+    let data: Vec<u8> = (0..48).map(|i| (i * 37 % 256) as u8).collect();
+
+    for filter in FILTERS {
+        let same = scale(&gray(8, 6, &data), 8, 6, filter).unwrap();
+        assert_eq!(same.to_packed(), data, "{:?}", filter);
+
+        // Weights sum to exactly one, so flat areas stay flat.
+        let flat = scale(&gray(8, 6, &[77; 48]), 5, 9, filter).unwrap();
+        assert!(flat.to_packed().iter().all(|&value| value == 77), "{:?}", filter);
+    }
+}
+
+#[test]
+fn test_golden_values() {
+    // Bilinear upscale of a two pixel ramp.
+    let ramp = scale(&gray(2, 1, &[0, 255]), 4, 1, ScaleFilter::Bilinear).unwrap();
+    assert_eq!(ramp.to_packed(), [0, 64, 191, 255]);
+
+    // Area downscale by two averages each 2x2 block.
+    let blocks = [10, 20, 100, 200, 30, 40, 0, 0];
+    let area = scale(&gray(4, 2, &blocks), 2, 1, ScaleFilter::Area).unwrap();
+    assert_eq!(area.to_packed(), [25, 75]);
+
+    // Lanczos ringing around a hard edge is clamped to the sample range.
+    let edge = [0, 0, 0, 255, 255, 255];
+    let ringing = scale(&gray(6, 1, &edge), 12, 1, ScaleFilter::Lanczos3).unwrap();
+    assert_eq!(ringing.to_packed(), [0, 2, 8, 0, 0, 54, 201, 255, 255, 247, 253, 255]);
+
+    let bicubic = scale(&gray(6, 1, &edge), 12, 1, ScaleFilter::Bicubic).unwrap();
+    assert_eq!(bicubic.to_packed(), [0, 0, 0, 0, 0, 52, 203, 255, 255, 255, 255, 255]);
+}
+
+#[test]
+fn test_planar_formats() {
+    // Luma and chroma of different values, scaled from 8x4 to 4x2.
+    let mut nv12 = FrameBuf::new(PixelFormat::Nv12, 8, 4).unwrap();
+    nv12.planes_mut()[0].data_mut().fill(100);
+    for pair in nv12.planes_mut()[1].data_mut().chunks_exact_mut(2) {
+        pair.copy_from_slice(&[50, 200]);
+    }
+
+    let scaler = Scaler::new(PixelFormat::Nv12, (8, 4), (4, 2), ScaleFilter::Lanczos3).unwrap();
+    let small = scaler.scale(&nv12.as_frame()).unwrap();
+    assert_eq!(small.planes()[0].data(), [100; 8]);
+    assert_eq!(small.planes()[1].data(), [50, 200, 50, 200]);
+
+    // P010 keeps its padding bits clear.
+    let mut p010 = FrameBuf::new(PixelFormat::P010, 4, 2).unwrap();
+    for (i, sample) in p010.planes_mut()[0].data_mut().chunks_exact_mut(2).enumerate() {
+        sample.copy_from_slice(&((i as u16 * 100) << 6).to_le_bytes());
+    }
+
+    let scaled = scale(&p010.as_frame(), 3, 3, ScaleFilter::Bicubic).unwrap();
+    let luma = scaled.planes()[0].data();
+    assert!(luma.chunks_exact(2).all(|sample| sample[0] & 0x3f == 0));
+
+    // I420 with odd sizes rounds the chroma planes up.
+    let i420 = FrameBuf::new(PixelFormat::I420, 9, 5).unwrap();
+    let scaled = scale(&i420.as_frame(), 5, 3, ScaleFilter::Area).unwrap();
+    let lengths: Vec<usize> = scaled.planes().iter().map(|plane| plane.data().len()).collect();
+    assert_eq!(lengths, [15, 6, 6]);
+}
+
+#[test]
+fn test_crop_pts_and_errors() {
+    let bgra = FrameBuf::new(PixelFormat::Bgra32, 1920, 1080).unwrap();
+    let frame = bgra
+        .as_frame()
+        .with_crop(Rect::new(0, 0, 1920, 1078))
+        .unwrap()
+        .with_pts(Duration::from_millis(500))
+        .with_duration(Timestamp::new(1, 30));
+
+    let scaler =
+        Scaler::new(PixelFormat::Bgra32, (1920, 1080), (640, 360), ScaleFilter::Area).unwrap();
+    let scaled = scaler.scale(&frame).unwrap();
+    assert_eq!(scaled.crop(), Rect::new(0, 0, 640, 360));
+    assert_eq!(scaled.pts(), Timestamp::new(1, 2));
+    assert_eq!(scaled.duration(), Some(Timestamp::new(1, 30)));
+
+    assert!(matches!(
+        scaler.scale(&FrameBuf::new(PixelFormat::Bgra32, 1280, 720).unwrap().as_frame()),
+        Err(ScaleError::FrameMismatch { .. })
+    ));
+    assert_eq!(
+        Scaler::new(PixelFormat::Yuyv, (4, 4), (2, 2), ScaleFilter::Bilinear).err(),
+        Some(ScaleError::UnsupportedFormat(PixelFormat::Yuyv))
+    );
+}
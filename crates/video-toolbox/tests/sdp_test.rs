@@ -0,0 +1,155 @@
+use video_toolbox::{
+    rtp::H264PacketizationMode,
+    sdp::{SessionDescription, VideoMediaDescription},
+    H264ParameterSets, HevcParameterSets, ParameterSets, VideoCodec,
+};
+
+const H264_SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe8];
+const H264_PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+#[test]
+fn test_hevc_round_trip_from_encoder_output() {
+    let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
+    let parameter_sets = HevcParameterSets::from_annex_b(hevc_bytes).unwrap();
+
+    let mut media = VideoMediaDescription::hevc(96, &parameter_sets);
+    media.control = Some("trackID=0".to_string());
+    let description = SessionDescription::new("video-toolbox", vec![media]);
+
+    let text = description.to_string();
+    assert!(text.starts_with("v=0\r\n"));
+    assert!(text.contains("m=video 0 RTP/AVP 96\r\n"));
+    assert!(text.contains("a=rtpmap:96 H265/90000\r\n"));
+    assert!(text.contains("profile-space=0;profile-id=1;tier-flag=0;level-id=150;"));
+    assert!(text.contains("a=control:trackID=0\r\n"));
+
+    let parsed = SessionDescription::parse(&text).unwrap();
+    assert_eq!(parsed, description);
+
+    let media = &parsed.media[0];
+    assert_eq!(media.parameter_sets(), Some(ParameterSets::Hevc(parameter_sets.clone())));
+
+    // SDP carries everything but the full set of compatibility flags.
+    let from_sdp = media.hevc_profile_tier_level().unwrap();
+    let from_sps = parameter_sets.profile_tier_level().unwrap();
+    assert_eq!(from_sdp.profile_idc, from_sps.profile_idc);
+    assert_eq!(from_sdp.tier_flag, from_sps.tier_flag);
+    assert_eq!(from_sdp.level_idc, from_sps.level_idc);
+    assert_eq!(from_sdp.constraint_indicator_flags, from_sps.constraint_indicator_flags);
+
+    // The decoder can be primed from the SDP alone.
+    let prefix = media.parameter_sets().unwrap().to_annex_b();
+    assert_eq!(&hevc_bytes[..prefix.len()], &prefix[..]);
+}
+
+#[test]
+fn test_hevc_profile_tier_level_from_sps() {
+    let hevc_bytes = include_bytes!("../../video-toolbox-sys/out.hevc");
+    let parameter_sets = HevcParameterSets::from_annex_b(hevc_bytes).unwrap();
+    let profile = parameter_sets.profile_tier_level().unwrap();
+
+    assert_eq!(profile.profile_space, 0);
+    assert!(!profile.tier_flag);
+    assert_eq!(profile.profile_idc, 1);
+    assert_eq!(profile.profile_compatibility_flags, 0x6000_0000);
+    assert_eq!(profile.constraint_indicator_flags, 0xb000_0000_0000);
+    assert_eq!(profile.level_idc, 150);
+}
+
+#[test]
+fn test_h264_media_description() {
+    let parameter_sets = H264ParameterSets { sps: H264_SPS.to_vec(), pps: H264_PPS.to_vec() };
+    let media =
+        VideoMediaDescription::h264(97, H264PacketizationMode::NonInterleaved, &parameter_sets);
+
+    assert_eq!(
+        media.fmtp_line(),
+        "a=fmtp:97 packetization-mode=1;profile-level-id=42c01f;\
+         sprop-parameter-sets=Z0LAH9oBQBbo,aM48gA=="
+    );
+    assert_eq!(media.packetization_mode(), Some(H264PacketizationMode::NonInterleaved));
+    assert_eq!(media.parameter_sets(), Some(ParameterSets::H264(parameter_sets)));
+    assert_eq!(media.hevc_profile_tier_level(), None);
+}
+
+#[test]
+fn test_parse_camera_sdp() {
+    let text = "v=0\r\n\
+        o=- 1681 1 IN IP4 192.168.1.20\r\n\
+        s=Media Presentation\r\n\
+        t=0 0\r\n\
+        a=control:*\r\n\
+        m=audio 0 RTP/AVP 0\r\n\
+        a=rtpmap:0 PCMU/8000\r\n\
+        m=video 0 RTP/AVP 35 96\r\n\
+        c=IN IP4 239.0.0.1/64\r\n\
+        a=rtpmap:96 H264/90000\r\n\
+        a=fmtp:96 profile-level-id=42c01f; packetization-mode=1; \
+        sprop-parameter-sets=Z0LAH9oBQBbo,aM48gA==\r\n\
+        a=control:rtsp://192.168.1.20/stream1/trackID=1\r\n";
+
+    let description = SessionDescription::parse(text).unwrap();
+    assert_eq!(description.session_id, 1681);
+    assert_eq!(description.origin_address, "192.168.1.20");
+    assert_eq!(description.session_name, "Media Presentation");
+    assert_eq!(description.connection_address.as_deref(), Some("239.0.0.1"));
+    assert_eq!(description.media.len(), 1);
+
+    let media = &description.media[0];
+    assert_eq!(media.codec, VideoCodec::H264);
+    assert_eq!(media.payload_type, 96);
+    assert_eq!(media.clock_rate, 90_000);
+    assert_eq!(media.fmtp_parameter("profile-level-id"), Some("42c01f"));
+    assert_eq!(media.control.as_deref(), Some("rtsp://192.168.1.20/stream1/trackID=1"));
+    assert_eq!(
+        media.parameter_sets(),
+        Some(ParameterSets::H264(H264ParameterSets {
+            sps: H264_SPS.to_vec(),
+            pps: H264_PPS.to_vec()
+        }))
+    );
+}
+
+#[test]
+fn test_hevc_defaults_when_fmtp_is_missing() {
+    let text = "v=0\no=- 0 0 IN IP4 127.0.0.1\ns=-\nt=0 0\nm=video 5004 RTP/AVP 98\n\
+        a=rtpmap:98 H265/90000\n";
+
+    let media = &SessionDescription::parse(text).unwrap().media[0];
+    assert_eq!(media.port, 5004);
+    assert_eq!(media.parameter_sets(), None);
+
+    let profile = media.hevc_profile_tier_level().unwrap();
+    assert_eq!(profile.profile_idc, 1);
+    assert_eq!(profile.level_idc, 93);
+    assert!(!profile.tier_flag);
+}
+
+#[test]
+fn test_hevc_out_of_range_profile_parameters() {
+    let media_with = |fmtp: &str| {
+        let text = format!(
+            "v=0\no=- 0 0 IN IP4 127.0.0.1\ns=-\nt=0 0\nm=video 5004 RTP/AVP 98\n\
+             a=rtpmap:98 H265/90000\na=fmtp:98 {}\n",
+            fmtp
+        );
+        SessionDescription::parse(&text).unwrap().media.remove(0)
+    };
+
+    let profile = media_with("profile-id=40").hevc_profile_tier_level().unwrap();
+    assert_eq!(profile.profile_idc, 40);
+    assert_eq!(profile.profile_compatibility_flags, 0);
+
+    let profile = media_with("profile-id=31").hevc_profile_tier_level().unwrap();
+    assert_eq!(profile.profile_compatibility_flags, 1);
+
+    assert_eq!(media_with("profile-space=4").hevc_profile_tier_level(), None);
+    assert_eq!(media_with("tier-flag=2").hevc_profile_tier_level(), None);
+}
+
+#[test]
+fn test_invalid_sdp() {
+    assert!(SessionDescription::parse("o=- 0 0 IN IP4 127.0.0.1\r\n").is_err());
+    assert!(SessionDescription::parse("v=0\r\ns=-\r\nm=audio 0 RTP/AVP 0\r\n").is_err());
+    assert!(SessionDescription::parse("v=0\r\nm=video abc RTP/AVP 96\r\n").is_err());
+}
@@ -0,0 +1,232 @@
+use video_toolbox::{FrameRate, H264ParameterSets, HevcParameterSets, SpsInfo};
+
+/// Writes SPS fields MSB first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_count: usize,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u64, count: u32) -> &mut Self {
+        for bit in (0..count).rev() {
+            if self.bit_count.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+
+            *self.bytes.last_mut().unwrap() |=
+                (((value >> bit) & 1) as u8) << (7 - self.bit_count % 8);
+            self.bit_count += 1;
+        }
+
+        self
+    }
+
+    fn ue(&mut self, value: u32) -> &mut Self {
+        let code = value as u64 + 1;
+        let len = 64 - code.leading_zeros();
+        self.bits(0, len - 1).bits(code, len)
+    }
+
+    /// Appends the RBSP stop bit after `header`.
+    fn nal(&mut self, header: &[u8]) -> Vec<u8> {
+        self.bits(1, 1);
+        [header, &self.bytes].concat()
+    }
+}
+
+/// A baseline H.264 SPS with the given size fields and cropping offsets.
+fn h264_sps(width_in_mbs_minus1: u32, height_in_map_units_minus1: u32, crop: [u32; 4]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+
+    // profile_idc, constraint flags and level_idc
+    writer.bits(66, 8).bits(0, 8).bits(31, 8);
+    // seq_parameter_set_id, log2_max_frame_num_minus4, pic_order_cnt_type
+    writer.ue(0).ue(0).ue(2);
+    // max_num_ref_frames, gaps_in_frame_num_value_allowed_flag
+    writer.ue(1).bits(0, 1);
+    writer.ue(width_in_mbs_minus1).ue(height_in_map_units_minus1);
+    // frame_mbs_only_flag, direct_8x8_inference_flag, frame_cropping_flag
+    writer.bits(1, 1).bits(1, 1).bits(1, 1);
+    crop.iter().for_each(|&offset| {
+        writer.ue(offset);
+    });
+    // vui_parameters_present_flag
+    writer.bits(0, 1);
+
+    writer.nal(&[0x67])
+}
+
+fn h264_sps_info(sps: Vec<u8>) -> Option<SpsInfo> {
+    H264ParameterSets { sps, pps: vec![0x68, 0xce, 0x3c, 0x80] }.sps_info()
+}
+
+/// A high profile H.264 SPS for 1280x720 with the given chroma format and bit depth.
+fn h264_high_sps(chroma_format_idc: u32, bit_depth_minus8: u32) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+
+    writer.bits(100, 8).bits(0, 8).bits(31, 8).ue(0);
+    writer.ue(chroma_format_idc).ue(bit_depth_minus8).ue(bit_depth_minus8);
+    // qpprime_y_zero_transform_bypass_flag, seq_scaling_matrix_present_flag
+    writer.bits(0, 2);
+    writer.ue(0).ue(2).ue(1).bits(0, 1).ue(79).ue(44);
+    // frame_mbs_only_flag, direct_8x8_inference_flag, frame_cropping_flag and no VUI
+    writer.bits(0b1100, 4);
+
+    writer.nal(&[0x67])
+}
+
+/// HEVC SPS fields with one sub-layer, ending in VUI timing info for 30000/1001.
+struct HevcSps {
+    chroma_format_idc: u32,
+    width: u32,
+    height: u32,
+    window: [u32; 4],
+    bit_depth_minus8: u32,
+    log2_max_pic_order_cnt_lsb_minus4: u32,
+    num_short_term_ref_pic_sets: u32,
+}
+
+impl Default for HevcSps {
+    fn default() -> Self {
+        Self {
+            chroma_format_idc: 1,
+            width: 1920,
+            height: 1080,
+            window: [0; 4],
+            bit_depth_minus8: 0,
+            log2_max_pic_order_cnt_lsb_minus4: 4,
+            num_short_term_ref_pic_sets: 1,
+        }
+    }
+}
+
+impl HevcSps {
+    fn to_nal(&self) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+
+        // sps_video_parameter_set_id, max_sub_layers_minus1, temporal_id_nesting
+        writer.bits(0, 4).bits(0, 3).bits(1, 1);
+        // general_profile_tier_level, Main profile
+        writer.bits(1, 8).bits(0x6000_0000, 32).bits(0, 48).bits(93, 8);
+        // sps_seq_parameter_set_id
+        writer.ue(0).ue(self.chroma_format_idc);
+
+        if self.chroma_format_idc == 3 {
+            // separate_colour_plane_flag
+            writer.bits(0, 1);
+        }
+
+        writer.ue(self.width).ue(self.height);
+        // conformance_window_flag
+        writer.bits(1, 1);
+        self.window.iter().for_each(|&offset| {
+            writer.ue(offset);
+        });
+        writer.ue(self.bit_depth_minus8).ue(self.bit_depth_minus8);
+        writer.ue(self.log2_max_pic_order_cnt_lsb_minus4);
+        // sub_layer_ordering_info_present_flag and its three values
+        writer.bits(1, 1).ue(0).ue(0).ue(0);
+        // block sizes and depths
+        writer.ue(0).ue(3).ue(0).ue(3).ue(0).ue(0);
+        // scaling_list_enabled_flag, amp, sao and pcm_enabled_flag
+        writer.bits(0, 4);
+
+        writer.ue(self.num_short_term_ref_pic_sets);
+        for index in 0..self.num_short_term_ref_pic_sets.min(64) {
+            if index != 0 {
+                // inter_ref_pic_set_prediction_flag
+                writer.bits(0, 1);
+            }
+
+            // num_negative_pics, num_positive_pics
+            writer.ue(0).ue(0);
+        }
+
+        // long_term_ref_pics_present_flag, temporal MVP and strong intra smoothing
+        writer.bits(0, 3);
+        // vui_parameters_present_flag, then no aspect ratio, overscan, signal type,
+        // chroma location, field info or default display window
+        writer.bits(1, 1).bits(0, 8);
+        writer.bits(1, 1).bits(1001, 32).bits(30_000, 32);
+
+        writer.nal(&[0x42, 0x01])
+    }
+}
+
+fn hevc_sps_info(sps: Vec<u8>) -> Option<SpsInfo> {
+    HevcParameterSets { vps: vec![], sps, pps: vec![] }.sps_info()
+}
+
+#[test]
+fn test_sps_sizes() {
+    let sps_info = h264_sps_info(h264_sps(79, 44, [0; 4])).unwrap();
+    assert_eq!((sps_info.width, sps_info.height), (1280, 720));
+
+    let sps_info = h264_sps_info(h264_sps(119, 67, [0, 0, 0, 4])).unwrap();
+    assert_eq!((sps_info.width, sps_info.height), (1920, 1080));
+
+    let sps_info = hevc_sps_info(
+        HevcSps { height: 1088, window: [0, 0, 0, 4], ..HevcSps::default() }.to_nal(),
+    )
+    .unwrap();
+    assert_eq!((sps_info.width, sps_info.height), (1920, 1080));
+}
+
+#[test]
+fn test_sps_size_overflow() {
+    // Macroblock counts whose pixel sizes do not fit in a u32.
+    assert_eq!(h264_sps_info(h264_sps(1 << 28, 44, [0; 4])), None);
+    assert_eq!(h264_sps_info(h264_sps(79, 1 << 28, [0; 4])), None);
+
+    // Cropping offsets whose sums or scaled sums overflow.
+    assert_eq!(h264_sps_info(h264_sps(79, 44, [u32::MAX - 1, u32::MAX - 1, 0, 0])), None);
+    assert_eq!(h264_sps_info(h264_sps(79, 44, [0, 0, 1 << 31, 0])), None);
+    assert_eq!(
+        hevc_sps_info(HevcSps { window: [1 << 31, 1 << 31, 0, 0], ..HevcSps::default() }.to_nal()),
+        None
+    );
+    assert_eq!(
+        hevc_sps_info(HevcSps { window: [0, 0, 0, u32::MAX - 1], ..HevcSps::default() }.to_nal()),
+        None
+    );
+}
+
+#[test]
+fn test_sps_field_limits() {
+    let sps_info = h264_sps_info(h264_high_sps(1, 2)).unwrap();
+    assert_eq!((sps_info.bit_depth_luma, sps_info.bit_depth_chroma), (10, 10));
+    assert_eq!(h264_sps_info(h264_high_sps(1, 7)), None);
+    assert_eq!(h264_sps_info(h264_high_sps(1, 256)), None);
+    assert_eq!(h264_sps_info(h264_high_sps(4, 0)), None);
+
+    let sps_info = hevc_sps_info(HevcSps::default().to_nal()).unwrap();
+    assert_eq!(sps_info.frame_rate, Some(FrameRate::new(30_000, 1001)));
+
+    let sps_info = hevc_sps_info(HevcSps { bit_depth_minus8: 8, ..HevcSps::default() }.to_nal());
+    assert_eq!(sps_info.unwrap().bit_depth_luma, 16);
+
+    for bit_depth_minus8 in [9, 248, u32::MAX - 1] {
+        let sps = HevcSps { bit_depth_minus8, ..HevcSps::default() };
+        assert_eq!(hevc_sps_info(sps.to_nal()), None);
+    }
+
+    let sps = HevcSps { chroma_format_idc: 4, ..HevcSps::default() };
+    assert_eq!(hevc_sps_info(sps.to_nal()), None);
+
+    // Fields past the bit depths only lose the VUI frame rate.
+    let sps = HevcSps { log2_max_pic_order_cnt_lsb_minus4: 12, ..HevcSps::default() };
+    assert!(hevc_sps_info(sps.to_nal()).unwrap().frame_rate.is_some());
+
+    let sps = HevcSps { num_short_term_ref_pic_sets: 64, ..HevcSps::default() };
+    assert!(hevc_sps_info(sps.to_nal()).unwrap().frame_rate.is_some());
+
+    for sps in [
+        HevcSps { log2_max_pic_order_cnt_lsb_minus4: 13, ..HevcSps::default() },
+        HevcSps { log2_max_pic_order_cnt_lsb_minus4: u32::MAX - 1, ..HevcSps::default() },
+        HevcSps { num_short_term_ref_pic_sets: 65, ..HevcSps::default() },
+        HevcSps { num_short_term_ref_pic_sets: u32::MAX - 1, ..HevcSps::default() },
+    ] {
+        assert_eq!(hevc_sps_info(sps.to_nal()).unwrap().frame_rate, None);
+    }
+}
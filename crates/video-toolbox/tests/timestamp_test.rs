@@ -0,0 +1,138 @@
+use std::{collections::HashSet, time::Duration};
+use video_toolbox::{FrameRate, Timestamp};
+
+#[test]
+fn test_rational_equality() {
+    assert_eq!(Timestamp::new(1, 2), Timestamp::new(45000, 90000));
+    assert_eq!(Timestamp::new(0, 90000), Timestamp::ZERO);
+    assert_ne!(Timestamp::new(1, 3), Timestamp::new(333, 1000));
+    assert_eq!(Timestamp::default(), Timestamp::ZERO);
+
+    let set: HashSet<Timestamp> =
+        [Timestamp::new(1, 2), Timestamp::new(3, 6), Timestamp::new(-2, 4), Timestamp::new(-1, 2)]
+            .into_iter()
+            .collect();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn test_ordering() {
+    let mut times = vec![
+        Timestamp::new(1001, 30000),
+        Timestamp::new(-1, 1),
+        Timestamp::new(1, 30),
+        Timestamp::ZERO,
+        Timestamp::new(i64::MAX, 1),
+        Timestamp::new(i64::MIN, u32::MAX),
+    ];
+    times.sort();
+
+    assert_eq!(
+        times,
+        [
+            Timestamp::new(i64::MIN, u32::MAX),
+            Timestamp::new(-1, 1),
+            Timestamp::ZERO,
+            Timestamp::new(1, 30),
+            Timestamp::new(1001, 30000),
+            Timestamp::new(i64::MAX, 1),
+        ]
+    );
+    assert!(Timestamp::new(1, 30) < Timestamp::new(1001, 30000));
+    assert_eq!(Timestamp::new(3, 4).max(Timestamp::new(2, 3)), Timestamp::new(3, 4));
+}
+
+#[test]
+fn test_rescale() {
+    let time = Timestamp::new(1001, 30000);
+
+    assert_eq!(time.rescale(90000).value(), 3003);
+    assert_eq!(time.rescale(90000).timescale(), 90000);
+    // 33.3667 ms rounds to the nearest millisecond.
+    assert_eq!(time.rescale(1000).value(), 33);
+    assert_eq!(Timestamp::new(2, 3).rescale(1000).value(), 667);
+
+    // Halves round away from zero.
+    assert_eq!(Timestamp::new(1, 2).rescale(1).value(), 1);
+    assert_eq!(Timestamp::new(-1, 2).rescale(1).value(), -1);
+    assert_eq!(Timestamp::new(-1, 3).rescale(1).value(), 0);
+    assert_eq!(Timestamp::new(-2, 3).rescale(1).value(), -1);
+
+    assert_eq!(Timestamp::new(i64::MAX, 1).checked_rescale(2), None);
+    assert_eq!(Timestamp::new(i64::MAX, 2).checked_rescale(1).map(Timestamp::value), Some(1 << 62));
+
+    assert_eq!(Timestamp::new(3003, 90000).reduced(), Timestamp::new(1001, 30000));
+    assert_eq!(Timestamp::new(3003, 90000).reduced().timescale(), 30000);
+    assert_eq!(Timestamp::new(0, 90000).reduced().timescale(), 1);
+}
+
+#[test]
+fn test_arithmetic() {
+    // Exact across timescales whose least common multiple fits.
+    let sum = Timestamp::new(1, 30) + Timestamp::new(1, 25);
+    assert_eq!(sum, Timestamp::new(11, 150));
+    assert_eq!(sum.timescale(), 150);
+
+    assert_eq!(Timestamp::new(1, 2) - Timestamp::new(3, 4), Timestamp::new(-1, 4));
+    assert_eq!(-Timestamp::new(1, 2), Timestamp::new(-1, 2));
+    assert_eq!(Timestamp::new(1001, 30000) * 30, Timestamp::new(1001, 1000));
+
+    let mut time = Timestamp::ZERO;
+    for _ in 0..30000 {
+        time += Timestamp::new(1001, 30000);
+    }
+    assert_eq!(time, Timestamp::from_secs(1001));
+    time -= Timestamp::from_secs(1);
+    assert_eq!(time, Timestamp::from_secs(1000));
+
+    // Coprime timescales too large to combine round to the finer one.
+    let sum = Timestamp::new(1, 4_000_000_007) + Timestamp::new(1, 4_000_000_009);
+    assert_eq!(sum.timescale(), 4_000_000_009);
+    assert_eq!(sum.value(), 2);
+
+    assert_eq!(Timestamp::new(i64::MAX, 1).checked_add(Timestamp::new(1, 1)), None);
+    assert_eq!(Timestamp::new(i64::MIN, 1).checked_neg(), None);
+    assert_eq!(Timestamp::new(i64::MAX, 1).checked_mul(2), None);
+    assert_eq!(Timestamp::new(1, 2).checked_sub(Timestamp::new(1, 2)), Some(Timestamp::ZERO));
+}
+
+#[test]
+#[should_panic(expected = "timestamp overflow")]
+fn test_overflow_panics() {
+    let _ = Timestamp::new(i64::MAX, 1) + Timestamp::new(1, 1);
+}
+
+#[test]
+#[should_panic(expected = "timescale must be positive")]
+fn test_zero_timescale_panics() {
+    Timestamp::new(1, 0);
+}
+
+#[test]
+fn test_durations() {
+    let time = Timestamp::from(Duration::from_millis(1500));
+    assert_eq!(time, Timestamp::new(3, 2));
+    assert_eq!(time.timescale(), 1_000_000_000);
+    assert_eq!(time.as_secs_f64(), 1.5);
+
+    assert_eq!(Timestamp::new(1, 3).to_duration(), Some(Duration::from_nanos(333_333_333)));
+    assert_eq!(Timestamp::new(2, 3).to_duration(), Some(Duration::from_nanos(666_666_667)));
+    assert_eq!(Timestamp::new(-1, 3).to_duration(), None);
+    assert!(Timestamp::new(-1, 3).is_negative());
+
+    assert_eq!(Timestamp::from(Duration::MAX).value(), i64::MAX);
+    assert_eq!(Timestamp::new(7, 9).to_string(), "7/9");
+}
+
+#[test]
+fn test_frame_rate_timestamps() {
+    let frame_rate = FrameRate::new(30000, 1001);
+
+    assert_eq!(frame_rate.timestamp_of(0), Timestamp::ZERO);
+    assert_eq!(frame_rate.timestamp_of(30000), Timestamp::from_secs(1001));
+    assert_eq!(frame_rate.timestamp_of(1).rescale(90000).value(), 3003);
+    assert_eq!(
+        Timestamp::from(frame_rate.duration_of(7)),
+        frame_rate.timestamp_of(7).rescale(1_000_000_000)
+    );
+}
@@ -0,0 +1,254 @@
+use std::io::Cursor;
+use video_toolbox::{
+    y4m::{
+        plane_psnr, Y4mChroma, Y4mChromaSiting, Y4mColorspace, Y4mError, Y4mHeader, Y4mInterlacing,
+        Y4mReader, Y4mWriter,
+    },
+    FrameBuf, FrameRate, PixelFormat,
+};
+
+/// A frame where every sample holds its index in the frame.
+fn test_frame(header: &Y4mHeader) -> Vec<u8> {
+    (0..header.frame_size()).map(|i| i as u8).collect()
+}
+
+#[test]
+fn test_header_round_trip() {
+    let text =
+        "YUV4MPEG2 W1920 H1080 F30000:1001 It A16:15 C420p10 XYSCSS=420P10 XCOLORRANGE=FULL\n";
+    let reader = Y4mReader::new(Cursor::new(text.as_bytes())).unwrap();
+    let header = reader.header();
+
+    assert_eq!((header.width, header.height), (1920, 1080));
+    assert_eq!(header.frame_rate, FrameRate::new(30_000, 1001));
+    assert_eq!(header.interlacing, Y4mInterlacing::TopFieldFirst);
+    assert_eq!(header.pixel_aspect, (16, 15));
+    assert_eq!(header.colorspace, Y4mColorspace::new(Y4mChroma::C420, 10));
+    assert_eq!(header.extensions, ["YSCSS=420P10", "COLORRANGE=FULL"]);
+    assert_eq!(format!("{}\n", header), text);
+
+    // Interlacing, aspect ratio and colorspace are optional.
+    let reader = Y4mReader::new(Cursor::new(b"YUV4MPEG2 W2 H2 F25:1\n")).unwrap();
+    assert_eq!(reader.header().to_string(), "YUV4MPEG2 W2 H2 F25:1 Ip A0:0 C420jpeg");
+}
+
+#[test]
+fn test_colorspaces() {
+    let cases: [(&str, Y4mChroma, u8, &[usize]); 7] = [
+        ("C420jpeg", Y4mChroma::C420, 8, &[15, 6, 6]),
+        ("C420mpeg2", Y4mChroma::C420, 8, &[15, 6, 6]),
+        ("C420p10", Y4mChroma::C420, 10, &[30, 12, 12]),
+        ("C422", Y4mChroma::C422, 8, &[15, 9, 9]),
+        ("C444p12", Y4mChroma::C444, 12, &[30, 30, 30]),
+        ("C444alpha", Y4mChroma::C444Alpha, 8, &[15, 15, 15, 15]),
+        ("Cmono10", Y4mChroma::Mono, 10, &[30]),
+    ];
+
+    for (tag, chroma, bit_depth, plane_sizes) in cases {
+        let text = format!("YUV4MPEG2 W5 H3 F30:1 {}\n", tag);
+        let reader = Y4mReader::new(Cursor::new(text.as_bytes())).unwrap();
+        let header = reader.header();
+
+        assert_eq!(header.colorspace.chroma, chroma, "{}", tag);
+        assert_eq!(header.colorspace.bit_depth, bit_depth, "{}", tag);
+        assert_eq!(header.plane_sizes(), plane_sizes, "{}", tag);
+        assert_eq!(header.colorspace.to_string(), tag);
+    }
+
+    let colorspace: Y4mColorspace = "420mpeg2".parse().unwrap();
+    assert_eq!(colorspace.siting, Y4mChromaSiting::Left);
+    assert!("411".parse::<Y4mColorspace>().is_err());
+    assert!("420p20".parse::<Y4mColorspace>().is_err());
+}
+
+#[test]
+fn test_frames_round_trip() {
+    let header =
+        Y4mHeader::new(5, 3, FrameRate::new(30, 1), Y4mColorspace::new(Y4mChroma::C420, 10));
+    let frame = test_frame(&header);
+
+    let mut writer = Y4mWriter::new(vec![], header.clone()).unwrap();
+    writer.write_frame(&frame).unwrap();
+    writer.write_frame(&frame).unwrap();
+
+    let error = writer.write_frame(&frame[1..]);
+    assert!(matches!(error, Err(Y4mError::FrameSizeMismatch { expected: 54, actual: 53 })));
+
+    let bytes = writer.finish().unwrap();
+    assert!(bytes.starts_with(b"YUV4MPEG2 W5 H3 F30:1 Ip A1:1 C420p10\nFRAME\n"));
+
+    let mut reader = Y4mReader::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(reader.header(), &header);
+    assert_eq!(reader.read_frame().unwrap(), Some(frame.clone()));
+    assert_eq!(reader.read_frame().unwrap(), Some(frame.clone()));
+    assert_eq!(reader.read_frame().unwrap(), None);
+
+    let planes = header.planes(&frame);
+    assert_eq!(planes.iter().map(|plane| plane.len()).collect::<Vec<_>>(), [30, 12, 12]);
+    assert_eq!(planes[1][0], 30);
+}
+
+/// A frame of varied samples within the stream's bit depth.
+fn test_samples(header: &Y4mHeader) -> Vec<u8> {
+    let max_sample = (1u32 << header.colorspace.bit_depth) - 1;
+    let mut frame = vec![];
+
+    for (width, height) in header.plane_dimensions() {
+        for i in 1..=width * height {
+            let sample = (i * 37) & max_sample;
+
+            match header.colorspace.bytes_per_sample() {
+                1 => frame.push(sample as u8),
+                _ => frame.extend_from_slice(&(sample as u16).to_le_bytes()),
+            }
+        }
+    }
+
+    frame
+}
+
+/// Reads `frame` as a [`FrameBuf`] and writes it back.
+fn round_trip_video_frame(header: &Y4mHeader, frame: &[u8]) -> (FrameBuf, Vec<u8>) {
+    let mut writer = Y4mWriter::new(vec![], header.clone()).unwrap();
+    writer.write_frame(frame).unwrap();
+
+    let mut reader = Y4mReader::new(Cursor::new(writer.finish().unwrap())).unwrap();
+    let video_frame = reader.read_video_frame().unwrap().unwrap();
+
+    let mut writer = Y4mWriter::new(vec![], header.clone()).unwrap();
+    writer.write_video_frame(&video_frame.as_frame()).unwrap();
+
+    let mut reader = Y4mReader::new(Cursor::new(writer.finish().unwrap())).unwrap();
+    (video_frame, reader.read_frame().unwrap().unwrap())
+}
+
+#[test]
+fn test_video_frame_colorspaces() {
+    let formats = [
+        (Y4mChroma::Mono, 8, Some(PixelFormat::Gray8)),
+        (Y4mChroma::Mono, 10, Some(PixelFormat::Gray16)),
+        (Y4mChroma::Mono, 16, Some(PixelFormat::Gray16)),
+        (Y4mChroma::C420, 8, Some(PixelFormat::I420)),
+        (Y4mChroma::C420, 10, Some(PixelFormat::P010)),
+        (Y4mChroma::C420, 12, None),
+        (Y4mChroma::C422, 8, Some(PixelFormat::P210)),
+        (Y4mChroma::C422, 10, Some(PixelFormat::P210)),
+        (Y4mChroma::C444, 8, Some(PixelFormat::Nv24)),
+        (Y4mChroma::C444, 10, Some(PixelFormat::P410)),
+        (Y4mChroma::C444Alpha, 8, None),
+    ];
+
+    for (chroma, bit_depth, format) in formats {
+        let colorspace = Y4mColorspace::new(chroma, bit_depth);
+        let header = Y4mHeader::new(5, 3, FrameRate::new(30, 1), colorspace);
+        assert_eq!(header.pixel_format(), format, "{}", colorspace);
+
+        let frame = test_samples(&header);
+
+        if format.is_none() {
+            let mut writer = Y4mWriter::new(vec![], header.clone()).unwrap();
+            writer.write_frame(&frame).unwrap();
+
+            let mut reader = Y4mReader::new(Cursor::new(writer.finish().unwrap())).unwrap();
+            assert!(matches!(reader.read_video_frame(), Err(Y4mError::UnsupportedPixelFormat)));
+            continue;
+        }
+
+        let (video_frame, written) = round_trip_video_frame(&header, &frame);
+        assert_eq!(video_frame.format(), format.unwrap());
+        assert_eq!(written, frame, "{}", colorspace);
+    }
+}
+
+#[test]
+fn test_p010_video_frames() {
+    let header =
+        Y4mHeader::new(2, 2, FrameRate::new(30, 1), Y4mColorspace::new(Y4mChroma::C420, 10));
+    let samples: [u16; 6] = [1, 2, 3, 1023, 500, 600];
+    let frame: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+
+    // Samples move to the high bits, with Cb and Cr interleaved.
+    let (video_frame, written) = round_trip_video_frame(&header, &frame);
+    let planes = video_frame.planes();
+    assert_eq!(planes[0].data(), [64, 0, 128, 0, 192, 0, 0xc0, 0xff]);
+    assert_eq!(planes[1].data(), [0x00, 0x7d, 0x00, 0x96]);
+    assert_eq!(written, frame);
+}
+
+#[test]
+fn test_8_bit_422_widens_to_p210() {
+    let header =
+        Y4mHeader::new(2, 1, FrameRate::new(30, 1), Y4mColorspace::new(Y4mChroma::C422, 8));
+    let frame = [16, 235, 128, 200];
+
+    let (video_frame, written) = round_trip_video_frame(&header, &frame);
+    assert_eq!(video_frame.planes()[0].data(), [0, 16, 0, 235]);
+    assert_eq!(video_frame.planes()[1].data(), [0, 128, 0, 200]);
+    assert_eq!(written, frame);
+
+    // Decoded samples round to the nearest 8-bit value.
+    let mut decoded = video_frame.clone();
+    decoded.planes_mut()[0].data_mut().copy_from_slice(&[0x7f, 16, 0x80, 234]);
+
+    let mut writer = Y4mWriter::new(vec![], header).unwrap();
+    writer.write_video_frame(&decoded.as_frame()).unwrap();
+    assert!(writer.finish().unwrap().ends_with(&[16, 235, 128, 200]));
+}
+
+#[test]
+fn test_malformed_streams() {
+    let open = |text: &[u8]| Y4mReader::new(Cursor::new(text.to_vec()));
+
+    assert!(matches!(open(b"YUV4MPEG W2 H2 F1:1\n"), Err(Y4mError::MissingSignature)));
+    assert!(matches!(open(b"YUV4MPEG2 W2 F1:1\n"), Err(Y4mError::MissingParameter("H"))));
+    assert!(matches!(open(b"YUV4MPEG2 W2 H2 F1\n"), Err(Y4mError::InvalidParameter(_))));
+    assert!(matches!(
+        open(b"YUV4MPEG2 W2 H2 F1:1 C411\n"),
+        Err(Y4mError::UnsupportedColorspace(_))
+    ));
+
+    let mut reader = open(b"YUV4MPEG2 W2 H2 F1:1 Cmono\nFRAMX\n0000").unwrap();
+    assert!(matches!(reader.read_frame(), Err(Y4mError::MissingFrameMarker)));
+
+    // A truncated frame is an IO error, not the end of the stream.
+    let mut reader = open(b"YUV4MPEG2 W2 H2 F1:1 Cmono\nFRAME Ip\n000").unwrap();
+    assert!(matches!(reader.read_frame(), Err(Y4mError::Io(_))));
+
+    // Headers that would need huge frames are rejected before allocating one.
+    assert!(matches!(
+        open(b"YUV4MPEG2 W4294967295 H2 F1:1 C420\n"),
+        Err(Y4mError::DimensionsTooLarge(4_294_967_295, 2))
+    ));
+    assert!(matches!(
+        open(b"YUV4MPEG2 W16384 H16385 F1:1\n"),
+        Err(Y4mError::DimensionsTooLarge(16384, 16385))
+    ));
+    assert_eq!(open(b"YUV4MPEG2 W16384 H16384 F1:1\n").unwrap().header().frame_size(), 402_653_184);
+
+    let header =
+        Y4mHeader::new(u32::MAX, u32::MAX, FrameRate::new(30, 1), Y4mColorspace::default());
+    assert_eq!(header.plane_dimensions()[1], (1 << 31, 1 << 31));
+    assert_eq!(header.frame_size(), usize::MAX);
+    assert!(matches!(Y4mWriter::new(vec![], header), Err(Y4mError::DimensionsTooLarge(..))));
+}
+
+#[test]
+fn test_psnr() {
+    let header = Y4mHeader::new(4, 4, FrameRate::new(30, 1), Y4mColorspace::default());
+    let reference = test_frame(&header);
+
+    let mut decoded = reference.clone();
+    for sample in &mut decoded[..16] {
+        *sample += 1;
+    }
+
+    let psnr = header.psnr(&reference, &decoded);
+    assert_eq!(psnr.len(), 3);
+    assert!((psnr[0] - 48.1308).abs() < 1e-4);
+    assert_eq!(psnr[1..], [f64::INFINITY, f64::INFINITY]);
+
+    // 10-bit samples with an error of 4 everywhere.
+    let reference: Vec<u8> = [512u16; 8].iter().flat_map(|s| s.to_le_bytes()).collect();
+    let decoded: Vec<u8> = [516u16; 8].iter().flat_map(|s| s.to_le_bytes()).collect();
+    assert!((plane_psnr(&reference, &decoded, 10) - 48.1563).abs() < 1e-4);
+}